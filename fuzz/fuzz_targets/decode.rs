@@ -0,0 +1,45 @@
+//! Feeds arbitrary opcodes and machine states to the decoder, looking for panics such as an
+//! index out of bounds in DXYN or FX55. Run with `cargo +nightly fuzz run decode` from the
+//! crate root.
+//!
+//! The input's first byte picks the preset and the quirks that aren't part of one, the next
+//! 16 are V0-VF, then two for I. The rest is both the program in memory and the opcodes run,
+//! straight through with execute_raw(), so jumps and skips move the PC without deciding what
+//! runs next. Memory and sprites then hold whatever the input does.
+
+#![no_main]
+
+use chip_8_rs::quirks::{Preset, Quirks};
+use chip_8_rs::{Chip8, FaultPolicy, INSTRUCTIONS_PER_FRAME};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, data)) = data.split_first() else {
+        return;
+    };
+    let (Some(registers), Some(index)) = (data.get(..16), data.get(16..18)) else {
+        return;
+    };
+    let program = &data[18..];
+    let preset = (0..flags % 5).fold(Preset::Chip8, |preset, _| preset.next());
+    let quirks = Quirks {
+        vip_rng: flags & 0x08 != 0,
+        vip_timing: flags & 0x10 != 0,
+        half_scroll: flags & 0x20 != 0,
+        index_overflow: flags & 0x40 != 0,
+        ..Quirks::preset(preset)
+    };
+    let mut chip_8 = Chip8::builder().quirks(quirks).rom_bytes(program).seed(0).build();
+    chip_8.set_fault_policy(FaultPolicy::Ignore);
+    for (x, &byte) in registers.iter().enumerate() {
+        chip_8.execute_raw(0x6000 | (x as u16) << 8 | byte as u16);
+    }
+    // as far as ANNN reaches; XO-CHIP's F000 NNNN in the program goes further
+    chip_8.execute_raw(0xA000 | u16::from_be_bytes([index[0], index[1]]) & 0xFFF);
+    for (n, opcode) in program.chunks_exact(2).enumerate() {
+        chip_8.execute_raw(u16::from_be_bytes([opcode[0], opcode[1]]));
+        if n % INSTRUCTIONS_PER_FRAME == 0 {
+            chip_8.tick_timers();
+        }
+    }
+});
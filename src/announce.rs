@@ -0,0 +1,79 @@
+//! The machine's state as plain text for screen readers, braille displays and terminals over
+//! SSH, where the window can't be seen: the registers, then the screen as its text dump.
+//! Announcements come every so many frames, or whenever asked for, and a screen that hasn't
+//! changed since the last one is only said to be unchanged rather than read out again
+
+use crate::debugger::Registers;
+use crate::Chip8;
+
+#[derive(Debug, Clone)]
+pub struct Announcer {
+    /// Frames between announcements, or 0 for only those asked for
+    every: u32,
+    frames: u32,
+    /// The screen dump last announced
+    screen: Option<String>,
+}
+
+impl Announcer {
+    pub fn new(every: u32) -> Self {
+        Self { every, frames: 0, screen: None }
+    }
+
+    /// Counts a frame, giving the announcement due at the end of it, if one is
+    pub fn frame(&mut self, chip_8: &Chip8) -> Option<String> {
+        if self.every == 0 {
+            return None;
+        }
+        self.frames += 1;
+        if self.frames < self.every {
+            return None;
+        }
+        Some(self.announce(chip_8))
+    }
+
+    /// The announcement for the machine as it is now, starting the wait for the next over
+    pub fn announce(&mut self, chip_8: &Chip8) -> String {
+        self.frames = 0;
+        let display = chip_8.framebuffer();
+        let (width, height) = display.dimensions();
+        let lit = display.pixels().filter(|&(.., on)| on).count();
+        let mut text = Registers(chip_8).to_string();
+        let screen = display.to_text();
+        if self.screen.as_ref() == Some(&screen) {
+            text.push_str(&format!("screen {width}x{height}, {lit} lit, unchanged\n"));
+        } else {
+            text.push_str(&format!("screen {width}x{height}, {lit} lit:\n{screen}"));
+            self.screen = Some(screen);
+        }
+        if chip_8.halted() {
+            text.push_str("halted\n");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcements_skip_an_unchanged_screen() {
+        // LD V0 1, LD F V1, draw the font's 0, then spin
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x01, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x06]);
+        let mut announcer = Announcer::new(2);
+        assert_eq!(announcer.frame(&chip_8), None);
+        let first = announcer.frame(&chip_8).unwrap();
+        assert!(first.starts_with("PC 0x200") && first.contains("screen 64x32, 0 lit:\n"));
+        for _ in 0..3 {
+            chip_8.step();
+        }
+        let drawn = announcer.announce(&chip_8);
+        assert!(drawn.contains("V0 01") && drawn.contains("screen 64x32, 14 lit:\n"));
+        assert!(drawn.lines().any(|line| line.starts_with("01111")));
+        // asking for one starts the count over
+        assert_eq!(announcer.frame(&chip_8), None);
+        assert!(announcer.frame(&chip_8).unwrap().ends_with("screen 64x32, 14 lit, unchanged\n"));
+        assert_eq!(Announcer::new(0).frame(&chip_8), None);
+    }
+}
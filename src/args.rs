@@ -1,3 +1,767 @@
-use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
-
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use chip_8_rs::audio::Waveform;
+use chip_8_rs::cheats::Cheat;
+use chip_8_rs::debugger::{OpcodePattern, WatchExpr, Watchpoint};
+use chip_8_rs::display::Palette;
+use chip_8_rs::fonts::FontSet;
+use chip_8_rs::highscore::ScoreWatch;
+use chip_8_rs::logging::Level;
+use chip_8_rs::netplay;
+use chip_8_rs::pad::{PadButton, PadProfile};
+use chip_8_rs::practice::LoopEnd;
+use chip_8_rs::{AlignmentPolicy, FaultPolicy, Profile, RPL_FLAGS};
+use chip_8_rs::quirks::{LoadStore, Preset};
+use chip_8_rs::variant::Variant;
+
+use crate::crt::CrtEffects;
+use crate::i18n::Locale;
+use crate::gamepad::parse_pad_binding;
+use crate::keymap::{parse_binding, Binding};
+use crate::rotation::Rotation;
+
+/// Without a subcommand the emulator runs, taking the same options as `run`
+#[derive(Debug, Parser)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Language for the messages the emulator prints; defaults to LC_ALL/LC_MESSAGES/LANG
+    #[arg(long, value_enum, global = true)]
+    pub lang: Option<Locale>,
+
+    /// How much to log of what the emulator does, such as faults and the devices it set up
+    #[arg(long, value_enum, default_value_t = Level::Info, global = true)]
+    pub log_level: Level,
+
+    /// Append the log to this file instead of writing it to stderr
+    #[arg(long, value_name = "FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// ROM to run, or a directory or zip archive of ROMs to pick from in the built-in menu;
+    /// `pack.zip/pong.ch8` runs one from an archive. `-` reads the ROM from stdin, and builds
+    /// with the net feature take http(s) URLs. Without one the built-in demo runs, as does
+    /// `builtin:demo`
+    #[arg(value_name = "ROM", value_parser = parse_existing)]
+    pub rom: Option<PathBuf>,
+
+    /// Another ROM to switch to with Ctrl+PageDown and back with Ctrl+PageUp, each keeping its
+    /// place while the others run (repeatable)
+    #[arg(long, value_name = "ROM", value_parser = parse_existing)]
+    pub also: Vec<PathBuf>,
+
+    /// Directory or zip archive of ROMs to pick from when no ROM is given; defaults to roms in
+    /// the data directory, if there is one
+    #[arg(long, value_name = "DIR", value_parser = parse_existing)]
+    pub rom_dir: Option<PathBuf>,
+
+    /// Initial window size in pixels per CHIP-8 pixel; the window can be resized freely after
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=32))]
+    pub scale: u32,
+
+    /// Instructions executed per second [default: 700, or the ROM database's rate for the ROM]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ips: Option<u32>,
+
+    /// Machine profile; `dev` prints bytes the ROM writes to 0xFFF on the console
+    #[arg(long, value_enum, default_value_t)]
+    pub profile: Profile,
+
+    /// Print which quirk-dependent behaviors the ROM relied on when exiting
+    #[arg(long)]
+    pub diagnose_quirks: bool,
+
+    /// How to treat the program counter landing on an odd address
+    #[arg(long, value_enum, default_value_t)]
+    pub odd_pc: AlignmentPolicy,
+
+    /// What to do when the ROM runs an invalid opcode, misuses the stack or reaches past memory
+    #[arg(long, value_enum, default_value_t)]
+    pub on_fault: FaultPolicy,
+
+    /// How deep subroutine calls can nest before a call faults
+    #[arg(long, default_value_t = chip_8_rs::STACK_DEPTH as u8, value_parser = clap::value_parser!(u8).range(1..))]
+    pub stack_depth: u8,
+
+    /// Also treat writes below 0x200, where the font and the original interpreters live, as faults
+    #[arg(long)]
+    pub strict_memory: bool,
+
+    /// Memory holding the game's score, tracked in a persistent leaderboard and shown in the
+    /// ROM menu, e.g. --score 0x3f0:2, or rpl:2 for the first two RPL flags
+    #[arg(long, value_name = "ADDR[:LEN]", value_parser = parse_score)]
+    pub score: Option<ScoreWatch>,
+
+    /// Interpret the score bytes as one decimal digit each (as stored by FX33)
+    #[arg(long, requires = "score")]
+    pub score_bcd: bool,
+
+    /// Write the session summary to this file on exit instead of printing it
+    #[arg(long, value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
+
+    /// On exit, write an HTML report with the final screen, an instruction histogram, a coverage
+    /// map, the invalid opcodes hit and the configuration, for attaching to bug reports
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// On exit, write a profile of the run to this file, or print it for -: the most executed
+    /// instructions, the hot loops, the instruction forms, and how much went to polling the
+    /// delay timer
+    #[arg(long, value_name = "PATH")]
+    pub profile_opcodes: Option<PathBuf>,
+
+    /// On exit, write a map of memory to this file, or print it for -: a character per byte
+    /// telling code that was executed from data that was only read or written, and both from
+    /// bytes never touched
+    #[arg(long, value_name = "PATH")]
+    pub coverage_map: Option<PathBuf>,
+
+    /// Log every instruction executed to stderr, with the registers it used and changed
+    #[arg(long)]
+    pub trace: bool,
+
+    /// Keep only the last N traced instructions, and log them when the program hits an invalid
+    /// opcode or halts
+    #[arg(long, value_name = "N", requires = "trace")]
+    pub trace_ring: Option<usize>,
+
+    /// Emulate a frame ahead and roll back when input changes, hiding a frame of input latency
+    #[arg(long)]
+    pub run_ahead: bool,
+
+    /// How many seconds of play holding Backspace can rewind; 0 disables rewinding
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub rewind_seconds: u32,
+
+    /// Frames between rewind snapshots; higher uses less memory but rewinds more coarsely
+    #[arg(long, value_name = "FRAMES", default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rewind_interval: u32,
+
+    /// Speed while the slow motion keys (Shift+Tab) are held, as a fraction of full speed
+    #[arg(long, value_name = "SPEED", default_value_t = 0.25, value_parser = parse_speed)]
+    pub slow_motion: f64,
+
+    /// Fast forward from the start until the ROM first draws or checks the keypad, past the
+    /// delay loops some spend a while in before showing anything
+    #[arg(long)]
+    pub turbo_boot: bool,
+
+    /// Shape of the beep
+    #[arg(long, value_enum, default_value_t)]
+    pub waveform: Waveform,
+
+    /// Pitch of the beep in Hz
+    #[arg(long, value_name = "HZ", default_value_t = 440.0, value_parser = parse_tone)]
+    pub tone: f32,
+
+    /// Loudness of the beep, from 0 (silent) to 1
+    #[arg(long, default_value_t = 0.25, value_parser = parse_volume)]
+    pub volume: f32,
+
+    /// Play the beep on this output device instead of the default one; see --list-audio-devices
+    #[arg(long, value_name = "NAME")]
+    pub audio_device: Option<String>,
+
+    /// List the audio output devices --audio-device can pick, then exit
+    #[arg(long)]
+    pub list_audio_devices: bool,
+
+    /// Check the core's arithmetic, BCD, FX55/FX65 and shifts under both quirk settings
+    /// instruction by instruction, then exit, failing if any check does
+    #[arg(long)]
+    pub self_test: bool,
+
+    /// Audio sample rate to ask the device for
+    #[arg(long, value_name = "HZ", default_value_t = 44100, value_parser = clap::value_parser!(i32).range(8000..=192000))]
+    pub sample_rate: i32,
+
+    /// Samples buffered at a time, a power of two; smaller cuts latency but may crackle
+    /// [default: about 5ms worth, 256 at 44100Hz]
+    #[arg(long, value_name = "SAMPLES", value_parser = parse_audio_buffer)]
+    pub audio_buffer: Option<u16>,
+
+    /// Fixed XO-CHIP audio playback rate in Hz, ignoring the pitch set by FX3A
+    #[arg(long, value_name = "HZ")]
+    pub playback_rate: Option<f32>,
+
+    /// Interpreter whose quirks to emulate [default: schip, or the ROM database's platform and
+    /// quirks for the ROM]
+    #[arg(long, value_enum)]
+    pub quirks: Option<Preset>,
+
+    /// Emulate one interpreter whole, faulting on the instructions it didn't have: chip8,
+    /// chip8x, schip-1.0, schip-1.1, xochip, megachip, or auto for the one the ROM's code needs.
+    /// Its quirks apply unless --quirks says otherwise
+    #[arg(long, value_name = "VARIANT", value_parser = parse_variant)]
+    pub variant: Option<VariantChoice>,
+
+    /// ROM database of the platform, rate and quirks known ROMs want, as `[crc32]` tables of
+    /// TOML keys; defaults to romdb.toml in the data directory
+    #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+    pub rom_db: Option<PathBuf>,
+
+    /// Override the preset's FX55/FX65 register range and I behavior
+    #[arg(long, value_enum)]
+    pub load_store: Option<LoadStore>,
+
+    /// Generate CXNN random numbers the way the COSMAC VIP interpreter did
+    #[arg(long)]
+    pub vip_rng: bool,
+
+    /// Give each instruction as long as it took on the COSMAC VIP, instead of running --ips
+    /// instructions a second whatever they are. DXYN then always waits for the next frame
+    #[arg(long)]
+    pub vip_timing: bool,
+
+    /// Scroll the lo-res screen half as far, as SUPER-CHIP 1.0 and 1.1 did
+    #[arg(long)]
+    pub half_scroll: bool,
+
+    /// Finish FX0A's wait as soon as a key goes down, instead of once it's released again as
+    /// on the COSMAC VIP, for ROMs that expect the key while it's still held
+    #[arg(long)]
+    pub key_wait_press: bool,
+
+    /// Make DXYN wait for the next 60Hz frame, as on the COSMAC VIP, which keeps games to a
+    /// sprite a frame and tames their flicker. Without --ips the rate goes up to 1000, the wait
+    /// then pacing the game
+    #[arg(long)]
+    pub display_wait: bool,
+
+    /// Seed the CXNN random number generator, so every run draws the same numbers
+    #[arg(long, value_parser = parse_number)]
+    pub seed: Option<u32>,
+
+    /// Record the keypad, frame by frame, along with the CXNN seed, for --replay
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    pub record_input: Option<PathBuf>,
+
+    /// Play back a recording made with --record-input instead of reading the keyboard
+    #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+    pub replay: Option<PathBuf>,
+
+    /// Host a two-player game for --netplay-join to join, waiting on ADDR, e.g. 0.0.0.0:7700.
+    /// Both machines run with both players' keys, and pausing either holds up the other
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["replay", "record_input", "run_ahead", "netplay_join"])]
+    pub netplay_host: Option<String>,
+
+    /// Join the two-player game hosted at HOST:PORT, under the host's --seed and delay
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["replay", "record_input", "run_ahead"])]
+    pub netplay_join: Option<String>,
+
+    /// Frames keys take to go into effect under --netplay-host, more hiding a slower connection
+    #[arg(long, value_name = "FRAMES", default_value_t = netplay::DEFAULT_DELAY)]
+    pub netplay_delay: u8,
+
+    /// Record every displayed frame to a frame journal
+    #[arg(long, value_name = "PATH")]
+    pub record_frames: Option<PathBuf>,
+
+    /// Compare every frame against a recorded journal, stopping at and highlighting the first difference
+    #[arg(long, value_name = "PATH")]
+    pub compare_frames: Option<PathBuf>,
+
+    /// IPS or BPS patch to apply to the ROM when loading it
+    #[arg(long, value_name = "PATH")]
+    pub patch: Option<PathBuf>,
+
+    /// Patch a ROM byte before running, e.g. --poke 0x2a0=0x12 (repeatable)
+    #[arg(long, value_name = "ADDR=BYTE", value_parser = parse_poke)]
+    pub poke: Vec<(u16, u8)>,
+
+    /// Keep a memory byte or register at a value every frame, e.g. --cheat 0x3f0=3 or
+    /// --cheat V5=9 (repeatable, on top of any .cht file next to the ROM)
+    #[arg(long, value_name = "TARGET=BYTE")]
+    pub cheat: Vec<Cheat>,
+
+    /// Run a hook script along with the ROM, pressing keys, setting memory and printing as it
+    /// says on frames, instructions and memory writes (see the script module for the language)
+    #[arg(long, value_name = "PATH")]
+    pub script: Option<PathBuf>,
+
+    /// Reset and load the ROM again whenever its file changes, e.g. rebuilt by an assembler
+    /// (toggle with Ctrl+L)
+    #[arg(long)]
+    pub reload: bool,
+
+    /// Where F2 writes the (patched) ROM
+    #[arg(long, value_name = "PATH")]
+    pub save_rom: Option<PathBuf>,
+
+    /// Write a crash report into DIR the first time the ROM faults: the fault, the ROM's hashes,
+    /// the quirks, the last instructions run and the state, to attach to an issue
+    #[arg(long, value_name = "DIR")]
+    pub crash_dir: Option<PathBuf>,
+
+    /// Where F12 saves screenshots
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub screenshot_dir: PathBuf,
+
+    /// Size of a CHIP-8 pixel in screenshots, in image pixels (half that in hi-res); 1 saves the
+    /// framebuffer exactly as it is
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=32))]
+    pub screenshot_scale: u32,
+
+    /// Save a text dump of the screen next to each screenshot, a 0 or 1 per pixel
+    #[arg(long)]
+    pub screenshot_text: bool,
+
+    /// Record gameplay from the start, as an animated GIF if PATH ends in .gif and otherwise
+    /// through ffmpeg, e.g. out.mp4. Ctrl+R starts and stops recording to the screenshot directory
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Write the sound as it's played, beeps and XO-CHIP patterns alike, to a WAV file
+    #[arg(long, value_name = "PATH")]
+    pub record_audio: Option<PathBuf>,
+
+    /// Size of a hi-res pixel in recordings, in video pixels, lo-res pixels being twice that
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub record_scale: u32,
+
+    /// Where to play: in an SDL window, drawn with text in the terminal, or nowhere, of the
+    /// frontends this build has
+    #[arg(long, value_enum, default_value_t)]
+    pub frontend: Frontend,
+
+    /// Run without a window or audio, for --frames frames or until the ROM halts or reaches a
+    /// breakpoint, then write the screen to --output; the same as --frontend headless
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Print the registers and the screen as text every SECS seconds, for screen readers and
+    /// terminals over SSH; without SECS only when Ctrl+T asks
+    #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "0")]
+    pub announce: Option<u32>,
+
+    /// Send announcements to a TCP listener, e.g. localhost:7878, instead of stdout
+    #[arg(long, value_name = "HOST:PORT", requires = "announce")]
+    pub announce_to: Option<String>,
+
+    /// How many 60Hz frames --headless runs for at most
+    #[arg(long, default_value_t = 600)]
+    pub frames: u32,
+
+    /// Where --headless writes the final screen: a .png or .ppm image scaled by
+    /// --screenshot-scale, any other path as text, or - for text on stdout
+    #[arg(long, value_name = "PATH", default_value = "-")]
+    pub output: PathBuf,
+
+    /// Run without a window, streaming the screen to and taking the keypad from clients
+    /// connected over TCP to this address, e.g. 0.0.0.0:8008 (see the connect command)
+    #[arg(long, value_name = "ADDR", conflicts_with = "headless")]
+    pub serve: Option<String>,
+
+    /// Let GDB or another frontend speaking its remote protocol attach on this address, e.g.
+    /// localhost:1234 for `target remote localhost:1234`
+    #[arg(long, value_name = "ADDR")]
+    pub gdb: Option<String>,
+
+    /// Pause in the debugger before executing the instruction at this address (repeatable)
+    #[arg(long = "break", visible_alias = "break-at", value_name = "ADDR", value_parser = parse_addr)]
+    pub breakpoints: Vec<u16>,
+
+    /// Pause in the debugger before executing any instruction like this, with X, Y or N for the
+    /// nibbles that don't matter, e.g. --break-on-op DXYN (repeatable)
+    #[arg(long = "break-on-op", value_name = "OPCODE")]
+    pub opcode_breakpoints: Vec<OpcodePattern>,
+
+    /// How many of the latest instructions the paused debugger can look back through with , and
+    /// ., 0 to keep none
+    #[arg(long, value_name = "N", default_value_t = 10_000)]
+    pub history: usize,
+
+    /// Pause in the debugger after an instruction reads or writes this address or register,
+    /// e.g. --watch 0x3f0, or --watch V5:w for writes only (repeatable)
+    #[arg(long, value_name = "TARGET[:r|:w]")]
+    pub watch: Vec<Watchpoint>,
+
+    /// Keep a value on screen as the game runs, in the top right corner: a register, I, PC, DT,
+    /// ST or a byte of memory, e.g. --show V3 or --show [0x300] (repeatable)
+    #[arg(long, value_name = "EXPR")]
+    pub show: Vec<WatchExpr>,
+
+    /// Symbol file naming the ROM's addresses for the debugger and traces, one `addr=name` or
+    /// Octo `:const name addr` a line; defaults to a .sym file next to the ROM
+    #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+    pub symbols: Option<PathBuf>,
+
+    /// Keymap file replacing the default 1234/QWER/ASDF/ZXCV bindings
+    #[arg(long, value_name = "PATH")]
+    pub keymap: Option<PathBuf>,
+
+    /// Settings file of palette, platform, quirks, ips and bindings, over the command line's
+    /// and reapplied whenever it changes; quirks wait for the next reset
+    #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+    pub config: Option<PathBuf>,
+
+    /// Bind the default keys by their names instead of their positions, so e.g. an AZERTY
+    /// keyboard's A is keypad 7 rather than its Q
+    #[arg(long, conflicts_with = "keymap")]
+    pub keycodes: bool,
+
+    /// Keep running and sounding while the window is in the background, instead of pausing
+    /// until it has focus again
+    #[arg(long)]
+    pub run_in_background: bool,
+
+    /// Save the machine's state on quitting, and pick up from it the next time the ROM runs
+    #[arg(long)]
+    pub autosave: bool,
+
+    /// Where a practice loop marked with Ctrl+A goes back to its start: an address the PC
+    /// reaches, e.g. 0x2a4, or a time, e.g. 10s. Without it, Ctrl+B marks the end as it's played
+    #[arg(long, value_name = "END")]
+    pub loop_end: Option<LoopEnd>,
+
+    /// Bind a host key to a keypad key, e.g. --bind Up=5 or --bind scancode:W=5 (repeatable)
+    #[arg(long, value_name = "NAME=KEY", value_parser = parse_binding)]
+    pub bind: Vec<(Binding, u8)>,
+
+    /// Controller profile: a layout, wasd or numpad, and any buttons bound otherwise, e.g.
+    /// --pad "numpad, b=4". Without it, the ROM database's for the ROM, if it has one
+    #[arg(long, value_name = "PROFILE")]
+    pub pad: Option<PadProfile>,
+
+    /// Bind a controller button to a keypad key, e.g. --pad-bind a=5, on top of the profile
+    /// (repeatable)
+    #[arg(long, value_name = "BUTTON=KEY", value_parser = parse_pad_binding)]
+    pub pad_bind: Vec<(PadButton, u8)>,
+
+    /// Rumble controllers that can while the sound timer runs
+    #[arg(long)]
+    pub rumble: bool,
+
+    /// Show which keys are held each frame along the bottom of the window (toggle with F4)
+    #[arg(long)]
+    pub input_display: bool,
+
+    /// Run the ROM a second time beside the first under this preset's quirks, with the same keys
+    /// held, pausing at the first frame they differ and naming the quirks behind it
+    #[arg(long, value_enum, value_name = "PRESET")]
+    pub compare: Option<Preset>,
+
+    /// Show a clickable keypad below the game, lighting the keys held and outlining the ones the
+    /// ROM checks
+    #[arg(long)]
+    pub keypad_panel: bool,
+
+    /// Turn the picture clockwise by this many degrees, for ROMs drawn for a screen on its side.
+    /// The 5/7/8/9 directions turn with it. The keypad panel only fits under an upright screen
+    #[arg(long, value_name = "DEGREES", default_value = "0", conflicts_with = "keypad_panel")]
+    pub rotate: Rotation,
+
+    /// Show the ROM's name and the frames and instructions per second in a corner of the game,
+    /// instead of in the window title
+    #[arg(long)]
+    pub hud: bool,
+
+    /// Show the registers, timers, stack and last instruction over the game (toggle with Ctrl+D)
+    #[arg(long)]
+    pub debug_overlay: bool,
+
+    /// Don't show the keypad overlay at startup for ROMs with input hints
+    #[arg(long)]
+    pub no_hints: bool,
+
+    /// Average this many frames when drawing, so games that flicker their sprites look steady
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u8).range(2..=8))]
+    pub flicker_fusion: Option<u8>,
+
+    /// Fade pixels out over this many frames when they're turned off, like a CRT's phosphor,
+    /// instead of averaging frames
+    #[arg(long, value_name = "FRAMES", conflicts_with = "flicker_fusion", value_parser = clap::value_parser!(u8).range(2..=30))]
+    pub phosphor: Option<u8>,
+
+    /// Hold back frames that would flash most of the screen more than three times a second,
+    /// for players sensitive to flashing, instead of averaging or fading frames
+    #[arg(long, conflicts_with_all = ["flicker_fusion", "phosphor"])]
+    pub reduce_flashing: bool,
+
+    /// CRT effects to draw over the game, any of scanlines, grid and vignette separated by
+    /// commas. Ctrl+1, Ctrl+2 and Ctrl+3 toggle them while playing
+    #[arg(long, value_name = "EFFECTS", default_value = "none")]
+    pub crt: CrtEffects,
+
+    /// Colors to draw in: classic, green, amber, lcd, inverted, contrast (high contrast) or
+    /// colorblind (XO-CHIP's planes in colors that stay apart with color blindness), or hex
+    /// colors for unlit and lit pixels, then optionally XO-CHIP's second plane and both planes,
+    /// e.g. 000000,ffffff
+    #[arg(long, value_name = "NAME|COLORS", default_value = "classic")]
+    pub palette: Palette,
+
+    /// Whose hex digit glyphs FX29 and FX30 draw: octo, vip, eti-660, dream-6800 or schip
+    #[arg(long, value_name = "NAME", default_value = "octo")]
+    pub font: FontSet,
+}
+
+/// Instructions per second when neither --ips nor the ROM database sets a rate
+pub const DEFAULT_IPS: u32 = 700;
+
+/// The instruction rate --display-wait runs at unless told otherwise: enough for the code
+/// between draws to finish within a frame, so that the wait sets the pace
+pub const DISPLAY_WAIT_IPS: u32 = 1000;
+
+impl RunArgs {
+    /// The --quirks preset, or the default one
+    pub fn preset(&self) -> Preset {
+        self.quirks.unwrap_or_default()
+    }
+
+    /// The --ips rate, or the default one
+    pub fn ips(&self) -> u32 {
+        self.ips.unwrap_or(DEFAULT_IPS)
+    }
+}
+
+/// Where a ROM plays. Only the frontends built in are offered, see COMPILED
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Frontend {
+    #[default]
+    Sdl,
+    Tui,
+    Headless,
+}
+
+impl Frontend {
+    /// The frontends this build has, by the cargo features it was built with
+    pub const COMPILED: &'static [Frontend] = &[
+        Frontend::Sdl,
+        #[cfg(feature = "tui")]
+        Frontend::Tui,
+        #[cfg(feature = "headless")]
+        Frontend::Headless,
+    ];
+
+    /// The cargo feature that builds it in
+    pub fn feature(self) -> &'static str {
+        match self {
+            Frontend::Sdl => "sdl",
+            Frontend::Tui => "tui",
+            Frontend::Headless => "headless",
+        }
+    }
+}
+
+impl clap::ValueEnum for Frontend {
+    fn value_variants<'a>() -> &'a [Self] {
+        Self::COMPILED
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        let (name, help) = match self {
+            Frontend::Sdl => ("sdl", "A window, with sound, controllers and the debugger"),
+            Frontend::Tui => ("tui", "Half-block characters, two pixels to a cell, with the keypad on the usual keys. Esc quits"),
+            Frontend::Headless => ("headless", "No window or audio, as --headless"),
+        };
+        Some(clap::builder::PossibleValue::new(name).help(help))
+    }
+}
+
+/// Running a ROM, and tools that work on ROM files instead
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a ROM, or pick one from a directory in the built-in menu (the default)
+    Run(Box<RunArgs>),
+    /// Print an annotated disassembly of a ROM, telling code from data by following its jumps
+    Disasm {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// Symbol file naming addresses in the listing; defaults to a .sym file next to the ROM
+        #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+        symbols: Option<PathBuf>,
+        /// Interpreter whose instructions to decode
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+    },
+    /// Run a ROM without a window for a while and report invalid opcodes, faults and the quirks it relies on
+    Check {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// How many 60Hz frames to run
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+        /// Interpreter whose quirks to emulate
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+    },
+    /// Run a ROM headless as fast as possible for a number of instructions and report how many
+    /// a second it managed, and where the time went; numbers worth comparing need --release
+    Bench {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// How many instructions to run
+        #[arg(long, default_value_t = 10_000_000, value_parser = clap::value_parser!(u64).range(1..))]
+        instructions: u64,
+        /// Interpreter whose quirks to emulate
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+    },
+    /// Run the built-in conformance checks on every preset and print a pass/fail matrix
+    Devtest {
+        /// Also run this ROM on every preset, passing if it doesn't fault
+        #[arg(long, value_parser = parse_existing)]
+        rom: Option<PathBuf>,
+        /// Keep going, checking again whenever the emulator's source or the ROM changes
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Run a ROM in lockstep with a reference emulator and stop where the two first disagree.
+    /// The reference is the command after `--`, talking the protocol described in lockstep.rs
+    Lockstep {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// How many 60Hz frames to run
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+        /// Interpreter whose quirks to emulate
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+        /// Instructions between comparisons; more is faster, 1 finds the exact instruction
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        granularity: u32,
+        #[arg(last = true, required = true, value_name = "REFERENCE")]
+        reference: Vec<String>,
+    },
+    /// Run a ROM alongside the built-in reference interpreter, or against a trace another
+    /// emulator recorded, and stop where they first disagree with both states; described in
+    /// reference.rs
+    Verify {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// How many 60Hz frames to run against the reference interpreter
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+        /// Interpreter whose quirks to emulate
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+        /// Check against this trace of registers instead, a line per instruction
+        #[arg(long, value_name = "PATH", value_parser = parse_existing)]
+        trace: Option<PathBuf>,
+    },
+    /// Answer the lockstep protocol on stdin and stdout, to be another instance's reference
+    LockstepServe {
+        #[arg(value_parser = parse_existing)]
+        rom: PathBuf,
+        /// Interpreter whose quirks to emulate
+        #[arg(long, value_enum, default_value_t)]
+        quirks: Preset,
+    },
+    /// Play a ROM another instance is running with --serve, in a window on this machine
+    Connect {
+        /// The server's address, e.g. 192.168.1.20:8008
+        addr: String,
+        /// Initial window size in pixels per CHIP-8 pixel
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=32))]
+        scale: u32,
+    },
+    /// Assemble a source file written in the disassembler's syntax, or Octo's for a .8o file, into a ROM
+    Asm {
+        #[arg(value_parser = parse_existing)]
+        source: PathBuf,
+        /// Where to write the ROM; defaults to the source path with a .ch8 extension
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print the registers, timers, stack, memory and pixels that differ between two save states,
+    /// as files or as Ctrl+C copies them; exits with 1 if anything does
+    Diff {
+        #[arg(value_parser = parse_existing)]
+        a: PathBuf,
+        #[arg(value_parser = parse_existing)]
+        b: PathBuf,
+    },
+}
+
+/// Accepts paths that exist, so a typo is reported up front rather than as a failed read later
+fn parse_existing(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if !path.exists() && s != "-" && s != crate::source::DEMO && !crate::source::is_url(s) && crate::source::zip_entry(&path).is_none() {
+        return Err(format!("no such file or directory: {s}"));
+    }
+    Ok(path)
+}
+
+/// Parses a decimal or 0x-prefixed hexadecimal number
+pub fn parse_number(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|e| format!("invalid number `{s}`: {e}"))
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed <= 1.0 => Ok(speed),
+        _ => Err(format!("expected a speed above 0 and at most 1, got `{s}`")),
+    }
+}
+
+fn parse_tone(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(hz) if (20.0..=20_000.0).contains(&hz) => Ok(hz),
+        _ => Err(format!("expected a frequency from 20 to 20000 Hz, got `{s}`")),
+    }
+}
+
+fn parse_audio_buffer(s: &str) -> Result<u16, String> {
+    match s.parse::<u16>() {
+        Ok(samples) if samples.is_power_of_two() && samples >= 64 => Ok(samples),
+        _ => Err(format!("expected a power of two from 64 to 32768, got `{s}`")),
+    }
+}
+
+fn parse_volume(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+        _ => Err(format!("expected a volume from 0 to 1, got `{s}`")),
+    }
+}
+
+/// A --variant by name, or the one detected from the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantChoice {
+    Auto,
+    Named(Variant),
+}
+
+fn parse_variant(s: &str) -> Result<VariantChoice, String> {
+    match s {
+        "auto" => Ok(VariantChoice::Auto),
+        _ => Variant::from_str(s, true).map(VariantChoice::Named),
+    }
+}
+
+fn parse_addr(s: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(s)?).map_err(|_| format!("address out of range: {s}"))
+}
+
+fn parse_poke(s: &str) -> Result<(u16, u8), String> {
+    let (addr, byte) = s.split_once('=').ok_or_else(|| format!("expected ADDR=BYTE, got `{s}`"))?;
+    let addr = parse_addr(addr)?;
+    let byte = u8::try_from(parse_number(byte)?).map_err(|_| format!("byte out of range: {byte}"))?;
+    Ok((addr, byte))
+}
+
+/// `ADDR[:LEN]` in memory, or `rpl[:LEN]` for the RPL flags from the first
+fn parse_score(s: &str) -> Result<ScoreWatch, String> {
+    let (addr, len) = s.split_once(':').unwrap_or((s, "1"));
+    let rpl = addr.trim().eq_ignore_ascii_case("rpl");
+    let addr = if rpl { 0 } else { parse_addr(addr)? };
+    match parse_number(len)? {
+        len if rpl && len as usize > RPL_FLAGS => Err(format!("there are only {RPL_FLAGS} RPL flags, got {len}")),
+        len @ 1..=8 => Ok(ScoreWatch { addr, len: len as u8, bcd: false, rpl }),
+        _ => Err(format!("score length must be 1 to 8 bytes, got {len}")),
+    }
+}
@@ -0,0 +1,226 @@
+//! Assembler for the mnemonic syntax the disassembler prints.
+//!
+//! One statement per line, `;` starts a comment and `name:` defines a label at the current
+//! address. Operands are registers (`V0`-`VF`, `I`, `[I]`, `DT`, `ST`, `K`, `F`, `HF`, `B`, `R`),
+//! numbers (decimal, `0x` hex or `0b` binary) or labels. `db` emits its comma separated operands
+//! as raw bytes, for sprites and other data.
+//!
+//! ```text
+//! start:  CLS
+//!         LD I, ball
+//!         DRW V0, V1, 2
+//! loop:   JP loop
+//! ball:   db 0b11000000, 0b11000000
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::disasm::PROGRAM_START;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based source line
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Value(u16),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+/// Assembles `source` into a ROM to be loaded at 0x200
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_at(source, PROGRAM_START)
+}
+
+/// Assembles `source` into a ROM to be loaded at `origin`, for machines such as CHIP-8X that
+/// load programs elsewhere
+pub fn assemble_at(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    // first pass: find every label's address
+    let mut labels = BTreeMap::new();
+    let mut statements = vec![];
+    let mut addr = origin as usize;
+    for (n, line) in source.lines().enumerate() {
+        let err = |message: String| AsmError { line: n + 1, message };
+        let mut rest = line.split(';').next().unwrap_or_default().trim();
+        while let Some((label, after)) = rest.split_once(':').filter(|(label, _)| is_identifier(label.trim())) {
+            if labels.insert(label.trim(), addr as u16).is_some() {
+                return Err(err(format!("label `{}` is defined twice", label.trim())));
+            }
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let operands: Vec<&str> = match operands.trim() {
+            "" => vec![],
+            operands => operands.split(',').map(str::trim).collect(),
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        addr += if mnemonic == "DB" { operands.len() } else { 2 };
+        if addr > 0x1000 {
+            return Err(err("program doesn't fit in memory".to_string()));
+        }
+        statements.push(Statement { line: n + 1, mnemonic, operands });
+    }
+
+    // second pass: encode, now that forward references resolve
+    let mut rom = vec![];
+    for statement in &statements {
+        let err = |message: String| AsmError { line: statement.line, message };
+        if statement.mnemonic == "DB" {
+            for operand in &statement.operands {
+                let byte = value(operand, &labels).and_then(|value| fits(value, 0xFF, "byte")).map_err(err)?;
+                rom.push(byte as u8);
+            }
+            continue;
+        }
+        let operands = statement.operands
+            .iter()
+            .map(|operand| parse_operand(operand, &labels))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(err)?;
+        let opcode = encode(&statement.mnemonic, &operands).map_err(err)?;
+        rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+    Ok(rom)
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn value(s: &str, labels: &BTreeMap<&str, u16>) -> Result<u16, String> {
+    let parsed = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16)
+    } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        u16::from_str_radix(bin, 2)
+    } else if s.starts_with(|c: char| c.is_ascii_digit()) {
+        s.parse()
+    } else {
+        return labels.get(s).copied().ok_or_else(|| format!("unknown label `{s}`"));
+    };
+    parsed.map_err(|e| format!("invalid number `{s}`: {e}"))
+}
+
+fn parse_operand(s: &str, labels: &BTreeMap<&str, u16>) -> Result<Operand, String> {
+    let upper = s.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        register if register.len() == 2 && register.starts_with('V') => {
+            match u16::from_str_radix(&register[1..], 16) {
+                Ok(x) => Operand::V(x),
+                Err(_) => Operand::Value(value(s, labels)?),
+            }
+        }
+        _ => Operand::Value(value(s, labels)?),
+    };
+    Ok(operand)
+}
+
+fn fits(value: u16, max: u16, what: &str) -> Result<u16, String> {
+    if value > max {
+        return Err(format!("{value:#x} doesn't fit in a {what}"));
+    }
+    Ok(value)
+}
+
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+    use Operand::*;
+    let addr = |a: u16| fits(a, 0xFFF, "12-bit address");
+    let byte = |b: u16| fits(b, 0xFF, "byte");
+    let nibble = |n: u16| fits(n, 0xF, "nibble");
+    let xy = |x: u16, y: u16| x << 8 | y << 4;
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Value(n)]) => 0x00C0 | nibble(*n)?,
+        ("SCU", [Value(n)]) => 0x00B0 | nibble(*n)?,
+        ("SCUP", [Value(n)]) => 0x00D0 | nibble(*n)?,
+        ("MEGAOFF", []) => 0x0010,
+        ("MEGAON", []) => 0x0011,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [Value(a)]) => addr(*a)?,
+        ("JP", [Value(a)]) => 0x1000 | addr(*a)?,
+        ("JP", [V(0), Value(a)]) => 0xB000 | addr(*a)?,
+        ("CALL", [Value(a)]) => 0x2000 | addr(*a)?,
+        ("SE", [V(x), Value(b)]) => 0x3000 | x << 8 | byte(*b)?,
+        ("SNE", [V(x), Value(b)]) => 0x4000 | x << 8 | byte(*b)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+        ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+        ("LD", [V(x), Value(b)]) => 0x6000 | x << 8 | byte(*b)?,
+        ("ADD", [V(x), Value(b)]) => 0x7000 | x << 8 | byte(*b)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+        ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+        ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+        ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+        ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+        ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+        ("SHR", [V(x)]) => 0x8006 | xy(*x, *x),
+        ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+        ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+        ("SHL", [V(x)]) => 0x800E | xy(*x, *x),
+        ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+        ("LD", [I, Value(a)]) => 0xA000 | addr(*a)?,
+        ("RND", [V(x), Value(b)]) => 0xC000 | x << 8 | byte(*b)?,
+        ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(*x, *y) | nibble(*n)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+        ("LD", [St, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [Hf, V(x)]) => 0xF030 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("AUDIO", []) => 0xF002,
+        ("PITCH", [V(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [R, V(x)]) => 0xF075 | x << 8,
+        ("LD", [V(x), R]) => 0xF085 | x << 8,
+        _ => return Err(format!("no `{mnemonic}` instruction takes these operands")),
+    };
+    Ok(opcode)
+}
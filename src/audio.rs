@@ -0,0 +1,226 @@
+//! The beep's tone, and XO-CHIP audio pitch handling
+
+/// Bytes in the XO-CHIP audio pattern buffer, 128 1-bit samples
+pub const AUDIO_PATTERN_LEN: usize = 16;
+
+/// Pitch register value XO-CHIP starts with, giving a 4000Hz playback rate
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// XO-CHIP sample playback rate for a pitch register value: 4000 * 2^((pitch - 64) / 48)
+pub fn playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// The pitch register and an optional fixed playback rate that overrides it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pitch {
+    pub register: u8,
+    pub rate_override: Option<f32>,
+}
+
+impl Default for Pitch {
+    fn default() -> Self {
+        Self { register: DEFAULT_PITCH, rate_override: None }
+    }
+}
+
+impl Pitch {
+    /// Samples per second the pattern buffer should play at
+    pub fn rate(&self) -> f32 {
+        self.rate_override.unwrap_or_else(|| playback_rate(self.register))
+    }
+}
+
+/// Ramps the output gain towards on or off over a short time instead of switching instantly,
+/// so pausing doesn't click and the device can keep running for a quick resume
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fader {
+    gain: f32,
+    target: f32,
+    step: f32,
+}
+
+impl Fader {
+    /// A fader that starts fully on and takes `fade` to go from on to off at `sample_rate`
+    pub fn new(sample_rate: i32, fade: std::time::Duration) -> Self {
+        let samples = (sample_rate as f32 * fade.as_secs_f32()).max(1.0);
+        Self { gain: 1.0, target: 1.0, step: 1.0 / samples }
+    }
+
+    /// A fader like [`Fader::new`] that starts fully off
+    pub fn silent(sample_rate: i32, fade: std::time::Duration) -> Self {
+        Self { gain: 0.0, target: 0.0, ..Self::new(sample_rate, fade) }
+    }
+
+    pub fn set(&mut self, on: bool) {
+        self.target = if on { 1.0 } else { 0.0 };
+    }
+
+    /// Gain for the next sample
+    pub fn next_gain(&mut self) -> f32 {
+        if self.gain < self.target {
+            self.gain = (self.gain + self.step).min(self.target);
+        } else if self.gain > self.target {
+            self.gain = (self.gain - self.step).max(self.target);
+        }
+        self.gain
+    }
+}
+
+/// Shape of the beep
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sine,
+    /// White noise, for a hiss instead of a tone
+    Noise,
+}
+
+/// What the beep sounds like
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// In Hz; for noise, how often a new random level is picked
+    pub frequency: f32,
+    /// Peak amplitude, 0 to 1
+    pub volume: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self { waveform: Waveform::Square, frequency: 440.0, volume: 0.25 }
+    }
+}
+
+/// Generates `tone` one sample at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oscillator {
+    tone: Tone,
+    /// Position in the current cycle, 0 to 1
+    phase: f32,
+    phase_inc: f32,
+    /// A 16-bit linear feedback shift register for noise, and the level it last gave
+    lfsr: u16,
+    noise: f32,
+}
+
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: i32) -> Self {
+        Self { tone, phase: 0.0, phase_inc: tone.frequency / sample_rate as f32, lfsr: 0xACE1, noise: 0.0 }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let phase = self.phase;
+        self.phase += self.phase_inc;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            let bit = (self.lfsr ^ self.lfsr >> 2 ^ self.lfsr >> 3 ^ self.lfsr >> 5) & 1;
+            self.lfsr = self.lfsr >> 1 | bit << 15;
+            self.noise = self.lfsr as f32 / u16::MAX as f32 * 2.0 - 1.0;
+        }
+        let level = match self.tone.waveform {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+            Waveform::Noise => self.noise,
+        };
+        level * self.tone.volume
+    }
+}
+
+/// Plays the XO-CHIP audio pattern, looping its 128 1-bit samples at the pitch's playback rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternPlayer {
+    pattern: Option<[u8; AUDIO_PATTERN_LEN]>,
+    /// Samples of the pattern per output sample
+    step: f32,
+    /// Position in the pattern, 0 to 128
+    position: f32,
+    sample_rate: f32,
+    volume: f32,
+}
+
+impl PatternPlayer {
+    pub fn new(sample_rate: i32, volume: f32) -> Self {
+        Self { pattern: None, step: 0.0, position: 0.0, sample_rate: sample_rate as f32, volume }
+    }
+
+    /// Plays `pattern` at `rate` samples per second from now on; None stops the pattern
+    pub fn set(&mut self, pattern: Option<[u8; AUDIO_PATTERN_LEN]>, rate: f32) {
+        self.pattern = pattern;
+        self.step = rate / self.sample_rate;
+    }
+
+    /// The next sample, or None without a pattern to play
+    pub fn next_sample(&mut self) -> Option<f32> {
+        let pattern = self.pattern?;
+        let bit = self.position as usize;
+        self.position = (self.position + self.step) % (AUDIO_PATTERN_LEN * 8) as f32;
+        let level = if pattern[bit / 8] >> (7 - bit % 8) & 1 == 1 { 1.0 } else { -1.0 };
+        Some(level * self.volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waveforms_stay_within_the_volume() {
+        for waveform in [Waveform::Square, Waveform::Triangle, Waveform::Sine, Waveform::Noise] {
+            let mut oscillator = Oscillator::new(Tone { waveform, frequency: 1000.0, volume: 0.5 }, 8000);
+            let samples: Vec<f32> = (0..64).map(|_| oscillator.next_sample()).collect();
+            assert!(samples.iter().all(|sample| sample.abs() <= 0.5), "{waveform:?}: {samples:?}");
+            assert!(samples.iter().any(|&sample| sample > 0.1) && samples.iter().any(|&sample| sample < -0.1), "{waveform:?}");
+        }
+        // 8 samples a cycle: high for the first half, low for the rest
+        let mut square = Oscillator::new(Tone { frequency: 1000.0, ..Tone::default() }, 8000);
+        let cycle: Vec<f32> = (0..8).map(|_| square.next_sample()).collect();
+        assert_eq!(cycle, [0.25, 0.25, 0.25, 0.25, -0.25, -0.25, -0.25, -0.25]);
+    }
+
+    #[test]
+    fn silent_faders_ramp_up_and_back_down() {
+        // 4 samples from off to on at 8kHz
+        let mut envelope = Fader::silent(8000, std::time::Duration::from_micros(500));
+        assert_eq!(envelope.next_gain(), 0.0);
+        envelope.set(true);
+        let attack: Vec<f32> = (0..5).map(|_| envelope.next_gain()).collect();
+        assert_eq!(attack, [0.25, 0.5, 0.75, 1.0, 1.0]);
+        envelope.set(false);
+        let release: Vec<f32> = (0..5).map(|_| envelope.next_gain()).collect();
+        assert_eq!(release, [0.75, 0.5, 0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn patterns_play_from_the_top_bit() {
+        let mut player = PatternPlayer::new(8000, 0.5);
+        assert_eq!(player.next_sample(), None);
+        let mut pattern = [0; AUDIO_PATTERN_LEN];
+        pattern[0] = 0b1010_0000;
+        // two output samples per pattern sample
+        player.set(Some(pattern), 4000.0);
+        let samples: Vec<f32> = (0..8).map(|_| player.next_sample().unwrap()).collect();
+        assert_eq!(samples, [0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn programs_load_patterns_with_f002() {
+        // LD I, pattern; AUDIO; spin
+        let mut rom = vec![0xA2, 0x06, 0xF0, 0x02, 0x12, 0x04];
+        rom.extend(1..=AUDIO_PATTERN_LEN as u8);
+        let mut chip_8 = crate::Chip8::from_rom(&rom);
+        assert_eq!(chip_8.audio_pattern(), None);
+        chip_8.step();
+        chip_8.step();
+        let pattern = chip_8.audio_pattern().unwrap();
+        assert_eq!(pattern[..3], [1, 2, 3]);
+        let state = chip_8.save_state();
+        chip_8.reset();
+        assert_eq!(chip_8.audio_pattern(), None);
+        chip_8.load_state(&state).unwrap();
+        assert_eq!(chip_8.audio_pattern(), Some(pattern));
+    }
+}
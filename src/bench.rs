@@ -0,0 +1,132 @@
+//! Benchmarking the core: a ROM run headless as fast as it goes for a fixed number of
+//! instructions, so a slowdown in decoding or drawing shows up as a number. The rate comes from
+//! a run with nothing measured along the way; the breakdown by subsystem from a second run
+//! timing every instruction, which is slower, so only its shares are meaningful.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::instruction::Instruction;
+use crate::{Chip8, INSTRUCTIONS_PER_FRAME};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subsystem {
+    pub name: &'static str,
+    pub instructions: u64,
+    /// Time spent in its instructions during the timed run
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    pub instructions: u64,
+    /// How long the untimed run took
+    pub elapsed: Duration,
+    /// Most time first, with the 60Hz timer ticks as their own entry
+    pub subsystems: Vec<Subsystem>,
+    /// Whether the ROM halted or got stuck before running all the instructions asked for
+    pub halted: bool,
+}
+
+impl BenchReport {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Runs `chip_8` for `instructions` instructions, twice, in frames of INSTRUCTIONS_PER_FRAME
+pub fn run(chip_8: &Chip8, instructions: u64) -> BenchReport {
+    let already = chip_8.counters().instructions;
+    let target = already + instructions;
+    let mut untimed = chip_8.clone();
+    let start = Instant::now();
+    while untimed.counters().instructions < target && !untimed.halted() {
+        let frame_start = untimed.counters().instructions;
+        // a frame at a time, so the timers tick as often as they would
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            if untimed.counters().instructions == target {
+                break;
+            }
+            untimed.step();
+        }
+        untimed.tick_timers();
+        // nothing runs any more, e.g. past the end of memory
+        if untimed.counters().instructions == frame_start {
+            break;
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let mut timed = chip_8.clone();
+    let mut subsystems: Vec<Subsystem> = vec![];
+    let mut add = |name, instructions, time| match subsystems.iter_mut().find(|subsystem| subsystem.name == name) {
+        Some(subsystem) => {
+            subsystem.instructions += instructions;
+            subsystem.time += time;
+        }
+        None => subsystems.push(Subsystem { name, instructions, time }),
+    };
+    while timed.counters().instructions < target && !timed.halted() {
+        let frame_start = timed.counters().instructions;
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            if timed.counters().instructions == target {
+                break;
+            }
+            let (before, start) = (timed.counters().instructions, Instant::now());
+            timed.step();
+            let time = start.elapsed();
+            if let Some((_, opcode)) = timed.last_instruction().filter(|_| timed.counters().instructions > before) {
+                add(Instruction::decode_with(opcode, &timed.quirks()).map_or("invalid", Instruction::subsystem), 1, time);
+            }
+        }
+        let start = Instant::now();
+        timed.tick_timers();
+        add("timer ticks", 0, start.elapsed());
+        if timed.counters().instructions == frame_start {
+            break;
+        }
+    }
+    subsystems.sort_by_key(|subsystem| std::cmp::Reverse(subsystem.time));
+    BenchReport { instructions: untimed.counters().instructions - already, elapsed, subsystems, halted: untimed.halted() }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions in {:.3}s: {:.0} instructions/s{}",
+            self.instructions,
+            self.elapsed.as_secs_f64(),
+            self.instructions_per_second(),
+            if self.halted { ", halted early" } else { "" },
+        )?;
+        let total: Duration = self.subsystems.iter().map(|subsystem| subsystem.time).sum();
+        for subsystem in &self.subsystems {
+            let share = 100.0 * subsystem.time.as_secs_f64() / total.as_secs_f64().max(f64::MIN_POSITIVE);
+            write!(f, "  {:<18}{share:>5.1}%", subsystem.name)?;
+            if subsystem.instructions > 0 {
+                let each = subsystem.time.as_nanos() / subsystem.instructions as u128;
+                write!(f, "  {:>12} instructions  {each:>6} ns each", subsystem.instructions)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_the_instructions_asked_for_and_splits_them_up() {
+        // LD V0 1, DRW V0 V0 1, JP 0x200
+        let rom = [0x60, 0x01, 0xD0, 0x01, 0x12, 0x00];
+        let report = run(&Chip8::from_rom(&rom), 3000);
+        assert_eq!(report.instructions, 3000);
+        assert!(!report.halted);
+        let count = |name| report.subsystems.iter().find(|subsystem| subsystem.name == name).map(|subsystem| subsystem.instructions);
+        assert_eq!((count("arithmetic"), count("draw"), count("control flow")), (Some(1000), Some(1000), Some(1000)));
+        assert!(report.to_string().starts_with("3000 instructions in "));
+    }
+}
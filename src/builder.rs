@@ -0,0 +1,122 @@
+//! Configuring a machine in one expression instead of a constructor followed by setters:
+//!
+//! ```
+//! use chip_8_rs::Chip8;
+//! use chip_8_rs::quirks::Preset;
+//!
+//! let chip_8 = Chip8::builder()
+//!     .platform(Preset::Schip)
+//!     .rom_bytes(&[0x12, 0x00])
+//!     .seed(7)
+//!     .build();
+//! assert_eq!(chip_8.pc(), 0x200);
+//! ```
+
+use crate::quirks::{Preset, Quirks};
+use crate::fonts::{FontSet, Fonts};
+use crate::variant::Variant;
+use crate::{AlignmentPolicy, Chip8, FaultPolicy, Profile, FONT};
+
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    quirks: Quirks,
+    rom: Vec<u8>,
+    seed: Option<u64>,
+    fonts: Fonts,
+    variant: Option<Variant>,
+    profile: Profile,
+    alignment: AlignmentPolicy,
+    fault_policy: FaultPolicy,
+    stack_depth: Option<usize>,
+    strict_memory: bool,
+}
+
+impl Builder {
+    /// Emulates the quirks of `preset`'s interpreter
+    pub fn platform(mut self, preset: Preset) -> Self {
+        self.quirks = Quirks::preset(preset);
+        self
+    }
+
+    /// Emulates `variant` whole: its quirks, and only its instructions
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.quirks = variant.quirks();
+        self.variant = Some(variant);
+        self
+    }
+
+    /// Emulates exactly these quirks, for combinations no preset has
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// The program loaded at 0x200; without one the machine starts on empty memory
+    pub fn rom_bytes(mut self, rom: &[u8]) -> Self {
+        self.rom = rom.to_vec();
+        self
+    }
+
+    /// Seeds the CXNN random number generator, making runs reproducible
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Replaces the 4x5 hex digit font FX29 points into, e.g. with another interpreter's glyphs.
+    /// It stays installed across load_rom()
+    pub fn font(mut self, font: [u8; FONT.len()]) -> Self {
+        self.fonts.small = font;
+        self
+    }
+
+    /// Installs another interpreter's glyphs for FX29 and FX30, see FontSet
+    pub fn font_set(mut self, set: FontSet) -> Self {
+        self.fonts = set.fonts();
+        self
+    }
+
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn alignment_policy(mut self, policy: AlignmentPolicy) -> Self {
+        self.alignment = policy;
+        self
+    }
+
+    pub fn fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.fault_policy = policy;
+        self
+    }
+
+    /// Lets subroutine calls nest `depth` deep instead of STACK_DEPTH
+    pub fn stack_depth(mut self, depth: usize) -> Self {
+        self.stack_depth = Some(depth);
+        self
+    }
+
+    /// Faults on writes below 0x200 as well as past the end of memory
+    pub fn strict_memory(mut self, strict: bool) -> Self {
+        self.strict_memory = strict;
+        self
+    }
+
+    pub fn build(self) -> Chip8 {
+        let mut chip_8 = Chip8 { fonts: self.fonts, variant: self.variant, ..Chip8::default() };
+        chip_8.load_rom(&self.rom);
+        chip_8.set_quirks(self.quirks);
+        chip_8.set_profile(self.profile);
+        chip_8.set_alignment_policy(self.alignment);
+        chip_8.set_fault_policy(self.fault_policy);
+        chip_8.set_strict_memory(self.strict_memory);
+        if let Some(depth) = self.stack_depth {
+            chip_8.set_stack_depth(depth);
+        }
+        if let Some(seed) = self.seed {
+            chip_8.seed_rng(seed);
+        }
+        chip_8
+    }
+}
@@ -0,0 +1,165 @@
+//! What the CPU reads and writes through: memory, plus the debug console in the dev profile.
+//! Accesses past the end of memory wrap around and are reported as faults, as are writes below
+//! the program in strict mode. Watched addresses are noted for the debugger, hooked ones for
+//! scripts, and all of them for coverage once it's enabled
+
+use crate::cheats::Target;
+use crate::coverage::Coverage;
+use crate::debugger::{Access, Watchpoint};
+use crate::fault::Fault;
+use crate::memory::{Memory, PROGRAM_START};
+use crate::{Profile, DEBUG_CONSOLE_ADDR};
+
+/// Faults kept between take_faults() calls; a ROM that's stuck faulting loses the extra ones
+const MAX_PENDING_FAULTS: usize = 256;
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Bus {
+    pub memory: Memory,
+    pub profile: Profile,
+    /// Fault on writes below PROGRAM_START too
+    pub strict: bool,
+    /// Address of the instruction being executed, which faults are attributed to
+    pub executing: u16,
+    /// The first fault the executing instruction raised
+    pub raised: Option<Fault>,
+    /// Memory and register watchpoints alike, though only memory ones are checked here
+    pub watchpoints: Vec<Watchpoint>,
+    /// The first watched access the executing instruction made
+    pub watched: Option<(Target, Access)>,
+    /// Addresses a script hooks writes to
+    pub hooked: Vec<u16>,
+    /// The hooked addresses the executing instruction wrote, in order
+    pub hooked_writes: Vec<u16>,
+    /// Data accesses are noted here and instructions executed by the CPU, once enabled
+    pub coverage: Option<Coverage>,
+    console: Vec<u8>,
+    /// Faults since the last take_faults(), oldest first
+    faults: Vec<(u16, Fault)>,
+}
+
+impl Bus {
+    /// Reads a byte on behalf of the program
+    pub fn read(&mut self, addr: usize) -> u8 {
+        self.watch(addr, Access::Read);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_read(addr);
+        }
+        self.fetch(addr)
+    }
+
+    /// Reads a byte of an instruction, which watchpoints don't see
+    pub fn fetch(&mut self, addr: usize) -> u8 {
+        if !self.memory.contains(addr) {
+            self.fault(Fault::OutOfBounds(addr as u32));
+        }
+        self.memory.get(addr)
+    }
+
+    /// Stores a byte on behalf of the program, routing it to the debug console when that's mapped
+    pub fn write(&mut self, addr: usize, byte: u8) {
+        self.watch(addr, Access::Write);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_write(addr);
+        }
+        if let Some(hooked) = u16::try_from(addr).ok().filter(|addr| self.hooked.contains(addr)) {
+            self.hooked_writes.push(hooked);
+        }
+        if self.profile == Profile::Dev && addr == DEBUG_CONSOLE_ADDR as usize {
+            self.console.push(byte);
+            return;
+        }
+        if !self.memory.contains(addr) {
+            self.fault(Fault::OutOfBounds(addr as u32));
+        } else if self.strict && addr < PROGRAM_START as usize {
+            self.fault(Fault::ProtectedWrite(addr as u16));
+        }
+        self.memory.set(addr, byte);
+    }
+
+    /// Notes `access` to `target` if it's watched
+    pub fn note_access(&mut self, target: Target, access: Access) {
+        if self.watched.is_none() && self.watchpoints.iter().any(|watchpoint| watchpoint.watches(target, access)) {
+            self.watched = Some((target, access));
+        }
+    }
+
+    fn watch(&mut self, addr: usize, access: Access) {
+        if !self.watchpoints.is_empty() && addr <= u16::MAX as usize {
+            self.note_access(Target::Memory(addr as u16), access);
+        }
+    }
+
+    /// Notes that the executing instruction did something undefined
+    pub fn fault(&mut self, fault: Fault) {
+        self.raised.get_or_insert(fault);
+        if self.faults.len() < MAX_PENDING_FAULTS {
+            self.faults.push((self.executing, fault));
+        }
+    }
+
+    pub fn take_faults(&mut self) -> Vec<(u16, Fault)> {
+        std::mem::take(&mut self.faults)
+    }
+
+    pub fn take_console(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.console)
+    }
+
+    /// Starts over with `memory`, dropping any faults still pending
+    pub fn reset(&mut self, memory: Memory) {
+        self.memory = memory;
+        self.faults.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MEMORY_SIZE;
+
+    #[test]
+    fn console_is_only_mapped_in_the_dev_profile() {
+        let mut bus = Bus::default();
+        bus.write(DEBUG_CONSOLE_ADDR as usize, b'a');
+        assert!(bus.take_console().is_empty());
+        assert_eq!(bus.memory.get(DEBUG_CONSOLE_ADDR as usize), b'a');
+
+        bus.profile = Profile::Dev;
+        bus.write(DEBUG_CONSOLE_ADDR as usize, b'b');
+        assert_eq!(bus.take_console(), b"b");
+        assert_eq!(bus.memory.get(DEBUG_CONSOLE_ADDR as usize), b'a');
+    }
+
+    #[test]
+    fn out_of_bounds_accesses_wrap_and_fault() {
+        let mut bus = Bus { executing: 0x246, ..Bus::default() };
+        bus.write(MEMORY_SIZE + 0x300, 9);
+        assert_eq!(bus.read(MEMORY_SIZE + 0x300), 9);
+        assert_eq!(bus.memory.get(0x300), 9);
+        let out_of_bounds = (0x246, Fault::OutOfBounds(MEMORY_SIZE as u32 + 0x300));
+        assert_eq!(bus.take_faults(), [out_of_bounds, out_of_bounds]);
+        assert!(bus.take_faults().is_empty());
+    }
+
+    #[test]
+    fn strict_mode_flags_writes_below_the_program() {
+        let mut bus = Bus::default();
+        bus.write(0x1FF, 1);
+        assert!(bus.take_faults().is_empty());
+        bus.strict = true;
+        bus.write(0x1FF, 2);
+        bus.write(0x200, 3);
+        assert_eq!(bus.take_faults(), [(0, Fault::ProtectedWrite(0x1FF))]);
+        assert_eq!(bus.memory.get(0x1FF), 2);
+    }
+
+    #[test]
+    fn pending_faults_are_capped() {
+        let mut bus = Bus::default();
+        for _ in 0..MAX_PENDING_FAULTS * 2 {
+            bus.fault(Fault::StackUnderflow);
+        }
+        assert_eq!(bus.take_faults().len(), MAX_PENDING_FAULTS);
+    }
+}
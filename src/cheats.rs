@@ -0,0 +1,135 @@
+//! Cheats: memory pokes and register freezes put back every frame, e.g. to keep a lives counter
+//! from running down. Cheat files hold one per line, an address or register, `=`, and the byte
+//! to keep there, with numbers in decimal or 0x-prefixed hex:
+//!
+//! ```text
+//! # Blinky: infinite lives
+//! 0x3f0=3
+//! V5=0x09
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Chip8;
+
+/// A memory address or a V register, also what debugger watchpoints watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    /// VX, by X
+    Register(u8),
+}
+
+/// `0x3f0` or `V5`
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Ok(match s.strip_prefix(['V', 'v']) {
+            Some(x) => Target::Register(u8::from_str_radix(x, 16).ok().filter(|&x| x < 16).ok_or_else(|| format!("`{s}` is not a register"))?),
+            None => Target::Memory(u16::try_from(number(s)?).map_err(|_| format!("address out of range: {s}"))?),
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Memory(addr) => write!(f, "{addr:#05x}"),
+            Target::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u8,
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, value) = s.split_once('=').ok_or_else(|| format!("expected `<addr or VX>=<byte>`, got `{s}`"))?;
+        let target = target.parse()?;
+        let value = u8::try_from(number(value)?).map_err(|_| format!("byte out of range: {}", value.trim()))?;
+        Ok(Self { target, value })
+    }
+}
+
+/// The way it's written in a cheat file
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={:#04x}", self.target, self.value)
+    }
+}
+
+/// A decimal or 0x-prefixed hexadecimal number, as cheat and symbol files write them
+pub(crate) fn number(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|e| format!("invalid number `{s}`: {e}"))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats(pub Vec<Cheat>);
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cheats = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            cheats.push(line.parse().map_err(|e| format!("line {}: {e}", n + 1))?);
+        }
+        Ok(Self(cheats))
+    }
+
+    /// Puts every cheat's byte back, for once a frame
+    pub fn apply(&self, chip_8: &mut Chip8) {
+        for cheat in &self.0 {
+            match cheat.target {
+                Target::Memory(addr) => chip_8.poke(addr, cheat.value),
+                Target::Register(x) => chip_8.cpu.v[x as usize] = cheat.value,
+            }
+        }
+    }
+}
+
+/// One line, e.g. `0x3f0=0x03, V5=0x09`
+impl fmt::Display for Cheats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, cheat) in self.0.iter().enumerate() {
+            write!(f, "{}{cheat}", if n > 0 { ", " } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cheats_hold_their_bytes_across_frames() {
+        // ADD V0 -1 and count the byte at 0x300 down, forever
+        let rom = [0x70, 0xFF, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        let cheats = Cheats::parse("# lives\n0x300=9\nv0 = 0x0a\n").unwrap();
+        assert_eq!(cheats.0[1], Cheat { target: Target::Register(0), value: 10 });
+        for _ in 0..3 {
+            cheats.apply(&mut chip_8);
+            chip_8.run_frame(4);
+        }
+        assert_eq!((chip_8.registers()[0], chip_8.peek(0x300)), (9, 9));
+        assert_eq!(Cheats::parse("VG=1").unwrap_err(), "line 1: `VG` is not a register");
+    }
+}
@@ -0,0 +1,310 @@
+//! Subcommands: everything the binary does besides running a ROM
+
+use std::collections::BTreeMap;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+
+use chip_8_rs::asm::assemble;
+use chip_8_rs::bench;
+use chip_8_rs::conformance::{Outcome, CHECKS};
+use chip_8_rs::disasm::Disassembly;
+use chip_8_rs::error;
+use chip_8_rs::export::base64_decode;
+use chip_8_rs::octo;
+use chip_8_rs::lockstep::{self, Reference};
+use chip_8_rs::logging::Level;
+use chip_8_rs::quirks::{Preset, Quirks};
+use chip_8_rs::reference::{self, Outcome as ReferenceOutcome};
+use chip_8_rs::self_test::SELF_TESTS;
+use chip_8_rs::state_diff::StateDiff;
+use chip_8_rs::symbols::Symbols;
+use chip_8_rs::{event, Chip8, INSTRUCTIONS_PER_FRAME, TIMER_HZ};
+
+use crate::args::Command;
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+
+pub fn run(command: &Command, locale: Locale) {
+    match command {
+        Command::Run(_) => unreachable!("running a ROM is main's job"),
+        Command::Connect { .. } => unreachable!("connecting is main's job, as it needs SDL"),
+        Command::Disasm { rom, symbols, quirks } => {
+            let path = symbols.clone().unwrap_or_else(|| rom.with_extension("sym"));
+            let symbols = match std::fs::read_to_string(&path) {
+                Ok(text) => Symbols::parse(&text).unwrap_or_else(|e| fail(format_args!("{}: {e}", path.display()))),
+                Err(e) if symbols.is_some() => fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])),
+                Err(_) => Symbols::default(),
+            };
+            let rom = error::read(rom).unwrap_or_else(|e| fail(e));
+            print!("{}", Disassembly::new(&rom, &Quirks::preset(*quirks)).with_symbols(symbols));
+        }
+        Command::Asm { source, output } => {
+            let text = std::fs::read_to_string(source)
+                .unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&source.display(), &e])));
+            let rom = match crate::source::is_source(source) {
+                true => octo::compile(&text).map(|program| program.rom),
+                false => assemble(&text),
+            };
+            let rom = rom.unwrap_or_else(|e| fail(format_args!("{}:{e}", source.display())));
+            let output = output.clone().unwrap_or_else(|| source.with_extension("ch8"));
+            std::fs::write(&output, &rom).unwrap_or_else(|e| fail(locale.format(Msg::FileCreateFailed, &[&output.display(), &e])));
+            println!("wrote {} bytes to {}", rom.len(), output.display());
+        }
+        Command::Diff { a, b } => {
+            let diff = StateDiff::between(&read_state(a, locale), &read_state(b, locale));
+            if diff.is_empty() {
+                println!("the states are the same");
+            } else {
+                print!("{diff}");
+                std::process::exit(1);
+            }
+        }
+        Command::Devtest { rom, watch } => {
+            let passed = devtest(rom.as_deref());
+            if *watch {
+                watch_for_changes(rom.as_deref(), locale);
+            }
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        Command::Lockstep { rom, frames, quirks, granularity, reference: command } => {
+            let data = error::read(rom).unwrap_or_else(|e| fail(e));
+            let mut chip_8 = Chip8::builder().platform(*quirks).rom_bytes(&data).seed(0).build();
+            let mut child = std::process::Command::new(&command[0])
+                .args(&command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap_or_else(|e| fail(locale.format(Msg::StartFailed, &[&command[0], &e])));
+            let replies = BufReader::new(child.stdout.take().expect("piped stdout"));
+            let mut reference = Reference::new(replies, child.stdin.take().expect("piped stdin"));
+            let result = lockstep::run(&mut chip_8, &mut reference, *frames as usize, INSTRUCTIONS_PER_FRAME, *granularity as usize);
+            reference.quit().ok();
+            drop(reference);
+            child.wait().ok();
+            match result {
+                Ok(None) => println!("{}: both agreed for {frames} frames", rom.display()),
+                Ok(Some(divergence)) => {
+                    println!(
+                        "{}: diverged after {} instructions, in frame {}: state {:08x} here, {:08x} in the reference",
+                        rom.display(),
+                        divergence.instructions,
+                        divergence.frame,
+                        divergence.ours,
+                        divergence.theirs,
+                    );
+                    println!("  here: PC {:#05x}, I {:#05x}, V {:02x?}", chip_8.pc(), chip_8.index(), chip_8.registers());
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    println!("lockstep with {} failed: {e}", command[0]);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Command::Verify { rom, frames, quirks, trace } => {
+            let data = error::read(rom).unwrap_or_else(|e| fail(e));
+            let mut chip_8 = Chip8::builder().platform(*quirks).rom_bytes(&data).seed(0).build();
+            let outcome = match trace {
+                Some(path) => {
+                    let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+                    let trace = reference::parse_trace(&text).unwrap_or_else(|e| fail(format_args!("{}: {e}", path.display())));
+                    reference::verify_trace(&mut chip_8, &trace, INSTRUCTIONS_PER_FRAME)
+                }
+                None => reference::verify(&mut chip_8, *frames as u64, INSTRUCTIONS_PER_FRAME)
+                    .unwrap_or_else(|| fail(format_args!("the reference interpreter can't run {quirks} programs"))),
+            };
+            match outcome {
+                ReferenceOutcome::Agreed(instructions) => println!("{}: agreed for {instructions} instructions", rom.display()),
+                ReferenceOutcome::Diverged(mismatch) => {
+                    print!("{}: {mismatch}", rom.display());
+                    std::process::exit(1);
+                }
+                ReferenceOutcome::Unsupported { addr, opcode } => {
+                    println!("{}: the reference interpreter stopped at {opcode:04X} at {addr:#05x}, an extension's instruction", rom.display());
+                    std::process::exit(2);
+                }
+            }
+        }
+        Command::LockstepServe { rom, quirks } => {
+            let data = error::read(rom).unwrap_or_else(|e| fail(e));
+            let mut chip_8 = Chip8::builder().platform(*quirks).rom_bytes(&data).seed(0).build();
+            lockstep::serve(&mut chip_8, std::io::stdin().lock(), std::io::stdout().lock())
+                .unwrap_or_else(|e| fail(format_args!("lockstep protocol error: {e}")));
+        }
+        Command::Bench { rom, instructions, quirks } => {
+            let data = error::read(rom).unwrap_or_else(|e| fail(e));
+            let chip_8 = Chip8::builder().platform(*quirks).rom_bytes(&data).seed(0).build();
+            print!("{}", bench::run(&chip_8, *instructions));
+        }
+        Command::Check { rom, frames, quirks } => {
+            let data = error::read(rom).unwrap_or_else(|e| fail(e));
+            let mut chip_8 = Chip8::builder().platform(*quirks).rom_bytes(&data).seed(0).build();
+            chip_8.enable_diagnostics();
+            // each distinct fault once, with where it first happened and how often
+            let mut faults = BTreeMap::new();
+            for _ in 0..*frames {
+                chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+                for (addr, fault) in chip_8.take_faults() {
+                    faults.entry(fault).or_insert((addr, 0)).1 += 1;
+                }
+                if chip_8.halted() {
+                    break;
+                }
+            }
+            let counters = chip_8.counters();
+            println!(
+                "{}: {} instructions, {} draws in {:.1}s of emulated time{}",
+                rom.display(),
+                counters.instructions,
+                counters.draws,
+                *frames as f32 / TIMER_HZ as f32,
+                if chip_8.halted() { ", then halted" } else { "" },
+            );
+            for (fault, (addr, count)) in &faults {
+                println!("  {fault} at {addr:#05x} ({count}x)");
+            }
+            if let Some(diagnostics) = chip_8.diagnostics() {
+                print!("{diagnostics}");
+            }
+            if !faults.is_empty() {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// How long the ROM row of devtest runs for on each preset
+const DEVTEST_FRAMES: u32 = 600;
+
+/// Polling rather than file system notifications, which would need another dependency
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Prints the checks' outcome on every preset, one row each, and whether they all passed
+fn devtest(rom: Option<&Path>) -> bool {
+    let presets = Preset::value_variants();
+    let row = |label: &str, cells: Vec<String>| {
+        let cells: Vec<_> = cells.iter().map(|cell| format!("{cell:<16}")).collect();
+        println!("{label:<12}{}", cells.join("").trim_end());
+    };
+    row("", presets.iter().map(Preset::to_string).collect());
+    let mut passed = true;
+    for check in &CHECKS {
+        let outcomes: Vec<_> = presets.iter().map(|preset| check.run(Quirks::preset(*preset))).collect();
+        passed &= outcomes.iter().all(|outcome| *outcome == Outcome::Pass);
+        row(check.name, outcomes.iter().map(Outcome::to_string).collect());
+    }
+    if let Some(rom) = rom {
+        let data = error::read(rom).unwrap_or_else(|e| fail(e));
+        let cells = presets
+            .iter()
+            .map(|preset| {
+                let mut chip_8 = Chip8::builder().platform(*preset).rom_bytes(&data).seed(0).build();
+                let mut faults = 0;
+                for _ in 0..DEVTEST_FRAMES {
+                    chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+                    faults += chip_8.take_faults().len();
+                }
+                passed &= faults == 0;
+                if faults == 0 { "ok".to_string() } else { format!("{faults} faults") }
+            })
+            .collect();
+        row(&rom.file_name().unwrap_or_default().to_string_lossy(), cells);
+    }
+    passed
+}
+
+/// Runs the self test, printing how each part went, and exits failing if any part did
+pub fn self_test() {
+    let mut passed = true;
+    for test in &SELF_TESTS {
+        match test.run() {
+            Ok(()) => println!("{:<12}ok", test.name),
+            Err(failures) => {
+                passed = false;
+                println!("{:<12}FAIL", test.name);
+                for failure in failures {
+                    println!("    {failure}");
+                }
+            }
+        }
+    }
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+/// Runs devtest again on every change: in this process when only the ROM changed, and through
+/// cargo when the source did, so the checks run against the rebuilt core
+fn watch_for_changes(rom: Option<&Path>, locale: Locale) -> ! {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let sources = root.join("src");
+    // cargo runs from the crate root, where a relative path would point elsewhere
+    let rom = rom.map(|rom| rom.canonicalize().unwrap_or_else(|_| rom.to_path_buf()));
+    let rom = rom.as_deref();
+    let snapshot = || (newest(&sources), rom.and_then(modified));
+    let mut seen = snapshot();
+    println!("watching {} for changes", sources.display());
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let now = snapshot();
+        if now == seen {
+            continue;
+        }
+        println!();
+        if now.0 != seen.0 {
+            let mut cargo = std::process::Command::new(env!("CARGO"));
+            cargo.current_dir(root).args(["run", "--quiet"]);
+            if !cfg!(debug_assertions) {
+                cargo.arg("--release");
+            }
+            cargo.args(["--", "devtest"]);
+            if let Some(rom) = rom {
+                cargo.arg("--rom").arg(rom);
+            }
+            if let Err(e) = cargo.status() {
+                event!(Level::Warn, "{}", locale.format(Msg::StartFailed, &[&"cargo", &e]));
+            }
+        } else {
+            devtest(rom);
+        }
+        seen = now;
+    }
+}
+
+/// When the most recently modified file under `dir` changed
+fn newest(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                newest(&path)
+            } else {
+                modified(&path)
+            }
+        })
+        .max()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// A machine in the save state at `path`, which is the state's bytes or, as Ctrl+C copies them,
+/// its base64
+fn read_state(path: &Path, locale: Locale) -> Chip8 {
+    let data = std::fs::read(path).unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+    let decoded = std::str::from_utf8(&data).ok().and_then(base64_decode);
+    let mut chip_8 = Chip8::default();
+    if let Err(e) = chip_8.load_state(decoded.as_deref().unwrap_or(&data)) {
+        fail(format_args!("{}: {e}", path.display()));
+    }
+    chip_8
+}
@@ -0,0 +1,155 @@
+//! Running one ROM twice side by side under two sets of quirks, with the same keys held on
+//! both, to find the first frame their behavior parts ways. Both machines note the quirk
+//! decisions they take, so a divergence comes with the ones taken in the frame it showed up in.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::diagnostics::Quirk;
+use crate::journal::Frame;
+use crate::quirks::Quirks;
+use crate::Chip8;
+
+/// The first frame the two machines disagreed after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Counting from 0 for the first frame run
+    pub frame: u64,
+    /// How many pixels differ
+    pub pixels: usize,
+    /// The registers that differ, by name
+    pub registers: Vec<String>,
+    /// Each quirk decided during the frame, with the choice each machine made, if it got to it
+    pub quirks: Vec<(Quirk, Option<&'static str>, Option<&'static str>)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let differ = |n: usize| if n == 1 { "differs" } else { "differ" };
+        write!(f, "frame {}:", self.frame)?;
+        if self.pixels > 0 {
+            write!(f, " {} {} {}", self.pixels, if self.pixels == 1 { "pixel" } else { "pixels" }, differ(self.pixels))?;
+        }
+        if !self.registers.is_empty() {
+            let comma = if self.pixels > 0 { "," } else { "" };
+            write!(f, "{comma} {} {}", self.registers.join(" "), differ(self.registers.len()))?;
+        }
+        for (n, (quirk, left, right)) in self.quirks.iter().enumerate() {
+            let prefix = if n == 0 { "; quirks in that frame:" } else { "," };
+            write!(f, "{prefix} {quirk} ({} vs {})", left.unwrap_or("-"), right.unwrap_or("-"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The two machines, left and right
+pub struct Comparison {
+    pub machines: [Chip8; 2],
+    frames: u64,
+    divergence: Option<Divergence>,
+}
+
+impl Comparison {
+    /// Copies of `chip_8` running under `left` and `right`
+    pub fn new(chip_8: &Chip8, left: Quirks, right: Quirks) -> Self {
+        let machines = [left, right].map(|quirks| {
+            let mut chip_8 = chip_8.clone();
+            chip_8.set_quirks(quirks);
+            chip_8.enable_diagnostics();
+            chip_8
+        });
+        Self { machines, frames: 0, divergence: None }
+    }
+
+    /// Runs a frame on both with `keys` held, returning the divergence if this is the frame
+    /// they first disagreed after
+    pub fn run_frame(&mut self, keys: u16, instructions: usize) -> Option<&Divergence> {
+        let before = self.machines.each_ref().map(decisions);
+        for chip_8 in self.machines.iter_mut() {
+            chip_8.set_keys(keys);
+            chip_8.run_frame(instructions);
+        }
+        let frame = self.frames;
+        self.frames += 1;
+        if self.divergence.is_some() {
+            return None;
+        }
+        let [left, right] = &self.machines;
+        let pixels = self.differing_pixels().len();
+        let mut registers: Vec<String> = (0..16)
+            .filter(|&n| left.registers()[n] != right.registers()[n])
+            .map(|n| format!("V{n:X}"))
+            .collect();
+        for (name, differs) in [("PC", left.pc() != right.pc()), ("I", left.index() != right.index())] {
+            if differs {
+                registers.push(name.to_string());
+            }
+        }
+        if pixels == 0 && registers.is_empty() {
+            return None;
+        }
+        // what each decided this frame
+        let [taken_left, taken_right] = [0, 1].map(|n| {
+            let mut taken = decisions(&self.machines[n]);
+            taken.retain(|key, count| before[n].get(key) != Some(count));
+            taken.into_keys().collect::<Vec<_>>()
+        });
+        let mut quirks: Vec<(Quirk, Option<&'static str>, Option<&'static str>)> = vec![];
+        for &(quirk, choice) in &taken_left {
+            quirks.push((quirk, Some(choice), None));
+        }
+        for &(quirk, choice) in &taken_right {
+            match quirks.iter_mut().find(|(q, _, right)| *q == quirk && right.is_none()) {
+                Some((_, _, right)) => *right = Some(choice),
+                None => quirks.push((quirk, None, Some(choice))),
+            }
+        }
+        self.divergence = Some(Divergence { frame, pixels, registers, quirks });
+        self.divergence.as_ref()
+    }
+
+    /// The first divergence seen, if there's been one
+    pub fn divergence(&self) -> Option<&Divergence> {
+        self.divergence.as_ref()
+    }
+
+    /// The pixels that differ between the two screens as they are now, in the left one's
+    /// coordinates
+    pub fn differing_pixels(&self) -> Vec<(usize, usize)> {
+        let [left, right] = &self.machines;
+        Frame::capture(left.framebuffer()).diff(&Frame::capture(right.framebuffer()))
+    }
+}
+
+/// How many times the machine took each quirk decision so far
+fn decisions(chip_8: &Chip8) -> BTreeMap<(Quirk, &'static str), u64> {
+    chip_8
+        .diagnostics()
+        .map(|diagnostics| diagnostics.usage().map(|(quirk, choice, usage)| ((quirk, choice), usage.count)).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    #[test]
+    fn the_first_frame_apart_names_the_quirk_behind_it() {
+        // LD V0 0x82, LD V1 2, SHR V0 V1, then spin; CHIP-8 shifts V1, SUPER-CHIP V0, and both
+        // shift out a 0 into VF
+        let rom = [0x60, 0x82, 0x61, 0x02, 0x80, 0x16, 0x12, 0x06];
+        let chip_8 = Chip8::from_rom(&rom);
+        let mut comparison = Comparison::new(&chip_8, Quirks::preset(Preset::Chip8), Quirks::preset(Preset::Schip));
+        let divergence = comparison.run_frame(0, 10).cloned().unwrap();
+        assert_eq!(divergence.frame, 0);
+        assert_eq!(divergence.pixels, 0);
+        assert_eq!(divergence.registers, ["V0"]);
+        let shift = divergence.quirks.iter().find(|(quirk, ..)| *quirk == Quirk::ShiftSource).copied();
+        assert_eq!(shift, Some((Quirk::ShiftSource, Some("copy vy into vx"), Some("shift vx in place"))));
+        assert!(divergence.to_string().contains("V0 differs; quirks in that frame: "), "{divergence}");
+        // only the first is reported
+        assert_eq!(comparison.run_frame(0, 10), None);
+        assert_eq!(comparison.divergence(), Some(&divergence));
+    }
+}
@@ -0,0 +1,99 @@
+//! A settings file, as --config takes it, read at start and again whenever it changes so
+//! settings can be tweaked while a ROM runs. It's the ROM database's subset of TOML without the
+//! tables: `palette`, `platform` (a preset name), `load_store`, `ips`, the boolean quirks by
+//! their Quirks field names, and `bind`, a list of host key bindings as --bind takes them:
+//!
+//! ```text
+//! palette = "amber"
+//! platform = "chip8"
+//! vf_reset = false
+//! ips = 1000
+//! bind = ["Up=5", "scancode:Space=6"]
+//! ```
+
+use clap::ValueEnum;
+
+use crate::display::Palette;
+use crate::quirks::{LoadStore, Preset, Quirks};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub palette: Option<Palette>,
+    pub platform: Option<Preset>,
+    pub load_store: Option<LoadStore>,
+    /// Boolean quirks to set on top of the platform's, by name
+    pub quirks: Vec<(String, bool)>,
+    /// Instructions per second
+    pub ips: Option<u32>,
+    /// `NAME=KEY` bindings for the frontend to parse, as it names host keys
+    pub bind: Vec<String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            let (key, value) = line.split_once('=').ok_or_else(|| err("expected `<key> = <value>`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let unquote = |value: &str| value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).map(str::to_string);
+            let string = || unquote(value).ok_or_else(|| err("expected a quoted string"));
+            match key {
+                "palette" => config.palette = Some(string()?.parse().map_err(|e: String| err(&e))?),
+                "platform" => config.platform = Some(Preset::from_str(&string()?, true).map_err(|_| err(&format!("unknown platform {value}")))?),
+                "load_store" => config.load_store = Some(LoadStore::from_str(&string()?, true).map_err(|_| err(&format!("unknown load_store {value}")))?),
+                "ips" => config.ips = Some(value.parse().ok().filter(|&ips| ips > 0).ok_or_else(|| err(&format!("`{value}` is not an instruction rate")))?),
+                "bind" => {
+                    let items = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')).ok_or_else(|| err("expected a list of quoted strings"))?;
+                    for item in items.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                        config.bind.push(unquote(item).ok_or_else(|| err("expected a list of quoted strings"))?);
+                    }
+                }
+                _ if Quirks::default().set(key, false) => {
+                    let on = value.parse().map_err(|_| err(&format!("`{value}` is not true or false")))?;
+                    config.quirks.push((key.to_string(), on));
+                }
+                _ => return Err(err(&format!("unknown key `{key}`"))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// The quirks it asks for on top of `current`, or None if it leaves them alone
+    pub fn quirks(&self, current: Quirks) -> Option<Quirks> {
+        if self.platform.is_none() && self.load_store.is_none() && self.quirks.is_empty() {
+            return None;
+        }
+        let mut quirks = self.platform.map_or(current, Quirks::preset);
+        if let Some(load_store) = self.load_store {
+            quirks.load_store = load_store;
+        }
+        for (name, on) in &self.quirks {
+            quirks.set(name, *on);
+        }
+        Some(quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read_from_the_file() {
+        let text = "# tweaks\npalette = \"amber\"\nplatform = \"chip8\"\nvf_reset = false\nips = 1000\nbind = [\"Up=5\", \"scancode:Space=6\"]\n";
+        let config = Config::parse(text).unwrap();
+        assert_eq!(config.palette, "amber".parse().ok());
+        assert_eq!(config.ips, Some(1000));
+        assert_eq!(config.bind, ["Up=5", "scancode:Space=6"]);
+        assert_eq!(config.quirks(Quirks::default()), Some(Quirks { vf_reset: false, ..Quirks::preset(Preset::Chip8) }));
+        // without any quirk settings the machine's stay
+        assert_eq!(Config::parse("ips = 500").unwrap().quirks(Quirks::default()), None);
+        assert_eq!(Config::parse("wrap = true").unwrap_err(), "line 1: unknown key `wrap`");
+        assert!(Config::parse("bind = [Up=5]").is_err() && Config::parse("ips = 0").is_err());
+    }
+}
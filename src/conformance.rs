@@ -0,0 +1,133 @@
+//! Small built-in programs that exercise one behavior each, with the result each set of quirks
+//! should produce. Every program leaves its result in V0 and exits (00FD)
+
+use crate::asm::assemble_at;
+use crate::quirks::Quirks;
+use crate::{Chip8, FONT, INSTRUCTIONS_PER_FRAME};
+
+/// Frames a program gets to exit before it's considered stuck
+const FRAMES_ALLOWED: usize = 10;
+
+pub struct Check {
+    pub name: &'static str,
+    source: &'static str,
+    expected: fn(&Quirks) -> u8,
+}
+
+/// How a check went on one set of quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    /// Exited with the wrong result in V0
+    Fail { got: u8, expected: u8 },
+    /// Didn't exit in time
+    Stuck,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Pass => f.write_str("ok"),
+            Outcome::Fail { got, expected } => write!(f, "FAIL {got:#04x}!={expected:#04x}"),
+            Outcome::Stuck => f.write_str("STUCK"),
+        }
+    }
+}
+
+impl Check {
+    pub fn run(&self, quirks: Quirks) -> Outcome {
+        let rom = assemble_at(self.source, quirks.program_start()).unwrap_or_else(|e| panic!("check `{}` doesn't assemble: {e}", self.name));
+        let mut chip_8 = Chip8::builder().quirks(quirks).rom_bytes(&rom).seed(0).build();
+        for _ in 0..FRAMES_ALLOWED {
+            chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+            if chip_8.halted() {
+                let (got, expected) = (chip_8.registers()[0], (self.expected)(&quirks));
+                return if got == expected { Outcome::Pass } else { Outcome::Fail { got, expected } };
+            }
+        }
+        Outcome::Stuck
+    }
+}
+
+pub const CHECKS: [Check; 9] = [
+    Check {
+        name: "add carry",
+        // 200 + 100 wraps to 44 and carries
+        source: "LD V1, 200\nLD V2, 100\nADD V1, V2\nLD V0, V1\nADD V0, VF\nEXIT",
+        expected: |_| 45,
+    },
+    Check {
+        name: "sub borrow",
+        // the borrow of 5 - 10, then of 10 - 5, as two bits
+        source: "LD V1, 5\nLD V2, 10\nSUB V1, V2\nLD V3, VF\n\
+                 LD V1, 10\nLD V2, 5\nSUB V1, V2\nLD V4, VF\nLD V0, V3\nADD V0, V0\nADD V0, V4\nEXIT",
+        expected: |_| 0b01,
+    },
+    Check {
+        name: "shift",
+        source: "LD V1, 1\nLD V2, 0x80\nSHL V1, V2\nLD V0, V1\nADD V0, VF\nEXIT",
+        expected: |quirks| if quirks.shift_vy { 1 } else { 2 },
+    },
+    Check {
+        name: "vf reset",
+        source: "LD VF, 5\nLD V1, 3\nOR V1, V1\nLD V0, VF\nEXIT",
+        expected: |quirks| if quirks.vf_reset { 0 } else { 5 },
+    },
+    Check {
+        name: "load/store",
+        // reads back whatever I is left pointing at
+        source: "LD I, buf\nLD V0, 1\nLD V1, 2\nLD [I], V1\nLD V0, [I]\nEXIT\nbuf: db 0, 0, 0x33",
+        expected: |quirks| match quirks.load_store.index_increment(1) {
+            0 => 1,
+            1 => 2,
+            _ => 0x33,
+        },
+    },
+    Check {
+        name: "jump",
+        // the target is at 0x2XX, so BXNN adds V2 instead of V0
+        source: "LD V0, 0\nLD V2, 2\nJP V0, land\nland: JP plain\nLD V0, 2\nEXIT\nplain: LD V0, 1\nEXIT",
+        expected: |quirks| if quirks.jump_vx { 2 } else { 1 },
+    },
+    Check {
+        name: "bcd",
+        // the digits of 195, weighted so a mix-up shows
+        source: "LD V1, 195\nLD I, buf\nLD B, V1\nLD V2, [I]\nSHL V1\nSHL V2\nSHL V2\n\
+                 ADD V0, V1\nADD V0, V2\nEXIT\nbuf: db 0, 0, 0",
+        expected: |_| 1 + 9 * 2 + 5 * 4,
+    },
+    Check {
+        name: "call/ret",
+        source: "CALL sub\nLD V0, V1\nEXIT\nsub: LD V1, 7\nRET",
+        expected: |_| 7,
+    },
+    Check {
+        name: "font",
+        // FX29 points I at the top row of the "A"
+        source: "LD V1, 0xA\nLD F, V1\nLD V0, [I]\nEXIT",
+        expected: |_| FONT[0xA * 5],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    #[test]
+    fn every_preset_conforms() {
+        for preset in [Preset::Chip8, Preset::Schip, Preset::Xochip, Preset::Chip8x, Preset::Megachip] {
+            for check in &CHECKS {
+                assert_eq!(check.run(Quirks::preset(preset)), Outcome::Pass, "{} on {preset}", check.name);
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_results_are_reported() {
+        let check = Check { expected: |_| 0, ..CHECKS[8] };
+        assert_eq!(check.run(Quirks::default()), Outcome::Fail { got: FONT[0xA * 5], expected: 0 });
+        let check = Check { name: "spin", source: "loop: JP loop", expected: |_| 0 };
+        assert_eq!(check.run(Quirks::default()), Outcome::Stuck);
+    }
+}
@@ -0,0 +1,98 @@
+//! The connect command: a thin client for --serve, drawing the screen the server sends and
+//! sending back the keys held, mapped with the default keymap. Nothing is emulated here
+
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::sync::mpsc;
+
+use chip_8_rs::audio::Tone;
+use chip_8_rs::display::Palette;
+use chip_8_rs::journal::Frame;
+use chip_8_rs::remote::{self, ClientMessage, ServerMessage};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+use crate::keymap::Keymap;
+use crate::startup;
+
+pub fn run(addr: &str, scale: u32, locale: Locale) {
+    let mut stream = TcpStream::connect(addr)
+        .and_then(|stream| stream.set_nodelay(true).map(|()| stream))
+        .unwrap_or_else(|e| fail(locale.format(Msg::ConnectFailed, &[&addr, &e])));
+    let mut reader = stream
+        .try_clone()
+        .map(BufReader::new)
+        .and_then(|mut reader| remote::expect_magic(&mut reader).map(|()| reader))
+        .unwrap_or_else(|e| fail(locale.format(Msg::ConnectFailed, &[&addr, &e])));
+    let (sender, messages) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let message = ServerMessage::read(&mut reader);
+        let failed = message.is_err();
+        if sender.send(message).is_err() || failed {
+            return;
+        }
+    });
+
+    let startup::Subsystems { canvas, event_pump, mut audio, .. } = startup::init(locale, scale, Tone::default(), &startup::AudioOutput::default());
+    // startup has already said why
+    let (Some(mut canvas), Some(mut event_pump)) = (canvas, event_pump) else {
+        std::process::exit(1);
+    };
+    let creator = canvas.texture_creator();
+    // hi-res sized, with lo-res pixels doubled up
+    let mut texture = creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, 128, 64)
+        .unwrap_or_else(|e| fail(locale.format(Msg::RenderFailed, &[&e])));
+    let keymap = Keymap::default();
+    let palette = Palette::default();
+    let mut frame = Frame { width: 64, height: 32, rows: vec![0; 32] };
+    let (mut keys, mut sent) = (0u16, 0u16);
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                Event::KeyDown { keycode, scancode, .. } => if let Some(key) = keymap.lookup(keycode, scancode) {
+                    keys |= 1 << key;
+                }
+                Event::KeyUp { keycode, scancode, .. } => if let Some(key) = keymap.lookup(keycode, scancode) {
+                    keys &= !(1 << key);
+                }
+                _ => {}
+            }
+        }
+        if keys != sent {
+            ClientMessage::Keys(keys)
+                .write(&mut stream)
+                .unwrap_or_else(|e| fail(locale.format(Msg::ConnectionLost, &[&e])));
+            sent = keys;
+        }
+        for message in messages.try_iter() {
+            match message {
+                Ok(ServerMessage::Frame(next)) => frame = next,
+                Ok(ServerMessage::Sound(on)) => if let Some(audio) = &mut audio {
+                    audio.lock().envelope.set(on);
+                }
+                Err(e) => fail(locale.format(Msg::ConnectionLost, &[&e])),
+            }
+        }
+        let mut data = Vec::with_capacity(128 * 64 * 3);
+        for y in 0..64 {
+            for x in 0..128 {
+                let lit = frame.pixel(x * frame.width / 128, y * frame.height / 64);
+                data.extend_from_slice(&if lit { palette.on() } else { palette.off() });
+            }
+        }
+        let drawn = texture
+            .update(None, &data, 128 * 3)
+            .map_err(|e| e.to_string())
+            .and_then(|()| canvas.copy(&texture, None, None));
+        if let Err(e) = drawn {
+            fail(locale.format(Msg::RenderFailed, &[&e]));
+        }
+        // paced by vsync, as in the main loop
+        canvas.present();
+    }
+}
@@ -0,0 +1,183 @@
+//! Which addresses a ROM executed and how often each instruction form ran, for reports and for
+//! telling the code a session reached from the code it never got to. The bytes the program read
+//! and wrote as data are noted too, so a map of memory tells code from data, see CoverageMap
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::disasm::pattern;
+use crate::memory::MEMORY_SIZE;
+use crate::quirks::Quirks;
+
+/// Bits of `Coverage::data`
+const READ: u8 = 1;
+const WRITTEN: u8 = 2;
+
+/// Addresses per row of a CoverageMap
+const MAP_WIDTH: usize = 64;
+
+/// What the program did with a byte of memory, the most telling first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Use {
+    /// Executed, and written too: code that was patched or generated
+    SelfModified,
+    Executed,
+    /// Written and maybe read, but never executed
+    Written,
+    Read,
+    Unused,
+}
+
+impl Use {
+    /// Its character in a CoverageMap
+    pub fn symbol(self) -> char {
+        match self {
+            Use::SelfModified => '!',
+            Use::Executed => '#',
+            Use::Written => 'w',
+            Use::Read => 'r',
+            Use::Unused => '.',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// Times an instruction was fetched from each address
+    hits: Vec<u64>,
+    /// READ and WRITTEN for each address the program accessed as data
+    data: Vec<u8>,
+    opcodes: HashMap<u16, u64>,
+    invalid: BTreeMap<(u16, u16), u64>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self { hits: vec![0; MEMORY_SIZE], data: vec![0; MEMORY_SIZE], opcodes: HashMap::new(), invalid: BTreeMap::new() }
+    }
+}
+
+impl Coverage {
+    /// Notes that `opcode` was executed from `addr`
+    pub fn record(&mut self, addr: u16, opcode: u16) {
+        self.hits[addr as usize % MEMORY_SIZE] += 1;
+        *self.opcodes.entry(opcode).or_default() += 1;
+    }
+
+    /// Notes that the program read the byte at `addr` as data
+    pub fn record_read(&mut self, addr: usize) {
+        self.data[addr % MEMORY_SIZE] |= READ;
+    }
+
+    /// Notes that the program wrote the byte at `addr`
+    pub fn record_write(&mut self, addr: usize) {
+        self.data[addr % MEMORY_SIZE] |= WRITTEN;
+    }
+
+    /// Notes that `opcode` at `addr` isn't an instruction
+    pub fn record_invalid(&mut self, addr: u16, opcode: u16) {
+        *self.invalid.entry((addr, opcode)).or_default() += 1;
+    }
+
+    /// How many times an instruction was executed from `addr`
+    pub fn hits(&self, addr: u16) -> u64 {
+        self.hits[addr as usize % MEMORY_SIZE]
+    }
+
+    /// Addresses that were executed at least once
+    pub fn executed(&self) -> usize {
+        self.hits.iter().filter(|&&hits| hits > 0).count()
+    }
+
+    /// What the program did with the byte at `addr`, where both bytes of an instruction count as
+    /// executed
+    pub fn usage(&self, addr: u16) -> Use {
+        let data = self.data[addr as usize % MEMORY_SIZE];
+        let executed = self.hits(addr) > 0 || self.hits(addr.wrapping_sub(1)) > 0;
+        match (executed, data) {
+            (true, data) if data & WRITTEN != 0 => Use::SelfModified,
+            (true, _) => Use::Executed,
+            (false, data) if data & WRITTEN != 0 => Use::Written,
+            (false, READ) => Use::Read,
+            _ => Use::Unused,
+        }
+    }
+
+    /// Instructions executed per form (`8XY4`, `DXYN`, ...) under `quirks`, most frequent first
+    pub fn histogram(&self, quirks: &Quirks) -> Vec<(String, u64)> {
+        let mut forms: BTreeMap<String, u64> = BTreeMap::new();
+        for (&opcode, &count) in &self.opcodes {
+            *forms.entry(pattern(opcode, quirks)).or_default() += count;
+        }
+        let mut histogram: Vec<_> = forms.into_iter().collect();
+        histogram.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        histogram
+    }
+
+    /// Every invalid opcode seen, as (address, opcode, times), by address
+    pub fn invalid(&self) -> impl Iterator<Item = (u16, u16, u64)> + '_ {
+        self.invalid.iter().map(|(&(addr, opcode), &count)| (addr, opcode, count))
+    }
+}
+
+/// Memory as text, a character per byte and 64 to a line, each line starting with its address:
+/// `#` executed, `!` executed and written, `w` written, `r` read and `.` untouched
+pub struct CoverageMap<'a>(pub &'a Coverage);
+
+impl fmt::Display for CoverageMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |wanted: &[Use]| (0..MEMORY_SIZE as u16).filter(|&addr| wanted.contains(&self.0.usage(addr))).count();
+        writeln!(
+            f,
+            "coverage: {} bytes executed ({} of them written), {} only data, {} unused",
+            count(&[Use::Executed, Use::SelfModified]),
+            count(&[Use::SelfModified]),
+            count(&[Use::Written, Use::Read]),
+            count(&[Use::Unused]),
+        )?;
+        for start in (0..MEMORY_SIZE).step_by(MAP_WIDTH) {
+            let row: String = (start..start + MAP_WIDTH).map(|addr| self.0.usage(addr as u16).symbol()).collect();
+            writeln!(f, "{start:03X} {row}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn counts_addresses_and_forms() {
+        // LD V0 1, LD V1 2, then JP back to the second instruction forever
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x02, 0xFF, 0xFF]);
+        chip_8.enable_coverage();
+        for _ in 0..7 {
+            chip_8.step();
+        }
+        let coverage = chip_8.coverage().unwrap();
+        assert_eq!((coverage.hits(0x200), coverage.hits(0x202), coverage.hits(0x204)), (1, 3, 3));
+        assert_eq!(coverage.executed(), 3);
+        assert_eq!(coverage.histogram(&Quirks::default()), [("6XNN".to_string(), 4), ("1NNN".to_string(), 3)]);
+        assert_eq!(coverage.invalid().count(), 0);
+    }
+
+    #[test]
+    fn data_is_told_from_code() {
+        // LD I 0x20C, LD V0-V1 [I], LD I 0x20A, LD [I] V0-V1, then spin, with the two bytes read
+        // after the two written
+        let rom = [0xA2, 0x0C, 0xF1, 0x65, 0xA2, 0x0A, 0xF1, 0x55, 0x12, 0x08, 0x00, 0x00, 0xAB, 0xCD];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        chip_8.enable_coverage();
+        for _ in 0..5 {
+            chip_8.step();
+        }
+        let coverage = chip_8.coverage().unwrap();
+        assert_eq!(coverage.usage(0x200), Use::Executed);
+        assert_eq!((coverage.usage(0x20A), coverage.usage(0x20D), coverage.usage(0x20E)), (Use::Written, Use::Read, Use::Unused));
+        let map = CoverageMap(coverage).to_string();
+        assert!(map.starts_with("coverage: 10 bytes executed (0 of them written), 4 only data"), "{map}");
+        assert!(map.lines().nth(9).is_some_and(|row| row.starts_with("200 ##########wwrr..")), "{map}");
+    }
+}
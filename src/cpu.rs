@@ -0,0 +1,977 @@
+//! The CPU: its registers, and fetching, decoding and executing instructions over the bus
+
+use crate::audio::AUDIO_PATTERN_LEN;
+use crate::cheats::Target;
+use crate::debugger::{Access, WatchHit};
+use crate::diagnostics::Quirk;
+use crate::display::Resolution;
+use crate::megachip::BlendMode;
+use crate::fault::Fault;
+use crate::instruction::Instruction;
+use crate::logging::Level;
+use crate::trace::TraceEntry;
+use crate::memory::PROGRAM_START;
+use crate::script;
+use crate::{AlignmentPolicy, Chip8, FaultPolicy, BIG_FONT_ADDR, FONT_ADDR};
+
+/// What an instruction did that a frontend may want to react to, as returned by Chip8::step()
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Nothing visible outside the machine, or no instruction ran at all
+    #[default]
+    None,
+    /// The display was drawn to or cleared
+    Draw,
+    /// FX0A found no key held and will run again until one is
+    WaitKey,
+    /// 00FD: the program exited, halting the machine
+    Exit,
+    /// 00FE/00FF (or MegaChip's 0010/0011) switched the display to high resolution (true) or
+    /// back to low resolution
+    HiResToggle(bool),
+    /// 00CN/00DN/00FB/00FC (or MegaChip's 00BN) scrolled the display
+    Scroll,
+    /// The instruction faulted; the fault is also queued for take_faults()
+    Error(Fault),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Cpu {
+    pub pc: u16,
+    pub i: u16,
+    /// The top byte of MegaChip's 24-bit I, which only 01NN NNNN sets
+    pub i_high: u8,
+    pub v: [u8; 16],
+    /// Return addresses of the subroutines being executed, innermost last
+    pub stack: Vec<u16>,
+    /// The key FX0A saw go down, which it finishes on once it's released
+    pub key_wait: Option<u8>,
+}
+
+impl Cpu {
+    /// The address I points at, all 24 bits of it
+    pub fn index(&self) -> usize {
+        (self.i_high as usize) << 16 | self.i as usize
+    }
+
+    /// Calls the subroutine at `addr`, or returns false if the stack already holds `depth` calls
+    pub fn call(&mut self, addr: u16, depth: usize) -> bool {
+        if self.stack.len() >= depth {
+            return false;
+        }
+        self.stack.push(self.pc);
+        self.pc = addr;
+        true
+    }
+
+    /// Returns from the innermost subroutine, or returns false if there isn't one
+    pub fn ret(&mut self) -> bool {
+        let Some(addr) = self.stack.pop() else {
+            return false;
+        };
+        self.pc = addr;
+        true
+    }
+
+    /// Skips the next instruction if `condition` holds
+    pub fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+}
+
+trait Nibbles {
+    fn n(&self) -> u8;
+    fn nn(&self) -> u8;
+    fn nnn(&self) -> u16;
+}
+
+impl Nibbles for u16 {
+    /// Returns the least significant 4 bits (0000_0000_0000_XXXX)
+    fn n(&self) -> u8 {
+        *self as u8 & 0xF
+    }
+
+    /// Returns the lower byte
+    fn nn(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the lowest 12 bits
+    fn nnn(&self) -> u16 {
+        self & 0xFFF
+    }
+}
+
+impl Chip8 {
+    /// Executes one instruction, returning what it did that the frontend may care about. The
+    /// timers are independent of this, see tick_timers()
+    pub fn step(&mut self) -> Effect {
+        if self.halted || self.vblank_wait {
+            return Effect::None;
+        }
+        self.bus.executing = self.cpu.pc;
+        self.bus.raised = None;
+        let Some(instruction) = self.fetch() else {
+            return Effect::None;
+        };
+        self.execute(instruction)
+    }
+
+    /// Executes `opcode` as if it had been fetched from the PC, whatever memory holds there, for
+    /// fuzzing the decoder with opcodes and machine states programs wouldn't reach. The PC moves
+    /// past it as usual, and a halted machine or one waiting for vblank runs it all the same
+    pub fn execute_raw(&mut self, opcode: u16) -> Effect {
+        self.bus.executing = self.cpu.pc;
+        self.bus.raised = None;
+        self.cpu.pc = self.cpu.pc.wrapping_add(2);
+        self.execute(opcode)
+    }
+
+    /// The rest of step(), once `instruction` is fetched
+    fn execute(&mut self, instruction: u16) -> Effect {
+        self.counters.instructions += 1;
+        let decoded = Instruction::decode_with(instruction, &self.quirks);
+        self.last_instruction = Some((self.bus.executing, instruction, decoded));
+        if let Some(coverage) = self.bus.coverage.as_mut() {
+            coverage.record(self.bus.executing, instruction);
+        }
+        let before = (self.trace.is_some() || self.history.is_some()).then(|| self.snapshot());
+        let effect = match decoded {
+            Some(decoded) if self.variant.is_none_or(|variant| variant.decodes(instruction)) => self.run_instruction(decoded, instruction),
+            _ => {
+                self.invalid_instruction(instruction);
+                Effect::None
+            }
+        };
+        // FX0A runs again and again until a key comes, but only the last time counts
+        let waiting = matches!(effect, Effect::WaitKey);
+        if !waiting && self.bus.watchpoints.iter().any(|watchpoint| matches!(watchpoint.target, Target::Register(_))) {
+            // the CPU uses its registers directly rather than through the bus, so register
+            // watchpoints go by the instruction instead
+            let (reads, writes) = decoded.map_or((0, 0), |decoded| decoded.registers(&self.quirks));
+            for (access, mask) in [(Access::Read, reads), (Access::Write, writes)] {
+                for x in (0..16).filter(|x| mask & 1 << x != 0) {
+                    self.bus.note_access(Target::Register(x), access);
+                }
+            }
+        }
+        if let Some(before) = before {
+            let entry = TraceEntry { addr: self.bus.executing, opcode: instruction, before, after: self.snapshot() };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.record(entry);
+            }
+            // the history would otherwise fill up with a key wait
+            if let Some(history) = self.history.as_mut().filter(|_| !waiting) {
+                history.record(entry);
+            }
+        }
+        if let Some((target, access)) = self.bus.watched.take() {
+            self.watch_hit.get_or_insert(WatchHit { target, access, pc: self.bus.executing, opcode: instruction, quirks: self.quirks });
+        }
+        if self.script.is_some() && !waiting {
+            self.run_script(script::Event::Exec(self.bus.executing));
+            for addr in std::mem::take(&mut self.bus.hooked_writes) {
+                self.run_script(script::Event::Write(addr));
+            }
+        }
+        // a fault matters more than whatever else the instruction managed to do
+        let Some(fault) = self.bus.raised.take() else {
+            return effect;
+        };
+        match self.fault_policy {
+            FaultPolicy::Ignore | FaultPolicy::Pause => {}
+            FaultPolicy::Log => crate::event!(Level::Warn, "{fault} at PC={:#05x}", self.bus.executing),
+            FaultPolicy::Exit => {
+                self.halted = true;
+                self.fatal = Some((self.bus.executing, fault));
+            }
+        }
+        Effect::Error(fault)
+    }
+
+    /// Decodes `opcode` and executes it, without execute()'s bookkeeping
+    #[cfg(test)]
+    pub(crate) fn decode(&mut self, opcode: u16) -> Effect {
+        match Instruction::decode_with(opcode, &self.quirks) {
+            Some(instruction) => self.run_instruction(instruction, opcode),
+            None => {
+                self.invalid_instruction(opcode);
+                Effect::None
+            }
+        }
+    }
+
+    /// Executes `instruction`, as decoded from `opcode`
+    fn run_instruction(&mut self, instruction: Instruction, opcode: u16) -> Effect {
+        use Instruction::*;
+        match instruction {
+            // MegaChip's instructions are in the 0NNN space
+            MegaOff | MegaOn | MegaScrollUp(_) | Background | Sys(0x100..=0x9FF) if self.quirks.mega_chip => return self.decode_mega(opcode),
+            Clear => {
+                self.display.clear();
+                return Effect::Draw;
+            }
+            Return => if !self.cpu.ret() {
+                self.bus.fault(Fault::StackUnderflow);
+            }
+            // SuperChip instructions
+            Exit => {
+                self.halted = true;
+                return Effect::Exit;
+            }
+            HighRes => {
+                self.display.set_resolution(Resolution::High);
+                return Effect::HiResToggle(true);
+            }
+            LowRes => {
+                self.display.set_resolution(Resolution::Low);
+                return Effect::HiResToggle(false);
+            }
+            ScrollDown(n) => return self.scroll(0, n as isize),
+            // XO-CHIP
+            ScrollUp(n) => return self.scroll(0, -(n as isize)),
+            ScrollRight => return self.scroll(4, 0),
+            ScrollLeft => return self.scroll(-4, 0),
+            // CHIP-8X: the next background color
+            Background => {
+                self.display.cycle_background();
+                return Effect::Draw;
+            }
+            // the machine code routine HIRES CHIP-8 programs clear their 64x64 screen with
+            Sys(0x230) if self.display.resolution() == Resolution::Tall => {
+                self.display.clear();
+                return Effect::Draw;
+            }
+            Sys(_) | MegaOff | MegaOn | MegaScrollUp(_) => { /*Ignore for modern interpreters*/ }
+            Jump(nnn) => self.cpu.pc = nnn,
+            Call(nnn) => if !self.cpu.call(nnn, self.stack_depth()) {
+                self.bus.fault(Fault::StackOverflow);
+            }
+            SkipEqualByte { x, nn } => self.skip_if(self.cpu.v[x] == nn),
+            SkipNotEqualByte { x, nn } => self.skip_if(self.cpu.v[x] != nn),
+            AddDigits { x, y } => {
+                let sum = (self.cpu.v[x] & 0x77) + (self.cpu.v[y] & 0x77);
+                self.cpu.v[x] = sum & 0x77;
+            }
+            SkipEqual { x, y } => self.skip_if(self.cpu.v[x] == self.cpu.v[y]),
+            // XO-CHIP: Vx to Vy in either order, leaving I alone
+            SaveRange { x, y } if self.quirks.wide_memory => {
+                for (n, r) in register_range(x, y).enumerate() {
+                    self.bus.write(self.cpu.index() + n, self.cpu.v[r]);
+                }
+            }
+            LoadRange { x, y } if self.quirks.wide_memory => {
+                for (n, r) in register_range(x, y).enumerate() {
+                    self.cpu.v[r] = self.bus.read(self.cpu.index() + n);
+                }
+            }
+            SaveRange { .. } | LoadRange { .. } => self.invalid_instruction(opcode),
+            LoadByte { x, nn } => self.cpu.v[x] = nn,
+            AddByte { x, nn } => self.cpu.v[x] = self.cpu.v[x].wrapping_add(nn),
+            Move { x, y } => self.cpu.v[x] = self.cpu.v[y],
+            Or { x, y } => {
+                self.cpu.v[x] |= self.cpu.v[y];
+                self.reset_vf();
+            }
+            And { x, y } => {
+                self.cpu.v[x] &= self.cpu.v[y];
+                self.reset_vf();
+            }
+            Xor { x, y } => {
+                self.cpu.v[x] ^= self.cpu.v[y];
+                self.reset_vf();
+            }
+            Add { x, y } => {
+                let (res, carry) = self.cpu.v[x].overflowing_add(self.cpu.v[y]);
+                self.cpu.v[x] = res;
+                self.cpu.v[0xF] = carry as u8;
+            }
+            Sub { x, y } => {
+                let (res, borrow) = self.cpu.v[x].overflowing_sub(self.cpu.v[y]);
+                self.cpu.v[x] = res;
+                self.cpu.v[0xF] = !borrow as u8;
+            }
+            ShiftRight { x, y } => {
+                self.shift_source(x, y);
+                self.cpu.v[0xF] = self.cpu.v[x] & 1;
+                self.cpu.v[x] >>= 1;
+            }
+            SubReverse { x, y } => {
+                let (res, borrow) = self.cpu.v[y].overflowing_sub(self.cpu.v[x]);
+                self.cpu.v[x] = res;
+                self.cpu.v[0xF] = !borrow as u8;
+            }
+            ShiftLeft { x, y } => {
+                self.shift_source(x, y);
+                self.cpu.v[0xF] = self.cpu.v[x] >> 7 & 1;
+                self.cpu.v[x] <<= 1;
+            }
+            SkipNotEqual { x, y } => self.skip_if(self.cpu.v[x] != self.cpu.v[y]),
+            LoadIndex(nnn) => (self.cpu.i, self.cpu.i_high) = (nnn, 0),
+            // CHIP-8X: color the zones set out by Vx and Vx+1 in Vy's color
+            ZoneColor { x, y, n } => {
+                let (vx, below, color) = (self.cpu.v[x] as usize, self.cpu.v[(x + 1) & 0xF] as usize, self.cpu.v[y]);
+                if n == 0 {
+                    // low digits the first 8-pixel column and 4-pixel row, high digits how many more
+                    let (column, row) = (vx & 0xF, (below & 0xF) * 4);
+                    self.display.set_zone_color(column..column + (vx >> 4) + 1, row..row + ((below >> 4) + 1) * 4, color);
+                } else {
+                    // one column of N pixel rows
+                    self.display.set_zone_color(vx / 8..vx / 8 + 1, below..below + n as usize, color);
+                }
+                return Effect::Draw;
+            }
+            JumpOffset { x, nnn } => {
+                if x != 0 {
+                    self.note_quirk(Quirk::JumpOffset, if self.quirks.jump_vx { "jump to xnn + vx" } else { "jump to nnn + v0" });
+                }
+                let offset = if self.quirks.jump_vx { x } else { 0 };
+                self.cpu.pc = nnn + self.cpu.v[offset] as u16;
+            }
+            Random { x, nn } => {
+                let random = if self.quirks.vip_rng {
+                    self.note_quirk(Quirk::RandomSource, "vip sequence");
+                    self.vip_rng.next(self.bus.memory.bytes())
+                } else {
+                    self.rng.next()
+                };
+                self.cpu.v[x] = random & nn;
+            }
+            Draw { x, y, n } if self.display.resolution() == Resolution::Mega => return self.draw_mega(x, y, n),
+            Draw { x, y, n } => {
+                let (width, height) = self.display.dimensions();
+                let x = self.cpu.v[x] as usize % width;
+                let y = self.cpu.v[y] as usize % height;
+                let lo_res = self.display.resolution() == Resolution::Low;
+                // DXY0 draws 16 rows, two bytes each unless it's SUPER-CHIP's lo-res 8x16
+                let (rows, wide) = match n as usize {
+                    0 => (16, !(lo_res && self.quirks.lores_tall_sprites)),
+                    n => (n, false),
+                };
+                let row_width = if wide { 16 } else { 8 };
+                if x + row_width > width || y + rows > height {
+                    self.note_quirk(Quirk::SpriteClip, if self.quirks.clip { "clip" } else { "wrap" });
+                }
+                // a sprite for each plane selected, one after the other
+                let planes = self.display.selected_planes().count_ones() as usize;
+                let mut sprite = vec![];
+                for byte in 0..rows * row_width / 8 * planes {
+                    sprite.push(self.bus.read(self.cpu.index() + byte));
+                }
+                self.counters.draws += 1;
+                let collision = self.display.draw_sprite(x, y, &sprite, wide, self.quirks.clip);
+                self.cpu.v[0xF] = match self.quirks.collision_rows && !lo_res {
+                    true => collision.rows,
+                    false => collision.collided as u8,
+                };
+                if self.quirks.display_wait {
+                    self.note_quirk(Quirk::DisplayWait, "wait for the next frame");
+                }
+                // the VIP's DXYN always waited, which VIP timing can't leave out
+                self.vblank_wait |= self.quirks.display_wait || self.quirks.vip_timing;
+                return Effect::Draw;
+            }
+            SkipKey { x } => {
+                self.counters.key_checks += 1;
+                self.checked_keys |= 1 << (self.cpu.v[x] & 0xF);
+                self.skip_if(self.is_pressed(self.cpu.v[x]));
+            }
+            SkipNotKey { x } => {
+                self.counters.key_checks += 1;
+                self.checked_keys |= 1 << (self.cpu.v[x] & 0xF);
+                self.skip_if(!self.is_pressed(self.cpu.v[x]));
+            }
+            // CHIP-8X: the same for the second keypad
+            SkipKey2 { x } => self.skip_if(self.keys_2 >> (self.cpu.v[x] & 0xF) & 1 == 1),
+            SkipNotKey2 { x } => self.skip_if(self.keys_2 >> (self.cpu.v[x] & 0xF) & 1 == 0),
+            // XO-CHIP long load, the address being the next instruction word
+            LongLoadIndex if self.quirks.wide_memory => {
+                let pc = self.cpu.pc as usize;
+                (self.cpu.i, self.cpu.i_high) = (u16::from_be_bytes([self.bus.fetch(pc), self.bus.fetch(pc + 1)]), 0);
+                self.cpu.pc = self.cpu.pc.wrapping_add(2);
+            }
+            LongLoadIndex => self.invalid_instruction(opcode),
+            // Set Vx to the value of the delay timer
+            LoadDelay { x } => self.cpu.v[x] = self.timers.delay,
+            // Wait for a key to be pressed and released again, as the VIP did, or only
+            // pressed. The wait is this instruction running again every step, so timers
+            // tick and frames end around it as ever
+            WaitKey { x } => match self.cpu.key_wait {
+                Some(key) if self.quirks.key_wait_press || !self.is_pressed(key) => {
+                    self.cpu.key_wait = None;
+                    self.cpu.v[x] = key;
+                }
+                _ => {
+                    // any key will do
+                    self.counters.key_checks += 1;
+                    self.checked_keys = u16::MAX;
+                    if self.cpu.key_wait.is_none() && self.keys != 0 {
+                        let key = self.keys.trailing_zeros() as u8;
+                        if self.quirks.key_wait_press {
+                            self.cpu.v[x] = key;
+                            return Effect::None;
+                        }
+                        self.cpu.key_wait = Some(key);
+                    }
+                    // rewind the pc so the next step() executes this instruction again until input
+                    self.cpu.pc = self.cpu.pc.wrapping_sub(2);
+                    return Effect::WaitKey;
+                }
+            }
+            // Set the delay timer to Vx
+            SetDelay { x } => self.timers.delay = self.cpu.v[x],
+            // Set the sound timer to Vx
+            SetSound { x } => self.timers.sound = self.cpu.v[x],
+            AddIndex { x } => {
+                let res = self.cpu.i.wrapping_add(self.cpu.v[x] as u16);
+                // If I + Vx overflows out of normal addressing range set VF to 1
+                // This was not universal, but when unused it shouldn't matter
+                if res > 0xFFF || res < self.cpu.i {
+                    self.note_quirk(Quirk::IndexOverflow, if self.quirks.index_overflow { "set vf" } else { "leave vf" });
+                    if self.quirks.index_overflow {
+                        self.cpu.v[0xF] = 1;
+                    }
+                }
+                self.cpu.i = res;
+            }
+            Font { x } => (self.cpu.i, self.cpu.i_high) = (FONT_ADDR + 5 * (self.cpu.v[x] & 0xF) as u16, 0),
+            // SuperChip BigHex characters
+            BigFont { x } => (self.cpu.i, self.cpu.i_high) = (BIG_FONT_ADDR + 10 * (self.cpu.v[x] & 0xF) as u16, 0),
+            // XO-CHIP plane select
+            Planes(planes) => self.display.select_planes(planes),
+            // XO-CHIP audio pattern, 16 bytes from I
+            AudioPattern => {
+                let mut pattern = [0; AUDIO_PATTERN_LEN];
+                for (n, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.bus.read(self.cpu.index() + n);
+                }
+                self.audio_pattern = Some(pattern);
+            }
+            // XO-CHIP audio pitch
+            Pitch { x } => self.pitch.register = self.cpu.v[x],
+            Bcd { x } => {
+                let vx = self.cpu.v[x];
+                self.bus.write(self.cpu.index(), vx / 100);
+                self.bus.write(self.cpu.index() + 1, (vx / 10) % 10);
+                self.bus.write(self.cpu.index() + 2, vx % 10);
+            }
+            Store { x } => {
+                self.note_quirk(Quirk::LoadStoreIndex, self.quirks.load_store.describe());
+                for n in 0..self.quirks.load_store.store_count(x) {
+                    self.bus.write(self.cpu.index() + n, self.cpu.v[n]);
+                }
+                self.cpu.i = self.cpu.i.wrapping_add(self.quirks.load_store.index_increment(x));
+            }
+            Load { x } => {
+                self.note_quirk(Quirk::LoadStoreIndex, self.quirks.load_store.describe());
+                for n in 0..=x {
+                    self.cpu.v[n] = self.bus.read(self.cpu.index() + n);
+                }
+                self.cpu.i = self.cpu.i.wrapping_add(self.quirks.load_store.index_increment(x));
+            }
+            // SuperChip RPL user flags
+            SaveFlags { x } => self.rpl[..x + 1].copy_from_slice(&self.cpu.v[..x + 1]),
+            LoadFlags { x } => self.cpu.v[..x + 1].copy_from_slice(&self.rpl[..x + 1]),
+        }
+        Effect::None
+    }
+
+    /// MegaChip's instructions, all in the 0NNN space CHIP-8 left to machine code routines
+    fn decode_mega(&mut self, instruction: u16) -> Effect {
+        let nn = instruction.nn();
+        match instruction.nnn() {
+            0x010 => {
+                self.display.set_resolution(Resolution::Low);
+                return Effect::HiResToggle(false);
+            }
+            0x011 => {
+                self.display.set_resolution(Resolution::Mega);
+                return Effect::HiResToggle(true);
+            }
+            0x0B0..=0x0BF => {
+                self.display.scroll(0, -(instruction.n() as isize));
+                return Effect::Scroll;
+            }
+            // the rest of I's 24 bits are the next instruction word
+            0x100..=0x1FF => {
+                let pc = self.cpu.pc as usize;
+                self.cpu.i = u16::from_be_bytes([self.bus.fetch(pc), self.bus.fetch(pc + 1)]);
+                self.cpu.i_high = nn;
+                self.cpu.pc = self.cpu.pc.wrapping_add(2);
+            }
+            0x200..=0x2FF => {
+                let colors: Vec<_> = (0..nn as usize * 4).map(|n| self.bus.read(self.cpu.index() + n)).collect();
+                if let Some(mega) = self.display.mega_mut() {
+                    mega.load_palette(&colors);
+                }
+            }
+            // 0 stands for 256
+            0x300..=0x3FF => if let Some(mega) = self.display.mega_mut() {
+                mega.sprite_width = if nn == 0 { 256 } else { nn as usize };
+            }
+            0x400..=0x4FF => if let Some(mega) = self.display.mega_mut() {
+                mega.sprite_height = if nn == 0 { 256 } else { nn as usize };
+            }
+            0x500..=0x5FF => if let Some(mega) = self.display.mega_mut() {
+                mega.alpha = nn;
+            }
+            // digitized sound isn't played, so starting and stopping it do nothing
+            0x600..=0x60F | 0x700 => {}
+            0x800..=0x80F => match (BlendMode::from_number(instruction.n()), self.display.mega_mut()) {
+                (Some(blend), Some(mega)) => mega.blend = blend,
+                (None, _) => self.invalid_instruction(instruction),
+                (Some(_), None) => {}
+            }
+            0x900..=0x9FF => if let Some(mega) = self.display.mega_mut() {
+                mega.collision_color = nn;
+            }
+            _ => self.invalid_instruction(instruction),
+        }
+        Effect::None
+    }
+
+    /// DXYN on the MegaChip screen: a sprite_width by sprite_height sprite of palette indices
+    /// at (Vx, Vy), or the usual N rows when I points at the fonts, in the font color
+    fn draw_mega(&mut self, x: usize, y: usize, n: u8) -> Effect {
+        let (x, y) = (self.cpu.v[x] as usize, self.cpu.v[y] as usize);
+        let font = self.cpu.index() < PROGRAM_START as usize;
+        let len = match self.display.mega() {
+            Some(_) if font => n as usize,
+            Some(mega) => mega.sprite_width * mega.sprite_height,
+            None => 0,
+        };
+        let sprite: Vec<_> = (0..len).map(|n| self.bus.read(self.cpu.index() + n)).collect();
+        self.counters.draws += 1;
+        let collided = self.display.mega_mut().is_some_and(|mega| if font { mega.draw_bits(x, y, &sprite) } else { mega.draw(x, y, &sprite) });
+        self.cpu.v[0xF] = collided as u8;
+        Effect::Draw
+    }
+
+    fn fetch(&mut self) -> Option<u16> {
+        let i = self.cpu.pc as usize;
+        if i % 2 == 1 {
+            match self.alignment {
+                AlignmentPolicy::Allow => {}
+                AlignmentPolicy::Warn => if self.misaligned.insert(self.cpu.pc) {
+                    crate::event!(Level::Warn, "executing from odd address: {:#05x}", self.cpu.pc);
+                }
+                AlignmentPolicy::Error => {
+                    crate::event!(Level::Error, "halting on odd address: {:#05x}", self.cpu.pc);
+                    self.halted = true;
+                    return None;
+                }
+            }
+        }
+        self.cpu.pc = self.cpu.pc.wrapping_add(2);
+        Some(u16::from_be_bytes([self.bus.fetch(i), self.bus.fetch(i + 1)]))
+    }
+
+    /// Skips the next instruction if `condition` holds, both words of an F000 NNNN with wide memory
+    fn skip_if(&mut self, condition: bool) {
+        let long = condition && self.quirks.wide_memory && self.next_instruction() == 0xF000;
+        self.cpu.skip_if(condition);
+        self.cpu.skip_if(long);
+    }
+
+    /// 8XY6/8XYE: under the shift quirk the value shifted is Vy's, copied into Vx first
+    fn shift_source(&mut self, x: usize, y: usize) {
+        if x != y {
+            self.note_quirk(Quirk::ShiftSource, if self.quirks.shift_vy { "copy vy into vx" } else { "shift vx in place" });
+            if self.quirks.shift_vy {
+                self.cpu.v[x] = self.cpu.v[y];
+            }
+        }
+    }
+
+    /// 8XY1/8XY2/8XY3 clear VF on the original interpreter, as a side effect of how it ran them
+    fn reset_vf(&mut self) {
+        self.note_quirk(Quirk::VfReset, if self.quirks.vf_reset { "reset vf" } else { "leave vf" });
+        if self.quirks.vf_reset {
+            self.cpu.v[0xF] = 0;
+        }
+    }
+
+    /// Scrolls by `dx` and `dy` pixels of the active resolution, or half as many on the lo-res
+    /// screen with half_scroll
+    fn scroll(&mut self, dx: isize, dy: isize) -> Effect {
+        if self.display.resolution() == Resolution::Low {
+            self.note_quirk(Quirk::ScrollDistance, if self.quirks.half_scroll { "half" } else { "full" });
+            if self.quirks.half_scroll {
+                self.display.scroll(dx / 2, dy / 2);
+                return Effect::Scroll;
+            }
+        }
+        self.display.scroll(dx, dy);
+        Effect::Scroll
+    }
+
+    /// Called from decode(), to attribute the quirk to the executing instruction
+    fn note_quirk(&mut self, quirk: Quirk, choice: &'static str) {
+        if let Some(diagnostics) = self.diagnostics.as_mut() {
+            diagnostics.record(self.bus.executing, quirk, choice);
+        }
+    }
+
+    fn invalid_instruction(&mut self, instruction: u16) {
+        self.counters.invalid_opcodes += 1;
+        if let Some(coverage) = self.bus.coverage.as_mut() {
+            coverage.record_invalid(self.bus.executing, instruction);
+        }
+        self.bus.fault(Fault::InvalidOpcode(instruction));
+    }
+}
+
+/// The registers 5XY2 and 5XY3 go through, from Vx to Vy, counting down if Y is below X
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y { Box::new(x..=y) } else { Box::new((y..=x).rev()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::{LoadStore, Preset, Quirks};
+    use crate::STACK_DEPTH;
+
+    fn machine() -> Chip8 {
+        let mut chip_8 = Chip8::from_rom(&[]);
+        chip_8.cpu.i = 0x300;
+        chip_8.cpu.v = *b"0123456789ABCDEF";
+        chip_8
+    }
+
+    fn store_and_load(load_store: LoadStore) -> Chip8 {
+        let mut chip_8 = machine();
+        chip_8.set_quirks(Quirks { load_store, ..Quirks::default() });
+        assert_eq!(chip_8.decode(0xF355), Effect::None);
+        chip_8
+    }
+
+    #[test]
+    fn vip_store_is_inclusive_and_advances_i_past_the_last_byte() {
+        let chip_8 = store_and_load(LoadStore::Vip);
+        assert_eq!(&chip_8.bus.memory.bytes()[0x300..0x305], b"0123\0");
+        assert_eq!(chip_8.cpu.i, 0x304);
+    }
+
+    #[test]
+    fn chip48_store_is_inclusive_and_advances_i_by_x() {
+        let chip_8 = store_and_load(LoadStore::Chip48);
+        assert_eq!(&chip_8.bus.memory.bytes()[0x300..0x305], b"0123\0");
+        assert_eq!(chip_8.cpu.i, 0x303);
+    }
+
+    #[test]
+    fn modern_store_is_inclusive_and_leaves_i() {
+        let chip_8 = store_and_load(LoadStore::Modern);
+        assert_eq!(&chip_8.bus.memory.bytes()[0x300..0x305], b"0123\0");
+        assert_eq!(chip_8.cpu.i, 0x300);
+    }
+
+    #[test]
+    fn legacy_store_skips_vx() {
+        let chip_8 = store_and_load(LoadStore::Legacy);
+        assert_eq!(&chip_8.bus.memory.bytes()[0x300..0x305], b"012\0\0");
+        assert_eq!(chip_8.cpu.i, 0x300);
+    }
+
+    #[test]
+    fn load_is_inclusive_in_every_mode() {
+        for (load_store, i) in [
+            (LoadStore::Vip, 0x304),
+            (LoadStore::Chip48, 0x303),
+            (LoadStore::Modern, 0x300),
+            (LoadStore::Legacy, 0x300),
+        ] {
+            let mut chip_8 = machine();
+            chip_8.set_quirks(Quirks { load_store, ..Quirks::default() });
+            for (n, &byte) in b"wxyz!".iter().enumerate() {
+                chip_8.bus.memory.set(0x300 + n, byte);
+            }
+            chip_8.decode(0xF365);
+            assert_eq!(&chip_8.cpu.v[..5], b"wxyz4", "{load_store:?}");
+            assert_eq!(chip_8.cpu.i, i, "{load_store:?}");
+        }
+    }
+
+    #[test]
+    fn calls_nest_up_to_the_stack_depth() {
+        let mut cpu = Cpu { pc: 0x202, ..Cpu::default() };
+        for _ in 0..STACK_DEPTH {
+            assert!(cpu.call(0x300, STACK_DEPTH));
+        }
+        assert!(!cpu.call(0x400, STACK_DEPTH));
+        assert_eq!(cpu.pc, 0x300);
+        for _ in 0..STACK_DEPTH - 1 {
+            assert!(cpu.ret());
+        }
+        assert!(cpu.ret());
+        assert_eq!(cpu.pc, 0x202);
+        assert!(!cpu.ret());
+    }
+
+    #[test]
+    fn exit_policy_halts_on_the_first_fault() {
+        // three nested calls, one more than a 2 deep stack holds
+        let mut chip_8 = Chip8::builder()
+            .rom_bytes(&[0x22, 0x02, 0x22, 0x04, 0x22, 0x06])
+            .stack_depth(2)
+            .fault_policy(FaultPolicy::Exit)
+            .build();
+        chip_8.step();
+        chip_8.step();
+        assert!(chip_8.exit_status().is_ok());
+        assert_eq!(chip_8.step(), Effect::Error(Fault::StackOverflow));
+        assert!(chip_8.halted());
+        assert!(matches!(chip_8.exit_status(), Err(crate::Chip8Error::Fault { pc: 0x204, fault: Fault::StackOverflow })));
+        assert_eq!(chip_8.step(), Effect::None);
+        chip_8.reset();
+        assert!(chip_8.exit_status().is_ok());
+    }
+
+    #[test]
+    fn faults_are_attributed_to_the_executing_instruction() {
+        // 0x200: CALL 0x204, 0x202: unused, 0x204: RET, then RET again with an empty stack
+        let mut chip_8 = Chip8::from_rom(&[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE]);
+        chip_8.step();
+        chip_8.step();
+        assert_eq!(chip_8.cpu.pc, 0x202);
+        chip_8.cpu.pc = 0x204;
+        chip_8.step();
+        assert_eq!(chip_8.take_faults(), [(0x204, Fault::StackUnderflow)]);
+    }
+
+    #[test]
+    fn every_opcode_runs_without_panicking_at_the_edges() {
+        for preset in [Preset::Chip8, Preset::Schip, Preset::Xochip, Preset::Chip8x] {
+            let mut edge = Chip8::builder().platform(preset).build();
+            edge.set_fault_policy(FaultPolicy::Ignore);
+            // every register at its largest, I at the end of memory and the PC on the last word
+            for x in 0..16 {
+                edge.execute_raw(0x60FF | x << 8);
+            }
+            edge.execute_raw(0xAFFF);
+            edge.execute_raw(0x1FFE);
+            for opcode in 0..=u16::MAX {
+                edge.clone().execute_raw(opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn checked_keys_are_the_ones_tested() {
+        // LD V1 5, SKP V1, SKNP V1 with V1 = 0xA skipping a blank word, then LD V0 K
+        let rom = [0x61, 0x05, 0xE1, 0x9E, 0x61, 0x0A, 0xE1, 0xA1, 0x00, 0x00, 0xF0, 0x0A];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        for _ in 0..4 {
+            chip_8.step();
+        }
+        assert_eq!(chip_8.take_checked_keys(), 1 << 0x5 | 1 << 0xA);
+        assert_eq!(chip_8.take_checked_keys(), 0);
+        chip_8.step();
+        assert_eq!(chip_8.take_checked_keys(), u16::MAX);
+        assert_eq!(chip_8.counters().key_checks, 3);
+    }
+
+    #[test]
+    fn latched_keys_change_between_frames() {
+        // SKP V0 twice, skipping nothing either way
+        let rom = [0xE0, 0x9E, 0x12, 0x04, 0xE0, 0x9E, 0x12, 0x08, 0x12, 0x08];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        chip_8.latch_keys(true);
+        chip_8.press(0x0);
+        assert_eq!(chip_8.keys(), 1);
+        chip_8.step();
+        assert_eq!(chip_8.pc(), 0x202);
+        chip_8.tick_timers();
+        chip_8.step();
+        chip_8.step();
+        assert_eq!(chip_8.pc(), 0x208);
+        chip_8.latch_keys(false);
+        chip_8.release(0x0);
+        assert_eq!(chip_8.keys(), 0);
+    }
+
+    #[test]
+    fn step_reports_effects() {
+        // CLS, HIGH, SCR, LD V0 K, then EXIT
+        let rom = [0x00, 0xE0, 0x00, 0xFF, 0x00, 0xFB, 0xF0, 0x0A, 0x00, 0xFD];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        assert_eq!(chip_8.step(), Effect::Draw);
+        assert_eq!(chip_8.step(), Effect::HiResToggle(true));
+        assert_eq!(chip_8.step(), Effect::Scroll);
+        assert_eq!(chip_8.step(), Effect::WaitKey);
+        assert_eq!(chip_8.step(), Effect::WaitKey);
+        chip_8.press(0x3);
+        assert_eq!(chip_8.step(), Effect::WaitKey);
+        chip_8.release(0x3);
+        assert_eq!(chip_8.step(), Effect::None);
+        assert_eq!(chip_8.step(), Effect::Exit);
+        assert!(chip_8.halted());
+        assert_eq!(chip_8.step(), Effect::None);
+    }
+
+    #[test]
+    fn hires_programs_get_a_64x64_display() {
+        // the jump to the patch, then at 0x2C0: LD V0 40, LD I to the font's 0, DRW V0 V0 5 and
+        // the patch's clear screen routine
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        rom.extend([0x60, 0x28, 0xA0, FONT_ADDR as u8, 0xD0, 0x05, 0x02, 0x30]);
+        let mut chip_8 = Chip8::from_rom(&rom);
+        assert_eq!((chip_8.pc(), chip_8.framebuffer().dimensions()), (0x2C0, (64, 64)));
+        for _ in 0..3 {
+            chip_8.step();
+        }
+        assert!(chip_8.framebuffer().pixel(40, 40));
+        assert_eq!(chip_8.step(), Effect::Draw);
+        assert!(!chip_8.framebuffer().pixel(40, 40));
+        chip_8.soft_reset();
+        assert_eq!(chip_8.pc(), 0x2C0);
+        assert_eq!(Chip8::from_rom(&[0x00, 0xE0]).framebuffer().dimensions(), (64, 32));
+    }
+
+    #[test]
+    fn chip_8x_colors_and_second_keypad() {
+        // 02A0, color columns 2-3 of rows 4-7 with V2, add the digits of 0x35 and 0x47, then skip
+        // on key 7 of the second keypad
+        let rom = [
+            0x02, 0xA0, 0x60, 0x12, 0x61, 0x01, 0x62, 0x05, 0xB0, 0x20,
+            0x63, 0x35, 0x64, 0x07, 0x65, 0x47, 0x53, 0x51, 0xE4, 0xF2, 0x00, 0xFD, 0x00, 0xFD,
+        ];
+        let mut chip_8 = Chip8::builder().platform(Preset::Chip8x).rom_bytes(&rom).build();
+        assert_eq!(chip_8.pc(), 0x300);
+        chip_8.set_second_keypad(1 << 7);
+        for _ in 0..10 {
+            chip_8.step();
+        }
+        let colors = chip_8.framebuffer().colors().unwrap();
+        assert_eq!(colors.background, 1);
+        assert_eq!((colors.zones[4][2], colors.zones[7][3], colors.zones[8][2], colors.zones[4][4]), (5, 5, 1, 1));
+        assert_eq!(chip_8.registers()[3], 0x74);
+        assert_eq!(chip_8.pc(), 0x316);
+    }
+
+    #[test]
+    fn xo_chip_reaches_64kb_through_long_loads() {
+        // LD I, long 0xE000, LD V0 7, LD [I] V0, SE V0 7 over a whole LD I, long 0x1234, LD V1 0x99
+        let rom = [0xF0, 0x00, 0xE0, 0x00, 0x60, 0x07, 0xF0, 0x55, 0x30, 0x07, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x99];
+        let mut chip_8 = Chip8::builder().platform(Preset::Xochip).rom_bytes(&rom).build();
+        for _ in 0..5 {
+            chip_8.step();
+        }
+        assert_eq!((chip_8.peek(0xE000), chip_8.cpu.i, chip_8.cpu.v[1]), (7, 0xE001, 0x99));
+        assert!(chip_8.take_faults().is_empty());
+        // 4KB that wraps around everywhere else, where there's no long load either
+        let mut chip_8 = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build();
+        assert_eq!(chip_8.step(), Effect::Error(Fault::InvalidOpcode(0xF000)));
+        chip_8.poke(0xE300, 5);
+        assert_eq!(chip_8.peek(0x300), 5);
+    }
+
+    #[test]
+    fn xo_chip_saves_and_loads_register_ranges() {
+        // LD V1 1, LD V2 2, LD V3 3, LD I 0x300, SAVE V3 - V1, LOAD V4 - V6
+        let rom = [0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00, 0x53, 0x12, 0x54, 0x63];
+        let mut chip_8 = Chip8::builder().platform(Preset::Xochip).rom_bytes(&rom).build();
+        for _ in 0..6 {
+            chip_8.step();
+        }
+        assert_eq!([chip_8.peek(0x300), chip_8.peek(0x301), chip_8.peek(0x302)], [3, 2, 1]);
+        assert_eq!((&chip_8.cpu.v[4..7], chip_8.cpu.i), (&[3, 2, 1][..], 0x300));
+        // only XO-CHIP has them
+        let mut chip_8 = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build();
+        for _ in 0..4 {
+            chip_8.step();
+        }
+        assert_eq!(chip_8.step(), Effect::Error(Fault::InvalidOpcode(0x5312)));
+    }
+
+    #[test]
+    fn faults_take_precedence() {
+        // RET with nothing to return to, then a draw reading past the end of memory
+        let mut chip_8 = Chip8::from_rom(&[0x00, 0xEE, 0xD0, 0x01]);
+        assert_eq!(chip_8.step(), Effect::Error(Fault::StackUnderflow));
+        chip_8.cpu.i = 0xFFFF;
+        assert_eq!(chip_8.step(), Effect::Error(Fault::OutOfBounds(0xFFFF)));
+        assert_eq!(chip_8.take_faults().len(), 2);
+    }
+
+    #[test]
+    fn key_wait_finishes_on_release() {
+        // LD V5, K
+        let mut chip_8 = Chip8::from_rom(&[0xF5, 0x0A]);
+        chip_8.press(0x7);
+        chip_8.step();
+        // a second key going down doesn't replace the one being waited on
+        chip_8.press(0x2);
+        chip_8.release(0x7);
+        chip_8.step();
+        assert_eq!(chip_8.cpu.v[5], 0x7);
+        assert_eq!(chip_8.cpu.pc, 0x202);
+        assert_eq!(chip_8.cpu.key_wait, None);
+        // or finishes on the press, with the quirk
+        let mut chip_8 = Chip8::from_rom(&[0xF5, 0x0A]);
+        chip_8.set_quirks(Quirks { key_wait_press: true, ..chip_8.quirks() });
+        assert_eq!(chip_8.step(), Effect::WaitKey);
+        chip_8.press(0x9);
+        assert_eq!(chip_8.step(), Effect::None);
+        assert_eq!((chip_8.cpu.v[5], chip_8.cpu.pc, chip_8.cpu.key_wait), (0x9, 0x202, None));
+    }
+
+    #[test]
+    fn random_numbers_follow_the_seed_and_the_script() {
+        let mut chip_8 = machine();
+        chip_8.seed_rng(7);
+        chip_8.decode(0xC0FF);
+        let mut again = machine();
+        again.seed_rng(7);
+        again.decode(0xC0FF);
+        assert_eq!(chip_8.cpu.v[0], again.cpu.v[0]);
+        chip_8.script_random(&[0x12, 0x34]);
+        chip_8.decode(0xC1FF);
+        let state = chip_8.save_state();
+        chip_8.decode(0xC20F);
+        assert_eq!(chip_8.cpu.v[1..3], [0x12, 0x04]);
+        // the script not yet drawn is part of the state
+        again.load_state(&state).unwrap();
+        again.decode(0xC3FF);
+        assert_eq!(again.cpu.v[3], 0x34);
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_sprites_and_counts_the_rows_hit() {
+        // HIGH, LD I 0x20A, LD V0 0, then DRW V0 V0 0 twice, all 16 rows the second time
+        // running into the first
+        let mut rom = vec![0x00, 0xFF, 0xA2, 0x0A, 0x60, 0x00, 0xD0, 0x00, 0xD0, 0x00];
+        rom.extend([0xFF; 32]);
+        let mut chip_8 = Chip8::from_rom(&rom);
+        for _ in 0..4 {
+            chip_8.step();
+        }
+        assert!(chip_8.framebuffer().pixel(15, 15) && !chip_8.framebuffer().pixel(16, 0));
+        assert_eq!(chip_8.cpu.v[0xF], 0);
+        chip_8.step();
+        assert_eq!(chip_8.cpu.v[0xF], 16);
+        chip_8.set_quirks(Quirks::preset(Preset::Xochip));
+        chip_8.reset();
+        for _ in 0..5 {
+            chip_8.step();
+        }
+        assert_eq!(chip_8.cpu.v[0xF], 1);
+        // lo-res, where SUPER-CHIP drew them 8 wide
+        let rom = [0xA2, 0x06, 0x60, 0x00, 0xD0, 0x00, 0xFF, 0xFF];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        for _ in 0..3 {
+            chip_8.step();
+        }
+        assert!(chip_8.framebuffer().pixel(0, 1) && !chip_8.framebuffer().pixel(8, 0));
+        chip_8.set_quirks(Quirks::preset(Preset::Xochip));
+        chip_8.reset();
+        for _ in 0..3 {
+            chip_8.step();
+        }
+        assert!(chip_8.framebuffer().pixel(15, 0) && !chip_8.framebuffer().pixel(0, 1));
+    }
+}
@@ -0,0 +1,88 @@
+//! Crash reports: what a ROM had run into and everything needed to make it happen again, as one
+//! plain text file to attach to an issue. The state at the end is in it in base64, as Ctrl+C
+//! copies states, so pasting it back with Ctrl+V picks up from just after the fault
+
+use std::fmt;
+
+use crate::debugger::Registers;
+use crate::export::base64_encode;
+use crate::fault::Fault;
+use crate::rom::RomId;
+use crate::symbols::Symbols;
+use crate::Chip8;
+
+/// The most of the machine's history a report lists, latest last
+pub const TRACE_LINES: usize = 64;
+
+/// Base64 characters per line of the state
+const STATE_LINE: usize = 76;
+
+pub struct CrashReport<'a> {
+    pub chip_8: &'a Chip8,
+    pub rom_id: &'a RomId,
+    pub symbols: &'a Symbols,
+    /// The instruction that faulted, and how
+    pub pc: u16,
+    pub fault: Fault,
+}
+
+impl fmt::Display for CrashReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chip_8 = self.chip_8;
+        writeln!(f, "chip_8_rs {} crash report", env!("CARGO_PKG_VERSION"))?;
+        writeln!(f, "fault: {} at {}", self.fault, self.symbols.describe(self.pc))?;
+        writeln!(f, "ROM: CRC32 {}, SHA-1 {}, {} bytes", self.rom_id.key(), self.rom_id.sha1_hex(), chip_8.rom().len())?;
+        match chip_8.variant() {
+            Some(variant) => writeln!(f, "variant: {variant}")?,
+            None => writeln!(f, "variant: none")?,
+        }
+        writeln!(f, "quirks: {:?}", chip_8.quirks())?;
+        writeln!(f)?;
+        write!(f, "{}", Registers(chip_8))?;
+        writeln!(f)?;
+        match chip_8.history().filter(|history| !history.is_empty()) {
+            Some(history) => {
+                writeln!(f, "last instructions, oldest first:")?;
+                for n in (0..history.len().min(TRACE_LINES)).rev() {
+                    if let Some(entry) = history.back(n) {
+                        writeln!(f, "  {}", entry.describe(self.symbols, &chip_8.quirks()))?;
+                    }
+                }
+            }
+            None => writeln!(f, "last instructions: none kept")?,
+        }
+        writeln!(f)?;
+        writeln!(f, "state, for Ctrl+V:")?;
+        let state = base64_encode(&chip_8.save_state());
+        for line in state.as_bytes().chunks(STATE_LINE) {
+            writeln!(f, "{}", String::from_utf8_lossy(line))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::base64_decode;
+
+    #[test]
+    fn reports_name_the_fault_and_carry_the_state() {
+        // LD V3, 07, then an opcode no interpreter has
+        let rom = [0x63, 0x07, 0xE3, 0x00];
+        let (mut chip_8, info) = Chip8::load_bytes(rom.to_vec(), None).unwrap();
+        chip_8.enable_history(8);
+        chip_8.step();
+        chip_8.step();
+        let (pc, fault) = chip_8.take_faults()[0];
+        let symbols = Symbols::default();
+        let report = CrashReport { chip_8: &chip_8, rom_id: &info.id, symbols: &symbols, pc, fault }.to_string();
+        assert!(report.contains("fault: invalid opcode E300 at 0x202"));
+        assert!(report.contains(&format!("CRC32 {}", info.id.key())));
+        assert!(report.contains("  200: 6307 LD V3, 0x07"));
+        let state = report.split_once("state, for Ctrl+V:\n").map(|(_, state)| base64_decode(state)).unwrap();
+        let mut fresh = Chip8::from_rom(&rom);
+        assert!(state.is_some_and(|state| fresh.load_state(&state).is_ok()));
+        assert_eq!(fresh.registers()[3], 7);
+    }
+}
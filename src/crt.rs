@@ -0,0 +1,105 @@
+//! CRT-style effects drawn over the game in window pixels: scanlines, a grid between the
+//! CHIP-8 pixels and a vignette darkening the edges. Each can be toggled while playing
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+/// Darkening bands the vignette is built from, from the edge inwards
+const VIGNETTE_BANDS: u32 = 12;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CrtEffects {
+    pub scanlines: bool,
+    pub grid: bool,
+    pub vignette: bool,
+}
+
+impl CrtEffects {
+    pub fn any(&self) -> bool {
+        self.scanlines || self.grid || self.vignette
+    }
+}
+
+impl std::str::FromStr for CrtEffects {
+    type Err = String;
+
+    /// Comma separated effect names, e.g. `scanlines,vignette`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut effects = Self::default();
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "scanlines" => effects.scanlines = true,
+                "grid" => effects.grid = true,
+                "vignette" => effects.vignette = true,
+                "none" | "" => {}
+                _ => return Err(format!("unknown effect `{name}`, expected scanlines, grid or vignette")),
+            }
+        }
+        Ok(effects)
+    }
+}
+
+/// Draws `effects` over the letterboxed display, `columns` by `rows` logical pixels at the top of
+/// the canvas
+pub fn draw(canvas: &mut Canvas<Window>, effects: CrtEffects, (columns, rows): (u32, u32)) {
+    if !effects.any() {
+        return;
+    }
+    // SDL scales the logical canvas by whole pixels and centers it; work out where the display
+    // landed, which is all of it unless the keypad panel is below
+    let (logical_width, logical_height) = canvas.logical_size();
+    let Ok((width, height)) = canvas.output_size() else {
+        return;
+    };
+    let scale = (width / logical_width).min(height / logical_height).max(1);
+    let screen = Rect::new(
+        ((width - logical_width * scale) / 2) as i32,
+        ((height - logical_height * scale) / 2) as i32,
+        columns * scale,
+        rows * scale,
+    );
+    canvas.set_logical_size(0, 0).expect("failed to draw effects");
+    canvas.set_blend_mode(BlendMode::Blend);
+    if effects.scanlines && scale >= 3 {
+        // the bottom third of every pixel row
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 110));
+        let thickness = scale / 3;
+        let lines: Vec<_> = (0..rows)
+            .map(|row| Rect::new(screen.x(), screen.y() + ((row + 1) * scale - thickness) as i32, screen.width(), thickness))
+            .collect();
+        canvas.fill_rects(&lines).expect("failed to draw effects");
+    }
+    if effects.grid && scale >= 4 {
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 90));
+        let columns = (1..columns).map(|col| Rect::new(screen.x() + (col * scale) as i32, screen.y(), 1, screen.height()));
+        let rows = (1..rows).map(|row| Rect::new(screen.x(), screen.y() + (row * scale) as i32, screen.width(), 1));
+        let lines: Vec<_> = columns.chain(rows).collect();
+        canvas.fill_rects(&lines).expect("failed to draw effects");
+    }
+    // a frame of bands around the edge, darkest on the outside
+    let depth = screen.height() / 6 / VIGNETTE_BANDS;
+    if effects.vignette && depth > 0 {
+        for band in 0..VIGNETTE_BANDS {
+            let inset = band * depth;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, (60 * (VIGNETTE_BANDS - band) / VIGNETTE_BANDS) as u8));
+            let outer = Rect::new(
+                screen.x() + inset as i32,
+                screen.y() + inset as i32,
+                screen.width() - 2 * inset,
+                screen.height() - 2 * inset,
+            );
+            let (w, h) = (outer.width(), outer.height());
+            let sides = [
+                Rect::new(outer.x(), outer.y(), w, depth),
+                Rect::new(outer.x(), outer.bottom() - depth as i32, w, depth),
+                Rect::new(outer.x(), outer.y() + depth as i32, depth, h - 2 * depth),
+                Rect::new(outer.right() - depth as i32, outer.y() + depth as i32, depth, h - 2 * depth),
+            ];
+            canvas.fill_rects(&sides).expect("failed to draw effects");
+        }
+    }
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_logical_size(logical_width, logical_height).expect("failed to draw effects");
+}
@@ -0,0 +1,33 @@
+//! How long instructions took on the COSMAC VIP, for the vip_timing quirk. The VIP's 1802 ran
+//! at 1.76MHz, 8 clocks to a machine cycle, which makes a 60Hz frame 3668 machine cycles. The
+//! 1861 display took 1024 of them to fetch the picture and the interrupt routine about 50 more
+//! to count the timers down, leaving the interpreter the rest.
+//!
+//! The counts are those of the interpreter's routines, rounded the way the published tables
+//! have them: close, but not exact, as a few depend on where things sit in memory
+
+/// Machine cycles the interpreter gets each frame
+pub const CYCLES_PER_FRAME: u32 = 3668 - 1024 - 50;
+
+/// Fetching an instruction and jumping to its routine, on top of what the routine takes. What
+/// each routine takes is the instruction's, see Instruction::vip_cycles()
+pub(crate) const FETCH: u32 = 40;
+
+#[cfg(test)]
+mod tests {
+    use crate::quirks::Quirks;
+    use crate::Chip8;
+
+    #[test]
+    fn frames_run_out_of_cycles_not_instructions() {
+        // ADD V0 1 and the jump back take 50 + 52 cycles a pass
+        let quirks = Quirks { vip_timing: true, ..Quirks::default() };
+        let mut chip_8 = Chip8::builder().quirks(quirks).rom_bytes(&[0x70, 0x01, 0x12, 0x00]).build();
+        chip_8.run_frame(1000);
+        assert_eq!(chip_8.registers()[0], 26);
+        // CLS takes a frame of its own, and then some
+        let mut chip_8 = Chip8::builder().quirks(quirks).rom_bytes(&[0x00, 0xE0, 0x70, 0x01, 0x12, 0x02]).build();
+        chip_8.run_frame(1000);
+        assert_eq!(chip_8.registers()[0], 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! The debugger's panes, drawn over the game in the text module's 3x5 font and updated every
+//! frame while it runs: the registers, timers, XO-CHIP pitch, stack and last instruction
+//! executed on the left, and a hex dump of memory on the right
+
+use chip_8_rs::debugger::{MemoryView, WatchExpr, MEMORY_ROW, MEMORY_ROWS};
+use chip_8_rs::disasm::mnemonic;
+use chip_8_rs::rom::RomId;
+use chip_8_rs::stats::Rates;
+use chip_8_rs::Chip8;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+use crate::text;
+
+/// The font grows by a pixel for every this many pixels of window width
+const WIDTH_PER_FONT_PIXEL: u32 = 256;
+
+/// Return addresses shown, innermost first
+const STACK_SHOWN: usize = 4;
+
+const TEXT: Color = Color::RGB(120, 255, 120);
+const PC_MARK: Color = Color::RGB(40, 70, 160);
+const I_MARK: Color = Color::RGB(150, 50, 50);
+const CURSOR_MARK: Color = Color::RGB(140, 140, 140);
+
+/// A run of characters drawn on a colored background
+struct Mark {
+    line: usize,
+    column: usize,
+    len: usize,
+    color: Color,
+}
+
+/// The register pane
+pub fn draw(canvas: &mut Canvas<Window>, chip_8: &Chip8) {
+    draw_panel(canvas, &lines(chip_8), &[], Corner::TopLeft);
+}
+
+/// The memory pane, with PC and I marked, and the cursor while `editing`
+pub fn draw_memory(canvas: &mut Canvas<Window>, chip_8: &Chip8, view: &MemoryView, editing: bool) {
+    let mut lines = vec![match view.pending() {
+        Some(digit) if editing => format!("EDIT {:03X}: {digit:X}_", view.cursor()),
+        _ if editing => format!("EDIT {:03X}: {:02X}", view.cursor(), chip_8.peek(view.cursor())),
+        _ => "MEMORY".to_string(),
+    }];
+    let mut marks = vec![];
+    for row in 0..MEMORY_ROWS {
+        let start = view.top() + row * MEMORY_ROW;
+        let bytes: Vec<_> = (0..MEMORY_ROW).map(|n| format!("{:02X}", chip_8.peek(start + n))).collect();
+        lines.push(format!("{start:03X} {}", bytes.join(" ")));
+        for n in 0..MEMORY_ROW {
+            let addr = start + n;
+            let color = if editing && addr == view.cursor() {
+                CURSOR_MARK
+            } else if addr == chip_8.pc() || addr == chip_8.pc().wrapping_add(1) {
+                PC_MARK
+            } else if addr == chip_8.index() {
+                I_MARK
+            } else {
+                continue;
+            };
+            marks.push(Mark { line: row as usize + 1, column: 4 + n as usize * 3, len: 2, color });
+        }
+    }
+    draw_panel(canvas, &lines, &marks, Corner::TopRight);
+}
+
+/// The --show values, in the top right corner
+pub fn draw_watches(canvas: &mut Canvas<Window>, chip_8: &Chip8, exprs: &[WatchExpr]) {
+    let lines: Vec<String> = exprs.iter().map(|expr| expr.show(chip_8)).collect();
+    draw_panel(canvas, &lines, &[], Corner::TopRight);
+}
+
+/// How fast the game runs, e.g. `X5` while fast forwarding, in the bottom right corner
+pub fn draw_speed(canvas: &mut Canvas<Window>, speed: f64) {
+    draw_panel(canvas, &[format!("X{speed}")], &[], Corner::BottomRight);
+}
+
+/// The ROM's file name and CRC-32 and how fast it's running, in the bottom left corner
+pub fn draw_hud(canvas: &mut Canvas<Window>, rom: &str, id: &RomId, rates: Rates) {
+    let lines = [
+        rom.to_string(),
+        format!("CRC32 {}", id.key()),
+        format!("{:.0} FPS", rates.frames_per_second),
+        format!("{:.0} IPS", rates.instructions_per_second),
+    ];
+    draw_panel(canvas, &lines, &[], Corner::BottomLeft);
+}
+
+/// The pause menu, in the middle of the window with the selected item marked
+pub fn draw_pause_menu(canvas: &mut Canvas<Window>, lines: &[String], selected: usize) {
+    let len = lines.iter().map(String::len).max().unwrap_or(0);
+    draw_panel(canvas, lines, &[Mark { line: selected, column: 0, len, color: CURSOR_MARK }], Corner::Center);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// Draws `lines` on a dark panel in `corner` of the window, or its middle
+fn draw_panel(canvas: &mut Canvas<Window>, lines: &[String], marks: &[Mark], corner: Corner) {
+    let Ok((width, height)) = canvas.output_size() else {
+        return;
+    };
+    let size = (width / WIDTH_PER_FONT_PIXEL).max(1) as i32;
+    // inside a 1 pixel margin
+    let (text_width, text_height) = text::measure(lines);
+    let panel_width = ((text_width + 1) * size) as u32;
+    let panel_height = ((text_height + 1) * size) as u32;
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => 0,
+        Corner::TopRight | Corner::BottomRight => width.saturating_sub(panel_width) as i32,
+        Corner::Center => (width.saturating_sub(panel_width) / 2) as i32,
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => 0,
+        Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(panel_height) as i32,
+        Corner::Center => (height.saturating_sub(panel_height) / 2) as i32,
+    };
+    let panel = Rect::new(left, top, panel_width, panel_height);
+    let (logical_width, logical_height) = canvas.logical_size();
+    canvas.set_logical_size(0, 0).expect("failed to draw overlay");
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 190));
+    canvas.fill_rect(panel).expect("failed to draw overlay");
+    for mark in marks {
+        let (x, y) = text::cell((left, top), mark.line, mark.column, size);
+        canvas.set_draw_color(mark.color);
+        let rect = Rect::new(x, y, ((mark.len as i32 * text::ADVANCE + 1) * size) as u32, ((text::LINE_HEIGHT + 1) * size) as u32);
+        canvas.fill_rect(rect).expect("failed to draw overlay");
+    }
+    text::draw(canvas, lines, (left, top), size, TEXT);
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_logical_size(logical_width, logical_height).expect("failed to draw overlay");
+}
+
+fn lines(chip_8: &Chip8) -> Vec<String> {
+    let (delay, sound) = chip_8.timers();
+    let pitch = chip_8.pitch();
+    let mut lines = vec![
+        format!("PC {:03X}  I {:03X}", chip_8.pc(), chip_8.index()),
+        format!("DT {delay:02X}  ST {sound:02X}  SP {:X}", chip_8.stack().len()),
+        format!("PITCH {:02X}  {:.0}HZ", pitch.register, pitch.rate()),
+    ];
+    let registers = chip_8.registers();
+    for (row, values) in registers.chunks(4).enumerate() {
+        let cells: Vec<_> = values.iter().enumerate().map(|(n, v)| format!("V{:X} {v:02X}", row * 4 + n)).collect();
+        lines.push(cells.join(" "));
+    }
+    let stack: Vec<_> = chip_8.stack().iter().rev().take(STACK_SHOWN).map(|addr| format!("{addr:03X}")).collect();
+    lines.push(format!("STACK {}", stack.join(" ")));
+    if let Some((addr, opcode)) = chip_8.last_instruction() {
+        lines.push(format!("{addr:03X}: {opcode:04X} {}", mnemonic(opcode, &chip_8.quirks()).as_deref().unwrap_or("???")));
+    }
+    lines
+}
@@ -0,0 +1,552 @@
+//! Execution control for the interactive debugger: pausing, single-stepping, stepping over
+//! subroutine calls, running to breakpoints and watchpoints, looking back through the
+//! instructions that led to a pause, and the memory viewer's cursor
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cheats::Target;
+use crate::disasm::mnemonic;
+use crate::instruction::Instruction;
+use crate::memory::MEMORY_SIZE;
+use crate::quirks::Quirks;
+use crate::trace::TraceEntry;
+use crate::{Chip8, Effect, FaultPolicy};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        })
+    }
+}
+
+/// Pauses the debugger after an instruction reads or writes `target`. Instruction fetches
+/// don't count as reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub target: Target,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    pub(crate) fn watches(&self, target: Target, access: Access) -> bool {
+        self.target == target && match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        }
+    }
+}
+
+/// `0x3f0` or `V5`, watching both ways, or with `:r` or `:w` after it one way
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, access) = s.split_once(':').unwrap_or((s, "rw"));
+        let (read, write) = match access {
+            "r" => (true, false),
+            "w" => (false, true),
+            "rw" => (true, true),
+            _ => return Err(format!("expected `r`, `w` or `rw` after the `:`, got `{access}`")),
+        };
+        Ok(Self { target: target.parse()?, read, write })
+    }
+}
+
+/// Opcodes to pause before executing, written as in opcode tables: hex digits for the nibbles
+/// that must match and X, Y or N for any, e.g. `DXYN` for every sprite or `FX29`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodePattern {
+    value: u16,
+    /// The nibbles that must match
+    mask: u16,
+}
+
+impl OpcodePattern {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl FromStr for OpcodePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() != 4 {
+            return Err(format!("expected 4 nibbles like DXYN, got `{s}`"));
+        }
+        let (mut value, mut mask) = (0, 0);
+        for c in s.chars() {
+            let (nibble, fixed) = match c.to_ascii_uppercase() {
+                'X' | 'Y' | 'N' => (0, 0),
+                c => (c.to_digit(16).ok_or_else(|| format!("`{c}` is neither a hex digit nor X, Y or N"))? as u16, 0xF),
+            };
+            (value, mask) = (value << 4 | nibble, mask << 4 | fixed);
+        }
+        Ok(Self { value, mask })
+    }
+}
+
+impl fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (shift, wildcard) in [(12, 'X'), (8, 'X'), (4, 'Y'), (0, 'N')] {
+            match self.mask >> shift & 0xF {
+                0 => write!(f, "{wildcard}")?,
+                _ => write!(f, "{:X}", self.value >> shift & 0xF)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A value --show keeps over the game as it runs: `V3`, `I`, `PC`, `DT`, `ST`, or a byte of
+/// memory, `[0x300]`, or `[I]` for the one I points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchExpr {
+    Register(u8),
+    Index,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    Memory(u16),
+    AtIndex,
+}
+
+impl WatchExpr {
+    /// Its value in `chip_8` now, and how many hex digits it's shown in
+    pub fn value(&self, chip_8: &Chip8) -> (u16, usize) {
+        match *self {
+            WatchExpr::Register(x) => (chip_8.registers()[x as usize] as u16, 2),
+            WatchExpr::Index => (chip_8.index(), 4),
+            WatchExpr::Pc => (chip_8.pc(), 3),
+            WatchExpr::DelayTimer => (chip_8.timers().0 as u16, 2),
+            WatchExpr::SoundTimer => (chip_8.timers().1 as u16, 2),
+            WatchExpr::Memory(addr) => (chip_8.peek(addr) as u16, 2),
+            WatchExpr::AtIndex => (chip_8.peek(chip_8.index()) as u16, 2),
+        }
+    }
+
+    /// E.g. `V3 1F`
+    pub fn show(&self, chip_8: &Chip8) -> String {
+        let (value, digits) = self.value(chip_8);
+        format!("{self} {value:0digits$X}")
+    }
+}
+
+impl FromStr for WatchExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let expected = || format!("expected V0-VF, I, PC, DT, ST or [ADDRESS], got `{s}`");
+        if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')).map(str::trim) {
+            if inner.eq_ignore_ascii_case("i") {
+                return Ok(WatchExpr::AtIndex);
+            }
+            return match inner.parse()? {
+                Target::Memory(addr) => Ok(WatchExpr::Memory(addr)),
+                Target::Register(_) => Err(expected()),
+            };
+        }
+        Ok(match s.to_ascii_uppercase().as_str() {
+            "I" => WatchExpr::Index,
+            "PC" => WatchExpr::Pc,
+            "DT" => WatchExpr::DelayTimer,
+            "ST" => WatchExpr::SoundTimer,
+            _ => match s.parse().map_err(|_| expected())? {
+                Target::Register(x) => WatchExpr::Register(x),
+                Target::Memory(_) => return Err(expected()),
+            },
+        })
+    }
+}
+
+impl fmt::Display for WatchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchExpr::Register(x) => write!(f, "V{x:X}"),
+            WatchExpr::Index => f.write_str("I"),
+            WatchExpr::Pc => f.write_str("PC"),
+            WatchExpr::DelayTimer => f.write_str("DT"),
+            WatchExpr::SoundTimer => f.write_str("ST"),
+            WatchExpr::Memory(addr) => write!(f, "[{addr:#05x}]"),
+            WatchExpr::AtIndex => f.write_str("[I]"),
+        }
+    }
+}
+
+/// What set a watchpoint off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub target: Target,
+    pub access: Access,
+    /// Address and opcode of the instruction responsible
+    pub pc: u16,
+    pub opcode: u16,
+    /// What the opcode was decoded as
+    pub quirks: Quirks,
+}
+
+/// E.g. `write of 0x3f0 at PC=0x24a (F155 LD [I], V1)`
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = mnemonic(self.opcode, &self.quirks).unwrap_or_else(|| String::from("???"));
+        write!(f, "{} of {} at PC={:#05x} ({:04X} {text})", self.access, self.target, self.pc, self.opcode)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    /// Addresses to pause at before executing the instruction there
+    pub breakpoints: BTreeSet<u16>,
+    /// Opcodes to pause at before executing, wherever they are
+    pub opcode_breakpoints: Vec<OpcodePattern>,
+    paused: bool,
+    /// Where a step over a CALL stops: its return address and the stack depth it returns to
+    step_over: Option<(u16, usize)>,
+    /// The watchpoint that paused the machine, until the frontend takes it
+    watch_hit: Option<WatchHit>,
+    /// How many instructions back through the history the paused machine is being looked at,
+    /// 0 for where it is
+    looking_back: usize,
+}
+
+impl Debugger {
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.step_over = None;
+        self.looking_back = 0;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.looking_back = 0;
+    }
+
+    /// Moves `delta` instructions back through `chip_8`'s history, forwards when negative,
+    /// stopping at the oldest it kept. Returns how far back that is and the instruction there,
+    /// None once back where the machine is
+    pub fn look_back<'a>(&mut self, chip_8: &'a Chip8, delta: isize) -> Option<(usize, &'a TraceEntry)> {
+        let history = chip_8.history()?;
+        self.looking_back = self.looking_back.saturating_add_signed(delta).min(history.len());
+        let back = self.looking_back.checked_sub(1)?;
+        history.back(back).map(|entry| (self.looking_back, entry))
+    }
+
+    /// Sets a breakpoint at `addr`, or clears the one already there. Returns whether it's now set
+    pub fn toggle_breakpoint(&mut self, addr: u16) -> bool {
+        let set = !self.breakpoints.remove(&addr);
+        if set {
+            self.breakpoints.insert(addr);
+        }
+        set
+    }
+
+    /// The watchpoint hit that last paused the machine, once
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Executes exactly one instruction and pauses
+    pub fn step(&mut self, chip_8: &mut Chip8) {
+        self.pause();
+        // an instruction stalled on the display wait quirk only runs once a frame has passed
+        if chip_8.waiting_for_vblank() {
+            chip_8.tick_timers();
+        }
+        chip_8.step();
+        self.watch_hit = chip_8.take_watch_hit();
+    }
+
+    /// Runs one frame, `instructions` instructions and a timer tick, and pauses, for going
+    /// through a game frame by frame. Breakpoints within the frame don't stop it
+    pub fn advance_frame(&mut self, chip_8: &mut Chip8, instructions: usize) {
+        self.pause();
+        chip_8.run_frame(instructions);
+        self.watch_hit = chip_8.take_watch_hit();
+    }
+
+    /// Like step(), but runs through a CALL's whole subroutine and pauses once it returns
+    pub fn step_over(&mut self, chip_8: &mut Chip8) {
+        if let Some(Instruction::Call(_)) = Instruction::decode_with(chip_8.next_instruction(), &chip_8.quirks) {
+            self.step_over = Some((chip_8.pc().wrapping_add(2), chip_8.stack().len()));
+            self.paused = false;
+        } else {
+            self.step(chip_8);
+        }
+    }
+
+    /// Executes up to `instructions` instructions unless paused, pausing on reaching a
+    /// breakpoint or an opcode breakpoint, the end of a step over, a watched access or, under
+    /// FaultPolicy::Pause, a fault. Returns how many instructions ran, which stops short when a
+    /// DXYN waits for the display: the time left until the next frame is lost to the wait, as it
+    /// was on the VIP
+    pub fn run(&mut self, chip_8: &mut Chip8, instructions: usize) -> usize {
+        for n in 0..instructions {
+            if self.paused || chip_8.waiting_for_vblank() {
+                return n;
+            }
+            let faulted = matches!(chip_8.step(), Effect::Error(_));
+            if faulted && chip_8.fault_policy() == FaultPolicy::Pause {
+                self.pause();
+                return n + 1;
+            }
+            if let Some(hit) = chip_8.take_watch_hit() {
+                self.pause();
+                self.watch_hit = Some(hit);
+                return n + 1;
+            }
+            let pc = chip_8.pc();
+            let opcode = chip_8.next_instruction();
+            if self.breakpoints.contains(&pc)
+                || self.opcode_breakpoints.iter().any(|pattern| pattern.matches(opcode))
+                || self.step_over == Some((pc, chip_8.stack().len()))
+            {
+                self.pause();
+                return n + 1;
+            }
+        }
+        instructions
+    }
+}
+
+/// Register dump for showing where the debugger stopped
+pub struct Registers<'a>(pub &'a Chip8);
+
+impl fmt::Display for Registers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let chip_8 = self.0;
+        writeln!(
+            f,
+            "PC {:#05x} [{:04X} {}]  I {:#05x}  DT {}  ST {}",
+            chip_8.pc(),
+            chip_8.next_instruction(),
+            mnemonic(chip_8.next_instruction(), &chip_8.quirks).as_deref().unwrap_or("???"),
+            chip_8.index(),
+            chip_8.timers.delay,
+            chip_8.timers.sound,
+        )?;
+        for (n, v) in chip_8.registers().iter().enumerate() {
+            write!(f, "{}V{n:X} {v:02X}", if n > 0 { " " } else { "" })?;
+        }
+        writeln!(f)?;
+        write!(f, "stack:")?;
+        for addr in chip_8.stack() {
+            write!(f, " {addr:#05x}")?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Bytes per row of the memory view
+pub const MEMORY_ROW: u16 = 8;
+
+/// Rows the memory view shows at once
+pub const MEMORY_ROWS: u16 = 16;
+
+/// Where the memory view is scrolled to, and the byte typed hex digits go to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryView {
+    /// Address of the first byte shown
+    top: u16,
+    cursor: u16,
+    /// The high digit typed for the byte under the cursor, waiting for the low one
+    pending: Option<u8>,
+}
+
+impl Default for MemoryView {
+    /// Opens at the program
+    fn default() -> Self {
+        Self { top: crate::memory::PROGRAM_START, cursor: crate::memory::PROGRAM_START, pending: None }
+    }
+}
+
+impl MemoryView {
+    pub fn top(&self) -> u16 {
+        self.top
+    }
+
+    pub fn cursor(&self) -> u16 {
+        self.cursor
+    }
+
+    pub fn pending(&self) -> Option<u8> {
+        self.pending
+    }
+
+    /// Puts the cursor on `addr`, scrolling as little as it takes to show it
+    pub fn go_to(&mut self, addr: u16) {
+        self.cursor = addr % MEMORY_SIZE as u16;
+        self.pending = None;
+        let row = self.cursor - self.cursor % MEMORY_ROW;
+        let last_row = self.top + (MEMORY_ROWS - 1) * MEMORY_ROW;
+        if row < self.top {
+            self.top = row;
+        } else if row > last_row {
+            self.top = row - (MEMORY_ROWS - 1) * MEMORY_ROW;
+        }
+    }
+
+    /// Moves the cursor `delta` bytes, wrapping around the address space
+    pub fn move_cursor(&mut self, delta: i32) {
+        self.go_to((self.cursor as i32 + delta).rem_euclid(MEMORY_SIZE as i32) as u16);
+    }
+
+    /// Takes a typed hex digit for the byte under the cursor. The second digit writes the byte
+    /// and moves on to the next one. Bytes in the program area are patched into the ROM image
+    /// too, so they survive a reset and end up in a saved ROM
+    pub fn type_digit(&mut self, chip_8: &mut Chip8, digit: u8) {
+        match self.pending.take() {
+            None => self.pending = Some(digit & 0xF),
+            Some(high) => {
+                let byte = high << 4 | digit & 0xF;
+                if !chip_8.patch_rom(self.cursor, byte) {
+                    chip_8.poke(self.cursor, byte);
+                }
+                self.move_cursor(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    #[test]
+    fn memory_view_edits_and_follows_the_cursor() {
+        let mut chip_8 = Chip8::from_rom(&[0x12, 0x00]);
+        let mut view = MemoryView::default();
+        view.type_digit(&mut chip_8, 0xA);
+        assert_eq!(chip_8.peek(0x200), 0x12);
+        view.type_digit(&mut chip_8, 0x5);
+        assert_eq!((chip_8.peek(0x200), view.cursor()), (0xA5, 0x201));
+        assert_eq!(chip_8.rom()[0], 0xA5);
+        // below the program area only the live memory changes
+        view.go_to(0x100);
+        view.type_digit(&mut chip_8, 0xF);
+        view.type_digit(&mut chip_8, 0xF);
+        assert_eq!((chip_8.peek(0x100), chip_8.rom().len()), (0xFF, 2));
+        view.go_to(0x201);
+        view.move_cursor(MEMORY_ROW as i32 * MEMORY_ROWS as i32);
+        assert_eq!((view.cursor(), view.top()), (0x281, 0x208));
+        view.go_to(0);
+        view.move_cursor(-1);
+        assert_eq!((view.cursor(), view.top()), (0xFFF, 0xF80));
+    }
+
+    #[test]
+    fn frame_advance_runs_a_frame_and_stays_paused() {
+        // LD V0 5, LD DT V0, then ADD V1 1 forever
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+        let mut debugger = Debugger::default();
+        debugger.pause();
+        debugger.breakpoints.insert(0x204);
+        debugger.advance_frame(&mut chip_8, 10);
+        assert!(debugger.paused());
+        assert_eq!(chip_8.registers()[1], 4);
+        assert_eq!(chip_8.timers().0, 4);
+    }
+
+    #[test]
+    fn watchpoints_pause_after_the_access() {
+        // LD V1 2, LD I 0x300, LD [I] V1, then ADD V2 1 forever
+        let mut chip_8 = Chip8::from_rom(&[0x61, 0x02, 0xA3, 0x00, 0xF1, 0x55, 0x72, 0x01, 0x12, 0x06]);
+        chip_8.add_watchpoint("0x301:w".parse().unwrap());
+        chip_8.add_watchpoint("V2:r".parse().unwrap());
+        let mut debugger = Debugger::default();
+        assert_eq!(debugger.run(&mut chip_8, 100), 3);
+        let hit = debugger.take_watch_hit().unwrap();
+        assert_eq!(hit.to_string(), "write of 0x301 at PC=0x204 (F155 LD [I], V1)");
+        debugger.resume();
+        assert_eq!(debugger.run(&mut chip_8, 100), 1);
+        let hit = debugger.take_watch_hit().unwrap();
+        assert_eq!((hit.target, hit.access, hit.pc), (Target::Register(2), Access::Read, 0x206));
+        assert!("V2:x".parse::<Watchpoint>().is_err());
+    }
+
+    #[test]
+    fn history_looks_back_through_the_latest_instructions() {
+        // LD V0 1, then ADD V0 1 forever
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+        chip_8.enable_history(4);
+        let mut debugger = Debugger::default();
+        assert_eq!(debugger.look_back(&chip_8, 1), None);
+        debugger.run(&mut chip_8, 5);
+        debugger.pause();
+        assert_eq!(debugger.look_back(&chip_8, 1).map(|(back, entry)| (back, entry.opcode)), Some((1, 0x1202)));
+        let (back, entry) = debugger.look_back(&chip_8, 1).unwrap();
+        assert_eq!((back, entry.addr, entry.before.v[0], entry.after.v[0]), (2, 0x202, 2, 3));
+        // the first two instructions are past what it keeps
+        let (back, entry) = debugger.look_back(&chip_8, 10).unwrap();
+        assert_eq!((back, entry.addr, entry.after.v[0]), (4, 0x202, 2));
+        assert_eq!(debugger.look_back(&chip_8, -2).map(|(back, entry)| (back, entry.opcode)), Some((2, 0x7001)));
+        assert_eq!(debugger.look_back(&chip_8, -5), None);
+        debugger.step(&mut chip_8);
+        assert_eq!(debugger.look_back(&chip_8, 1).map(|(_, entry)| (entry.addr, entry.after.v[0])), Some((0x202, 4)));
+        chip_8.reset();
+        assert!(chip_8.history().is_some_and(Trace::is_empty));
+    }
+
+    #[test]
+    fn watch_expressions_show_registers_and_memory() {
+        // LD V3 0x1F, LD I 0x206, then spin over 0xAB
+        let mut chip_8 = Chip8::from_rom(&[0x63, 0x1F, 0xA2, 0x06, 0x12, 0x04, 0xAB]);
+        Debugger::default().run(&mut chip_8, 2);
+        let shown: Vec<String> = ["v3", "I", "pc", "[0x206]", "[ i ]", "ST"]
+            .iter()
+            .map(|expr| expr.parse::<WatchExpr>().unwrap().show(&chip_8))
+            .collect();
+        assert_eq!(shown, ["V3 1F", "I 0206", "PC 204", "[0x206] AB", "[I] AB", "ST 00"]);
+        assert!("0x300".parse::<WatchExpr>().is_err() && "[V1]".parse::<WatchExpr>().is_err());
+        assert!("VG".parse::<WatchExpr>().is_err());
+    }
+
+    #[test]
+    fn runs_stop_while_the_display_wait_holds_the_machine() {
+        // DRW V0 V0 1, then ADD V1 1 forever, under the VIP's quirks
+        let mut chip_8 = Chip8::builder().platform(crate::quirks::Preset::Chip8).rom_bytes(&[0xD0, 0x01, 0x71, 0x01, 0x12, 0x02]).build();
+        let mut debugger = Debugger::default();
+        assert_eq!(debugger.run(&mut chip_8, 100), 1);
+        assert!(chip_8.waiting_for_vblank() && !debugger.paused());
+        chip_8.tick_timers();
+        assert_eq!(debugger.run(&mut chip_8, 3), 3);
+        assert_eq!(chip_8.registers()[1], 2);
+    }
+
+    #[test]
+    fn opcode_breakpoints_pause_before_any_instruction_they_match() {
+        // LD V0 1, CLS, LD V1 2, DRW V0 V1 5, then jump back to the CLS
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x01, 0x00, 0xE0, 0x61, 0x02, 0xD0, 0x15, 0x12, 0x02]);
+        let mut debugger = Debugger::default();
+        let pattern: OpcodePattern = "dxyn".parse().unwrap();
+        assert_eq!(pattern.to_string(), "DXYN");
+        debugger.opcode_breakpoints.push(pattern);
+        assert_eq!(debugger.run(&mut chip_8, 100), 3);
+        assert_eq!((chip_8.pc(), chip_8.next_instruction()), (0x206, 0xD015));
+        debugger.resume();
+        assert_eq!(debugger.run(&mut chip_8, 100), 4);
+        assert_eq!(chip_8.pc(), 0x206);
+        debugger.opcode_breakpoints = vec!["00E0".parse().unwrap()];
+        debugger.resume();
+        assert_eq!(debugger.run(&mut chip_8, 100), 2);
+        assert_eq!(chip_8.pc(), 0x202);
+        assert_eq!("FX29".parse::<OpcodePattern>().map(|p| p.matches(0xF329)), Ok(true));
+        assert!("DXY".parse::<OpcodePattern>().is_err() && "DXYZ".parse::<OpcodePattern>().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+; The built-in demo, run when no ROM is given: the CHIP-8 logo above a ball that bounces
+; around beeping off the walls, to show the screen, the timers and the sound all work.
+;
+; V0/V1 where the next letter goes, V2 letters left, V3/V4 the ball, V5/V6 how far it
+; moves each step, V7 scratch
+
+        CLS
+        LD I, logo
+        LD V0, 8
+        LD V1, 5
+        LD V2, 6
+        LD V7, 8
+letter: DRW V0, V1, 8
+        ADD I, V7
+        ADD V0, 8
+        ADD V2, 255
+        SE V2, 0
+        JP letter
+
+        LD V3, 30
+        LD V4, 18
+        LD V5, 1
+        LD V6, 1
+        LD I, ball
+        DRW V3, V4, 2
+step:   LD V7, 2                ; a step every two frames
+        LD DT, V7
+wait:   LD V7, DT
+        SE V7, 0
+        JP wait
+        DRW V3, V4, 2           ; drawing the ball again erases it
+        ADD V3, V5
+        ADD V4, V6
+        ; bouncing off x 0 and 62, and y 17 and 30
+        SNE V3, 0
+        CALL flipx
+        SNE V3, 62
+        CALL flipx
+        SNE V4, 17
+        CALL flipy
+        SNE V4, 30
+        CALL flipy
+        DRW V3, V4, 2
+        JP step
+
+flipx:  LD V7, 0
+        SUB V7, V5
+        LD V5, V7
+        JP beep
+flipy:  LD V7, 0
+        SUB V7, V6
+        LD V6, V7
+beep:   LD V7, 3
+        LD ST, V7
+        RET
+
+ball:   db 0b11000000, 0b11000000
+logo:   db 0x7C, 0xC6, 0xC0, 0xC0, 0xC0, 0xC0, 0xC6, 0x7C  ; C
+        db 0xC6, 0xC6, 0xC6, 0xFE, 0xFE, 0xC6, 0xC6, 0xC6  ; H
+        db 0x7C, 0x38, 0x38, 0x38, 0x38, 0x38, 0x38, 0x7C  ; I
+        db 0xFC, 0xC6, 0xC6, 0xC6, 0xFC, 0xC0, 0xC0, 0xC0  ; P
+        db 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00, 0x00  ; -
+        db 0x7C, 0xC6, 0xC6, 0x7C, 0xC6, 0xC6, 0xC6, 0x7C  ; 8
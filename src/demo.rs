@@ -0,0 +1,28 @@
+//! The demo the frontends run when no ROM is given, so there's something on screen from the
+//! first start: the CHIP-8 logo and a ball bouncing under it with a beep off every wall. The
+//! image is assembled from demo.asm, next to it
+
+/// The demo's ROM image
+pub const ROM: &[u8] = include_bytes!("demo.ch8");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::Chip8;
+
+    #[test]
+    fn the_demo_is_its_source_assembled_and_shows_and_sounds() {
+        assert_eq!(assemble(include_str!("demo.asm")).unwrap(), ROM);
+        let mut chip_8 = Chip8::from_rom(ROM);
+        let mut beeped = false;
+        for _ in 0..60 {
+            chip_8.run_frame(11);
+            beeped |= chip_8.sound_active();
+        }
+        // the logo's C at the top left has its top row lit from x 9
+        let display = chip_8.framebuffer();
+        assert!((9..14).all(|x| display.planes_lit(x, 5) == 1));
+        assert!(beeped);
+    }
+}
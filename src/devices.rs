@@ -0,0 +1,105 @@
+//! The devices a frontend plugs into the core: a screen, which is a [`FrameSink`], a keypad
+//! and a buzzer. Hosts can also read the machine directly, as the SDL frontend does for its
+//! overlays; these traits are for the ones that only need the three, and for driving the
+//! core from tests with stand-in devices. Embedders that would rather be handed a frame's
+//! results than implement them can call `Chip8::next_frame` for a [`FrameOutput`].
+
+use crate::audio::AUDIO_PATTERN_LEN;
+use crate::display::{Display, FrameSink};
+
+pub trait Keypad {
+    /// The keys held right now, one bit per key like `Chip8::keys`
+    fn keys(&mut self) -> u16;
+}
+
+pub trait Buzzer {
+    /// Told once a frame whether the sound timer is running, with the XO-CHIP audio pattern
+    /// to play if the ROM loaded one, at `rate` samples a second
+    fn sound(&mut self, on: bool, pattern: Option<[u8; AUDIO_PATTERN_LEN]>, rate: f32);
+}
+
+/// Everything `Chip8::run_frame_on` drives
+pub struct Devices<'a> {
+    pub screen: &'a mut dyn FrameSink,
+    pub keypad: &'a mut dyn Keypad,
+    pub buzzer: &'a mut dyn Buzzer,
+}
+
+/// What the buzzer should be doing, as told to `Buzzer::sound`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sound {
+    pub on: bool,
+    pub pattern: Option<[u8; AUDIO_PATTERN_LEN]>,
+    pub rate: f32,
+}
+
+/// What a frame of `Chip8::next_frame` left for the host to show and play
+#[derive(Debug, Clone, Copy)]
+pub struct FrameOutput<'a> {
+    /// Whether the display changed since it was last presented, i.e. needs drawing again
+    pub display_changed: bool,
+    pub display: &'a Display,
+    pub sound: Sound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[derive(Default)]
+    struct Mock {
+        keys: u16,
+        frames: usize,
+        sounding: Vec<bool>,
+    }
+
+    impl FrameSink for Mock {
+        fn frame(&mut self, _rgba: &[u8], _width: usize, _height: usize) {
+            self.frames += 1;
+        }
+    }
+
+    impl Keypad for Mock {
+        fn keys(&mut self) -> u16 {
+            self.keys
+        }
+    }
+
+    impl Buzzer for Mock {
+        fn sound(&mut self, on: bool, _pattern: Option<[u8; AUDIO_PATTERN_LEN]>, _rate: f32) {
+            self.sounding.push(on);
+        }
+    }
+
+    #[test]
+    fn frames_read_the_keypad_and_drive_the_screen_and_buzzer() {
+        // SKNP V0, which key 0 being held keeps from skipping, then draw the 0 glyph, sound for
+        // 2 frames and loop forever
+        let rom = [0xE0, 0xA1, 0xF0, 0x29, 0xD0, 0x05, 0x60, 0x02, 0xF0, 0x18, 0x60, 0x00, 0x12, 0x0C];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        let (mut screen, mut keypad, mut buzzer) = (Mock::default(), Mock { keys: 1, ..Mock::default() }, Mock::default());
+        for _ in 0..4 {
+            let mut devices = Devices { screen: &mut screen, keypad: &mut keypad, buzzer: &mut buzzer };
+            chip_8.run_frame_on(8, &mut devices);
+        }
+        assert_eq!(chip_8.keys(), 1);
+        // the frame drawn, then nothing new
+        assert_eq!(screen.frames, 1);
+        assert_eq!(buzzer.sounding, [true, false, false, false]);
+    }
+
+    #[test]
+    fn next_frame_hands_back_the_display_and_sound() {
+        // the same ROM, key 0 held for the first frame only
+        let rom = [0xE0, 0xA1, 0xF0, 0x29, 0xD0, 0x05, 0x60, 0x02, 0xF0, 0x18, 0x60, 0x00, 0x12, 0x0C];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        let frame = chip_8.next_frame(1);
+        assert!(frame.display_changed && frame.sound.on);
+        assert!(frame.display.pixel(0, 0));
+        assert_eq!(frame.sound.pattern, None);
+        let frame = chip_8.next_frame(0);
+        assert!(!frame.display_changed);
+        assert_eq!(chip_8.keys(), 0);
+    }
+}
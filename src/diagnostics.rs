@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+/// Interpreter behaviors that differ between CHIP-8 implementations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Quirk {
+    /// 8XY6/8XYE shift Vx in place or copy Vy first
+    ShiftSource,
+    /// BNNN jumps to NNN + V0 or to XNN + Vx
+    JumpOffset,
+    /// FX1E sets VF when I leaves the addressable range
+    IndexOverflow,
+    /// FX55/FX65 leave I untouched or advance it
+    LoadStoreIndex,
+    /// DXYN clips sprites at the screen edge or wraps them around
+    SpriteClip,
+    /// 8XY1/8XY2/8XY3 reset VF or leave it alone
+    VfReset,
+    /// DXYN waits for the next frame
+    DisplayWait,
+    /// CXNN draws from a PRNG or the VIP's sequence
+    RandomSource,
+    /// 00CN/00DN/00FB/00FC scroll the lo-res screen by lo-res or hi-res pixels
+    ScrollDistance,
+}
+
+impl std::fmt::Display for Quirk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Quirk::ShiftSource => "shift source",
+            Quirk::JumpOffset => "jump offset",
+            Quirk::IndexOverflow => "index overflow",
+            Quirk::LoadStoreIndex => "load/store index",
+            Quirk::SpriteClip => "sprite clipping",
+            Quirk::VfReset => "vf reset",
+            Quirk::DisplayWait => "display wait",
+            Quirk::RandomSource => "random source",
+            Quirk::ScrollDistance => "scroll distance",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How often a quirk decision was taken and where it first and last happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuirkUsage {
+    pub count: u64,
+    pub first_pc: u16,
+    pub last_pc: u16,
+}
+
+/// Records every point where execution depended on a quirk, so users can see which ones a ROM
+/// exercises
+#[derive(Debug, Default, Clone)]
+pub struct Diagnostics {
+    usage: BTreeMap<(Quirk, &'static str), QuirkUsage>,
+}
+
+impl Diagnostics {
+    /// Notes that the instruction at `pc` relied on `quirk`, resolving it as `choice`
+    pub fn record(&mut self, pc: u16, quirk: Quirk, choice: &'static str) {
+        self.usage
+            .entry((quirk, choice))
+            .and_modify(|usage| {
+                usage.count += 1;
+                usage.last_pc = pc;
+            })
+            .or_insert(QuirkUsage { count: 1, first_pc: pc, last_pc: pc });
+    }
+
+    /// Every quirk decision observed so far, ordered by quirk
+    pub fn usage(&self) -> impl Iterator<Item = (Quirk, &'static str, QuirkUsage)> + '_ {
+        self.usage.iter().map(|(&(quirk, choice), &usage)| (quirk, choice, usage))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.usage.is_empty()
+    }
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no quirk-dependent instructions were executed");
+        }
+        writeln!(f, "quirk-dependent instructions executed:")?;
+        for (quirk, choice, usage) in self.usage() {
+            writeln!(
+                f,
+                "  {quirk}: {choice} x{} (first at {:#05x}, last at {:#05x})",
+                usage.count, usage.first_pc, usage.last_pc
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! Compact fingerprints of display output, for comparing runs without storing every frame
+
+use crate::display::Display;
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// FNV-1a hash of the lit pixels of `display`, including its resolution
+pub fn frame_hash(display: &Display) -> u64 {
+    let (width, height) = display.dimensions();
+    let mut hash = fnv(FNV_OFFSET, &(width as u32).to_le_bytes());
+    hash = fnv(hash, &(height as u32).to_le_bytes());
+    for y in 0..height {
+        let mut row = 0u128;
+        for x in 0..width {
+            row = row << 1 | display.pixel(x, y) as u128;
+        }
+        hash = fnv(hash, &row.to_le_bytes());
+    }
+    hash
+}
+
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Summary of a run of frames: the final frame's hash and a hash chained over every frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameDigest {
+    pub frames: u64,
+    /// Hash of the last frame alone
+    pub last_frame: u64,
+    /// Hash over every frame in order, so any divergence along the way shows up
+    pub all_frames: u64,
+}
+
+impl FrameDigest {
+    /// Folds the frame currently on `display` into the digest
+    pub fn push(&mut self, display: &Display) {
+        let hash = frame_hash(display);
+        let chain = if self.frames == 0 { FNV_OFFSET } else { self.all_frames };
+        self.all_frames = fnv(chain, &hash.to_le_bytes());
+        self.last_frame = hash;
+        self.frames += 1;
+    }
+}
+
+impl std::fmt::Display for FrameDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frames, last {:016x}, all {:016x}", self.frames, self.last_frame, self.all_frames)
+    }
+}
@@ -0,0 +1,233 @@
+//! Disassembler: opcodes to mnemonics, and whole ROMs to annotated listings.
+//!
+//! Mnemonics follow Cowgod's CHIP-8 reference (`LD Vx, byte`, `DRW Vx, Vy, n`, ...) with the
+//! SUPER-CHIP additions, `PLANE n`, `AUDIO`, `PITCH Vx` and `SCUP n` for XO-CHIP's FN01, F002,
+//! FX3A and 00DN, and `MEGAON`, `MEGAOFF` and `SCU n` for MegaChip's 0011, 0010 and 00BN.
+//! Listings separate code from data by following every path the program can take from 0x200:
+//! bytes no path reaches are data, shown as `db` with each byte drawn as a row of sprite pixels.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::symbols::Symbols;
+
+/// Where ROMs are loaded
+pub use crate::memory::PROGRAM_START;
+
+/// The mnemonic for `opcode` on a machine with `quirks`, or None for opcodes it doesn't define
+pub fn mnemonic(opcode: u16, quirks: &Quirks) -> Option<String> {
+    Instruction::decode_with(opcode, quirks).map(|instruction| instruction.mnemonic(quirks).to_string())
+}
+
+/// Like mnemonic(), with the address a jump, call or LD I refers to given by name when
+/// `symbols` has one for it
+pub fn labelled(opcode: u16, symbols: &Symbols, quirks: &Quirks) -> Option<String> {
+    let instruction = Instruction::decode_with(opcode, quirks)?;
+    let text = instruction.mnemonic(quirks).to_string();
+    let named = instruction.address().and_then(|nnn| Some((text.strip_suffix(&format!("{nnn:#05x}"))?, symbols.name(nnn)?)));
+    match named {
+        Some((rest, name)) => Some(format!("{rest}{name}")),
+        None => Some(text),
+    }
+}
+
+/// The form `opcode` takes on a machine with `quirks` in the usual hex notation, e.g. `8XY4` or
+/// `DXYN`, with the fixed nibbles kept and the operands replaced by letters. Opcodes it doesn't
+/// define come out as the four digits
+pub fn pattern(opcode: u16, quirks: &Quirks) -> String {
+    Instruction::decode_with(opcode, quirks).map_or_else(|| format!("{opcode:04X}"), |instruction| instruction.pattern().to_string())
+}
+
+/// One line of a listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Code { addr: u16, opcode: u16, text: Option<String> },
+    Data { addr: u16, byte: u8 },
+}
+
+impl Line {
+    pub fn addr(&self) -> u16 {
+        match *self {
+            Line::Code { addr, .. } | Line::Data { addr, .. } => addr,
+        }
+    }
+}
+
+/// A ROM split into code and data, with the addresses other instructions refer to
+#[derive(Debug, Clone, Default)]
+pub struct Disassembly {
+    pub lines: Vec<Line>,
+    /// Targets of jumps and calls
+    pub code_labels: BTreeSet<u16>,
+    /// Addresses loaded into I, usually sprites
+    pub data_labels: BTreeSet<u16>,
+    /// Names from a symbol file, labelling their addresses in place of `L` and `D` numbers
+    pub symbols: Symbols,
+    /// What the opcodes are decoded as
+    pub quirks: Quirks,
+}
+
+impl Disassembly {
+    /// `rom` as a machine with `quirks` would run it
+    pub fn new(rom: &[u8], quirks: &Quirks) -> Self {
+        let end = PROGRAM_START as usize + rom.len();
+        let byte = |addr: u16| rom[addr as usize - PROGRAM_START as usize];
+        let in_rom = |addr: u16| (PROGRAM_START as usize..end - 1).contains(&(addr as usize));
+
+        // code starts wherever execution can reach, walking every branch
+        let mut code = BTreeMap::new();
+        let mut disassembly = Self { quirks: *quirks, ..Self::default() };
+        let mut pending = vec![PROGRAM_START];
+        while let Some(addr) = pending.pop() {
+            if !in_rom(addr) || code.contains_key(&addr) {
+                continue;
+            }
+            let opcode = u16::from_be_bytes([byte(addr), byte(addr + 1)]);
+            code.insert(addr, opcode);
+            let next = addr + 2;
+            match Instruction::decode_with(opcode, quirks) {
+                // stop at returns, exit and anything undefined
+                None | Some(Instruction::Return | Instruction::Exit) => {}
+                // the target depends on V0, so only the base is known
+                Some(Instruction::Jump(nnn) | Instruction::JumpOffset { nnn, .. }) => {
+                    disassembly.code_labels.insert(nnn);
+                    pending.push(nnn);
+                }
+                Some(Instruction::Call(nnn)) => {
+                    disassembly.code_labels.insert(nnn);
+                    pending.extend([next, nnn]);
+                }
+                // skips may jump over the next instruction
+                Some(
+                    Instruction::SkipEqualByte { .. }
+                    | Instruction::SkipNotEqualByte { .. }
+                    | Instruction::SkipEqual { .. }
+                    | Instruction::SkipNotEqual { .. }
+                    | Instruction::SkipKey { .. }
+                    | Instruction::SkipNotKey { .. }
+                    | Instruction::SkipKey2 { .. }
+                    | Instruction::SkipNotKey2 { .. },
+                ) => pending.extend([next, next + 2]),
+                Some(Instruction::LoadIndex(nnn)) => {
+                    disassembly.data_labels.insert(nnn);
+                    pending.push(next);
+                }
+                // the next word is the address
+                Some(Instruction::LongLoadIndex) => pending.push(next + 2),
+                Some(_) => pending.push(next),
+            }
+        }
+
+        let mut addr = PROGRAM_START;
+        while (addr as usize) < end {
+            match code.get(&addr) {
+                Some(&opcode) => {
+                    disassembly.lines.push(Line::Code { addr, opcode, text: mnemonic(opcode, quirks) });
+                    addr += 2;
+                }
+                None => {
+                    disassembly.lines.push(Line::Data { addr, byte: byte(addr) });
+                    addr += 1;
+                }
+            }
+        }
+        disassembly
+    }
+
+    /// Names the addresses `symbols` knows, both as labels and in the instructions using them
+    pub fn with_symbols(mut self, symbols: Symbols) -> Self {
+        for line in &mut self.lines {
+            if let Line::Code { opcode, text, .. } = line {
+                *text = labelled(*opcode, &symbols, &self.quirks);
+            }
+        }
+        self.symbols = symbols;
+        self
+    }
+}
+
+/// The listing: address, raw bytes, mnemonic, with labels above referenced addresses
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            let addr = line.addr();
+            if let Some(name) = self.symbols.name(addr) {
+                writeln!(f, "{name}:")?;
+            } else {
+                if self.code_labels.contains(&addr) {
+                    writeln!(f, "L{addr:03X}:")?;
+                }
+                if self.data_labels.contains(&addr) {
+                    writeln!(f, "D{addr:03X}:")?;
+                }
+            }
+            match line {
+                Line::Code { opcode, text: Some(text), .. } => writeln!(f, "    {addr:#05x}  {opcode:04X}  {text}")?,
+                Line::Code { opcode, text: None, .. } => writeln!(f, "    {addr:#05x}  {opcode:04X}  ; unknown opcode")?,
+                Line::Data { byte, .. } => {
+                    let pixels: String = (0..8).map(|bit| if byte & 0x80 >> bit != 0 { '#' } else { '.' }).collect();
+                    writeln!(f, "    {addr:#05x}  {byte:02X}    db {byte:#04x}  ; {pixels}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    /// LD V0 5, LD I with the sprite, DRW, CALL a loop, then the sprite for 0
+    const ROM: [u8; 15] = [0x60, 0x05, 0xA2, 0x0A, 0xD0, 0x15, 0x22, 0x08, 0x12, 0x08, 0xF0, 0x90, 0x90, 0x90, 0xF0];
+
+    #[test]
+    fn listings_label_code_and_draw_data() {
+        let listing = Disassembly::new(&ROM, &Quirks::default()).to_string();
+        let expected = [
+            "    0x200  6005  LD V0, 0x05",
+            "    0x202  A20A  LD I, 0x20a",
+            "    0x204  D015  DRW V0, V1, 5",
+            "    0x206  2208  CALL 0x208",
+            "L208:",
+            "    0x208  1208  JP 0x208",
+            "D20A:",
+            "    0x20a  F0    db 0xf0  ; ####....",
+            "    0x20b  90    db 0x90  ; #..#....",
+            "    0x20c  90    db 0x90  ; #..#....",
+            "    0x20d  90    db 0x90  ; #..#....",
+            "    0x20e  F0    db 0xf0  ; ####....",
+        ];
+        assert_eq!(listing.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn only_reachable_bytes_are_code() {
+        // JP over two bytes to a skip, both of whose paths are code, then a byte after RET
+        let rom = [0x12, 0x04, 0xFF, 0xFF, 0x30, 0x00, 0x12, 0x04, 0x00, 0xEE, 0x12];
+        let disassembly = Disassembly::new(&rom, &Quirks::default());
+        let kinds: Vec<_> = disassembly.lines.iter().map(|line| (line.addr(), matches!(line, Line::Code { .. }))).collect();
+        assert_eq!(kinds, [(0x200, true), (0x202, false), (0x203, false), (0x204, true), (0x206, true), (0x208, true), (0x20A, false)]);
+        assert_eq!(disassembly.code_labels, BTreeSet::from([0x204]));
+        // a lone byte at the end can't be an instruction even when reached
+        let lines = Disassembly::new(&[0x00, 0xE0, 0x12], &Quirks::default()).lines;
+        assert_eq!(lines[1..], [Line::Data { addr: 0x202, byte: 0x12 }]);
+    }
+
+    #[test]
+    fn listings_assemble_back_to_the_rom() {
+        // the mnemonics and db lines, without the address and raw byte columns
+        let source: String = Disassembly::new(&ROM, &Quirks::default())
+            .to_string()
+            .lines()
+            .map(|line| match line.trim_start().splitn(3, "  ").nth(2) {
+                Some(statement) => format!("{}\n", statement.trim()),
+                None => format!("{line}\n"),
+            })
+            .collect();
+        assert_eq!(assemble(&source).unwrap(), ROM);
+    }
+}
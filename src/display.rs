@@ -1,164 +1,1021 @@
-use sdl2::{render::{Canvas, Texture}, video::Window, rect::Point, pixels::Color};
-
-pub const COLOR_ON: [u8; 3] = [255, 255, 255];
-pub const COLOR_OFF: [u8; 3] = [0, 0, 0];
-
-#[derive(Debug)]
-pub struct Display {
-    changed: bool,
-    hi_mode: bool,
-    lo_res: [u64; 32],
-    hi_res: [u128; 64],
-}
-
-impl Default for Display {
-    fn default() -> Self {
-        Self {
-            changed: false,
-            hi_mode: false,
-            lo_res: [0; 32],
-            hi_res: [0; 64], 
-        }
-    }
-}
-
-impl std::fmt::Display for Display {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.hi_mode {
-            true => for row in self.hi_res.iter() {
-                writeln!(f, "{row:0128b}")?;
-            }
-            false => for row in self.lo_res.iter() {
-                writeln!(f, "{row:064b}")?;
-            }
-        }
-        Ok(())
-    }
-}
-
-impl Display {
-    pub fn set_mode(&mut self, hi_res_mode: bool) {
-        self.hi_mode = hi_res_mode;
-    }
-    
-    pub fn draw(&mut self, x: u8, y: usize, sprite: Vec<u8>) -> bool {
-        self.changed = true;
-        let mut res = false;
-        let (rows, columns, T) = if self.hi_mode {
-            (64, 120, u128)
-        } else {
-            (32, 56, u64)
-        };
-        for row in 0..sprite.len() {
-            if y + row >= rows {
-                break;
-            }
-            let sprite = (sprite[row] as T) << (columns - x);
-            if !res && self.hi_res[y + row] & sprite != 0 {
-                res = true;
-            }
-            self.hi_res[y + row] ^= sprite;
-        }
-        res
-    }
-
-    pub fn changed(&self) -> bool {
-        self.changed
-    }
-
-    pub fn render(&mut self, texture: &mut Texture, canvas: &mut Canvas<Window>) {
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        let mut data = vec![];
-        if self.hi_mode {
-            for (i, row) in self.hi_res.iter().enumerate() {
-                for col in (0..128).rev() {
-                    if row >> col & 1 == 1 {
-                        canvas.draw_point(Point::new(col, i as i32)).expect("failed to draw line");
-                    } 
-                }
-            }
-            texture.update(None, &data, 128 * 3).expect("couldn't update texture");
-        } else {
-            for row in self.lo_res.iter() {
-                for col in (0..64).rev() {
-                    if row >> col & 1 == 1 {
-                        data.extend_from_slice(&COLOR_ON);
-                    } else {
-                        data.extend_from_slice(&COLOR_OFF);
-                    }
-                }
-            }
-            texture.update(None, &data, 64 * 3).expect("couldn't update texture");
-        }
-        // let mut data = vec![];
-        // let pixel = |row, col| {
-        //     (if self.hi_mode { self.hi_res[row] } else { self.lo_res[row] as u128 } >> col) & 1 == 1
-        // };
-        // for row in 0..rows {
-        //     for col in (0..cols).rev() {
-        //         if pixel(row, col) {
-        //             data.extend_from_slice(&self.color_on);
-        //         } else {
-        //             data.extend_from_slice(&self.color_off);
-        //         };
-        //     }
-        // }
-        self.changed = false;
-        canvas.copy(texture, None, None).unwrap();
-        canvas.present();
-    }
-
-    pub fn clear(&mut self) {
-        if self.hi_mode {
-            self.hi_res.fill(0);
-        } else {
-            self.lo_res.fill(0);
-        }
-        self.changed = true;
-    }
-
-    pub(crate) fn scroll_down(&mut self, rows: usize) {
-        if self.hi_mode {
-            // move down all rows starting from the back
-            for row in (rows..64).rev() {
-                self.hi_res[row] = self.hi_res[row - rows];
-            }
-            // set the remainder to 0
-            for row in 0..rows {
-                self.hi_res[row] = 0;
-            }
-        } else {
-            for row in (rows..32).rev() {
-                self.hi_res[row] = self.hi_res[row - rows];
-            }
-            for row in 0..rows {
-                self.hi_res[row] = 0;
-            }
-        }
-    }
-
-    pub(crate) fn scroll_right(&mut self) {
-        if self.hi_mode {
-            for row in self.hi_res.iter_mut() {
-                *row >>= 4;
-            }
-        } else {
-            for row in self.lo_res.iter_mut() {
-                *row >>= 4;
-            }
-        }
-    }
-
-    pub(crate) fn scroll_left(&mut self) {
-        if self.hi_mode {
-            for row in self.hi_res.iter_mut() {
-                *row <<= 4;
-            }
-        } else {
-            for row in self.lo_res.iter_mut() {
-                *row <<= 4;
-            }
-        }
-    }
-}
\ No newline at end of file
+use std::collections::VecDeque;
+
+use crate::megachip::{self, MegaScreen};
+use crate::state::{Reader, StateError, Writer};
+
+/// The colors the display is drawn in. `colors[0]` is for unlit pixels and the rest are indexed
+/// by which planes are lit: 1 for the first, 2 for the second, 3 for both. Only XO-CHIP has a
+/// second plane, so everything else just uses the first two
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Palette {
+    pub const CLASSIC: Self = Self { colors: [[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]] };
+    pub const GREEN: Self = Self { colors: [[0, 20, 0], [51, 255, 51], [26, 153, 26], [179, 255, 179]] };
+    pub const AMBER: Self = Self { colors: [[26, 13, 0], [255, 176, 0], [153, 85, 0], [255, 221, 136]] };
+    pub const LCD: Self = Self { colors: [[155, 188, 15], [15, 56, 15], [139, 172, 15], [48, 98, 48]] };
+    pub const INVERTED: Self = Self { colors: [[255, 255, 255], [0, 0, 0], [85, 85, 85], [170, 170, 170]] };
+    /// XO-CHIP's planes in fully saturated colors on black, for low vision
+    pub const CONTRAST: Self = Self { colors: [[0, 0, 0], [255, 255, 255], [255, 255, 0], [0, 255, 255]] };
+    /// XO-CHIP's planes in Okabe and Ito's sky blue and orange, which stay apart with every
+    /// kind of color blindness, and apart from the white of the first plane by brightness
+    pub const COLORBLIND: Self = Self { colors: [[0, 0, 0], [255, 255, 255], [86, 180, 233], [230, 159, 0]] };
+
+    /// The named palettes, as accepted by from_str()
+    pub const NAMED: [(&'static str, Self); 7] = [
+        ("classic", Self::CLASSIC),
+        ("green", Self::GREEN),
+        ("amber", Self::AMBER),
+        ("lcd", Self::LCD),
+        ("inverted", Self::INVERTED),
+        ("contrast", Self::CONTRAST),
+        ("colorblind", Self::COLORBLIND),
+    ];
+
+    pub fn off(&self) -> [u8; 3] {
+        self.colors[0]
+    }
+
+    pub fn on(&self) -> [u8; 3] {
+        self.colors[1]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+
+    /// A name from NAMED, or comma separated hex colors: unlit and lit, optionally followed by
+    /// the second plane's color and the color where both planes are lit
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, palette)) = Self::NAMED.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)) {
+            return Ok(*palette);
+        }
+        let colors = s.split(',').map(parse_color).collect::<Result<Vec<_>, _>>()?;
+        match colors[..] {
+            [off, on] => Ok(Self { colors: [off, on, on, on] }),
+            [off, on, second, both] => Ok(Self { colors: [off, on, second, both] }),
+            _ => {
+                let names: Vec<_> = Self::NAMED.iter().map(|(name, _)| *name).collect();
+                Err(format!("expected {} or 2 or 4 hex colors, got `{s}`", names.join(", ")))
+            }
+        }
+    }
+}
+
+/// Parses RRGGBB, with or without a leading #
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("invalid color `{s}`, expected RRGGBB"));
+    }
+    let channel = |n: usize| u8::from_str_radix(&hex[n * 2..][..2], 16).map_err(|_| format!("invalid color `{s}`"));
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
+
+/// The VP-590 color board's foreground colors, as CHIP-8X numbers them
+pub const CHIP_8X_COLORS: [[u8; 3]; 8] = [
+    [0, 0, 0],
+    [255, 0, 0],
+    [0, 0, 255],
+    [255, 0, 255],
+    [0, 255, 0],
+    [255, 255, 0],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// The background colors 02A0 steps through, starting with the first
+pub const CHIP_8X_BACKGROUNDS: [[u8; 3]; 4] = [[0, 0, 128], [0, 0, 0], [0, 128, 0], [128, 0, 0]];
+
+/// Columns of 8 pixels CHIP-8X colors the lo-res screen in
+pub const COLOR_COLUMNS: usize = 8;
+
+/// CHIP-8X's color attributes: one background color for the whole screen, and a foreground
+/// color for each 8 pixels of each row, which lit pixels are drawn in instead of the palette's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorLayer {
+    /// Index into CHIP_8X_BACKGROUNDS
+    pub background: u8,
+    /// Indices into CHIP_8X_COLORS, by row and column
+    pub zones: [[u8; COLOR_COLUMNS]; 32],
+}
+
+impl Default for ColorLayer {
+    /// Red on blue, as the color board comes up
+    fn default() -> Self {
+        Self { background: 0, zones: [[1; COLOR_COLUMNS]; 32] }
+    }
+}
+
+/// XO-CHIP's bit planes, each a whole screen of its own that FN01 picks which of to draw on
+pub const PLANES: usize = 2;
+
+/// Receives every completed frame, so hosts can draw it without going through SDL
+pub trait FrameSink {
+    /// `rgba` holds `width * height` pixels, 4 bytes each, row by row from the top left
+    fn frame(&mut self, rgba: &[u8], width: usize, height: usize);
+}
+
+/// The resolutions a display can be in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// 64x32, plain CHIP-8 and SuperChip's low resolution
+    #[default]
+    Low,
+    /// 64x64, HIRES CHIP-8's two pages of the VIP's display in one
+    Tall,
+    /// 64x48, the ETI-660's
+    Eti,
+    /// 128x64, SuperChip's high resolution
+    High,
+    /// 256x192 in color, MegaChip's
+    Mega,
+}
+
+/// What a sprite ran into when it was drawn
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Collision {
+    /// Whether it turned any lit pixel off, in any plane
+    pub collided: bool,
+    /// The sprite's rows that turned a lit pixel off in some plane or were clipped off the
+    /// bottom of the screen, which SUPER-CHIP 1.1 put in VF on its hi-res screen
+    pub rows: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Display {
+    changed: bool,
+    /// Rows changed since the frame was last presented, so hosts can redraw only those
+    dirty: [bool; megachip::HEIGHT],
+    resolution: Resolution,
+    lo_res: [[u64; 32]; PLANES],
+    hi_res: [[u128; 64]; PLANES],
+    /// The planes drawing, clearing and scrolling act on, one bit each with the first plane in
+    /// bit 0
+    selected: u8,
+    palette: Palette,
+    /// Only for CHIP-8X
+    colors: Option<ColorLayer>,
+    /// Only in MegaChip mode
+    mega: Option<Box<MegaScreen>>,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            changed: false,
+            // nothing has been presented yet
+            dirty: [true; megachip::HEIGHT],
+            resolution: Resolution::Low,
+            lo_res: [[0; 32]; PLANES],
+            hi_res: [[0; 64]; PLANES],
+            selected: 1,
+            palette: Palette::default(),
+            colors: None,
+            mega: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Display {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // lit in any plane
+        let hi_res = (0..64).map(|y| self.hi_res.iter().fold(0, |row, plane| row | plane[y]));
+        let lo_res = (0..32).map(|y| self.lo_res.iter().fold(0, |row, plane| row | plane[y]));
+        match self.resolution {
+            Resolution::High => for row in hi_res {
+                writeln!(f, "{row:0128b}")?;
+            }
+            Resolution::Tall | Resolution::Eti => for row in hi_res.take(self.dimensions().1) {
+                writeln!(f, "{:064b}", row >> 64)?;
+            }
+            Resolution::Low => for row in lo_res {
+                writeln!(f, "{row:064b}")?;
+            }
+            Resolution::Mega => for y in 0..megachip::HEIGHT {
+                let row: String = (0..megachip::WIDTH).map(|x| if self.pixel(x, y) { '1' } else { '0' }).collect();
+                writeln!(f, "{row}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display {
+    /// A blank display drawn in `palette`
+    pub fn with_palette(palette: Palette) -> Self {
+        Self { palette, ..Self::default() }
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.touch_all();
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switching to Resolution::Mega starts a blank MegaChip screen, switching away drops it
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+        match resolution {
+            Resolution::Mega => self.mega = Some(Box::default()),
+            _ => self.mega = None,
+        }
+        self.touch_all();
+    }
+
+    /// The MegaChip screen, while MegaChip mode is on
+    pub fn mega(&self) -> Option<&MegaScreen> {
+        self.mega.as_deref()
+    }
+
+    /// Only clear() changes the picture shown, so this leaves the rows clean
+    pub(crate) fn mega_mut(&mut self) -> Option<&mut MegaScreen> {
+        self.changed = true;
+        self.mega.as_deref_mut()
+    }
+
+    /// Turns CHIP-8X's color attributes on, all zones back to how the color board comes up
+    pub fn enable_colors(&mut self) {
+        self.colors = Some(ColorLayer::default());
+        self.touch_all();
+    }
+
+    pub fn colors(&self) -> Option<&ColorLayer> {
+        self.colors.as_ref()
+    }
+
+    /// Moves on to the next background color, for 02A0
+    pub fn cycle_background(&mut self) {
+        if let Some(colors) = self.colors.as_mut() {
+            colors.background = (colors.background + 1) % CHIP_8X_BACKGROUNDS.len() as u8;
+            self.touch_all();
+        }
+    }
+
+    /// Colors the zones in `columns` (8 pixels each) of `rows` (pixel rows) in CHIP_8X_COLORS'
+    /// `color`, cut off at the screen's edges
+    pub fn set_zone_color(&mut self, columns: std::ops::Range<usize>, rows: std::ops::Range<usize>, color: u8) {
+        let Some(colors) = self.colors.as_mut() else {
+            return;
+        };
+        for row in colors.zones.iter_mut().take(rows.end).skip(rows.start) {
+            for zone in row.iter_mut().take(columns.end).skip(columns.start) {
+                *zone = color % CHIP_8X_COLORS.len() as u8;
+            }
+        }
+        self.touch_all();
+    }
+
+    /// The planes drawing, clearing and scrolling act on, one bit each with the first in bit 0
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+
+    /// Selects the planes in `planes` for FN01, ignoring bits past the last plane
+    pub fn select_planes(&mut self, planes: u8) {
+        self.selected = planes & ((1 << PLANES) - 1);
+    }
+
+    /// The planes the pixel at (`x`, `y`) is lit in, one bit each, which index the palette
+    pub fn planes_lit(&self, x: usize, y: usize) -> u8 {
+        (0..PLANES).filter(|&plane| self.plane_pixel(plane, x, y)).fold(0, |lit, plane| lit | 1 << plane)
+    }
+
+    /// The color to show the pixel at (`x`, `y`) in, from the color attributes when there are any
+    pub fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        if let Some(mega) = &self.mega {
+            return mega.color(x, y);
+        }
+        let lit = self.pixel(x, y);
+        match &self.colors {
+            // the zones are laid out on the lo-res screen whatever the resolution
+            Some(colors) if lit => {
+                let (width, height) = self.dimensions();
+                CHIP_8X_COLORS[colors.zones[y * 32 / height][x * COLOR_COLUMNS / width] as usize]
+            }
+            Some(colors) => CHIP_8X_BACKGROUNDS[colors.background as usize],
+            None => self.palette.colors[self.planes_lit(x, y) as usize],
+        }
+    }
+
+    /// Whether the active resolution keeps its rows in `hi_res`; 64x64 and 64x48 use their left
+    /// halves
+    fn uses_hi_res(&self) -> bool {
+        matches!(self.resolution, Resolution::Tall | Resolution::Eti | Resolution::High)
+    }
+    
+    /// The selected planes, first plane first
+    fn selected(&self) -> impl Iterator<Item = usize> {
+        let selected = self.selected;
+        (0..PLANES).filter(move |plane| selected >> plane & 1 == 1)
+    }
+
+    /// XORs an 8-pixel-wide sprite onto the screen at (`x`, `y`), returning whether any lit pixel
+    /// was turned off. `sprite` holds as many sprites as there are planes selected, all the same
+    /// height, one after the other for the planes in order, as XO-CHIP reads them from I. The
+    /// coordinates wrap around to start the sprite on the screen; the parts of it past the edges
+    /// are then dropped when `clip` is set, as SUPER-CHIP does, and wrap around to the other
+    /// side otherwise, as the COSMAC VIP did. The MegaChip screen draws its own sprites, so
+    /// nothing is drawn on it here
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        self.draw_sprite(x, y, sprite, false, clip).collided
+    }
+
+    /// Like draw(), but 16 pixels wide when `wide`, two bytes a row, as DXY0 draws them, and
+    /// with the rows that collided counted as well
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8], wide: bool, clip: bool) -> Collision {
+        let planes: Vec<usize> = self.selected().collect();
+        if self.mega.is_some() || planes.is_empty() {
+            return Collision::default();
+        }
+        self.changed = true;
+        let (width, height) = self.dimensions();
+        let (x, y) = (x % width, y % height);
+        let row_bytes = if wide { 2 } else { 1 };
+        let mut collided = false;
+        // one bit per sprite row, for whichever plane
+        let mut rows = 0u32;
+        for (&plane, sprite) in planes.iter().zip(sprite.chunks(sprite.len().div_ceil(planes.len()).max(1))) {
+            for (row, bytes) in sprite.chunks(row_bytes).enumerate() {
+                if clip && y + row >= height {
+                    rows |= 1 << row;
+                    continue;
+                }
+                let bits = bytes.iter().fold(0, |bits, &byte| bits << 8 | byte as u16) << (8 * (row_bytes - bytes.len()));
+                if self.xor_row(plane, (y + row) % height, row_mask(bits, 8 * row_bytes, x, width, clip)) {
+                    collided = true;
+                    rows |= 1 << row;
+                }
+            }
+        }
+        Collision { collided, rows: rows.count_ones() as u8 }
+    }
+
+    /// XORs `mask`, with the row's leftmost pixel in bit `width - 1`, onto row `y` of `plane`,
+    /// returning whether it turned any lit pixel off
+    fn xor_row(&mut self, plane: usize, y: usize, mask: u128) -> bool {
+        self.dirty[y] = true;
+        let (row, mask) = match self.resolution {
+            Resolution::Low => {
+                let row = &mut self.lo_res[plane][y];
+                let mask = mask as u64;
+                let collided = *row & mask != 0;
+                *row ^= mask;
+                return collided;
+            }
+            // 64x64 and 64x48 keep their rows in the left halves
+            Resolution::Tall | Resolution::Eti => (&mut self.hi_res[plane][y], mask << 64),
+            Resolution::High | Resolution::Mega => (&mut self.hi_res[plane][y], mask),
+        };
+        let collided = *row & mask != 0;
+        *row ^= mask;
+        collided
+    }
+
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    /// The rows of the active resolution changed since the frame was last presented, top first
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.dimensions().1).filter(|&y| self.dirty[y])
+    }
+
+    fn touch_all(&mut self) {
+        self.changed = true;
+        self.dirty.fill(true);
+    }
+
+    /// Width and height of the active resolution
+    pub fn dimensions(&self) -> (usize, usize) {
+        match self.resolution {
+            Resolution::Low => (64, 32),
+            Resolution::Tall => (64, 64),
+            Resolution::Eti => (64, 48),
+            Resolution::High => (128, 64),
+            Resolution::Mega => (megachip::WIDTH, megachip::HEIGHT),
+        }
+    }
+
+    /// Whether the pixel at (`x`, `y`) in the active resolution is lit in any plane, which on
+    /// the MegaChip screen means not black
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        if let Some(mega) = &self.mega {
+            mega.color(x, y) != [0; 3]
+        } else {
+            self.planes_lit(x, y) != 0
+        }
+    }
+
+    /// Every pixel of the active resolution as (x, y, lit), row by row from the top left, as
+    /// pixel() has them
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        let (width, height) = self.dimensions();
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y, self.pixel(x, y))))
+    }
+
+    /// The screen as text, a row per line and a digit per pixel for the planes it's lit in,
+    /// which is the Display dump until XO-CHIP draws on the second plane. MegaChip's screen
+    /// only says which pixels aren't black
+    pub fn to_text(&self) -> String {
+        let (width, height) = self.dimensions();
+        let mut text = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            for x in 0..width {
+                let lit = match self.mega {
+                    Some(_) => self.pixel(x, y).into(),
+                    None => self.planes_lit(x, y),
+                };
+                text.push(char::from(b'0' + lit));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Reads back a screen written by to_text() or the Display dump, in the resolution its
+    /// size is. A MegaChip screen's colors aren't in the text, so it can't be
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let rows: Vec<&[u8]> = text.lines().map(str::as_bytes).collect();
+        let resolution = match (rows.first().map_or(0, |row| row.len()), rows.len()) {
+            (64, 32) => Resolution::Low,
+            (64, 64) => Resolution::Tall,
+            (64, 48) => Resolution::Eti,
+            (128, 64) => Resolution::High,
+            (width, height) => return Err(format!("a {width}x{height} screen isn't 64x32, 64x48, 64x64 or 128x64")),
+        };
+        let mut display = Self::default();
+        display.set_resolution(resolution);
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != rows[0].len() {
+                return Err(format!("row {y} is {} pixels wide, not {}", row.len(), rows[0].len()));
+            }
+            for (x, &pixel) in row.iter().enumerate() {
+                let lit = match pixel {
+                    b'0'..=b'3' => pixel - b'0',
+                    _ => return Err(format!("`{}` at ({x}, {y}) isn't a pixel", pixel as char)),
+                };
+                for plane in (0..PLANES).filter(|plane| lit >> plane & 1 == 1) {
+                    match display.uses_hi_res() {
+                        true => display.hi_res[plane][y] |= 1 << (127 - x),
+                        false => display.lo_res[plane][y] |= 1 << (63 - x),
+                    }
+                }
+            }
+        }
+        Ok(display)
+    }
+
+    fn plane_pixel(&self, plane: usize, x: usize, y: usize) -> bool {
+        if self.uses_hi_res() {
+            self.hi_res[plane][y] >> (127 - x) & 1 == 1
+        } else {
+            self.lo_res[plane][y] >> (63 - x) & 1 == 1
+        }
+    }
+
+    /// The active framebuffer as RGBA bytes
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b] = self.color(x, y);
+                data.extend_from_slice(&[r, g, b, 0xFF]);
+            }
+        }
+        data
+    }
+
+    /// Hands the current frame to `sink` and marks it as presented
+    pub fn present(&mut self, sink: &mut dyn FrameSink) {
+        let (width, height) = self.dimensions();
+        sink.frame(&self.to_rgba(), width, height);
+        self.mark_presented();
+    }
+
+    pub(crate) fn save(&self, w: &mut Writer) {
+        // a bool before 64x64 came along, which the numbering keeps loading
+        w.u8(match self.resolution {
+            Resolution::Low => 0,
+            Resolution::High => 1,
+            Resolution::Tall => 2,
+            Resolution::Mega => 3,
+            Resolution::Eti => 4,
+        });
+        for row in self.lo_res[0] {
+            w.u64(row);
+        }
+        for row in self.hi_res[0] {
+            w.u128(row);
+        }
+    }
+
+    /// The planes selected and XO-CHIP's second plane, saved apart from the first as they came
+    /// later
+    pub(crate) fn save_planes(&self, w: &mut Writer) {
+        w.u8(self.selected);
+        for row in self.lo_res[1] {
+            w.u64(row);
+        }
+        for row in self.hi_res[1] {
+            w.u128(row);
+        }
+    }
+
+    pub(crate) fn load_planes(&mut self, r: &mut Reader) -> Result<(), StateError> {
+        let selected = r.u8()?;
+        if selected >= 1 << PLANES {
+            return Err(StateError::Invalid("plane selection"));
+        }
+        self.selected = selected;
+        for row in self.lo_res[1].iter_mut() {
+            *row = r.u64()?;
+        }
+        for row in self.hi_res[1].iter_mut() {
+            *row = r.u128()?;
+        }
+        self.touch_all();
+        Ok(())
+    }
+
+    /// The color attributes, saved apart from the rest as they came later
+    pub(crate) fn save_colors(&self, w: &mut Writer) {
+        w.bool(self.colors.is_some());
+        let colors = self.colors.clone().unwrap_or_default();
+        w.u8(colors.background);
+        w.raw(colors.zones.as_flattened());
+    }
+
+    pub(crate) fn load_colors(&mut self, r: &mut Reader) -> Result<(), StateError> {
+        let enabled = r.bool()?;
+        let background = r.u8()?;
+        let mut zones = [[0; COLOR_COLUMNS]; 32];
+        for row in zones.iter_mut() {
+            *row = r.array()?;
+        }
+        if background as usize >= CHIP_8X_BACKGROUNDS.len() || zones.as_flattened().iter().any(|&color| color as usize >= CHIP_8X_COLORS.len()) {
+            return Err(StateError::Invalid("color attribute"));
+        }
+        self.colors = enabled.then_some(ColorLayer { background, zones });
+        self.touch_all();
+        Ok(())
+    }
+
+    /// The MegaChip screen, saved apart from the rest as it came later
+    pub(crate) fn save_mega(&self, w: &mut Writer) {
+        w.bool(self.mega.is_some());
+        if let Some(mega) = &self.mega {
+            mega.save(w);
+        }
+    }
+
+    /// Loads what save_mega() wrote, after load() has read the resolution it goes with
+    pub(crate) fn load_mega(&mut self, r: &mut Reader) -> Result<(), StateError> {
+        self.mega = match r.bool()? {
+            true => Some(Box::new(MegaScreen::load(r)?)),
+            false => None,
+        };
+        if self.mega.is_some() != (self.resolution == Resolution::Mega) {
+            return Err(StateError::Invalid("MegaChip screen"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load(&mut self, r: &mut Reader) -> Result<(), StateError> {
+        self.resolution = match r.u8()? {
+            0 => Resolution::Low,
+            1 => Resolution::High,
+            2 => Resolution::Tall,
+            3 => Resolution::Mega,
+            4 => Resolution::Eti,
+            _ => return Err(StateError::Invalid("display resolution")),
+        };
+        // states from before MegaChip have none, later ones load it with load_mega(); the same
+        // goes for the second plane and load_planes()
+        self.mega = None;
+        self.selected = 1;
+        self.lo_res[1] = [0; 32];
+        self.hi_res[1] = [0; 64];
+        for row in self.lo_res[0].iter_mut() {
+            *row = r.u64()?;
+        }
+        for row in self.hi_res[0].iter_mut() {
+            *row = r.u128()?;
+        }
+        self.touch_all();
+        Ok(())
+    }
+
+    /// Marks the current frame as shown, so changed() reports false and dirty_rows() reports
+    /// none until the next draw
+    pub fn mark_presented(&mut self) {
+        self.changed = false;
+        self.dirty.fill(false);
+    }
+
+    /// Blanks the selected planes. On the MegaChip screen, puts up what was drawn and starts the
+    /// next picture blank instead
+    pub fn clear(&mut self) {
+        if let Some(mega) = self.mega.as_mut() {
+            mega.present();
+        } else {
+            let hi_res = self.uses_hi_res();
+            for plane in self.selected().collect::<Vec<_>>() {
+                if hi_res {
+                    self.hi_res[plane].fill(0);
+                } else {
+                    self.lo_res[plane].fill(0);
+                }
+            }
+        }
+        self.touch_all();
+    }
+
+    /// Moves the selected planes' picture `dx` pixels right and `dy` down, or left and up when
+    /// they're negative, blanking what's uncovered
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        self.touch_all();
+        if let Some(mega) = self.mega.as_mut() {
+            return mega.scroll(dx, dy);
+        }
+        let shift = |row: u128| match dx {
+            0.. => row.checked_shr(dx as u32).unwrap_or(0),
+            _ => row.checked_shl(dx.unsigned_abs() as u32).unwrap_or(0),
+        };
+        let height = self.dimensions().1;
+        for plane in self.selected().collect::<Vec<_>>() {
+            let (lo_res, hi_res) = (&mut self.lo_res[plane], &mut self.hi_res[plane]);
+            match self.resolution {
+                Resolution::Low => {
+                    // the rows sit in the high bits, so nothing shifted off the right comes back
+                    for row in lo_res.iter_mut() {
+                        *row = (shift((*row as u128) << 64) >> 64) as u64;
+                    }
+                    shift_rows(lo_res, dy);
+                }
+                Resolution::Tall | Resolution::Eti => {
+                    // the rows below a 64x48 screen stay blank
+                    let hi_res = &mut hi_res[..height];
+                    for row in hi_res.iter_mut() {
+                        *row = shift(*row) & u128::MAX << 64;
+                    }
+                    shift_rows(hi_res, dy);
+                }
+                Resolution::High | Resolution::Mega => {
+                    for row in hi_res.iter_mut() {
+                        *row = shift(*row);
+                    }
+                    shift_rows(hi_res, dy);
+                }
+            }
+        }
+    }
+}
+
+/// The pixels of sprite row `bits`, `bits_width` of them, drawn at column `x` of a row `width`
+/// pixels wide, leftmost in bit `width - 1`, with those past the right edge dropped or wrapped
+/// around to the left
+fn row_mask(bits: u16, bits_width: usize, x: usize, width: usize, clip: bool) -> u128 {
+    let placed = (bits as u128) << (width - bits_width);
+    let wrapped = if clip { 0 } else { placed.checked_shl((width - x) as u32).unwrap_or(0) };
+    let row = u128::MAX >> (128 - width);
+    (placed >> x | wrapped) & row
+}
+
+/// Moves `rows` down by `dy`, or up when it's negative, blanking the rows uncovered
+fn shift_rows<T: Copy + Default>(rows: &mut [T], dy: isize) {
+    let n = dy.unsigned_abs().min(rows.len());
+    if dy >= 0 {
+        rows.rotate_right(n);
+        rows[..n].fill(T::default());
+    } else {
+        rows.rotate_left(n);
+        let len = rows.len();
+        rows[len - n..].fill(T::default());
+    }
+}
+
+/// Draws pixels from more than just the current frame, to tame games that flicker their sprites
+/// by erasing and redrawing them every frame
+pub trait Afterglow {
+    /// Adds the frame about to be shown
+    fn push(&mut self, display: &Display);
+
+    /// The color to draw the pixel at (`x`, `y`) in
+    fn color(&self, x: usize, y: usize) -> [u8; 3];
+}
+
+/// Averages the last few frames so sprites that games flicker on alternate frames show up as
+/// steady, dimmer pixels instead of strobing
+#[derive(Debug, Clone)]
+pub struct FlickerFusion {
+    frames: VecDeque<Display>,
+    capacity: usize,
+}
+
+impl FlickerFusion {
+    /// Blends the last `frames` frames
+    pub fn new(frames: usize) -> Self {
+        Self { frames: VecDeque::with_capacity(frames), capacity: frames.max(1) }
+    }
+}
+
+impl Afterglow for FlickerFusion {
+    /// A change of resolution starts the average over
+    fn push(&mut self, display: &Display) {
+        if self.frames.back().is_some_and(|last| last.resolution != display.resolution) {
+            self.frames.clear();
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(display.clone());
+    }
+
+    /// The palette's lit color if the pixel was lit in every frame averaged, its unlit color if
+    /// it was lit in none, and in between otherwise
+    fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        let lit = self.frames.iter().filter(|frame| frame.pixel(x, y)).count();
+        let total = self.frames.len().max(1);
+        let palette = self.frames.back().map(Display::palette).unwrap_or_default();
+        blend(palette, lit as f32 / total as f32)
+    }
+}
+
+/// Lets pixels fade out over a few frames when they're turned off, like a slow CRT phosphor,
+/// instead of going dark at once
+#[derive(Debug, Clone)]
+pub struct PhosphorDecay {
+    /// How lit each pixel of the active resolution is, 1 for fully
+    intensity: Vec<f32>,
+    width: usize,
+    /// Intensity lost per frame
+    step: f32,
+    palette: Palette,
+}
+
+impl PhosphorDecay {
+    /// Fades pixels out over `frames` frames
+    pub fn new(frames: usize) -> Self {
+        Self { intensity: vec![], width: 0, step: 1.0 / frames.max(1) as f32, palette: Palette::default() }
+    }
+}
+
+impl Afterglow for PhosphorDecay {
+    /// A change of resolution drops the afterglow
+    fn push(&mut self, display: &Display) {
+        let (width, height) = display.dimensions();
+        if self.width != width {
+            self.width = width;
+            self.intensity = vec![0.0; width * height];
+        }
+        for (n, intensity) in self.intensity.iter_mut().enumerate() {
+            *intensity = if display.pixel(n % width, n / width) { 1.0 } else { (*intensity - self.step).max(0.0) };
+        }
+        self.palette = display.palette;
+    }
+
+    fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        let intensity = self.intensity.get(y * self.width + x).copied().unwrap_or_default();
+        blend(self.palette, intensity)
+    }
+}
+
+/// Share of the screen changing from one frame to the next that counts as a flash
+const FLASH_SHARE: usize = 4;
+
+/// Frames from one flash until the next is let through, three a second at 60Hz as the
+/// photosensitivity guidelines allow
+const FLASH_GAP: usize = 20;
+
+/// Holds back frames that would flash the screen more than three times a second, like a game
+/// inverting it or a ROM flickering all of it, leaving the last frame up until the next may
+/// show. Smaller changes show as they come
+#[derive(Debug, Clone)]
+pub struct FlashLimiter {
+    shown: Option<Display>,
+    /// Frames since the last flash shown
+    since_flash: usize,
+}
+
+impl Default for FlashLimiter {
+    /// The first flash goes through
+    fn default() -> Self {
+        Self { shown: None, since_flash: FLASH_GAP }
+    }
+}
+
+impl Afterglow for FlashLimiter {
+    fn push(&mut self, display: &Display) {
+        self.since_flash = self.since_flash.saturating_add(1);
+        let flash = self.shown.as_ref().is_some_and(|shown| {
+            let (width, height) = display.dimensions();
+            let changed = display.pixels().filter(|&(x, y, lit)| shown.pixel(x, y) != lit).count();
+            shown.resolution == display.resolution && changed * FLASH_SHARE >= width * height
+        });
+        if flash && self.since_flash < FLASH_GAP {
+            return;
+        }
+        if flash {
+            self.since_flash = 0;
+        }
+        self.shown = Some(display.clone());
+    }
+
+    fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        self.shown.as_ref().map_or([0; 3], |shown| shown.color(x, y))
+    }
+}
+
+/// The color `lit` of the way from `palette`'s unlit color to its lit one
+fn blend(palette: Palette, lit: f32) -> [u8; 3] {
+    let (on, off) = (palette.on(), palette.off());
+    std::array::from_fn(|n| (off[n] as f32 + (on[n] as f32 - off[n] as f32) * lit).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_by_name_or_color() {
+        assert_eq!("Amber".parse(), Ok(Palette::AMBER));
+        let two: Palette = "#102030,ffffff".parse().unwrap();
+        assert_eq!(two.colors, [[0x10, 0x20, 0x30], [255; 3], [255; 3], [255; 3]]);
+        let four: Palette = "000000,ffffff,ff0000,00ff00".parse().unwrap();
+        assert_eq!(four.colors[3], [0, 255, 0]);
+        assert!("000000".parse::<Palette>().is_err());
+        assert!("000000,fffffg".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn screens_round_trip_through_text() {
+        let mut display = Display::default();
+        display.set_resolution(Resolution::High);
+        display.draw(0, 0, &[0x81], false);
+        display.select_planes(2);
+        display.draw(7, 0, &[0x80], false);
+        display.draw(7, 63, &[0x80], false);
+        let text = display.to_text();
+        assert_eq!(&text[..9], "100000030");
+        let read = Display::from_text(&text).unwrap();
+        assert_eq!(read.resolution(), Resolution::High);
+        assert_eq!((read.planes_lit(7, 0), read.planes_lit(7, 63), read.planes_lit(8, 0)), (3, 2, 0));
+        assert_eq!(read.to_text(), text);
+        // the Display dump reads back too, lit in the first plane
+        let mut display = Display::default();
+        display.draw(62, 31, &[0xC0], false);
+        let read = Display::from_text(&display.to_string()).unwrap();
+        assert_eq!(read.to_text(), display.to_string());
+        assert!(Display::from_text("0101\n").is_err());
+        assert!(Display::from_text(&display.to_string().replace('1', "#")).is_err());
+    }
+
+    #[test]
+    fn phosphor_fades_out() {
+        let mut display = Display::default();
+        display.draw(0, 0, &[0x80], false);
+        let mut phosphor = PhosphorDecay::new(4);
+        phosphor.push(&display);
+        assert_eq!(phosphor.color(0, 0), [255; 3]);
+        display.clear();
+        phosphor.push(&display);
+        phosphor.push(&display);
+        assert_eq!(phosphor.color(0, 0), [128; 3]);
+        phosphor.push(&display);
+        phosphor.push(&display);
+        assert_eq!(phosphor.color(0, 0), [0; 3]);
+        assert_eq!(phosphor.color(1, 0), [0; 3]);
+    }
+
+    #[test]
+    fn flashes_are_held_back() {
+        let lit = Display::from_text(&format!("{}\n", "1".repeat(64)).repeat(32)).unwrap();
+        let unlit = Display::default();
+        let mut limiter = FlashLimiter::default();
+        limiter.push(&unlit);
+        limiter.push(&lit);
+        assert_eq!(limiter.color(0, 0), [255; 3]);
+        // inverting every frame only gets through once FLASH_GAP frames have passed
+        for n in 1..=FLASH_GAP {
+            limiter.push(if n % 2 == 0 { &lit } else { &unlit });
+            assert_eq!(limiter.color(0, 0), [255; 3]);
+        }
+        limiter.push(&unlit);
+        assert_eq!(limiter.color(0, 0), [0; 3]);
+        // a sprite drawn isn't a flash
+        let mut sprite = unlit.clone();
+        sprite.draw(0, 0, &[0xFF], false);
+        limiter.push(&sprite);
+        assert_eq!((limiter.color(0, 0), limiter.color(0, 1)), ([255; 3], [0; 3]));
+    }
+
+    #[test]
+    fn palette_survives_clearing_the_machine() {
+        let mut chip_8 = crate::Chip8::from_rom(&[0x00, 0xE0]);
+        chip_8.set_palette(Palette::LCD);
+        chip_8.reset();
+        assert_eq!(chip_8.framebuffer().palette(), Palette::LCD);
+        assert_eq!(chip_8.framebuffer().to_rgba()[..4], [155, 188, 15, 255]);
+    }
+
+    #[test]
+    fn sprites_clip_or_wrap_at_the_edges() {
+        let mut display = Display::default();
+        // starting past the bottom right corner wraps to (4, 1) either way
+        assert!(!display.draw(68, 33, &[0xFF], true));
+        assert!((4..12).all(|x| display.pixel(x, 1)));
+        let lit: Vec<_> = display.pixels().filter(|&(_, _, lit)| lit).map(|(x, y, _)| (x, y)).collect();
+        assert_eq!(lit, (4..12).map(|x| (x, 1)).collect::<Vec<_>>());
+        assert_eq!(display.pixels().count(), 64 * 32);
+        display.clear();
+        display.draw(60, 31, &[0xFF, 0xFF], true);
+        assert!((60..64).all(|x| display.pixel(x, 31)));
+        assert!(!display.pixel(0, 31) && !display.pixel(60, 0));
+        display.clear();
+        display.draw(60, 31, &[0xFF, 0xFF], false);
+        assert!((60..64).chain(0..4).all(|x| display.pixel(x, 31) && display.pixel(x, 0)));
+        assert!(!display.pixel(4, 31));
+        assert!(display.draw(0, 0, &[0x80], false));
+
+        display.set_resolution(Resolution::Tall);
+        display.draw(60, 0, &[0xFF], false);
+        assert!((60..64).chain(0..4).all(|x| display.pixel(x, 0)));
+        assert!(!display.pixel(4, 0));
+
+        // 16 wide, the first row colliding and the last two clipped off the bottom
+        display.set_resolution(Resolution::High);
+        display.draw(0, 60, &[0x80], true);
+        let collision = display.draw_sprite(0, 60, &[0x80, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF], true, true);
+        assert_eq!(collision, Collision { collided: true, rows: 3 });
+        assert!(!display.pixel(0, 60) && display.pixel(15, 60) && !display.pixel(16, 60));
+    }
+
+    #[test]
+    fn sprites_draw_to_and_clear_the_selected_planes() {
+        // PLANE 3, LD I 0x20C, LD V0 0, DRW V0 V0 1, PLANE 2, CLS, then a row for each plane
+        let rom = [0xF3, 0x01, 0xA2, 0x0C, 0x60, 0x00, 0xD0, 0x01, 0xF2, 0x01, 0x00, 0xE0, 0xC0, 0xA0];
+        let mut chip_8 = crate::Chip8::from_rom(&rom);
+        for _ in 0..4 {
+            chip_8.step();
+        }
+        let display = chip_8.framebuffer();
+        assert_eq!((0..4).map(|x| display.planes_lit(x, 0)).collect::<Vec<_>>(), [3, 1, 2, 0]);
+        assert_eq!(display.color(2, 0), Palette::CLASSIC.colors[2]);
+        chip_8.step();
+        chip_8.step();
+        let display = chip_8.framebuffer();
+        assert_eq!((0..4).map(|x| display.planes_lit(x, 0)).collect::<Vec<_>>(), [1, 1, 0, 0]);
+        assert!(display.pixel(0, 0) && !display.pixel(2, 0));
+    }
+
+    #[test]
+    fn only_drawn_rows_are_dirty() {
+        let mut display = Display::default();
+        assert_eq!(display.dirty_rows().count(), 32);
+        display.mark_presented();
+        display.draw(0, 30, &[0x80, 0x80, 0x80], false);
+        assert_eq!(display.dirty_rows().collect::<Vec<_>>(), [0, 30, 31]);
+        display.mark_presented();
+        assert_eq!(display.dirty_rows().count(), 0);
+        display.set_resolution(Resolution::High);
+        assert_eq!(display.dirty_rows().count(), 64);
+    }
+
+    #[test]
+    fn lo_res_scrolls_move_the_lo_res_screen() {
+        // draw the font's 0 at (1, 1), then SCD 2, SCR and SCU 1
+        let rom = [0x60, 0x01, 0x61, 0x00, 0xF1, 0x29, 0xD0, 0x05, 0x00, 0xC2, 0x00, 0xFB, 0x00, 0xD1];
+        let mut chip_8 = crate::Chip8::from_rom(&rom);
+        for _ in 0..7 {
+            chip_8.step();
+        }
+        let display = chip_8.framebuffer();
+        assert!((5..9).all(|x| display.pixel(x, 2)));
+        assert!(!display.pixel(4, 2) && !display.pixel(1, 1));
+
+        // half as far: down 1, right 2, and the half row up dropped
+        let quirks = crate::quirks::Quirks { half_scroll: true, ..Default::default() };
+        let mut chip_8 = crate::Chip8::builder().quirks(quirks).rom_bytes(&rom).build();
+        for _ in 0..7 {
+            chip_8.step();
+        }
+        assert!((3..7).all(|x| chip_8.framebuffer().pixel(x, 2)));
+    }
+}
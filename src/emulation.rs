@@ -0,0 +1,99 @@
+//! The machine on a thread of its own. When it runs free, the thread runs instructions and
+//! timers on its own clocks, and the window's loop takes the lock once a frame to handle input,
+//! draw and everything else, letting go of it before waiting for vsync. A slow frame or a
+//! stalled present then only holds the picture up, never the game. The frame by frame modes
+//! run frames from the window's loop instead, with the thread left out
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use chip_8_rs::debugger::Debugger;
+use chip_8_rs::timing::FixedTimestep;
+use chip_8_rs::Chip8;
+
+/// How long the thread sleeps between batches of instructions
+const SLICE: Duration = Duration::from_millis(1);
+
+/// What the thread and the window's loop share
+pub struct Core {
+    pub chip_8: Chip8,
+    pub debugger: Debugger,
+    /// Instructions run since the window's loop last took the count
+    pub executed: usize,
+    /// How fast to run relative to real time, e.g. below 1 in slow motion
+    pub speed: f64,
+    /// How many instructions to run for every one --ips asks for, on top of `speed`
+    pub rate: f64,
+    /// Whether the thread should run the machine, as opposed to e.g. the window's loop rewinding it
+    pub running: bool,
+    /// Set to make the thread's clocks forget time that passed, after a pause or reset
+    pub restart_clocks: bool,
+    /// Set by the thread when a breakpoint stops the machine
+    pub hit_breakpoint: bool,
+}
+
+impl Core {
+    pub fn new(chip_8: Chip8, debugger: Debugger) -> Self {
+        Self { chip_8, debugger, executed: 0, speed: 1.0, rate: 1.0, running: true, restart_clocks: false, hit_breakpoint: false }
+    }
+}
+
+pub struct Emulation {
+    core: Arc<Mutex<Core>>,
+    worker: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Emulation {
+    /// The machine, run by the thread at `ips` instructions a second when `free_running`
+    pub fn new(core: Core, ips: u32, free_running: bool) -> Self {
+        let core = Arc::new(Mutex::new(core));
+        let worker = free_running.then(|| {
+            let stop = Arc::new(AtomicBool::new(false));
+            let (shared, stopping) = (core.clone(), stop.clone());
+            (stop, std::thread::spawn(move || run(&shared, &stopping, ips)))
+        });
+        Self { core, worker }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Core> {
+        // the thread panicking leaves the state as it was after its last instruction
+        self.core.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Stops the thread and hands the machine back
+    pub fn finish(mut self) -> Core {
+        if let Some((stop, handle)) = self.worker.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle.join().ok();
+        }
+        let core = Arc::try_unwrap(self.core).unwrap_or_else(|_| unreachable!("the thread has ended"));
+        core.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn run(core: &Mutex<Core>, stop: &AtomicBool, ips: u32) {
+    let mut cpu = FixedTimestep::new(ips);
+    let mut timers = FixedTimestep::timers();
+    // after a stall (window drag, breakpoint in a debugger) drop the backlog instead of racing through it
+    let max_batch = (ips / 10).max(1) as f64;
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(SLICE);
+        let mut core = core.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let core = &mut *core;
+        cpu.set_speed(core.speed * core.rate);
+        timers.set_speed(core.speed);
+        if std::mem::take(&mut core.restart_clocks) || !core.running || core.debugger.paused() {
+            cpu.reset();
+            timers.reset();
+            continue;
+        }
+        let batch = cpu.ticks().min((max_batch * core.speed * core.rate).ceil() as u32);
+        core.executed += core.debugger.run(&mut core.chip_8, batch as usize);
+        core.hit_breakpoint |= core.debugger.paused();
+        for _ in 0..timers.ticks() {
+            core.chip_8.tick_timers();
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Errors from setting a machine up, for frontends to report instead of panicking
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::asm::AsmError;
+use crate::fault::Fault;
+use crate::patch::PatchError;
+use crate::rom::MAX_ROM_SIZE;
+use crate::zip::ZipError;
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// A ROM or patch file couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    /// The patch couldn't be applied to the ROM
+    Patch(PatchError),
+    /// The ROM couldn't be read out of its zip archive
+    Zip(ZipError),
+    /// The ROM is Octo source that didn't compile
+    Compile { path: PathBuf, error: AsmError },
+    /// The ROM file holds nothing
+    EmptyRom,
+    /// The ROM is this many bytes, more than fits between 0x200 and the end of memory
+    RomTooLarge(usize),
+    /// The program faulted at `pc` under FaultPolicy::Exit
+    Fault { pc: u16, fault: Fault },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::Io { path, source } => write!(f, "couldn't read {}: {source}", path.display()),
+            Chip8Error::Patch(e) => write!(f, "couldn't apply the patch: {e}"),
+            Chip8Error::Zip(e) => write!(f, "couldn't read the ROM from the archive: {e}"),
+            Chip8Error::Compile { path, error } => write!(f, "couldn't compile {}: {error}", path.display()),
+            Chip8Error::EmptyRom => write!(f, "the ROM is empty"),
+            Chip8Error::RomTooLarge(len) => {
+                write!(f, "the ROM is {len} bytes, but only {MAX_ROM_SIZE} fit between 0x200 and the end of memory")
+            }
+            Chip8Error::Fault { pc, fault } => write!(f, "the program stopped on {fault} at {pc:#05x}"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Io { source, .. } => Some(source),
+            Chip8Error::Patch(e) => Some(e),
+            Chip8Error::Zip(e) => Some(e),
+            Chip8Error::Compile { error, .. } => Some(error),
+            Chip8Error::EmptyRom | Chip8Error::RomTooLarge(_) | Chip8Error::Fault { .. } => None,
+        }
+    }
+}
+
+impl From<PatchError> for Chip8Error {
+    fn from(e: PatchError) -> Self {
+        Chip8Error::Patch(e)
+    }
+}
+
+impl From<ZipError> for Chip8Error {
+    fn from(e: ZipError) -> Self {
+        Chip8Error::Zip(e)
+    }
+}
+
+/// Reads the file at `path`, naming it in the error
+pub fn read(path: impl Into<PathBuf>) -> Result<Vec<u8>, Chip8Error> {
+    let path = path.into();
+    std::fs::read(&path).map_err(|source| Chip8Error::Io { path, source })
+}
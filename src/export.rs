@@ -0,0 +1,418 @@
+//! Getting screenshots, recordings and save states out of the emulator as files or text:
+//! minimal PNG and animated GIF encoders, a WAV writer for the sound, and base64 for putting
+//! binary data on a text-only clipboard
+
+use std::collections::HashMap;
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::display::{Display, Palette};
+use crate::patch::crc32;
+
+/// `display` as a PNG, each pixel drawn as a `scale` pixel square, or half that in hi-res so
+/// both resolutions come out the same size
+pub fn screenshot(display: &Display, scale: usize) -> Vec<u8> {
+    let (rgba, width, height) = scaled(display, scale);
+    png(&rgba, width, height)
+}
+
+/// `display` scaled like screenshot() as a binary PPM, the simplest format image tools read
+pub fn ppm(display: &Display, scale: usize) -> Vec<u8> {
+    let (rgba, width, height) = scaled(display, scale);
+    let mut ppm = format!("P6\n{width} {height}\n255\n").into_bytes();
+    ppm.extend(rgba.chunks(4).flat_map(|pixel| &pixel[..3]));
+    ppm
+}
+
+/// `display` as RGBA scaled for a screenshot, and its width and height
+fn scaled(display: &Display, scale: usize) -> (Vec<u8>, usize, usize) {
+    let (width, height) = display.dimensions();
+    let scale = (scale * 64 / width).max(1);
+    let rgba = display.to_rgba();
+    let mut scaled = Vec::with_capacity(rgba.len() * scale * scale);
+    for row in rgba.chunks(width * 4) {
+        let scaled_row: Vec<u8> = row.chunks(4).flat_map(|pixel| pixel.repeat(scale)).collect();
+        for _ in 0..scale {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+    (scaled, width * scale, height * scale)
+}
+
+/// Encodes `rgba`, `width * height` pixels of 4 bytes row by row, as an 8-bit RGBA PNG. The
+/// image data is stored uncompressed, which keeps this short and is fine at CHIP-8 sizes
+pub fn png(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    // each row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+    for row in rgba.chunks(width * 4).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, then the only compression, filtering and (no) interlacing there are
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// `data` as a zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    out
+}
+
+/// Recordings are the same size in both resolutions, so a ROM switching mid-game stays one video
+pub const RECORDING_SIZE: (usize, usize) = (128, 64);
+
+/// `display` as RGBA at `scale` times RECORDING_SIZE, hi-res pixels `scale` pixels square and
+/// lo-res twice that. Raw video for an encoder such as ffmpeg
+pub fn recording_frame(display: &Display, scale: usize) -> Vec<u8> {
+    let palette = display.palette();
+    recording_pixels(display, scale)
+        .into_iter()
+        .flat_map(|lit| {
+            let [r, g, b] = if lit { palette.on() } else { palette.off() };
+            [r, g, b, 0xFF]
+        })
+        .collect()
+}
+
+/// Which pixels of a recording frame are lit, row by row
+fn recording_pixels(display: &Display, scale: usize) -> Vec<bool> {
+    let (width, height) = (RECORDING_SIZE.0 * scale, RECORDING_SIZE.1 * scale);
+    // 64x64 pixels come out half as tall as they're wide, as they did on the VIP
+    let (columns, rows) = display.dimensions();
+    let (pixel_width, pixel_height) = (width / columns, height / rows);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        pixels.extend((0..width).map(|x| display.pixel(x / pixel_width, y / pixel_height)));
+    }
+    pixels
+}
+
+/// Writes an animated GIF of the display, shown at 60 frames a second. A run of identical frames
+/// is written as one frame shown for longer, which keeps recordings of mostly still games small
+pub struct GifWriter<W: Write> {
+    out: W,
+    scale: usize,
+    /// The latest frame as palette indices, and how many frames in a row it has been shown;
+    /// written out once it changes
+    pending: Option<(Vec<u8>, u64)>,
+    /// Frames gone by in the frames written so far, and the hundredths of a second they took
+    frames: u64,
+    centiseconds: u64,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Starts a GIF at `scale` times RECORDING_SIZE in `palette`'s colors
+    pub fn new(mut out: W, palette: &Palette, scale: usize) -> io::Result<Self> {
+        let scale = scale.max(1);
+        out.write_all(b"GIF89a")?;
+        out.write_all(&((RECORDING_SIZE.0 * scale) as u16).to_le_bytes())?;
+        out.write_all(&((RECORDING_SIZE.1 * scale) as u16).to_le_bytes())?;
+        // a global color table of 4 colors at 8 bits per channel, background color 0, square pixels
+        out.write_all(&[0xF1, 0, 0])?;
+        out.write_all(palette.colors.as_flattened())?;
+        // loop forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\0\0\0")?;
+        Ok(Self { out, scale, pending: None, frames: 0, centiseconds: 0 })
+    }
+
+    /// Adds the next frame
+    pub fn frame(&mut self, display: &Display) -> io::Result<()> {
+        let indices: Vec<u8> = recording_pixels(display, self.scale).into_iter().map(u8::from).collect();
+        match &mut self.pending {
+            Some((pending, count)) if *pending == indices => *count += 1,
+            _ => {
+                self.flush(false)?;
+                self.pending = Some((indices, 1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes out the last frame and ends the GIF
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush(true)?;
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Writes out the pending frame. Delays are in hundredths of a second, so each frame's end is
+    /// rounded to the nearest one rather than its length, which would drift. Browsers slow down
+    /// frames shorter than 2, so a frame that would be is dropped and its time given to the next
+    fn flush(&mut self, last: bool) -> io::Result<()> {
+        let Some((indices, count)) = self.pending.take() else {
+            return Ok(());
+        };
+        self.frames += count;
+        let end = (self.frames * 100 + 30) / 60;
+        if end < self.centiseconds + 2 && !last {
+            return Ok(());
+        }
+        let delay = (end - self.centiseconds).max(2);
+        self.centiseconds += delay;
+        // graphic control extension with the delay, then an image descriptor covering the screen
+        self.out.write_all(&[0x21, 0xF9, 4, 0])?;
+        self.out.write_all(&(delay as u16).to_le_bytes())?;
+        self.out.write_all(&[0, 0, 0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&((RECORDING_SIZE.0 * self.scale) as u16).to_le_bytes())?;
+        self.out.write_all(&((RECORDING_SIZE.1 * self.scale) as u16).to_le_bytes())?;
+        self.out.write_all(&[0, GIF_MIN_CODE_SIZE])?;
+        for block in lzw(&indices).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+}
+
+/// Writes mono audio as a 16-bit PCM WAV as it comes. The sizes in the header are only known
+/// at the end, so finish() goes back and fills them in
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32) -> io::Result<Self> {
+        out.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        // 16 bytes of format: PCM, 1 channel, the rate, bytes per second, bytes per sample, bits
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&[1, 0, 1, 0])?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * 2).to_le_bytes())?;
+        out.write_all(&[2, 0, 16, 0])?;
+        out.write_all(b"data\0\0\0\0")?;
+        Ok(Self { out, samples: 0 })
+    }
+
+    /// Adds `samples`, clipped to -1.0..=1.0
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes()).collect();
+        self.out.write_all(&bytes)?;
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    /// Fills in the sizes and ends the WAV
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = self.samples * 2;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Bits per palette index in GIF image data: the 4 colors of a Palette
+const GIF_MIN_CODE_SIZE: u8 = 2;
+
+/// `indices` compressed the way GIF image data is, with variable-length LZW codes packed least
+/// significant bit first
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << GIF_MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut out = Bits::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = GIF_MIN_CODE_SIZE + 1;
+    out.push(clear, size);
+    let Some((&first, rest)) = indices.split_first() else {
+        out.push(end, size);
+        return out.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        out.push(prefix, size);
+        // codes max out at 12 bits, after which the table starts over
+        if next == 4096 {
+            out.push(clear, size);
+            table.clear();
+            next = end + 1;
+            size = GIF_MIN_CODE_SIZE + 1;
+        } else {
+            if next == 1 << size {
+                size += 1;
+            }
+            table.insert((prefix, index), next);
+            next += 1;
+        }
+        prefix = index as u16;
+    }
+    out.push(prefix, size);
+    out.push(end, size);
+    out.finish()
+}
+
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    pending: u32,
+    count: u8,
+}
+
+impl Bits {
+    fn push(&mut self, code: u16, size: u8) {
+        self.pending |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.pending as u8);
+            self.pending >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.pending as u8);
+        }
+        self.bytes
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64, with padding
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (n, &byte)| bits | (byte as u32) << (16 - n * 8));
+        for n in 0..4 {
+            if n <= group.len() {
+                text.push(BASE64[(bits >> (18 - n * 6) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decodes standard base64, ignoring whitespace such as line breaks picked up along the way
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    let digits = digits.strip_suffix(b"==").or_else(|| digits.strip_suffix(b"=")).unwrap_or(&digits);
+    let mut data = Vec::with_capacity(digits.len() * 3 / 4);
+    for group in digits.chunks(4) {
+        if group.len() == 1 {
+            return None;
+        }
+        let mut bits = 0u32;
+        for (n, &digit) in group.iter().enumerate() {
+            let value = BASE64.iter().position(|&c| c == digit)? as u32;
+            bits |= value << (18 - n * 6);
+        }
+        data.extend((0..group.len() - 1).map(|n| (bits >> (16 - n * 8)) as u8));
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips() {
+        assert_eq!(base64_encode(b"CHIP-8"), "Q0hJUC04");
+        assert_eq!(base64_encode(b"CHIP8"), "Q0hJUDg=");
+        assert_eq!(base64_encode(b"CHIP"), "Q0hJUA==");
+        for data in [&b""[..], b"a", b"ab", b"abc", &[0xFF, 0x00, 0x80, 0x7F]] {
+            assert_eq!(base64_decode(&base64_encode(data)).as_deref(), Some(data));
+        }
+        assert_eq!(base64_decode("Q0hJ\nUDg=").unwrap(), b"CHIP8");
+        assert_eq!(base64_decode("Q0h!"), None);
+    }
+
+    #[test]
+    fn gifs_merge_repeated_frames() {
+        let mut display = Display::default();
+        let mut gif = GifWriter::new(vec![], &Palette::GREEN, 1).unwrap();
+        for _ in 0..3 {
+            gif.frame(&display).unwrap();
+        }
+        display.draw(0, 0, &[0xF0, 0x90, 0xF0], false);
+        gif.frame(&display).unwrap();
+        let gif = gif.finish().unwrap();
+        assert_eq!(gif[..13], *b"GIF89a\x80\0\x40\0\xF1\0\0");
+        assert_eq!(gif[13..16], Palette::GREEN.colors[0]);
+        // the 3 blank frames as one shown for 5/100 seconds, then the drawn one
+        let delays: Vec<_> = (0..gif.len() - 6)
+            .filter(|&i| gif[i..i + 4] == [0x21, 0xF9, 4, 0])
+            .map(|i| u16::from_le_bytes([gif[i + 4], gif[i + 5]]))
+            .collect();
+        assert_eq!(delays, [5, 2]);
+        assert_eq!(gif.last(), Some(&0x3B));
+    }
+
+    #[test]
+    fn wavs_get_their_sizes_filled_in() {
+        let mut wav = WavWriter::new(io::Cursor::new(vec![]), 44_100).unwrap();
+        wav.write(&[0.0, 1.0, -1.0]).unwrap();
+        wav.write(&[2.0]).unwrap();
+        let wav = wav.finish().unwrap().into_inner();
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(wav[..12], *b"RIFF\x2c\0\0\0WAVE");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 44_100);
+        assert_eq!(wav[36..44], *b"data\x08\0\0\0");
+        let samples: Vec<i16> = wav[44..].chunks(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn ppms_have_a_header_and_rgb_pixels() {
+        let ppm = ppm(&Display::with_palette(Palette::LCD), 2);
+        assert!(ppm.starts_with(b"P6\n128 64\n255\n"));
+        assert_eq!(ppm.len(), 14 + 128 * 64 * 3);
+        assert_eq!(ppm[14..17], Palette::LCD.off());
+    }
+
+    #[test]
+    fn screenshots_are_valid_pngs() {
+        let png = screenshot(&Display::default(), 4);
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        // IHDR with the scaled size
+        assert_eq!(png[12..24], *b"IHDR\0\0\x01\0\0\0\0\x80");
+        let ihdr_crc = u32::from_be_bytes(png[29..33].try_into().unwrap());
+        assert_eq!(ihdr_crc, crc32(&png[12..29]));
+        assert_eq!(png[png.len() - 12..], *b"\0\0\0\0IEND\xae\x42\x60\x82");
+    }
+}
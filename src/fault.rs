@@ -0,0 +1,102 @@
+//! Faults: things a ROM did that no interpreter defines, and spotting a ROM stuck repeating one
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::instruction::Instruction;
+use crate::quirks::{Preset, Quirks};
+use crate::TIMER_HZ;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Fault {
+    InvalidOpcode(u16),
+    /// A read or write past the end of memory, which wraps around instead
+    OutOfBounds(u32),
+    /// In strict memory mode, a write below PROGRAM_START, where the original interpreters kept
+    /// their own variables; it's carried out all the same
+    ProtectedWrite(u16),
+    /// 00EE with nothing to return to, which is ignored
+    StackUnderflow,
+    /// 2NNN with STACK_DEPTH calls already nested, which is ignored
+    StackOverflow,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::InvalidOpcode(opcode) => write!(f, "invalid opcode {opcode:04X}"),
+            Fault::OutOfBounds(addr) => write!(f, "out-of-range memory access to {addr:#06x}"),
+            Fault::ProtectedWrite(addr) => write!(f, "write to interpreter memory at {addr:#05x}"),
+            Fault::StackUnderflow => write!(f, "return with an empty stack"),
+            Fault::StackOverflow => write!(f, "call with a full stack"),
+        }
+    }
+}
+
+/// The same fault recurring at the same address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashLoop {
+    pub pc: u16,
+    pub fault: Fault,
+    /// Times it happened in the last second
+    pub count: u32,
+}
+
+impl CrashLoop {
+    /// Another preset worth trying: XO-CHIP if the ROM tried one of its instructions,
+    /// otherwise simply the next one
+    pub fn suggest(&self, current: Preset) -> Preset {
+        match self.fault {
+            Fault::InvalidOpcode(opcode)
+                if current != Preset::Xochip
+                    && Instruction::decode_with(opcode, &Quirks::preset(current)).and_then(Instruction::platform) == Some(Preset::Xochip) =>
+            {
+                Preset::Xochip
+            }
+            _ => current.next(),
+        }
+    }
+}
+
+/// Watches the faults a machine reports each frame and flags one that recurs at the same PC
+/// `threshold` or more times within a second of emulated time
+#[derive(Debug, Clone)]
+pub struct CrashLoopDetector {
+    threshold: u32,
+    counts: BTreeMap<(u16, Fault), u32>,
+    frames: u32,
+}
+
+impl Default for CrashLoopDetector {
+    fn default() -> Self {
+        Self::new(30)
+    }
+}
+
+impl CrashLoopDetector {
+    pub fn new(threshold: u32) -> Self {
+        Self { threshold: threshold.max(1), counts: BTreeMap::new(), frames: 0 }
+    }
+
+    /// Call once per frame with the faults from Chip8::take_faults()
+    pub fn observe(&mut self, faults: &[(u16, Fault)]) -> Option<CrashLoop> {
+        self.frames += 1;
+        if self.frames > TIMER_HZ {
+            self.frames = 1;
+            self.counts.clear();
+        }
+        let mut worst = None;
+        for &(pc, fault) in faults {
+            let count = self.counts.entry((pc, fault)).or_default();
+            *count += 1;
+            if *count >= self.threshold {
+                worst = Some(CrashLoop { pc, fault, count: *count });
+            }
+        }
+        if worst.is_some() {
+            // start over so resuming doesn't immediately trip again
+            self.counts.clear();
+        }
+        worst
+    }
+}
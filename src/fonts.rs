@@ -0,0 +1,161 @@
+//! The glyphs FX29 and FX30 point I at. Interpreters each drew their hex digits their own way,
+//! and the odd program sprites with them or checks their bytes, so the others are here too. The
+//! default is Octo's, which is FONT and BIG_FONT.
+
+use crate::{BIG_FONT, FONT};
+
+/// A 4x5 font for FX29 and an 8x10 one for FX30, installed at FONT_ADDR and BIG_FONT_ADDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fonts {
+    pub small: [u8; FONT.len()],
+    pub big: [u8; BIG_FONT.len()],
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        FontSet::default().fonts()
+    }
+}
+
+/// Which interpreter's glyphs to install. Those from before SUPER-CHIP had no big font, and
+/// come with Octo's
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FontSet {
+    #[default]
+    Octo,
+    /// The COSMAC VIP's, as its interpreter read them from the monitor ROM
+    Vip,
+    #[value(name = "eti-660")]
+    Eti660,
+    #[value(name = "dream-6800")]
+    Dream6800,
+    /// SUPER-CHIP 1.1's big digits, which had no letters; A-F are left blank
+    Schip,
+}
+
+impl FontSet {
+    pub fn fonts(self) -> Fonts {
+        let small = match self {
+            FontSet::Octo | FontSet::Schip => FONT,
+            FontSet::Vip => VIP_FONT,
+            FontSet::Eti660 => ETI_660_FONT,
+            FontSet::Dream6800 => DREAM_6800_FONT,
+        };
+        let big = match self {
+            FontSet::Schip => SCHIP_BIG_FONT,
+            _ => BIG_FONT,
+        };
+        Fonts { small, big }
+    }
+}
+
+const VIP_FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const ETI_660_FONT: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xE0, 0x80, 0x80, // F
+];
+
+const DREAM_6800_FONT: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const SCHIP_BIG_FONT: [u8; 160] = {
+    let mut font = [0; 160];
+    let digits = [
+        0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+        0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+        0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+        0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+        0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+        0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+        0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    ];
+    let mut n = 0;
+    while n < digits.len() {
+        font[n] = digits[n];
+        n += 1;
+    }
+    font
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, BIG_FONT_ADDR, FONT_ADDR};
+
+    #[test]
+    fn font_sets_install_their_glyphs_where_fx29_and_fx30_point() {
+        for set in [FontSet::Octo, FontSet::Vip, FontSet::Eti660, FontSet::Dream6800, FontSet::Schip] {
+            let fonts = set.fonts();
+            // 4 pixels wide, so only the high nibble is ever lit
+            assert!(fonts.small.iter().all(|row| row & 0x0F == 0), "{set:?}");
+            let mut chip_8 = Chip8::builder().font_set(set).rom_bytes(&[0x12, 0x00]).build();
+            // LD V1 7; LD F V1 and LD HF V1
+            chip_8.execute_raw(0x6107);
+            chip_8.execute_raw(0xF129);
+            assert_eq!(chip_8.index(), FONT_ADDR + 7 * 5);
+            assert_eq!(chip_8.peek(chip_8.index()), fonts.small[7 * 5], "{set:?}");
+            chip_8.execute_raw(0xF130);
+            assert_eq!(chip_8.index(), BIG_FONT_ADDR + 7 * 10);
+            assert_eq!(chip_8.peek(chip_8.index() + 9), fonts.big[7 * 10 + 9], "{set:?}");
+        }
+        assert_eq!(FontSet::Vip.fonts().small[5..10], [0x60, 0x20, 0x20, 0x20, 0x70]);
+        assert_eq!(Fonts::default(), Fonts { small: FONT, big: BIG_FONT });
+        // switching keeps the set across loads
+        let mut chip_8 = Chip8::from_rom(&[0x12, 0x00]);
+        chip_8.set_font_set(FontSet::Dream6800);
+        assert_eq!(chip_8.peek(FONT_ADDR + 5), 0x40);
+        chip_8.load_rom(&[0x12, 0x00]);
+        assert_eq!(chip_8.peek(FONT_ADDR + 5), 0x40);
+    }
+}
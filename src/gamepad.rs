@@ -0,0 +1,174 @@
+//! Game controllers: buttons, d-pad and left stick mapped to keypad keys by a PadProfile, with
+//! hot-plugging.
+//!
+//! The default profile suits the WASD-style layout most directional games use: the d-pad and
+//! stick press 5/7/8/9 (up/left/down/right), A presses 6 and B presses 4, the two keys next to
+//! them that games tend to use for actions. The ROM database or --pad pick another.
+//!
+//! With --rumble, controllers that can rumble do while the sound timer runs, for games that
+//! beep when something is hit.
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+use chip_8_rs::logging::Level;
+use chip_8_rs::pad::{PadButton, PadProfile};
+use chip_8_rs::{event, Chip8};
+
+use crate::i18n::{Locale, Msg};
+use crate::keymap::parse_keypad_key;
+use crate::rotation::Rotation;
+
+/// How far the stick has to move from center to count as a direction
+const STICK_DEADZONE: i16 = 12_000;
+
+/// The low and high frequency motors' strength while the sound timer runs
+const RUMBLE_STRENGTH: (u16, u16) = (0x4000, 0xC000);
+
+/// How long each frame's rumble lasts, a few frames' worth so it's continuous while it's renewed
+/// and stops by itself if it isn't
+const RUMBLE_MS: u32 = 50;
+
+pub struct Gamepads {
+    subsystem: Option<GameControllerSubsystem>,
+    /// Open controllers; dropping one closes it
+    controllers: Vec<GameController>,
+    profile: PadProfile,
+    /// Turns the directions the profile binds for a turned screen
+    rotation: Rotation,
+    /// Stick directions currently past the deadzone: left, right, up, down
+    stick: [bool; 4],
+    /// Whether to rumble with the sound timer, and whether they're rumbling
+    rumble: bool,
+    rumbling: bool,
+    locale: Locale,
+}
+
+impl Gamepads {
+    pub fn new(subsystem: Option<GameControllerSubsystem>, profile: PadProfile, rotation: Rotation, rumble: bool, locale: Locale) -> Self {
+        Self { subsystem, controllers: vec![], profile, rotation, stick: [false; 4], rumble, rumbling: false, locale }
+    }
+
+    pub fn profile(&self) -> &PadProfile {
+        &self.profile
+    }
+
+    pub fn set_profile(&mut self, profile: PadProfile) {
+        self.profile = profile;
+    }
+
+    /// Switches to the next built-in layout, keeping the buttons bound otherwise
+    pub fn next_layout(&mut self, chip_8: &mut Chip8) {
+        // what the old layout held would otherwise stay held
+        self.release_all(chip_8);
+        self.profile.layout = self.profile.layout.next();
+    }
+
+    /// Keeps the controllers rumbling while `sounding`, once a frame, if --rumble asked for it.
+    /// Controllers without motors are left alone
+    pub fn rumble(&mut self, sounding: bool) {
+        if !self.rumble || (!sounding && !self.rumbling) {
+            return;
+        }
+        self.rumbling = sounding;
+        let ((low, high), ms) = if sounding { (RUMBLE_STRENGTH, RUMBLE_MS) } else { ((0, 0), 0) };
+        for controller in &mut self.controllers {
+            controller.set_rumble(low, high, ms).ok();
+        }
+    }
+
+    fn release_all(&mut self, chip_8: &mut Chip8) {
+        for button in PadButton::ALL {
+            if let Some(key) = self.profile.key(button) {
+                chip_8.release(self.rotation.turn_key(key));
+            }
+        }
+        self.stick = [false; 4];
+    }
+
+    fn key(&self, button: Button) -> Option<u8> {
+        let key = self.profile.key(pad_button(button)?)?;
+        Some(self.rotation.turn_key(key))
+    }
+
+    /// Handles controller events, returning false for other events, which are left to the caller
+    pub fn handle(&mut self, event: &Event, chip_8: &mut Chip8) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let Some(subsystem) = self.subsystem.as_ref() else {
+                    return true;
+                };
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        event!(Level::Info, "{}", self.locale.format(Msg::ControllerConnected, &[&controller.name()]));
+                        self.controllers.push(controller);
+                    }
+                    Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::ControllerFailed, &[&which, &e])),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let locale = self.locale;
+                self.controllers.retain(|controller| {
+                    let removed = controller.instance_id() == which;
+                    if removed {
+                        event!(Level::Info, "{}", locale.format(Msg::ControllerDisconnected, &[&controller.name()]));
+                    }
+                    !removed
+                });
+            }
+            Event::ControllerButtonDown { button, .. } => if let Some(key) = self.key(button) {
+                chip_8.press(key);
+            }
+            Event::ControllerButtonUp { button, .. } => if let Some(key) = self.key(button) {
+                chip_8.release(key);
+            }
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                // the stick stands in for the d-pad, so it presses whatever the d-pad is bound to
+                let (first, negative, positive) = match axis {
+                    Axis::LeftX => (0, Button::DPadLeft, Button::DPadRight),
+                    Axis::LeftY => (2, Button::DPadUp, Button::DPadDown),
+                    _ => return true,
+                };
+                let directions = [(negative, value < -STICK_DEADZONE), (positive, value > STICK_DEADZONE)];
+                for (n, (button, held)) in directions.into_iter().enumerate() {
+                    // only act on crossing the deadzone, so jitter doesn't release keys held elsewhere
+                    if std::mem::replace(&mut self.stick[first + n], held) == held {
+                        continue;
+                    }
+                    match (self.key(button), held) {
+                        (Some(key), true) => chip_8.press(key),
+                        (Some(key), false) => chip_8.release(key),
+                        (None, _) => {}
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn pad_button(button: Button) -> Option<PadButton> {
+    Some(match button {
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadRight => PadButton::DPadRight,
+        Button::A => PadButton::A,
+        Button::B => PadButton::B,
+        Button::X => PadButton::X,
+        Button::Y => PadButton::Y,
+        Button::Back => PadButton::Back,
+        Button::Start => PadButton::Start,
+        Button::LeftShoulder => PadButton::LeftShoulder,
+        Button::RightShoulder => PadButton::RightShoulder,
+        _ => return None,
+    })
+}
+
+/// Parses `BUTTON=KEY` for --pad-bind, with SDL's button names (a, b, x, y, dpup, start, ...)
+pub fn parse_pad_binding(s: &str) -> Result<(PadButton, u8), String> {
+    let (name, key) = s.split_once('=').ok_or_else(|| format!("expected BUTTON=KEY, got `{s}`"))?;
+    Ok((name.parse()?, parse_keypad_key(key)?))
+}
@@ -0,0 +1,333 @@
+//! A stub speaking enough of GDB's remote serial protocol for a debugger frontend to attach to
+//! a running game: reading and writing registers and memory, breakpoints, stepping and
+//! continuing. The stub only turns packets into replies; moving the bytes is up to the caller,
+//! which hands whatever arrived to feed() and calls poll() after running each frame.
+//!
+//! There's no CHIP-8 architecture in GDB, so target.xml describes the registers instead, in
+//! this order: V0 to VF a byte each, then I and PC two bytes each, big-endian like the rest of
+//! the machine, then the stack depth and the delay and sound timers a byte each.
+
+use std::fmt::Write as _;
+
+use crate::debugger::Debugger;
+use crate::Chip8;
+
+/// Sent for qXfer:features:read:target.xml
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/// Registers in the order `g` sends them, and each one's size in bytes
+const REGISTER_SIZES: [usize; 21] = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 1, 1, 1];
+
+/// The interrupt GDB sends outside of a packet when the user presses Ctrl+C
+const INTERRUPT: u8 = 0x03;
+
+/// Stop reasons: a trap for breakpoints and steps, an interrupt for Ctrl+C
+const SIGTRAP: u8 = 5;
+const SIGINT: u8 = 2;
+
+#[derive(Debug, Default)]
+pub struct GdbStub {
+    /// Bytes of a packet still arriving
+    input: Vec<u8>,
+    /// Whether GDB continued the machine and is waiting to hear that it stopped
+    running: bool,
+    /// Whether GDB detached, after which the connection can be closed
+    detached: bool,
+}
+
+impl GdbStub {
+    /// A stub for a debugger that just connected; pauses the machine, as GDB expects to find
+    /// it stopped
+    pub fn attach(debugger: &mut Debugger) -> Self {
+        debugger.pause();
+        Self::default()
+    }
+
+    pub fn detached(&self) -> bool {
+        self.detached
+    }
+
+    /// Handles bytes from GDB, returning the bytes to send back
+    pub fn feed(&mut self, bytes: &[u8], chip_8: &mut Chip8, debugger: &mut Debugger) -> Vec<u8> {
+        let mut out = vec![];
+        for &byte in bytes {
+            if self.input.is_empty() {
+                match byte {
+                    b'$' => self.input.push(byte),
+                    INTERRUPT if self.running => {
+                        debugger.pause();
+                        self.running = false;
+                        out.extend(packet(&format!("S{SIGINT:02x}")));
+                    }
+                    // acknowledgements, which there's no need for over TCP
+                    _ => {}
+                }
+                continue;
+            }
+            self.input.push(byte);
+            // the packet ends two checksum digits after the '#'
+            let len = self.input.len();
+            if len < 4 || self.input[len - 3] != b'#' {
+                continue;
+            }
+            let frame = std::mem::take(&mut self.input);
+            let data = &frame[1..len - 3];
+            let sum = std::str::from_utf8(&frame[len - 2..]).ok().and_then(|sum| u8::from_str_radix(sum, 16).ok());
+            if sum != Some(checksum(data)) {
+                out.push(b'-');
+                continue;
+            }
+            out.push(b'+');
+            let command = String::from_utf8_lossy(data);
+            if let Some(reply) = self.command(&command, chip_8, debugger) {
+                out.extend(packet(&reply));
+            }
+        }
+        out
+    }
+
+    /// Call after each frame the machine ran: tells GDB once the machine stopped at a
+    /// breakpoint or halted, returning the bytes to send
+    pub fn poll(&mut self, chip_8: &Chip8, debugger: &Debugger) -> Vec<u8> {
+        if !self.running {
+            return vec![];
+        }
+        if chip_8.halted() {
+            self.running = false;
+            return packet("W00");
+        }
+        if debugger.paused() {
+            self.running = false;
+            return packet(&format!("S{SIGTRAP:02x}"));
+        }
+        vec![]
+    }
+
+    /// The reply to a packet, or None if the reply comes later, once the machine stops
+    fn command(&mut self, command: &str, chip_8: &mut Chip8, debugger: &mut Debugger) -> Option<String> {
+        let stopped = format!("S{SIGTRAP:02x}");
+        let ok = "OK".to_string();
+        let error = "E01".to_string();
+        let (kind, args) = command.split_at(command.chars().next().map_or(0, char::len_utf8));
+        let reply = match kind {
+            "?" => stopped,
+            "g" => to_hex(&registers(chip_8)),
+            "G" => match from_hex(args) {
+                Some(bytes) if bytes.len() == REGISTER_SIZES.iter().sum() => {
+                    let mut rest = bytes.as_slice();
+                    for (n, size) in REGISTER_SIZES.into_iter().enumerate() {
+                        let (value, tail) = rest.split_at(size);
+                        set_register(chip_8, n, value);
+                        rest = tail;
+                    }
+                    ok
+                }
+                _ => error,
+            },
+            "p" => match usize::from_str_radix(args, 16).ok().and_then(|n| register(chip_8, n)) {
+                Some(value) => to_hex(&value),
+                None => error,
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(n, value)| Some((usize::from_str_radix(n, 16).ok()?, from_hex(value)?)));
+                match parsed {
+                    Some((n, value)) if REGISTER_SIZES.get(n) == Some(&value.len()) => {
+                        set_register(chip_8, n, &value);
+                        ok
+                    }
+                    _ => error,
+                }
+            }
+            "m" => match address_range(args) {
+                Some((addr, len)) => to_hex(&(0..len).map(|n| chip_8.peek(addr.wrapping_add(n))).collect::<Vec<_>>()),
+                None => error,
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| Some((address_range(range)?, from_hex(data)?)));
+                match parsed {
+                    Some(((addr, len), data)) if data.len() == len as usize => {
+                        for (n, byte) in data.into_iter().enumerate() {
+                            chip_8.poke(addr.wrapping_add(n as u16), byte);
+                        }
+                        ok
+                    }
+                    _ => error,
+                }
+            }
+            // software and hardware breakpoints are the same thing here
+            "Z" | "z" => match args.split(',').collect::<Vec<_>>()[..] {
+                ["0" | "1", addr, _] => match u16::from_str_radix(addr, 16) {
+                    Ok(addr) if kind == "Z" => {
+                        debugger.breakpoints.insert(addr);
+                        ok
+                    }
+                    Ok(addr) => {
+                        debugger.breakpoints.remove(&addr);
+                        ok
+                    }
+                    Err(_) => error,
+                },
+                // watchpoints
+                _ => String::new(),
+            },
+            "s" => {
+                debugger.step(chip_8);
+                if chip_8.halted() { "W00".to_string() } else { stopped }
+            }
+            "c" => {
+                debugger.resume();
+                self.running = true;
+                return None;
+            }
+            // killing would take the game with it, so both just let it go on without GDB
+            "D" | "k" => {
+                debugger.resume();
+                self.running = false;
+                self.detached = true;
+                if kind == "k" {
+                    return None;
+                }
+                ok
+            }
+            "H" => ok,
+            "q" if args.starts_with("Supported") => "PacketSize=1000;qXfer:features:read+".to_string(),
+            "q" if args == "Attached" => "1".to_string(),
+            "q" if args == "C" => "QC1".to_string(),
+            "q" if args == "fThreadInfo" => "m1".to_string(),
+            "q" if args == "sThreadInfo" => "l".to_string(),
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = &args["Xfer:features:read:target.xml:".len()..];
+                match range.split_once(',').map(|(offset, len)| (usize::from_str_radix(offset, 16), usize::from_str_radix(len, 16))) {
+                    Some((Ok(offset), Ok(len))) => {
+                        let chunk = TARGET_XML.get(offset.min(TARGET_XML.len())..).unwrap_or_default();
+                        let chunk = &chunk[..len.min(chunk.len())];
+                        let more = offset + chunk.len() < TARGET_XML.len();
+                        format!("{}{chunk}", if more { 'm' } else { 'l' })
+                    }
+                    _ => error,
+                }
+            }
+            // anything else isn't supported, which an empty reply tells GDB
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+}
+
+/// Frames `data` as a packet, with the checksum
+fn packet(data: &str) -> Vec<u8> {
+    format!("${data}#{:02x}", checksum(data.as_bytes())).into_bytes()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").ok();
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|n| u8::from_str_radix(hex.get(n..n + 2)?, 16).ok()).collect()
+}
+
+/// Parses `addr,len`
+fn address_range(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((u16::from_str_radix(addr, 16).ok()?, u16::from_str_radix(len, 16).ok()?))
+}
+
+fn registers(chip_8: &Chip8) -> Vec<u8> {
+    (0..REGISTER_SIZES.len()).flat_map(|n| register(chip_8, n).unwrap_or_default()).collect()
+}
+
+fn register(chip_8: &Chip8, n: usize) -> Option<Vec<u8>> {
+    let value = match n {
+        0..=15 => vec![chip_8.cpu.v[n]],
+        16 => chip_8.cpu.i.to_be_bytes().to_vec(),
+        17 => chip_8.cpu.pc.to_be_bytes().to_vec(),
+        18 => vec![chip_8.cpu.stack.len() as u8],
+        19 => vec![chip_8.timers.delay],
+        20 => vec![chip_8.timers.sound],
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Sets register `n` from a value of its size; the stack depth can't be set
+fn set_register(chip_8: &mut Chip8, n: usize, value: &[u8]) {
+    let word = || u16::from_be_bytes([value[0], value[1]]);
+    match n {
+        0..=15 => chip_8.cpu.v[n] = value[0],
+        16 => chip_8.cpu.i = word(),
+        17 => chip_8.cpu.pc = word(),
+        19 => chip_8.timers.delay = value[0],
+        20 => chip_8.timers.sound = value[0],
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gdb_can_inspect_break_and_continue() {
+        let mut chip_8 = Chip8::builder().rom_bytes(&[0x60, 0x2a, 0x12, 0x02]).build();
+        let mut debugger = Debugger::default();
+        let mut gdb = GdbStub::attach(&mut debugger);
+        assert!(debugger.paused());
+        let send = |gdb: &mut GdbStub, chip_8: &mut Chip8, debugger: &mut Debugger, data: &str| {
+            String::from_utf8(gdb.feed(&packet(data), chip_8, debugger)).unwrap()
+        };
+        assert_eq!(send(&mut gdb, &mut chip_8, &mut debugger, "?"), "+$S05#b8");
+        assert_eq!(send(&mut gdb, &mut chip_8, &mut debugger, "m200,4"), "+$602a1202#be");
+        assert_eq!(String::from_utf8(gdb.feed(b"$m200,4#00", &mut chip_8, &mut debugger)).unwrap(), "-");
+
+        assert_eq!(send(&mut gdb, &mut chip_8, &mut debugger, "s"), "+$S05#b8");
+        let reply = send(&mut gdb, &mut chip_8, &mut debugger, "g");
+        // V0, then I and PC after 16 register bytes
+        assert!(reply.starts_with("+$2a"));
+        assert_eq!(&reply[2 + 32..2 + 40], "00000202");
+
+        assert_eq!(send(&mut gdb, &mut chip_8, &mut debugger, "Z0,202,2"), "+$OK#9a");
+        assert_eq!(send(&mut gdb, &mut chip_8, &mut debugger, "c"), "+");
+        assert!(gdb.poll(&chip_8, &debugger).is_empty());
+        debugger.run(&mut chip_8, 10);
+        assert_eq!(gdb.poll(&chip_8, &debugger), b"$S05#b8");
+        assert_eq!(chip_8.pc(), 0x202);
+    }
+}
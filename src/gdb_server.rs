@@ -0,0 +1,64 @@
+//! --gdb: a TCP socket the main loop checks once a frame for a GDB connection, passing what it
+//! sends to the library's stub and the stub's replies back. One debugger at a time; another
+//! can attach once it detaches or hangs up
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use chip_8_rs::debugger::Debugger;
+use chip_8_rs::gdb::GdbStub;
+use chip_8_rs::Chip8;
+
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+
+pub struct GdbServer {
+    listener: TcpListener,
+    connection: Option<(TcpStream, GdbStub)>,
+    locale: Locale,
+}
+
+impl GdbServer {
+    pub fn listen(addr: &str, locale: Locale) -> Self {
+        let listener = TcpListener::bind(addr)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .unwrap_or_else(|e| fail(locale.format(Msg::ServeFailed, &[&addr, &e])));
+        let local = listener.local_addr().map_or_else(|_| addr.to_string(), |local| local.to_string());
+        println!("{}", locale.format(Msg::GdbListening, &[&local]));
+        Self { listener, connection: None, locale }
+    }
+
+    /// Call once a frame: takes a new connection, answers what GDB sent, and tells it when the
+    /// machine stopped
+    pub fn poll(&mut self, chip_8: &mut Chip8, debugger: &mut Debugger) {
+        if self.connection.is_none() {
+            if let Ok((stream, peer)) = self.listener.accept() {
+                if stream.set_nonblocking(true).and_then(|()| stream.set_nodelay(true)).is_ok() {
+                    println!("{}", self.locale.format(Msg::GdbAttached, &[&peer]));
+                    self.connection = Some((stream, GdbStub::attach(debugger)));
+                }
+            }
+        }
+        let Some((stream, stub)) = self.connection.as_mut() else {
+            return;
+        };
+        let mut out = stub.poll(chip_8, debugger);
+        let mut buf = [0; 4096];
+        let closed = loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break true,
+                Ok(n) => out.extend(stub.feed(&buf[..n], chip_8, debugger)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break false,
+                Err(_) => break true,
+            }
+        };
+        // replies are small, so one that doesn't fit the socket's buffer means GDB is gone
+        let closed = closed || stream.write_all(&out).is_err() || stub.detached();
+        if closed {
+            // let the game go on without the debugger that stopped it
+            debugger.resume();
+            println!("{}", self.locale.text(Msg::GdbDetached));
+            self.connection = None;
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Running without SDL, for CI and servers: the ROM runs for a number of frames, or until it
+//! halts or reaches a breakpoint, and the final screen is written out as an image or as text.
+//! Progress goes to stderr so the screen can go to stdout
+
+use std::io::Write;
+
+use chip_8_rs::announce::Announcer;
+use chip_8_rs::debugger::Debugger;
+use chip_8_rs::export::{ppm, screenshot};
+use chip_8_rs::logging::Level;
+use chip_8_rs::symbols::Symbols;
+use chip_8_rs::{event, Chip8, TIMER_HZ};
+
+use crate::args::RunArgs;
+use crate::i18n::{Locale, Msg};
+use crate::input_log::InputLog;
+
+pub fn run(cli: &RunArgs, mut chip_8: Chip8, symbols: &Symbols, locale: Locale) {
+    let mut debugger = Debugger::default();
+    debugger.breakpoints.extend(&cli.breakpoints);
+    let instructions_per_frame = (cli.ips() / TIMER_HZ).max(1) as usize;
+    let mut input_log = InputLog::start(cli, &mut chip_8, locale);
+    let mut announcer = cli.announce.map(|secs| (Announcer::new(secs * TIMER_HZ), crate::announcements(cli, locale)));
+    let mut frames = 0;
+    while frames < cli.frames && !chip_8.halted() && !debugger.paused() {
+        input_log.frame(&mut chip_8);
+        debugger.run(&mut chip_8, instructions_per_frame);
+        chip_8.tick_timers();
+        // stdout may be taking the screen
+        for line in chip_8.take_script_output() {
+            eprintln!("{line}");
+        }
+        let went_wrong = !chip_8.take_faults().is_empty() || chip_8.halted();
+        crate::write_trace(&mut chip_8, cli, went_wrong, symbols);
+        if let Some((due, out)) = announcer.as_mut() {
+            if let Some(text) = due.frame(&chip_8) {
+                if let Err(e) = out.write_all(text.as_bytes()).and_then(|()| out.flush()) {
+                    event!(Level::Warn, "{}", locale.format(Msg::AnnounceFailed, &[&e]));
+                    announcer = None;
+                }
+            }
+        }
+        frames += 1;
+    }
+    input_log.finish();
+    if chip_8.halted() {
+        eprintln!("{}", locale.format(Msg::HeadlessHalted, &[&frames]));
+    } else if debugger.paused() {
+        let pc = symbols.describe(chip_8.pc());
+        eprintln!("{}", locale.format(Msg::HeadlessBreakpoint, &[&pc, &frames]));
+    } else {
+        eprintln!("{}", locale.format(Msg::HeadlessRan, &[&frames]));
+    }
+    if let Some(diagnostics) = chip_8.diagnostics() {
+        eprint!("{diagnostics}");
+    }
+    crate::write_profile(&chip_8, cli, locale);
+
+    let display = chip_8.framebuffer();
+    let scale = cli.screenshot_scale as usize;
+    let path = &cli.output;
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let res = match extension.as_deref() {
+        _ if path.as_os_str() == "-" => {
+            let mut stdout = std::io::stdout();
+            write!(stdout, "{}", display.to_text()).and_then(|()| stdout.flush())
+        }
+        Some("png") => std::fs::write(path, screenshot(display, scale)),
+        Some("ppm") => std::fs::write(path, ppm(display, scale)),
+        _ => std::fs::write(path, display.to_text()),
+    };
+    if let Err(e) = res {
+        event!(Level::Warn, "{}", locale.format(Msg::ScreenshotFailed, &[&path.display(), &e]));
+        std::process::exit(1);
+    }
+}
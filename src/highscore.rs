@@ -0,0 +1,184 @@
+//! Tracks the best score a ROM ever reached by watching memory or its RPL flags, and how long
+//! it's been played for, each persisted in a small JSON file
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::storage::Storage;
+use crate::Chip8;
+
+/// Where a game keeps its score and how the bytes encode it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreWatch {
+    pub addr: u16,
+    pub len: u8,
+    /// Each byte holds one decimal digit, as written by FX33
+    pub bcd: bool,
+    /// `addr` is the first of the RPL flags, where SUPER-CHIP games save their high scores,
+    /// rather than memory
+    pub rpl: bool,
+}
+
+impl ScoreWatch {
+    /// Decodes the score from the watched bytes, most significant first
+    pub fn read(&self, peek: impl Fn(u16) -> u8) -> u64 {
+        (0..self.len as u16).fold(0, |acc, n| {
+            let byte = peek(self.addr.wrapping_add(n)) as u64;
+            if self.bcd {
+                acc * 10 + byte % 10
+            } else {
+                acc << 8 | byte
+            }
+        })
+    }
+
+    /// The score `chip_8` has now, from memory or the RPL flags; flags past the last read as 0
+    pub fn read_from(&self, chip_8: &Chip8) -> u64 {
+        match self.rpl {
+            true => self.read(|n| chip_8.rpl_flags().get(n as usize).copied().unwrap_or(0)),
+            false => self.read(|addr| chip_8.peek(addr)),
+        }
+    }
+}
+
+/// Storage key the leaderboard is kept under
+pub const SCORES_KEY: &str = "scores.json";
+
+/// Best scores per ROM, keyed by a ROM identifier
+#[derive(Debug, Default)]
+pub struct Leaderboard {
+    best: BTreeMap<String, u64>,
+}
+
+impl Leaderboard {
+    /// Loads the leaderboard from `storage`, starting empty if there is none or it can't be parsed
+    pub fn load(storage: &dyn Storage) -> Self {
+        Self { best: load(storage, SCORES_KEY) }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> std::io::Result<()> {
+        storage.write(SCORES_KEY, to_json(&self.best).as_bytes())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.best.is_empty()
+    }
+
+    pub fn best(&self, rom: &str) -> Option<u64> {
+        self.best.get(rom).copied()
+    }
+
+    /// Records `score` for `rom`, returning true if it beat the previous best
+    pub fn submit(&mut self, rom: &str, score: u64) -> bool {
+        match self.best.get_mut(rom) {
+            Some(best) if *best >= score => false,
+            Some(best) => {
+                *best = score;
+                true
+            }
+            None => {
+                self.best.insert(rom.to_string(), score);
+                true
+            }
+        }
+    }
+}
+
+/// Storage key play times are kept under
+pub const PLAY_TIME_KEY: &str = "playtime.json";
+
+/// How long each ROM has been played for in all, in whole seconds, keyed like the leaderboard
+#[derive(Debug, Default)]
+pub struct PlayTimes {
+    seconds: BTreeMap<String, u64>,
+}
+
+impl PlayTimes {
+    /// Loads the play times from `storage`, starting empty if there are none or they can't be parsed
+    pub fn load(storage: &dyn Storage) -> Self {
+        Self { seconds: load(storage, PLAY_TIME_KEY) }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> std::io::Result<()> {
+        storage.write(PLAY_TIME_KEY, to_json(&self.seconds).as_bytes())
+    }
+
+    /// How long `rom` has been played for, if it has been
+    pub fn of(&self, rom: &str) -> Option<Duration> {
+        self.seconds.get(rom).map(|&seconds| Duration::from_secs(seconds))
+    }
+
+    /// Counts `played` more of `rom`
+    pub fn add(&mut self, rom: &str, played: Duration) {
+        *self.seconds.entry(rom.to_string()).or_default() += played.as_secs();
+    }
+}
+
+/// The map stored under `key`, empty if there is none or it can't be parsed
+fn load(storage: &dyn Storage, key: &str) -> BTreeMap<String, u64> {
+    storage.read(key)
+        .ok()
+        .flatten()
+        .and_then(|json| String::from_utf8(json).ok())
+        .and_then(|json| parse(&json))
+        .unwrap_or_default()
+}
+
+fn to_json(map: &BTreeMap<String, u64>) -> String {
+    let entries: Vec<String> = map
+        .iter()
+        .map(|(rom, value)| format!("  \"{}\": {value}", rom.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+/// Parses the flat `{"rom": score, ...}` object written by to_json()
+fn parse(json: &str) -> Option<BTreeMap<String, u64>> {
+    let body = json.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut best = BTreeMap::new();
+    let mut rest = body.trim();
+    while !rest.is_empty() {
+        rest = rest.strip_prefix('"')?;
+        let mut key = String::new();
+        let mut chars = rest.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (_, '\\') => key.push(chars.next()?.1),
+                (i, '"') => break i,
+                (_, c) => key.push(c),
+            }
+        };
+        rest = rest[end + 1..].trim_start().strip_prefix(':')?.trim_start();
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        best.insert(key, rest[..digits].parse().ok()?);
+        rest = rest[digits..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    Some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn scores_and_play_times_persist() {
+        let mut storage = MemoryStorage::default();
+        let mut board = Leaderboard::load(&storage);
+        assert!(board.submit("1a2b3c4d", 120) && !board.submit("1a2b3c4d", 90));
+        board.save(&mut storage).unwrap();
+        assert_eq!(Leaderboard::load(&storage).best("1a2b3c4d"), Some(120));
+        let mut times = PlayTimes::load(&storage);
+        times.add("1a2b3c4d", Duration::from_secs_f64(61.5));
+        times.add("1a2b3c4d", Duration::from_secs(30));
+        times.save(&mut storage).unwrap();
+        assert_eq!(PlayTimes::load(&storage).of("1a2b3c4d"), Some(Duration::from_secs(91)));
+        assert_eq!(PlayTimes::load(&storage).of("ffffffff"), None);
+        // SUPER-CHIP games keep theirs in the RPL flags, here as two BCD digits
+        let mut chip_8 = Chip8::default();
+        chip_8.set_rpl_flags([0, 4, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let watch = ScoreWatch { addr: 1, len: 2, bcd: true, rpl: true };
+        assert_eq!(watch.read_from(&chip_8), 42);
+    }
+}
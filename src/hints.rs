@@ -0,0 +1,78 @@
+//! Per-ROM input hints: which keypad keys a game uses and what they do.
+//!
+//! Hints are plain text, one action per line, keys first and separated by spaces or slashes:
+//!
+//! ```text
+//! # Pong
+//! 1/4: left paddle up/down
+//! C D: right paddle up/down
+//! ```
+
+use std::fmt;
+
+/// The COSMAC VIP keypad, row by row, as the keys are laid out on the device
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    /// One bit per keypad key, like `Chip8::keys`
+    pub keys: u16,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hints(pub Vec<Hint>);
+
+impl Hints {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut hints = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            let (keys, action) = line.split_once(':').ok_or_else(|| err("expected `<keys>: <action>`"))?;
+            let mut mask = 0;
+            for key in keys.split(|c: char| c == '/' || c.is_whitespace()).filter(|key| !key.is_empty()) {
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| err(&format!("`{key}` is not a keypad key")))?;
+                mask |= 1 << key;
+            }
+            if mask == 0 {
+                return Err(err("no keys given"));
+            }
+            hints.push(Hint { keys: mask, action: action.trim().to_string() });
+        }
+        Ok(Self(hints))
+    }
+
+    /// Every key any hint mentions
+    pub fn keys(&self) -> u16 {
+        self.0.iter().fold(0, |mask, hint| mask | hint.keys)
+    }
+}
+
+/// One line, e.g. `4/6 move, 5 fire`
+impl fmt::Display for Hints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, hint) in self.0.iter().enumerate() {
+            if n > 0 {
+                write!(f, ", ")?;
+            }
+            let keys = (0..16).filter(|key| hint.keys & 1 << key != 0);
+            for (n, key) in keys.enumerate() {
+                write!(f, "{}{key:X}", if n > 0 { "/" } else { "" })?;
+            }
+            write!(f, " {}", hint.action)?;
+        }
+        Ok(())
+    }
+}
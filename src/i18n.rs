@@ -0,0 +1,661 @@
+//! Per-locale tables for the messages the frontend prints. Each message is a template with
+//! `{}` placeholders that [`Locale::format`] fills in order; translations must keep the
+//! placeholders in the same order as the English text
+
+use std::fmt::{self, Write};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Msg {
+    SdlFailed,
+    InputNone,
+    VideoHeadless,
+    VideoAccelerated,
+    AcceleratedUnavailable,
+    VideoSoftware,
+    AudioRate,
+    AudioNone,
+    AudioDeviceFailed,
+    AudioDevices,
+    AudioDevicesNone,
+    PokeOutsideProgram,
+    NoDataDir,
+    HighScore,
+    NewHighScore,
+    PlayedBefore,
+    PlayTimeSaveFailed,
+    ScoresSaveFailed,
+    RomSaved,
+    RomSaveFailed,
+    StateSaved,
+    StateSaveFailed,
+    StateLoaded,
+    StateLoadFailed,
+    SlotEmpty,
+    SlotReadFailed,
+    SlotSelected,
+    Autosaved,
+    AutosaveFailed,
+    AutosaveLoaded,
+    AutosaveLoadFailed,
+    LoopMarkedAt,
+    LoopMarkedAfter,
+    LoopMarkedOpen,
+    LoopEndMarked,
+    Looped,
+    LoopCleared,
+    NoLoop,
+    RomSwitched,
+    NoOtherRoms,
+    FrameDiverges,
+    AudioPitch,
+    SummaryWriteFailed,
+    Controls,
+    BadHints,
+    CheatsOn,
+    BadCheats,
+    BadSymbols,
+    BadScript,
+    BadRomDb,
+    LogFileFailed,
+    Paused,
+    Resumed,
+    BreakpointHit,
+    HistoryEntry,
+    PadProfile,
+    WatchpointHit,
+    BreakpointSet,
+    BreakpointCleared,
+    BreakpointPrompt,
+    BadAddress,
+    CrashLoop,
+    SwitchPreset,
+    PresetSwitched,
+    ControllerConnected,
+    ControllerDisconnected,
+    ControllerFailed,
+    ControllersNone,
+    MenuEmpty,
+    MenuFull,
+    MenuNeedsWindow,
+    CompareNeedsWindow,
+    MachinesDiverged,
+    Reset,
+    SoftReset,
+    RomReloaded,
+    RomReloadFailed,
+    ReloadOn,
+    ReloadOff,
+    ReportWritten,
+    ReportWriteFailed,
+    FullscreenFailed,
+    ScreenshotCopied,
+    StateCopied,
+    StatePasted,
+    PastedStateInvalid,
+    ClipboardNoState,
+    ClipboardFailed,
+    AnnounceConnectFailed,
+    AnnounceFailed,
+    ScreenshotSaved,
+    ScreenshotFailed,
+    CrashReportWritten,
+    CrashReportFailed,
+    RecordingStarted,
+    RecordingSaved,
+    AudioRecordingNeedsDevice,
+    RecordingFailed,
+    RomFileNeeded,
+    FrontendMissing,
+    RunningDemo,
+    RunningVariant,
+    HeadlessRan,
+    HeadlessHalted,
+    HeadlessBreakpoint,
+    TuiNeedsTerminal,
+    RomLoadFailed,
+    FileReadFailed,
+    FileCreateFailed,
+    StartFailed,
+    KeymapInvalid,
+    ConfigInvalid,
+    ConfigReloaded,
+    QuirksOnReset,
+    JournalInvalid,
+    RenderFailed,
+    RomLoaded,
+    RomIdentified,
+    KnownRom,
+    RomWarning,
+    RomNeedsPreset,
+    ReplayInvalid,
+    ReplayFinished,
+    ServeFailed,
+    Serving,
+    ClientConnected,
+    ClientDisconnected,
+    ConnectFailed,
+    ConnectionLost,
+    GdbListening,
+    GdbAttached,
+    GdbDetached,
+    NetplayWaiting,
+    NetplayConnected,
+    NetplayFailed,
+    NetplayLost,
+    NetplayDesync,
+    NetplayPaused,
+    NetplayRefused,
+    ProfileWritten,
+    ProfileWriteFailed,
+    CoverageMapWritten,
+    CoverageMapWriteFailed,
+    FaultExit,
+    InstructionRate,
+    RplReadFailed,
+    RplSaveFailed,
+    RecentSaveFailed,
+}
+
+impl Locale {
+    /// Picks the locale from `LC_ALL`, `LC_MESSAGES` or `LANG` the way POSIX tools do,
+    /// falling back to English for anything without a table
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map_or(Locale::En, |value| match value.get(..2) {
+                Some("de") => Locale::De,
+                Some("es") => Locale::Es,
+                _ => Locale::En,
+            })
+    }
+
+    pub fn text(self, msg: Msg) -> &'static str {
+        match self {
+            Locale::En => en(msg),
+            Locale::De => de(msg),
+            Locale::Es => es(msg),
+        }
+    }
+
+    /// Fills the message's placeholders with `args` in order
+    pub fn format(self, msg: Msg, args: &[&dyn fmt::Display]) -> String {
+        let mut parts = self.text(msg).split("{}");
+        let mut out = String::from(parts.next().unwrap_or_default());
+        for (part, arg) in parts.zip(args) {
+            write!(out, "{arg}{part}").ok();
+        }
+        out
+    }
+}
+
+fn en(msg: Msg) -> &'static str {
+    match msg {
+        Msg::SdlFailed => "video: headless, audio: none (SDL failed to initialize: {})",
+        Msg::InputNone => "input: none ({})",
+        Msg::VideoHeadless => "video: headless ({})",
+        Msg::VideoAccelerated => "video: accelerated",
+        Msg::AcceleratedUnavailable => "accelerated video unavailable ({}), trying software rendering",
+        Msg::VideoSoftware => "video: software",
+        Msg::AudioRate => "audio: {}Hz, buffer of {} samples",
+        Msg::AudioNone => "audio: none ({})",
+        Msg::AudioDeviceFailed => "audio device {} unavailable ({}), using the default one",
+        Msg::AudioDevices => "audio output devices:",
+        Msg::AudioDevicesNone => "no audio output devices found",
+        Msg::PokeOutsideProgram => "ignoring poke outside the program area: {}",
+        Msg::NoDataDir => "no data directory found, scores and save states won't be kept after exit",
+        Msg::HighScore => "high score: {}",
+        Msg::NewHighScore => "new high score: {}",
+        Msg::PlayedBefore => "played for {} before",
+        Msg::PlayTimeSaveFailed => "failed to save play time: {}",
+        Msg::ScoresSaveFailed => "failed to save high scores: {}",
+        Msg::RomSaved => "saved ROM to {}",
+        Msg::RomSaveFailed => "failed to save ROM to {}: {}",
+        Msg::StateSaved => "saved state to slot {}",
+        Msg::StateSaveFailed => "failed to save state to slot {}: {}",
+        Msg::StateLoaded => "loaded state from slot {}",
+        Msg::StateLoadFailed => "failed to load state from slot {}: {}",
+        Msg::SlotEmpty => "slot {} is empty",
+        Msg::SlotReadFailed => "failed to read slot {}: {}",
+        Msg::SlotSelected => "save slot {}",
+        Msg::Autosaved => "saved the state to pick up from next time",
+        Msg::AutosaveFailed => "failed to autosave: {}",
+        Msg::AutosaveLoaded => "picked up from the state saved last time",
+        Msg::AutosaveLoadFailed => "failed to pick up from the autosave: {}",
+        Msg::LoopMarkedAt => "practice loop marked; it comes back here when the PC reaches {}",
+        Msg::LoopMarkedAfter => "practice loop marked; it comes back here after {}s",
+        Msg::LoopMarkedOpen => "practice loop marked; Ctrl+B marks where it comes back from",
+        Msg::LoopEndMarked => "the practice loop comes back to its start after {}s",
+        Msg::Looped => "back to the start of the practice loop ({})",
+        Msg::LoopCleared => "practice loop cleared",
+        Msg::NoLoop => "no practice loop to end; Ctrl+A marks one",
+        Msg::RomSwitched => "ROM {} of {}: {}",
+        Msg::NoOtherRoms => "no other ROMs to switch to; --also adds them",
+        Msg::FrameDiverges => "frame {} diverges from the reference in {} pixels",
+        Msg::AudioPitch => "audio pitch: {} ({}Hz)",
+        Msg::SummaryWriteFailed => "failed to write session summary to {}: {}",
+        Msg::Controls => "controls: {} (F1 shows the keypad)",
+        Msg::BadHints => "ignoring input hints: {}",
+        Msg::CheatsOn => "cheats on: {}",
+        Msg::BadCheats => "ignoring cheats: {}",
+        Msg::BadSymbols => "ignoring symbols: {}",
+        Msg::BadScript => "error in the script {}: {}",
+        Msg::BadRomDb => "ignoring ROM database: {}",
+        Msg::LogFileFailed => "failed to open log file {}: {}",
+        Msg::Paused => "paused (F8 resume, F10 step over, F11 step, B breakpoint, , and . history, G controller layout)",
+        Msg::Resumed => "resumed",
+        Msg::BreakpointHit => "stopped at {}",
+        Msg::HistoryEntry => "{} back: {}",
+        Msg::PadProfile => "controller profile: {}",
+        Msg::WatchpointHit => "watchpoint: {}",
+        Msg::BreakpointSet => "breakpoint set at {}",
+        Msg::BreakpointCleared => "breakpoint cleared at {}",
+        Msg::BreakpointPrompt => "breakpoint at: {}_",
+        Msg::BadAddress => "not an address: {}",
+        Msg::CrashLoop => "paused: the ROM keeps hitting {} at {} ({} times in the last second). \
+            It was probably written for another platform, or expects different quirks",
+        Msg::SwitchPreset => "press F3 to restart with the {} preset",
+        Msg::PresetSwitched => "restarted with the {} preset",
+        Msg::ControllerConnected => "controller connected: {}",
+        Msg::ControllerDisconnected => "controller disconnected: {}",
+        Msg::ControllerFailed => "failed to open controller {}: {}",
+        Msg::ControllersNone => "controllers: none ({})",
+        Msg::MenuEmpty => "no ROMs to pick from in {}",
+        Msg::MenuFull => "the menu only has room for the first {} ROMs",
+        Msg::MenuNeedsWindow => "the ROM menu needs a window and keyboard",
+        Msg::CompareNeedsWindow => "--compare needs a window and keyboard",
+        Msg::MachinesDiverged => "{} and {} diverged, paused (Enter runs on): {}",
+        Msg::Reset => "reset",
+        Msg::SoftReset => "soft reset: restarted at 0x200, memory kept",
+        Msg::RomReloaded => "{} changed, reloaded",
+        Msg::RomReloadFailed => "{} changed but failed to load, keeping the old ROM: {}",
+        Msg::ReloadOn => "reloading the ROM when it changes",
+        Msg::ReloadOff => "no longer reloading the ROM when it changes",
+        Msg::ReportWritten => "wrote session report to {}",
+        Msg::ReportWriteFailed => "failed to write session report to {}: {}",
+        Msg::FullscreenFailed => "couldn't toggle fullscreen: {}",
+        Msg::ScreenshotCopied => "copied a screenshot to the clipboard",
+        Msg::StateCopied => "copied the machine state to the clipboard",
+        Msg::StatePasted => "loaded the state from the clipboard",
+        Msg::PastedStateInvalid => "couldn't load the state from the clipboard: {}",
+        Msg::ClipboardNoState => "the clipboard doesn't hold a copied state",
+        Msg::ClipboardFailed => "clipboard unavailable: {}",
+        Msg::AnnounceConnectFailed => "couldn't connect to {} for announcements: {}",
+        Msg::AnnounceFailed => "announcements stopped: {}",
+        Msg::ScreenshotSaved => "saved screenshot to {}",
+        Msg::ScreenshotFailed => "failed to save screenshot to {}: {}",
+        Msg::CrashReportWritten => "wrote a crash report to {}; attach it when reporting the fault",
+        Msg::CrashReportFailed => "failed to write a crash report to {}: {}",
+        Msg::RecordingStarted => "recording to {}",
+        Msg::RecordingSaved => "saved recording to {}",
+        Msg::AudioRecordingNeedsDevice => "--record-audio needs an audio device, so no sound is recorded",
+        Msg::RecordingFailed => "recording to {} failed: {}",
+        Msg::RomFileNeeded => "{} needs a ROM file, not a directory or an archive of several",
+        Msg::FrontendMissing => "this build has no {} frontend; it comes with the `{}` cargo feature",
+        Msg::RunningDemo => "no ROM given, so here's the built-in demo; pass a ROM file, or a directory to pick one from",
+        Msg::RunningVariant => "running as {}, where the instructions it didn't have are invalid",
+        Msg::HeadlessRan => "ran {} frames",
+        Msg::HeadlessHalted => "halted after {} frames",
+        Msg::HeadlessBreakpoint => "reached the breakpoint at {} after {} frames",
+        Msg::TuiNeedsTerminal => "the terminal frontend needs a terminal and stty: {}",
+        Msg::RomLoadFailed => "failed to load the ROM: {}",
+        Msg::FileReadFailed => "failed to read {}: {}",
+        Msg::FileCreateFailed => "failed to create {}: {}",
+        Msg::StartFailed => "failed to start {}: {}",
+        Msg::KeymapInvalid => "bad keymap {}: {}",
+        Msg::ConfigInvalid => "bad config {}: {}",
+        Msg::ConfigReloaded => "applied {}",
+        Msg::QuirksOnReset => "the new quirks apply on the next reset",
+        Msg::JournalInvalid => "bad frame journal {}: {}",
+        Msg::RenderFailed => "rendering failed: {}",
+        Msg::RomLoaded => "loaded {} ({})",
+        Msg::RomIdentified => "checksums: {}",
+        Msg::KnownRom => "known ROM: {}",
+        Msg::RomWarning => "warning: {}",
+        Msg::RomNeedsPreset => "warning: {}; try --quirks {}",
+        Msg::ReplayInvalid => "{} is not an input recording: {}",
+        Msg::ReplayFinished => "replay finished after {} frames, back to the keyboard",
+        Msg::ServeFailed => "failed to listen on {}: {}",
+        Msg::Serving => "serving on {}; play with `chip_8_rs connect {}`",
+        Msg::ClientConnected => "{} connected",
+        Msg::ClientDisconnected => "{} disconnected",
+        Msg::ConnectFailed => "failed to connect to {}: {}",
+        Msg::ConnectionLost => "lost the connection to the server: {}",
+        Msg::GdbListening => "waiting for GDB on {}",
+        Msg::GdbAttached => "GDB attached from {}",
+        Msg::GdbDetached => "GDB detached",
+        Msg::NetplayWaiting => "waiting for the other player on {}",
+        Msg::NetplayConnected => "playing with {}",
+        Msg::NetplayFailed => "couldn't play with {}: {}",
+        Msg::NetplayLost => "lost the other player: {}",
+        Msg::NetplayDesync => "the two machines differ from frame {} on",
+        Msg::NetplayPaused => "the other player paused, waiting for them",
+        Msg::NetplayRefused => "not while playing over the network, it would put the two machines out of step",
+        Msg::ProfileWritten => "wrote profile to {}",
+        Msg::ProfileWriteFailed => "failed to write profile to {}: {}",
+        Msg::CoverageMapWritten => "wrote coverage map to {}",
+        Msg::CoverageMapWriteFailed => "failed to write coverage map to {}: {}",
+        Msg::FaultExit => "exiting: {}",
+        Msg::InstructionRate => "instructions now run at {}x the --ips rate",
+        Msg::RplReadFailed => "failed to read the saved RPL flags: {}",
+        Msg::RplSaveFailed => "failed to save the RPL flags: {}",
+        Msg::RecentSaveFailed => "failed to save the recently played ROMs: {}",
+    }
+}
+
+fn de(msg: Msg) -> &'static str {
+    match msg {
+        Msg::SdlFailed => "Video: kein Fenster, Audio: keins (SDL konnte nicht initialisiert werden: {})",
+        Msg::InputNone => "Eingabe: keine ({})",
+        Msg::VideoHeadless => "Video: kein Fenster ({})",
+        Msg::VideoAccelerated => "Video: beschleunigt",
+        Msg::AcceleratedUnavailable => "beschleunigtes Video nicht verfügbar ({}), versuche Software-Rendering",
+        Msg::VideoSoftware => "Video: Software",
+        Msg::AudioRate => "Audio: {}Hz, Puffer mit {} Samples",
+        Msg::AudioNone => "Audio: keins ({})",
+        Msg::AudioDeviceFailed => "Audiogerät {} nicht verfügbar ({}), das Standardgerät wird verwendet",
+        Msg::AudioDevices => "Audioausgabegeräte:",
+        Msg::AudioDevicesNone => "keine Audioausgabegeräte gefunden",
+        Msg::PokeOutsideProgram => "Poke außerhalb des Programmbereichs ignoriert: {}",
+        Msg::NoDataDir => "kein Datenverzeichnis gefunden, Punktestände und Spielstände gehen beim Beenden verloren",
+        Msg::HighScore => "Highscore: {}",
+        Msg::NewHighScore => "neuer Highscore: {}",
+        Msg::PlayedBefore => "bisher {} gespielt",
+        Msg::PlayTimeSaveFailed => "Spielzeit konnte nicht gespeichert werden: {}",
+        Msg::ScoresSaveFailed => "Highscores konnten nicht gespeichert werden: {}",
+        Msg::RomSaved => "ROM gespeichert unter {}",
+        Msg::RomSaveFailed => "ROM konnte nicht unter {} gespeichert werden: {}",
+        Msg::StateSaved => "Spielstand in Slot {} gespeichert",
+        Msg::StateSaveFailed => "Spielstand konnte nicht in Slot {} gespeichert werden: {}",
+        Msg::StateLoaded => "Spielstand aus Slot {} geladen",
+        Msg::StateLoadFailed => "Spielstand aus Slot {} konnte nicht geladen werden: {}",
+        Msg::SlotEmpty => "Slot {} ist leer",
+        Msg::SlotReadFailed => "Slot {} konnte nicht gelesen werden: {}",
+        Msg::SlotSelected => "Speicherslot {}",
+        Msg::Autosaved => "Spielstand fürs nächste Mal gespeichert",
+        Msg::AutosaveFailed => "automatisches Speichern fehlgeschlagen: {}",
+        Msg::AutosaveLoaded => "beim zuletzt gespeicherten Spielstand weitergemacht",
+        Msg::AutosaveLoadFailed => "automatisch gespeicherter Spielstand konnte nicht geladen werden: {}",
+        Msg::LoopMarkedAt => "Übungsschleife gesetzt; sie kehrt hierher zurück, wenn der PC {} erreicht",
+        Msg::LoopMarkedAfter => "Übungsschleife gesetzt; sie kehrt nach {}s hierher zurück",
+        Msg::LoopMarkedOpen => "Übungsschleife gesetzt; Strg+B legt fest, wo sie zurückkehrt",
+        Msg::LoopEndMarked => "die Übungsschleife kehrt nach {}s an ihren Anfang zurück",
+        Msg::Looped => "zurück am Anfang der Übungsschleife ({})",
+        Msg::LoopCleared => "Übungsschleife entfernt",
+        Msg::NoLoop => "keine Übungsschleife zum Beenden; Strg+A setzt eine",
+        Msg::RomSwitched => "ROM {} von {}: {}",
+        Msg::NoOtherRoms => "keine anderen ROMs zum Wechseln; --also fügt welche hinzu",
+        Msg::FrameDiverges => "Frame {} weicht in {} Pixeln von der Referenz ab",
+        Msg::AudioPitch => "Tonhöhe: {} ({}Hz)",
+        Msg::SummaryWriteFailed => "Sitzungsübersicht konnte nicht nach {} geschrieben werden: {}",
+        Msg::Controls => "Steuerung: {} (F1 zeigt das Tastenfeld)",
+        Msg::BadHints => "Eingabehinweise ignoriert: {}",
+        Msg::CheatsOn => "Cheats aktiv: {}",
+        Msg::BadCheats => "Cheats ignoriert: {}",
+        Msg::BadSymbols => "Symbole ignoriert: {}",
+        Msg::BadScript => "Fehler im Skript {}: {}",
+        Msg::BadRomDb => "ROM-Datenbank ignoriert: {}",
+        Msg::LogFileFailed => "Logdatei {} konnte nicht geöffnet werden: {}",
+        Msg::Paused => "angehalten (F8 fortsetzen, F10 Prozedurschritt, F11 Einzelschritt, B Haltepunkt, , und . Verlauf, G Controller-Belegung)",
+        Msg::Resumed => "fortgesetzt",
+        Msg::BreakpointHit => "angehalten bei {}",
+        Msg::HistoryEntry => "{} zurück: {}",
+        Msg::PadProfile => "Controller-Profil: {}",
+        Msg::WatchpointHit => "Watchpoint: {}",
+        Msg::BreakpointSet => "Haltepunkt gesetzt bei {}",
+        Msg::BreakpointCleared => "Haltepunkt entfernt bei {}",
+        Msg::BreakpointPrompt => "Haltepunkt bei: {}_",
+        Msg::BadAddress => "keine Adresse: {}",
+        Msg::CrashLoop => "angehalten: das ROM löst wiederholt {} bei {} aus ({}-mal in der letzten Sekunde). \
+            Vermutlich wurde es für eine andere Plattform geschrieben oder erwartet andere Quirks",
+        Msg::SwitchPreset => "F3 startet neu mit dem Preset {}",
+        Msg::PresetSwitched => "neu gestartet mit dem Preset {}",
+        Msg::ControllerConnected => "Controller verbunden: {}",
+        Msg::ControllerDisconnected => "Controller getrennt: {}",
+        Msg::ControllerFailed => "Controller {} konnte nicht geöffnet werden: {}",
+        Msg::ControllersNone => "Controller: keine ({})",
+        Msg::MenuEmpty => "keine ROMs zur Auswahl in {}",
+        Msg::MenuFull => "das Menü hat nur Platz für die ersten {} ROMs",
+        Msg::MenuNeedsWindow => "das ROM-Menü braucht ein Fenster und eine Tastatur",
+        Msg::CompareNeedsWindow => "--compare braucht ein Fenster und eine Tastatur",
+        Msg::MachinesDiverged => "{} und {} laufen auseinander, angehalten (Enter läuft weiter): {}",
+        Msg::Reset => "zurückgesetzt",
+        Msg::SoftReset => "Soft-Reset: Neustart bei 0x200, Speicher behalten",
+        Msg::RomReloaded => "{} geändert, neu geladen",
+        Msg::RomReloadFailed => "{} geändert, aber nicht ladbar, das alte ROM bleibt: {}",
+        Msg::ReloadOn => "ROM wird bei Änderungen neu geladen",
+        Msg::ReloadOff => "ROM wird bei Änderungen nicht mehr neu geladen",
+        Msg::ReportWritten => "Sitzungsbericht nach {} geschrieben",
+        Msg::ReportWriteFailed => "Sitzungsbericht konnte nicht nach {} geschrieben werden: {}",
+        Msg::FullscreenFailed => "Vollbild konnte nicht umgeschaltet werden: {}",
+        Msg::ScreenshotCopied => "Bildschirmfoto in die Zwischenablage kopiert",
+        Msg::StateCopied => "Maschinenzustand in die Zwischenablage kopiert",
+        Msg::StatePasted => "Zustand aus der Zwischenablage geladen",
+        Msg::PastedStateInvalid => "Zustand aus der Zwischenablage konnte nicht geladen werden: {}",
+        Msg::ClipboardNoState => "die Zwischenablage enthält keinen kopierten Zustand",
+        Msg::ClipboardFailed => "Zwischenablage nicht verfügbar: {}",
+        Msg::AnnounceConnectFailed => "keine Verbindung zu {} für Ansagen: {}",
+        Msg::AnnounceFailed => "Ansagen beendet: {}",
+        Msg::ScreenshotSaved => "Bildschirmfoto nach {} gespeichert",
+        Msg::ScreenshotFailed => "Bildschirmfoto konnte nicht nach {} gespeichert werden: {}",
+        Msg::CrashReportWritten => "Absturzbericht nach {} geschrieben; bitte beim Melden des Fehlers anhängen",
+        Msg::CrashReportFailed => "Absturzbericht konnte nicht nach {} geschrieben werden: {}",
+        Msg::RecordingStarted => "Aufnahme nach {}",
+        Msg::RecordingSaved => "Aufnahme nach {} gespeichert",
+        Msg::AudioRecordingNeedsDevice => "--record-audio braucht ein Audiogerät, also wird kein Ton aufgenommen",
+        Msg::RecordingFailed => "Aufnahme nach {} fehlgeschlagen: {}",
+        Msg::RomFileNeeded => "{} braucht eine ROM-Datei, kein Verzeichnis oder Archiv mit mehreren",
+        Msg::FrontendMissing => "dieser Build hat kein {}-Frontend; es kommt mit dem Cargo-Feature `{}`",
+        Msg::RunningDemo => "keine ROM angegeben, daher die eingebaute Demo; gib eine ROM-Datei an oder ein Verzeichnis zur Auswahl",
+        Msg::RunningVariant => "läuft als {}, Befehle, die es dort nicht gab, sind ungültig",
+        Msg::HeadlessRan => "{} Frames ausgeführt",
+        Msg::HeadlessHalted => "nach {} Frames angehalten",
+        Msg::HeadlessBreakpoint => "Haltepunkt bei {} nach {} Frames erreicht",
+        Msg::TuiNeedsTerminal => "die Terminal-Oberfläche braucht ein Terminal und stty: {}",
+        Msg::RomLoadFailed => "ROM konnte nicht geladen werden: {}",
+        Msg::FileReadFailed => "{} konnte nicht gelesen werden: {}",
+        Msg::FileCreateFailed => "{} konnte nicht angelegt werden: {}",
+        Msg::StartFailed => "{} konnte nicht gestartet werden: {}",
+        Msg::KeymapInvalid => "fehlerhafte Tastenbelegung {}: {}",
+        Msg::ConfigInvalid => "fehlerhafte Einstellungen {}: {}",
+        Msg::ConfigReloaded => "{} übernommen",
+        Msg::QuirksOnReset => "die neuen Quirks gelten ab dem nächsten Reset",
+        Msg::JournalInvalid => "fehlerhaftes Frame-Journal {}: {}",
+        Msg::RenderFailed => "Darstellung fehlgeschlagen: {}",
+        Msg::RomLoaded => "{} geladen ({})",
+        Msg::RomIdentified => "Prüfsummen: {}",
+        Msg::KnownRom => "bekanntes ROM: {}",
+        Msg::RomWarning => "Warnung: {}",
+        Msg::RomNeedsPreset => "Warnung: {}; versuche --quirks {}",
+        Msg::ReplayInvalid => "{} ist keine Eingabeaufnahme: {}",
+        Msg::ReplayFinished => "Wiedergabe nach {} Frames beendet, zurück zur Tastatur",
+        Msg::ServeFailed => "Lauschen auf {} fehlgeschlagen: {}",
+        Msg::Serving => "Server läuft auf {}; spielen mit `chip_8_rs connect {}`",
+        Msg::ClientConnected => "{} verbunden",
+        Msg::ClientDisconnected => "{} getrennt",
+        Msg::ConnectFailed => "Verbindung zu {} fehlgeschlagen: {}",
+        Msg::ConnectionLost => "Verbindung zum Server verloren: {}",
+        Msg::GdbListening => "warte auf GDB an {}",
+        Msg::GdbAttached => "GDB verbunden von {}",
+        Msg::GdbDetached => "GDB getrennt",
+        Msg::NetplayWaiting => "warte auf den anderen Spieler an {}",
+        Msg::NetplayConnected => "spiele mit {}",
+        Msg::NetplayFailed => "Spiel mit {} nicht möglich: {}",
+        Msg::NetplayLost => "Verbindung zum anderen Spieler verloren: {}",
+        Msg::NetplayDesync => "die beiden Maschinen weichen ab Frame {} voneinander ab",
+        Msg::NetplayPaused => "der andere Spieler hat pausiert, warte auf ihn",
+        Msg::NetplayRefused => "nicht während einer Netzwerkpartie, die beiden Maschinen liefen sonst auseinander",
+        Msg::ProfileWritten => "Profil nach {} geschrieben",
+        Msg::ProfileWriteFailed => "Profil konnte nicht nach {} geschrieben werden: {}",
+        Msg::CoverageMapWritten => "Abdeckungskarte nach {} geschrieben",
+        Msg::CoverageMapWriteFailed => "Abdeckungskarte konnte nicht nach {} geschrieben werden: {}",
+        Msg::FaultExit => "beendet: {}",
+        Msg::InstructionRate => "Befehle laufen jetzt mit dem {}-fachen der --ips-Rate",
+        Msg::RplReadFailed => "gespeicherte RPL-Flags konnten nicht gelesen werden: {}",
+        Msg::RplSaveFailed => "RPL-Flags konnten nicht gespeichert werden: {}",
+        Msg::RecentSaveFailed => "Zuletzt gespielte ROMs konnten nicht gespeichert werden: {}",
+    }
+}
+
+fn es(msg: Msg) -> &'static str {
+    match msg {
+        Msg::SdlFailed => "vídeo: sin ventana, audio: ninguno (no se pudo inicializar SDL: {})",
+        Msg::InputNone => "entrada: ninguna ({})",
+        Msg::VideoHeadless => "vídeo: sin ventana ({})",
+        Msg::VideoAccelerated => "vídeo: acelerado",
+        Msg::AcceleratedUnavailable => "vídeo acelerado no disponible ({}), probando renderizado por software",
+        Msg::VideoSoftware => "vídeo: software",
+        Msg::AudioRate => "audio: {}Hz, búfer de {} muestras",
+        Msg::AudioNone => "audio: ninguno ({})",
+        Msg::AudioDeviceFailed => "el dispositivo de audio {} no está disponible ({}), se usa el predeterminado",
+        Msg::AudioDevices => "dispositivos de salida de audio:",
+        Msg::AudioDevicesNone => "no se encontraron dispositivos de salida de audio",
+        Msg::PokeOutsideProgram => "se ignora un poke fuera del área del programa: {}",
+        Msg::NoDataDir => "no se encontró un directorio de datos, las puntuaciones y partidas no se conservarán al salir",
+        Msg::HighScore => "récord: {}",
+        Msg::NewHighScore => "nuevo récord: {}",
+        Msg::PlayedBefore => "jugado antes durante {}",
+        Msg::PlayTimeSaveFailed => "no se pudo guardar el tiempo de juego: {}",
+        Msg::ScoresSaveFailed => "no se pudieron guardar los récords: {}",
+        Msg::RomSaved => "ROM guardada en {}",
+        Msg::RomSaveFailed => "no se pudo guardar la ROM en {}: {}",
+        Msg::StateSaved => "partida guardada en la ranura {}",
+        Msg::StateSaveFailed => "no se pudo guardar la partida en la ranura {}: {}",
+        Msg::StateLoaded => "partida cargada de la ranura {}",
+        Msg::StateLoadFailed => "no se pudo cargar la partida de la ranura {}: {}",
+        Msg::SlotEmpty => "la ranura {} está vacía",
+        Msg::SlotReadFailed => "no se pudo leer la ranura {}: {}",
+        Msg::SlotSelected => "ranura de guardado {}",
+        Msg::Autosaved => "partida guardada para continuar la próxima vez",
+        Msg::AutosaveFailed => "no se pudo guardar la partida automáticamente: {}",
+        Msg::AutosaveLoaded => "se continúa la partida guardada la última vez",
+        Msg::AutosaveLoadFailed => "no se pudo continuar la partida guardada automáticamente: {}",
+        Msg::LoopMarkedAt => "bucle de práctica marcado; vuelve aquí cuando el PC llega a {}",
+        Msg::LoopMarkedAfter => "bucle de práctica marcado; vuelve aquí después de {}s",
+        Msg::LoopMarkedOpen => "bucle de práctica marcado; Ctrl+B marca desde dónde vuelve",
+        Msg::LoopEndMarked => "el bucle de práctica vuelve a su inicio después de {}s",
+        Msg::Looped => "de vuelta al inicio del bucle de práctica ({})",
+        Msg::LoopCleared => "bucle de práctica quitado",
+        Msg::NoLoop => "no hay bucle de práctica que terminar; Ctrl+A marca uno",
+        Msg::RomSwitched => "ROM {} de {}: {}",
+        Msg::NoOtherRoms => "no hay otras ROMs a las que cambiar; --also las añade",
+        Msg::FrameDiverges => "el fotograma {} difiere de la referencia en {} píxeles",
+        Msg::AudioPitch => "tono: {} ({}Hz)",
+        Msg::SummaryWriteFailed => "no se pudo escribir el resumen de la sesión en {}: {}",
+        Msg::Controls => "controles: {} (F1 muestra el teclado)",
+        Msg::BadHints => "se ignoran las indicaciones de controles: {}",
+        Msg::CheatsOn => "trucos activos: {}",
+        Msg::BadCheats => "se ignoran los trucos: {}",
+        Msg::BadSymbols => "se ignoran los símbolos: {}",
+        Msg::BadScript => "error en el script {}: {}",
+        Msg::BadRomDb => "se ignora la base de datos de ROMs: {}",
+        Msg::LogFileFailed => "no se pudo abrir el archivo de registro {}: {}",
+        Msg::Paused => "en pausa (F8 continuar, F10 paso por encima, F11 paso a paso, B punto de interrupción, , y . historial, G disposición del mando)",
+        Msg::Resumed => "reanudado",
+        Msg::BreakpointHit => "detenido en {}",
+        Msg::HistoryEntry => "{} atrás: {}",
+        Msg::PadProfile => "perfil del mando: {}",
+        Msg::WatchpointHit => "punto de observación: {}",
+        Msg::BreakpointSet => "punto de interrupción en {}",
+        Msg::BreakpointCleared => "punto de interrupción eliminado en {}",
+        Msg::BreakpointPrompt => "punto de interrupción en: {}_",
+        Msg::BadAddress => "dirección no válida: {}",
+        Msg::CrashLoop => "en pausa: la ROM provoca una y otra vez {} en {} ({} veces en el último segundo). \
+            Probablemente se escribió para otra plataforma o espera otros quirks",
+        Msg::SwitchPreset => "pulsa F3 para reiniciar con el preset {}",
+        Msg::PresetSwitched => "reiniciado con el preset {}",
+        Msg::ControllerConnected => "mando conectado: {}",
+        Msg::ControllerDisconnected => "mando desconectado: {}",
+        Msg::ControllerFailed => "no se pudo abrir el mando {}: {}",
+        Msg::ControllersNone => "mandos: ninguno ({})",
+        Msg::MenuEmpty => "no hay ROMs para elegir en {}",
+        Msg::MenuFull => "el menú solo tiene sitio para las primeras {} ROMs",
+        Msg::MenuNeedsWindow => "el menú de ROMs necesita una ventana y un teclado",
+        Msg::CompareNeedsWindow => "--compare necesita una ventana y un teclado",
+        Msg::MachinesDiverged => "{} y {} divergen, en pausa (Intro continúa): {}",
+        Msg::Reset => "reiniciado",
+        Msg::SoftReset => "reinicio suave: reiniciado en 0x200, memoria conservada",
+        Msg::RomReloaded => "{} cambió, recargada",
+        Msg::RomReloadFailed => "{} cambió pero no se pudo cargar, se mantiene la ROM anterior: {}",
+        Msg::ReloadOn => "se recargará la ROM cuando cambie",
+        Msg::ReloadOff => "ya no se recarga la ROM cuando cambia",
+        Msg::ReportWritten => "informe de la sesión escrito en {}",
+        Msg::ReportWriteFailed => "no se pudo escribir el informe de la sesión en {}: {}",
+        Msg::FullscreenFailed => "no se pudo cambiar a pantalla completa: {}",
+        Msg::ScreenshotCopied => "captura de pantalla copiada al portapapeles",
+        Msg::StateCopied => "estado de la máquina copiado al portapapeles",
+        Msg::StatePasted => "estado cargado desde el portapapeles",
+        Msg::PastedStateInvalid => "no se pudo cargar el estado del portapapeles: {}",
+        Msg::ClipboardNoState => "el portapapeles no contiene un estado copiado",
+        Msg::ClipboardFailed => "portapapeles no disponible: {}",
+        Msg::AnnounceConnectFailed => "no se pudo conectar a {} para los anuncios: {}",
+        Msg::AnnounceFailed => "anuncios detenidos: {}",
+        Msg::ScreenshotSaved => "captura de pantalla guardada en {}",
+        Msg::ScreenshotFailed => "no se pudo guardar la captura de pantalla en {}: {}",
+        Msg::CrashReportWritten => "informe de fallo escrito en {}; adjúntalo al informar del fallo",
+        Msg::CrashReportFailed => "no se pudo escribir el informe de fallo en {}: {}",
+        Msg::RecordingStarted => "grabando en {}",
+        Msg::RecordingSaved => "grabación guardada en {}",
+        Msg::AudioRecordingNeedsDevice => "--record-audio necesita un dispositivo de audio, así que no se graba sonido",
+        Msg::RecordingFailed => "falló la grabación en {}: {}",
+        Msg::RomFileNeeded => "{} necesita un archivo ROM, no un directorio ni un archivo comprimido con varios",
+        Msg::FrontendMissing => "esta compilación no tiene el frontend {}; viene con la característica de cargo `{}`",
+        Msg::RunningDemo => "no se indicó ninguna ROM, así que va la demo integrada; pasa un archivo ROM, o un directorio para elegir una",
+        Msg::RunningVariant => "se ejecuta como {}, donde las instrucciones que no tenía son inválidas",
+        Msg::HeadlessRan => "{} fotogramas ejecutados",
+        Msg::HeadlessHalted => "detenido tras {} fotogramas",
+        Msg::HeadlessBreakpoint => "punto de interrupción en {} alcanzado tras {} fotogramas",
+        Msg::TuiNeedsTerminal => "la interfaz de terminal necesita una terminal y stty: {}",
+        Msg::RomLoadFailed => "no se pudo cargar la ROM: {}",
+        Msg::FileReadFailed => "no se pudo leer {}: {}",
+        Msg::FileCreateFailed => "no se pudo crear {}: {}",
+        Msg::StartFailed => "no se pudo iniciar {}: {}",
+        Msg::KeymapInvalid => "asignación de teclas no válida {}: {}",
+        Msg::ConfigInvalid => "configuración no válida {}: {}",
+        Msg::ConfigReloaded => "aplicado {}",
+        Msg::QuirksOnReset => "los nuevos quirks se aplican en el próximo reinicio",
+        Msg::JournalInvalid => "registro de fotogramas no válido {}: {}",
+        Msg::RenderFailed => "falló el dibujado: {}",
+        Msg::RomLoaded => "{} cargada ({})",
+        Msg::RomIdentified => "sumas de verificación: {}",
+        Msg::KnownRom => "ROM conocida: {}",
+        Msg::RomWarning => "aviso: {}",
+        Msg::RomNeedsPreset => "aviso: {}; prueba --quirks {}",
+        Msg::ReplayInvalid => "{} no es una grabación de entrada: {}",
+        Msg::ReplayFinished => "reproducción terminada tras {} fotogramas, de vuelta al teclado",
+        Msg::ServeFailed => "no se pudo escuchar en {}: {}",
+        Msg::Serving => "sirviendo en {}; juega con `chip_8_rs connect {}`",
+        Msg::ClientConnected => "{} conectado",
+        Msg::ClientDisconnected => "{} desconectado",
+        Msg::ConnectFailed => "no se pudo conectar a {}: {}",
+        Msg::ConnectionLost => "se perdió la conexión con el servidor: {}",
+        Msg::GdbListening => "esperando a GDB en {}",
+        Msg::GdbAttached => "GDB conectado desde {}",
+        Msg::GdbDetached => "GDB desconectado",
+        Msg::NetplayWaiting => "esperando al otro jugador en {}",
+        Msg::NetplayConnected => "jugando con {}",
+        Msg::NetplayFailed => "no se pudo jugar con {}: {}",
+        Msg::NetplayLost => "se perdió al otro jugador: {}",
+        Msg::NetplayDesync => "las dos máquinas difieren desde el fotograma {}",
+        Msg::NetplayPaused => "el otro jugador ha pausado, esperándolo",
+        Msg::NetplayRefused => "no durante una partida en red, las dos máquinas se desincronizarían",
+        Msg::ProfileWritten => "perfil escrito en {}",
+        Msg::ProfileWriteFailed => "no se pudo escribir el perfil en {}: {}",
+        Msg::CoverageMapWritten => "mapa de cobertura escrito en {}",
+        Msg::CoverageMapWriteFailed => "no se pudo escribir el mapa de cobertura en {}: {}",
+        Msg::FaultExit => "saliendo: {}",
+        Msg::InstructionRate => "las instrucciones van ahora a {}x la velocidad de --ips",
+        Msg::RplReadFailed => "no se pudieron leer las banderas RPL guardadas: {}",
+        Msg::RplSaveFailed => "no se pudieron guardar las banderas RPL: {}",
+        Msg::RecentSaveFailed => "no se pudieron guardar las ROMs jugadas recientemente: {}",
+    }
+}
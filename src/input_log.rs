@@ -0,0 +1,79 @@
+//! --replay and --record-input: the keypad played back from a recording instead of the
+//! keyboard, and the keypad written to one, a frame at a time. Both seed CXNN from the recording
+//! so the run repeats, which only holds when every frame runs the same number of instructions
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use chip_8_rs::logging::Level;
+use chip_8_rs::replay::{InputRecorder, Replay};
+use chip_8_rs::{event, Chip8};
+
+use crate::args::RunArgs;
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+
+pub struct InputLog {
+    replay: Option<Replay>,
+    recorder: Option<(InputRecorder<BufWriter<File>>, PathBuf)>,
+    frame: u64,
+    locale: Locale,
+}
+
+impl InputLog {
+    /// Reads the recording to replay or creates the one to record, seeding `chip_8` from it
+    pub fn start(cli: &RunArgs, chip_8: &mut Chip8, locale: Locale) -> Self {
+        let replay = cli.replay.as_ref().map(|path| {
+            let text = std::fs::read_to_string(path)
+                .unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+            let replay = Replay::parse(&text).unwrap_or_else(|e| fail(locale.format(Msg::ReplayInvalid, &[&path.display(), &e])));
+            chip_8.seed_rng(replay.seed as u64);
+            replay
+        });
+        let recorder = cli.record_input.as_ref().map(|path| {
+            let seed = cli.seed.unwrap_or_else(rand::random);
+            chip_8.seed_rng(seed as u64);
+            let recorder = File::create(path)
+                .and_then(|file| InputRecorder::new(BufWriter::new(file), seed))
+                .unwrap_or_else(|e| fail(locale.format(Msg::FileCreateFailed, &[&path.display(), &e])));
+            (recorder, path.clone())
+        });
+        Self { replay, recorder, frame: 0, locale }
+    }
+
+    /// Whether frames have to run one at a time for the recording to line up
+    pub fn active(&self) -> bool {
+        self.replay.is_some() || self.recorder.is_some()
+    }
+
+    /// Call before emulating each frame: holds the recorded keys while replaying, and records
+    /// the ones held while recording. At the end of a replay the keyboard takes over again
+    pub fn frame(&mut self, chip_8: &mut Chip8) {
+        if let Some(replay) = self.replay.as_mut() {
+            match replay.frame(self.frame) {
+                Some(keys) => chip_8.set_keys(keys),
+                None => {
+                    eprintln!("{}", self.locale.format(Msg::ReplayFinished, &[&self.frame]));
+                    self.replay = None;
+                }
+            }
+        }
+        if let Some((recorder, path)) = self.recorder.as_mut() {
+            if let Err(e) = recorder.frame(chip_8.keys()) {
+                event!(Level::Warn, "{}", self.locale.format(Msg::RecordingFailed, &[&path.display(), &e]));
+                self.recorder = None;
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Writes out the end of the recording
+    pub fn finish(self) {
+        if let Some((recorder, path)) = self.recorder {
+            if let Err(e) = recorder.finish() {
+                event!(Level::Warn, "{}", self.locale.format(Msg::RecordingFailed, &[&path.display(), &e]));
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Per-frame keypad history, for an input display that shows exactly which frame a key went down
+
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+pub struct InputHistory {
+    /// The keys held in each of the most recent frames, oldest first
+    frames: VecDeque<u16>,
+    capacity: usize,
+    /// Frames pushed since the start, including those that fell out of the history
+    total: u64,
+}
+
+impl InputHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self { frames: VecDeque::with_capacity(capacity), capacity, total: 0 }
+    }
+
+    /// Records the keys held during the next frame
+    pub fn push(&mut self, keys: u16) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        if self.capacity > 0 {
+            self.frames.push_back(keys);
+        }
+        self.total += 1;
+    }
+
+    /// The kept frames, oldest first, each with its frame number counting from 0
+    pub fn frames(&self) -> impl Iterator<Item = (u64, u16)> + '_ {
+        let first = self.total - self.frames.len() as u64;
+        self.frames.iter().enumerate().map(move |(n, &keys)| (first + n as u64, keys))
+    }
+
+    /// The keys held in the latest frame
+    pub fn current(&self) -> u16 {
+        self.frames.back().copied().unwrap_or(0)
+    }
+}
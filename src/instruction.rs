@@ -0,0 +1,490 @@
+//! Instructions decoded from their opcodes, once, for everything that needs to know what an
+//! opcode is: the CPU executing it, and the disassembler, coverage, VIP timing, the debugger's
+//! register watchpoints and the variants' instruction sets going by its metadata.
+//!
+//! Decoding takes every platform's instructions at once, as opcodes the platforms gave
+//! different meanings are rare: MegaChip's and the machine code routines of the 0NNN space are
+//! told apart when executing, and CHIP-8X's few take its quirk, see decode_with()
+
+use std::fmt;
+
+use crate::cycles::FETCH;
+use crate::display::PLANES;
+use crate::quirks::{Preset, Quirks};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    Clear,
+    /// 00EE
+    Return,
+    /// 00CN
+    ScrollDown(u8),
+    /// XO-CHIP's 00DN
+    ScrollUp(u8),
+    /// MegaChip's 00BN
+    MegaScrollUp(u8),
+    /// MegaChip's 0010
+    MegaOff,
+    /// MegaChip's 0011
+    MegaOn,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    LowRes,
+    /// 00FF
+    HighRes,
+    /// 0NNN, a machine code routine
+    Sys(u16),
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipEqualByte { x: usize, nn: u8 },
+    /// 4XNN
+    SkipNotEqualByte { x: usize, nn: u8 },
+    /// 5XY0
+    SkipEqual { x: usize, y: usize },
+    /// XO-CHIP's 5XY2, Vx to Vy into memory at I
+    SaveRange { x: usize, y: usize },
+    /// XO-CHIP's 5XY3, Vx to Vy from memory at I
+    LoadRange { x: usize, y: usize },
+    /// 6XNN
+    LoadByte { x: usize, nn: u8 },
+    /// 7XNN
+    AddByte { x: usize, nn: u8 },
+    /// 8XY0
+    Move { x: usize, y: usize },
+    /// 8XY1
+    Or { x: usize, y: usize },
+    /// 8XY2
+    And { x: usize, y: usize },
+    /// 8XY3
+    Xor { x: usize, y: usize },
+    /// 8XY4
+    Add { x: usize, y: usize },
+    /// 8XY5
+    Sub { x: usize, y: usize },
+    /// 8XY6
+    ShiftRight { x: usize, y: usize },
+    /// 8XY7
+    SubReverse { x: usize, y: usize },
+    /// 8XYE
+    ShiftLeft { x: usize, y: usize },
+    /// 9XY0
+    SkipNotEqual { x: usize, y: usize },
+    /// ANNN
+    LoadIndex(u16),
+    /// BNNN, or BXNN with the jump_vx quirk
+    JumpOffset { x: usize, nnn: u16 },
+    /// CXNN
+    Random { x: usize, nn: u8 },
+    /// DXYN
+    Draw { x: usize, y: usize, n: u8 },
+    /// EX9E
+    SkipKey { x: usize },
+    /// EXA1
+    SkipNotKey { x: usize },
+    /// XO-CHIP's F000 NNNN, the address being the next instruction word
+    LongLoadIndex,
+    /// FX07
+    LoadDelay { x: usize },
+    /// FX0A
+    WaitKey { x: usize },
+    /// FX15
+    SetDelay { x: usize },
+    /// FX18
+    SetSound { x: usize },
+    /// FX1E
+    AddIndex { x: usize },
+    /// FX29
+    Font { x: usize },
+    /// SUPER-CHIP's FX30
+    BigFont { x: usize },
+    /// XO-CHIP's FN01
+    Planes(u8),
+    /// XO-CHIP's F002
+    AudioPattern,
+    /// XO-CHIP's FX3A
+    Pitch { x: usize },
+    /// FX33
+    Bcd { x: usize },
+    /// FX55
+    Store { x: usize },
+    /// FX65
+    Load { x: usize },
+    /// SUPER-CHIP's FX75
+    SaveFlags { x: usize },
+    /// SUPER-CHIP's FX85
+    LoadFlags { x: usize },
+    /// CHIP-8X's 02A0, the next background color
+    Background,
+    /// CHIP-8X's 5XY1, adding the digits of Vx and Vy apart, each octal and wrapping
+    AddDigits { x: usize, y: usize },
+    /// CHIP-8X's BXYN, coloring the zones set out by Vx and Vx+1 in Vy's color
+    ZoneColor { x: usize, y: usize, n: u8 },
+    /// CHIP-8X's EXF2, EX9E on the second keypad
+    SkipKey2 { x: usize },
+    /// CHIP-8X's EXF5, EXA1 on the second keypad
+    SkipNotKey2 { x: usize },
+}
+
+impl Instruction {
+    /// The instruction `opcode` is, or None for opcodes no supported platform defines.
+    /// CHIP-8X's meanings are left out, as its opcodes mean other things elsewhere
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let x = (opcode >> 8) as usize & 0xF;
+        let y = (opcode >> 4) as usize & 0xF;
+        let n = opcode as u8 & 0xF;
+        let nn = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let instruction = match (opcode >> 12, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => Self::Clear,
+            (0x0, 0x0, 0xE, 0xE) => Self::Return,
+            (0x0, 0x0, 0xC, _) => Self::ScrollDown(n),
+            (0x0, 0x0, 0xB, _) => Self::MegaScrollUp(n),
+            (0x0, 0x0, 0xD, _) => Self::ScrollUp(n),
+            (0x0, 0x0, 0x1, 0x0) => Self::MegaOff,
+            (0x0, 0x0, 0x1, 0x1) => Self::MegaOn,
+            (0x0, 0x0, 0xF, 0xB) => Self::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Self::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Self::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Self::LowRes,
+            (0x0, 0x0, 0xF, 0xF) => Self::HighRes,
+            (0x0, ..) => Self::Sys(nnn),
+            (0x1, ..) => Self::Jump(nnn),
+            (0x2, ..) => Self::Call(nnn),
+            (0x3, ..) => Self::SkipEqualByte { x, nn },
+            (0x4, ..) => Self::SkipNotEqualByte { x, nn },
+            (0x5, _, _, 0x0) => Self::SkipEqual { x, y },
+            (0x5, _, _, 0x2) => Self::SaveRange { x, y },
+            (0x5, _, _, 0x3) => Self::LoadRange { x, y },
+            (0x6, ..) => Self::LoadByte { x, nn },
+            (0x7, ..) => Self::AddByte { x, nn },
+            (0x8, _, _, 0x0) => Self::Move { x, y },
+            (0x8, _, _, 0x1) => Self::Or { x, y },
+            (0x8, _, _, 0x2) => Self::And { x, y },
+            (0x8, _, _, 0x3) => Self::Xor { x, y },
+            (0x8, _, _, 0x4) => Self::Add { x, y },
+            (0x8, _, _, 0x5) => Self::Sub { x, y },
+            (0x8, _, _, 0x6) => Self::ShiftRight { x, y },
+            (0x8, _, _, 0x7) => Self::SubReverse { x, y },
+            (0x8, _, _, 0xE) => Self::ShiftLeft { x, y },
+            (0x9, _, _, 0x0) => Self::SkipNotEqual { x, y },
+            (0xA, ..) => Self::LoadIndex(nnn),
+            (0xB, ..) => Self::JumpOffset { x, nnn },
+            (0xC, ..) => Self::Random { x, nn },
+            (0xD, ..) => Self::Draw { x, y, n },
+            (0xE, _, 0x9, 0xE) => Self::SkipKey { x },
+            (0xE, _, 0xA, 0x1) => Self::SkipNotKey { x },
+            (0xF, 0x0, 0x0, 0x0) => Self::LongLoadIndex,
+            (0xF, _, 0x0, 0x7) => Self::LoadDelay { x },
+            (0xF, _, 0x0, 0xA) => Self::WaitKey { x },
+            (0xF, _, 0x1, 0x5) => Self::SetDelay { x },
+            (0xF, _, 0x1, 0x8) => Self::SetSound { x },
+            (0xF, _, 0x1, 0xE) => Self::AddIndex { x },
+            (0xF, _, 0x2, 0x9) => Self::Font { x },
+            (0xF, _, 0x3, 0x0) => Self::BigFont { x },
+            (0xF, _, 0x0, 0x1) if x < 1 << PLANES => Self::Planes(x as u8),
+            (0xF, 0x0, 0x0, 0x2) => Self::AudioPattern,
+            (0xF, _, 0x3, 0xA) => Self::Pitch { x },
+            (0xF, _, 0x3, 0x3) => Self::Bcd { x },
+            (0xF, _, 0x5, 0x5) => Self::Store { x },
+            (0xF, _, 0x6, 0x5) => Self::Load { x },
+            (0xF, _, 0x7, 0x5) => Self::SaveFlags { x },
+            (0xF, _, 0x8, 0x5) => Self::LoadFlags { x },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// The instruction `opcode` is on a machine with `quirks`, CHIP-8X's own where it has them
+    pub fn decode_with(opcode: u16, quirks: &Quirks) -> Option<Self> {
+        let (x, y, n) = ((opcode >> 8) as usize & 0xF, (opcode >> 4) as usize & 0xF, opcode as u8 & 0xF);
+        match opcode >> 12 {
+            _ if !quirks.chip_8x => Self::decode(opcode),
+            0x0 if opcode == 0x02A0 => Some(Self::Background),
+            0x5 if n == 1 => Some(Self::AddDigits { x, y }),
+            0xB => Some(Self::ZoneColor { x, y, n }),
+            0xE if opcode & 0xFF == 0xF2 => Some(Self::SkipKey2 { x }),
+            0xE if opcode & 0xFF == 0xF5 => Some(Self::SkipNotKey2 { x }),
+            _ => Self::decode(opcode),
+        }
+    }
+
+    /// The platform that introduced it, or None for plain CHIP-8's
+    pub fn platform(self) -> Option<Preset> {
+        match self {
+            Self::MegaOff | Self::MegaOn => Some(Preset::Megachip),
+            Self::ScrollUp(_) | Self::SaveRange { .. } | Self::LoadRange { .. } | Self::LongLoadIndex | Self::Planes(_) | Self::AudioPattern | Self::Pitch { .. } => {
+                Some(Preset::Xochip)
+            }
+            Self::ScrollDown(_) | Self::ScrollRight | Self::ScrollLeft | Self::Exit | Self::LowRes | Self::HighRes => Some(Preset::Schip),
+            Self::Draw { n: 0, .. } | Self::BigFont { .. } | Self::SaveFlags { .. } | Self::LoadFlags { .. } => Some(Preset::Schip),
+            Self::Background | Self::AddDigits { .. } | Self::ZoneColor { .. } | Self::SkipKey2 { .. } | Self::SkipNotKey2 { .. } => Some(Preset::Chip8x),
+            _ => None,
+        }
+    }
+
+    /// The form it takes in the usual hex notation, e.g. `8XY4` or `DXYN`, with the fixed
+    /// nibbles kept and the operands replaced by letters
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Self::Clear => "00E0",
+            Self::Return => "00EE",
+            Self::ScrollDown(_) => "00CN",
+            Self::ScrollUp(_) => "00DN",
+            Self::MegaScrollUp(_) => "00BN",
+            Self::MegaOff => "0010",
+            Self::MegaOn => "0011",
+            Self::ScrollRight => "00FB",
+            Self::ScrollLeft => "00FC",
+            Self::Exit => "00FD",
+            Self::LowRes => "00FE",
+            Self::HighRes => "00FF",
+            Self::Sys(_) => "0NNN",
+            Self::Background => "02A0",
+            Self::Jump(_) => "1NNN",
+            Self::Call(_) => "2NNN",
+            Self::SkipEqualByte { .. } => "3XNN",
+            Self::SkipNotEqualByte { .. } => "4XNN",
+            Self::SkipEqual { .. } => "5XY0",
+            Self::AddDigits { .. } => "5XY1",
+            Self::SaveRange { .. } => "5XY2",
+            Self::LoadRange { .. } => "5XY3",
+            Self::LoadByte { .. } => "6XNN",
+            Self::AddByte { .. } => "7XNN",
+            Self::Move { .. } => "8XY0",
+            Self::Or { .. } => "8XY1",
+            Self::And { .. } => "8XY2",
+            Self::Xor { .. } => "8XY3",
+            Self::Add { .. } => "8XY4",
+            Self::Sub { .. } => "8XY5",
+            Self::ShiftRight { .. } => "8XY6",
+            Self::SubReverse { .. } => "8XY7",
+            Self::ShiftLeft { .. } => "8XYE",
+            Self::SkipNotEqual { .. } => "9XY0",
+            Self::LoadIndex(_) => "ANNN",
+            Self::JumpOffset { .. } => "BNNN",
+            Self::ZoneColor { .. } => "BXYN",
+            Self::Random { .. } => "CXNN",
+            Self::Draw { .. } => "DXYN",
+            Self::SkipKey { .. } => "EX9E",
+            Self::SkipNotKey { .. } => "EXA1",
+            Self::SkipKey2 { .. } => "EXF2",
+            Self::SkipNotKey2 { .. } => "EXF5",
+            Self::LongLoadIndex => "F000",
+            Self::LoadDelay { .. } => "FX07",
+            Self::WaitKey { .. } => "FX0A",
+            Self::SetDelay { .. } => "FX15",
+            Self::SetSound { .. } => "FX18",
+            Self::AddIndex { .. } => "FX1E",
+            Self::Font { .. } => "FX29",
+            Self::BigFont { .. } => "FX30",
+            Self::Planes(_) => "FN01",
+            Self::AudioPattern => "F002",
+            Self::Pitch { .. } => "FX3A",
+            Self::Bcd { .. } => "FX33",
+            Self::Store { .. } => "FX55",
+            Self::Load { .. } => "FX65",
+            Self::SaveFlags { .. } => "FX75",
+            Self::LoadFlags { .. } => "FX85",
+        }
+    }
+
+    /// What it mostly exercises, for the benchmark's breakdown by subsystem
+    pub fn subsystem(self) -> &'static str {
+        match self {
+            Self::Draw { .. } => "draw",
+            Self::Return | Self::Jump(_) | Self::Call(_) | Self::JumpOffset { .. } => "control flow",
+            Self::SkipEqualByte { .. } | Self::SkipNotEqualByte { .. } | Self::SkipEqual { .. } | Self::SkipNotEqual { .. } => "control flow",
+            Self::Clear | Self::ScrollDown(_) | Self::ScrollUp(_) | Self::MegaScrollUp(_) | Self::MegaOff | Self::MegaOn => "display",
+            Self::ScrollRight | Self::ScrollLeft | Self::Exit | Self::LowRes | Self::HighRes | Self::Sys(_) => "display",
+            Self::Planes(_) | Self::Background | Self::ZoneColor { .. } => "display",
+            Self::LoadByte { .. } | Self::AddByte { .. } | Self::Move { .. } | Self::Or { .. } | Self::And { .. } | Self::Xor { .. } => "arithmetic",
+            Self::Add { .. } | Self::Sub { .. } | Self::ShiftRight { .. } | Self::SubReverse { .. } | Self::ShiftLeft { .. } => "arithmetic",
+            Self::Random { .. } | Self::AddDigits { .. } => "arithmetic",
+            Self::SkipKey { .. } | Self::SkipNotKey { .. } | Self::SkipKey2 { .. } | Self::SkipNotKey2 { .. } | Self::WaitKey { .. } => "keypad",
+            Self::LoadDelay { .. } | Self::SetDelay { .. } | Self::SetSound { .. } | Self::AudioPattern | Self::Pitch { .. } => "timers and sound",
+            Self::LoadIndex(_) | Self::LongLoadIndex | Self::AddIndex { .. } | Self::Font { .. } | Self::BigFont { .. } | Self::Bcd { .. } => "memory",
+            Self::Store { .. } | Self::Load { .. } | Self::SaveRange { .. } | Self::LoadRange { .. } | Self::SaveFlags { .. } | Self::LoadFlags { .. } => "memory",
+        }
+    }
+
+    /// The address it jumps to, calls or loads into I, for naming it
+    pub fn address(self) -> Option<u16> {
+        match self {
+            Self::Sys(nnn) | Self::Jump(nnn) | Self::Call(nnn) | Self::LoadIndex(nnn) | Self::JumpOffset { nnn, .. } => Some(nnn),
+            _ => None,
+        }
+    }
+
+    /// Machine cycles it took on the COSMAC VIP, see the cycles module, with `v` the registers
+    /// before it ran and `skipped` whether it skipped the next instruction. Instructions the
+    /// VIP didn't have count as its nearest
+    pub fn vip_cycles(self, v: &[u8; 16], skipped: bool) -> u32 {
+        let skip = if skipped { 4 } else { 0 };
+        FETCH + match self {
+            Self::Clear => 3078,
+            Self::Return => 10,
+            Self::Jump(_) | Self::Move { .. } | Self::LoadIndex(_) => 12,
+            Self::Call(_) => 26,
+            Self::SkipEqualByte { .. } | Self::SkipNotEqualByte { .. } => 10 + skip,
+            Self::SkipEqual { .. } | Self::SkipNotEqual { .. } | Self::SaveRange { .. } | Self::LoadRange { .. } | Self::AddDigits { .. } => 14 + skip,
+            Self::LoadByte { .. } => 6,
+            Self::AddByte { .. } => 10,
+            Self::Or { .. } | Self::And { .. } | Self::Xor { .. } | Self::Add { .. } | Self::Sub { .. } => 44,
+            Self::ShiftRight { .. } | Self::SubReverse { .. } | Self::ShiftLeft { .. } => 44,
+            Self::JumpOffset { .. } | Self::ZoneColor { .. } => 22,
+            Self::Random { .. } => 36,
+            // each row is shifted into place unless the sprite sits on a byte of the display
+            Self::Draw { x, n, .. } => 26 + n as u32 * if v[x].is_multiple_of(8) { 46 } else { 68 },
+            Self::SkipKey { .. } | Self::SkipNotKey { .. } | Self::SkipKey2 { .. } | Self::SkipNotKey2 { .. } => 14 + skip,
+            Self::AddIndex { .. } | Self::Font { .. } => 16,
+            // the digits are counted out by repeated subtraction
+            Self::Bcd { x } => 80 + 16 * (v[x] / 100 + v[x] / 10 % 10 + v[x] % 10) as u32,
+            Self::Store { x } | Self::Load { x } => 14 + 14 * (x as u32 + 1),
+            Self::LongLoadIndex | Self::LoadDelay { .. } | Self::WaitKey { .. } | Self::SetDelay { .. } | Self::SetSound { .. } => 10,
+            Self::BigFont { .. } | Self::Planes(_) | Self::AudioPattern | Self::Pitch { .. } | Self::SaveFlags { .. } | Self::LoadFlags { .. } => 10,
+            // machine code routines take as long as they take; only the call is counted
+            _ => 0,
+        }
+    }
+
+    /// The registers it reads and writes under `quirks`, as masks with bit N for VN
+    pub fn registers(self, quirks: &Quirks) -> (u16, u16) {
+        let bit = |n: usize| 1u16 << n;
+        let through = |x: usize| u16::MAX >> (15 - x);
+        let range = |x: usize, y: usize| through(x.max(y)) & !(through(x.min(y)) >> 1);
+        let vf = bit(0xF);
+        match self {
+            Self::SkipEqualByte { x, .. } | Self::SkipNotEqualByte { x, .. } => (bit(x), 0),
+            Self::SkipKey { x } | Self::SkipNotKey { x } | Self::SkipKey2 { x } | Self::SkipNotKey2 { x } => (bit(x), 0),
+            Self::SkipEqual { x, y } | Self::SkipNotEqual { x, y } => (bit(x) | bit(y), 0),
+            Self::SaveRange { x, y } => (range(x, y), 0),
+            Self::LoadRange { x, y } => (0, range(x, y)),
+            Self::AddDigits { x, y } => (bit(x) | bit(y), bit(x)),
+            Self::LoadByte { x, .. } | Self::Random { x, .. } | Self::LoadDelay { x } | Self::WaitKey { x } => (0, bit(x)),
+            Self::AddByte { x, .. } => (bit(x), bit(x)),
+            Self::Move { x, y } => (bit(y), bit(x)),
+            Self::Or { x, y } | Self::And { x, y } | Self::Xor { x, y } => {
+                (bit(x) | bit(y), if quirks.vf_reset { bit(x) | vf } else { bit(x) })
+            }
+            Self::Add { x, y } | Self::Sub { x, y } | Self::SubReverse { x, y } => (bit(x) | bit(y), bit(x) | vf),
+            Self::ShiftRight { x, y } | Self::ShiftLeft { x, y } => (if quirks.shift_vy { bit(y) } else { bit(x) }, bit(x) | vf),
+            Self::JumpOffset { x, .. } => (if quirks.jump_vx { bit(x) } else { bit(0) }, 0),
+            Self::ZoneColor { x, y, .. } => (bit(x) | bit((x + 1) & 0xF) | bit(y), 0),
+            Self::Draw { x, y, .. } => (bit(x) | bit(y), vf),
+            Self::AddIndex { x } => (bit(x), if quirks.index_overflow { vf } else { 0 }),
+            Self::SetDelay { x } | Self::SetSound { x } | Self::Font { x } | Self::BigFont { x } | Self::Bcd { x } | Self::Pitch { x } => (bit(x), 0),
+            Self::Store { x } | Self::SaveFlags { x } => (through(x), 0),
+            Self::Load { x } | Self::LoadFlags { x } => (0, through(x)),
+            _ => (0, 0),
+        }
+    }
+
+    /// The mnemonic under `quirks`, after Cowgod's CHIP-8 reference, see the disasm module
+    pub fn mnemonic(self, quirks: &Quirks) -> impl fmt::Display + '_ {
+        Mnemonic(self, quirks)
+    }
+}
+
+struct Mnemonic<'a>(Instruction, &'a Quirks);
+
+impl fmt::Display for Mnemonic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {n}"),
+            Instruction::MegaScrollUp(n) => write!(f, "SCU {n}"),
+            Instruction::ScrollUp(n) => write!(f, "SCUP {n}"),
+            Instruction::MegaOff => write!(f, "MEGAOFF"),
+            Instruction::MegaOn => write!(f, "MEGAON"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Sys(nnn) => write!(f, "SYS {nnn:#05x}"),
+            Instruction::Jump(nnn) => write!(f, "JP {nnn:#05x}"),
+            Instruction::Call(nnn) => write!(f, "CALL {nnn:#05x}"),
+            Instruction::SkipEqualByte { x, nn } => write!(f, "SE V{x:X}, {nn:#04x}"),
+            Instruction::SkipNotEqualByte { x, nn } => write!(f, "SNE V{x:X}, {nn:#04x}"),
+            Instruction::SkipEqual { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{x:X} - V{y:X}"),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{x:X} - V{y:X}"),
+            Instruction::LoadByte { x, nn } => write!(f, "LD V{x:X}, {nn:#04x}"),
+            Instruction::AddByte { x, nn } => write!(f, "ADD V{x:X}, {nn:#04x}"),
+            Instruction::Move { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::Add { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::SkipNotEqual { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::LoadIndex(nnn) => write!(f, "LD I, {nnn:#05x}"),
+            Instruction::JumpOffset { x, nnn } if self.1.jump_vx => write!(f, "JP V{x:X}, {nnn:#05x}"),
+            Instruction::JumpOffset { nnn, .. } => write!(f, "JP V0, {nnn:#05x}"),
+            Instruction::Random { x, nn } => write!(f, "RND V{x:X}, {nn:#04x}"),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::SkipKey { x } => write!(f, "SKP V{x:X}"),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{x:X}"),
+            Instruction::LongLoadIndex => write!(f, "LD I, long"),
+            Instruction::LoadDelay { x } => write!(f, "LD V{x:X}, DT"),
+            Instruction::WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{x:X}"),
+            Instruction::Font { x } => write!(f, "LD F, V{x:X}"),
+            Instruction::BigFont { x } => write!(f, "LD HF, V{x:X}"),
+            Instruction::Bcd { x } => write!(f, "LD B, V{x:X}"),
+            Instruction::Planes(n) => write!(f, "PLANE {n}"),
+            Instruction::AudioPattern => write!(f, "AUDIO"),
+            Instruction::Pitch { x } => write!(f, "PITCH V{x:X}"),
+            Instruction::Store { x } => write!(f, "LD [I], V{x:X}"),
+            Instruction::Load { x } => write!(f, "LD V{x:X}, [I]"),
+            Instruction::SaveFlags { x } => write!(f, "LD R, V{x:X}"),
+            Instruction::LoadFlags { x } => write!(f, "LD V{x:X}, R"),
+            Instruction::Background => write!(f, "BGND"),
+            Instruction::AddDigits { x, y } => write!(f, "ADDD V{x:X}, V{y:X}"),
+            Instruction::ZoneColor { x, y, n } => write!(f, "COL V{x:X}, V{y:X}, {n}"),
+            Instruction::SkipKey2 { x } => write!(f, "SKP2 V{x:X}"),
+            Instruction::SkipNotKey2 { x } => write!(f, "SKNP2 V{x:X}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcodes_decode_with_their_metadata() {
+        let add = Instruction::decode(0x8AB4).unwrap();
+        assert_eq!(add, Instruction::Add { x: 0xA, y: 0xB });
+        assert_eq!((add.mnemonic(&Quirks::default()).to_string(), add.pattern(), add.platform()), ("ADD VA, VB".to_string(), "8XY4", None));
+        assert_eq!(add.registers(&Quirks::default()), (1 << 0xA | 1 << 0xB, 1 << 0xA | 1 << 0xF));
+        assert_eq!(Instruction::decode(0xD010).and_then(Instruction::platform), Some(Preset::Schip));
+        assert_eq!(Instruction::decode(0x5122).and_then(Instruction::platform), Some(Preset::Xochip));
+        assert_eq!(Instruction::decode(0x8008).or(Instruction::decode(0x5121)), None);
+        // CHIP-8X's own take its quirk
+        let quirks = Quirks::preset(Preset::Chip8x);
+        assert_eq!(Instruction::decode_with(0xB123, &quirks), Some(Instruction::ZoneColor { x: 1, y: 2, n: 3 }));
+        assert_eq!(Instruction::decode(0xB123).and_then(Instruction::address), Some(0x123));
+        assert_eq!(Instruction::decode(0xA300).map(|i| i.vip_cycles(&[0; 16], false)), Some(52));
+        assert_eq!(Instruction::SaveRange { x: 3, y: 1 }.registers(&quirks), (0b1110, 0));
+        // BXNN jumps by Vx
+        let jump = Instruction::decode(0xB345).unwrap();
+        assert_eq!(jump.mnemonic(&Quirks::preset(Preset::Chip8)).to_string(), "JP V0, 0x345");
+        assert_eq!(jump.mnemonic(&Quirks::preset(Preset::Schip)).to_string(), "JP V3, 0x345");
+    }
+}
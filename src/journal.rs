@@ -0,0 +1,98 @@
+//! Frame journals: a plain-text record of every frame a run displayed, used to compare runs
+//! against each other or against frames exported from another emulator.
+//!
+//! ```text
+//! frame 0 64x32
+//! 0000000000000000
+//! ...one line of hex per row, most significant bit leftmost...
+//! ```
+
+use std::io::{self, Write};
+
+use crate::display::Display;
+
+/// One captured frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Each row's pixels, the leftmost pixel in bit `width - 1`
+    pub rows: Vec<u128>,
+}
+
+impl Frame {
+    pub fn capture(display: &Display) -> Self {
+        let (width, height) = display.dimensions();
+        let rows = (0..height)
+            .map(|y| (0..width).fold(0, |row, x| row << 1 | display.pixel(x, y) as u128))
+            .collect();
+        Self { width, height, rows }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] >> (self.width - 1 - x) & 1 == 1
+    }
+
+    /// Coordinates of every pixel that differs from `other`; all of them if the resolutions differ
+    pub fn diff(&self, other: &Frame) -> Vec<(usize, usize)> {
+        let mut diff = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let same = self.width == other.width
+                    && self.height == other.height
+                    && self.pixel(x, y) == other.pixel(x, y);
+                if !same {
+                    diff.push((x, y));
+                }
+            }
+        }
+        diff
+    }
+}
+
+/// Appends frames to a journal as they are displayed
+pub struct JournalWriter<W: Write> {
+    out: W,
+    frames: usize,
+}
+
+impl<W: Write> JournalWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, frames: 0 }
+    }
+
+    pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        writeln!(self.out, "frame {} {}x{}", self.frames, frame.width, frame.height)?;
+        for row in &frame.rows {
+            writeln!(self.out, "{row:0width$x}", width = frame.width / 4)?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Parses a journal written by JournalWriter, returning the frames in order
+pub fn read_journal(text: &str) -> Result<Vec<Frame>, String> {
+    let mut frames = vec![];
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    while let Some((n, header)) = lines.next() {
+        let err = |msg: &str| format!("line {}: {msg}", n + 1);
+        let mut words = header.split_whitespace();
+        let (Some("frame"), Some(_), Some(size)) = (words.next(), words.next(), words.next()) else {
+            return Err(err("expected `frame <index> <width>x<height>`"));
+        };
+        let (width, height) = size.split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+            .filter(|&(w, _)| (4..=128).contains(&w) && w % 4 == 0)
+            .ok_or_else(|| err("bad frame size"))?;
+        let mut rows = Vec::with_capacity(height);
+        for _ in 0..height {
+            let (n, line) = lines.next().ok_or_else(|| err("journal ends mid-frame"))?;
+            let row = u128::from_str_radix(line.trim(), 16)
+                .map_err(|e| format!("line {}: {e}", n + 1))?;
+            rows.push(row);
+        }
+        frames.push(Frame { width, height, rows });
+    }
+    Ok(frames)
+}
@@ -0,0 +1,142 @@
+//! Which host keys press which keypad keys.
+//!
+//! The default binds the 1234/QWER/ASDF/ZXCV block by scancode, so it sits in the same place
+//! on any keyboard layout, AZERTY, QWERTZ and Dvorak included; --keycodes binds the keys with
+//! those names instead, wherever the layout puts them. Keymap files replace either, one binding
+//! per line, `#` for comments:
+//!
+//! ```text
+//! # arrows move, space fires
+//! Up = 5
+//! Down = 8
+//! Left = 7
+//! Right = 9
+//! Space = 6
+//! scancode:W = 5
+//! ```
+//!
+//! Names are SDL's key names, or scancode names after `scancode:` to bind a physical position.
+
+use sdl2::keyboard::{Keycode, Scancode};
+
+use crate::rotation::Rotation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(Keycode),
+    Position(Scancode),
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap(Vec<(Binding, u8)>);
+
+/// The keypad's 4x4 grid on the left of a QWERTY keyboard
+const LAYOUT: [(Scancode, u8); 16] = {
+    use Scancode::*;
+    [
+        (Num1, 0x1), (Num2, 0x2), (Num3, 0x3), (Num4, 0xC),
+        (Q, 0x4), (W, 0x5), (E, 0x6), (R, 0xD),
+        (A, 0x7), (S, 0x8), (D, 0x9), (F, 0xE),
+        (Z, 0xA), (X, 0x0), (C, 0xB), (V, 0xF),
+    ]
+};
+
+/// The ETI-660's keypad in the same place, which counts up from 0 along its rows
+const ETI_660_LAYOUT: [(Scancode, u8); 16] = {
+    use Scancode::*;
+    [
+        (Num1, 0x0), (Num2, 0x1), (Num3, 0x2), (Num4, 0x3),
+        (Q, 0x4), (W, 0x5), (E, 0x6), (R, 0x7),
+        (A, 0x8), (S, 0x9), (D, 0xA), (F, 0xB),
+        (Z, 0xC), (X, 0xD), (C, 0xE), (V, 0xF),
+    ]
+};
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self(LAYOUT.iter().map(|&(scancode, key)| (Binding::Position(scancode), key)).collect())
+    }
+}
+
+impl Keymap {
+    /// The default grid bound by key name rather than position, for players who'd rather
+    /// press the keys labelled 1234/QWER/ASDF/ZXCV on a layout that moves them
+    pub fn by_keycode() -> Self {
+        // scancodes are named after the key a QWERTY layout has there
+        let bindings = LAYOUT.iter().filter_map(|&(scancode, key)| Some((Binding::Key(Keycode::from_name(scancode.name())?), key)));
+        Self(bindings.collect())
+    }
+
+    /// The ETI-660's keypad on the default grid, for its ROMs
+    pub fn eti_660() -> Self {
+        Self(ETI_660_LAYOUT.iter().map(|&(scancode, key)| (Binding::Position(scancode), key)).collect())
+    }
+
+    /// Parses a keymap file, see the module docs for the format
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keymap = Self(vec![]);
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (binding, key) = parse_binding(line).map_err(|e| format!("line {}: {e}", n + 1))?;
+            keymap.bind(binding, key);
+        }
+        Ok(keymap)
+    }
+
+    /// Binds `binding` to keypad `key`, replacing whatever it was bound to
+    pub fn bind(&mut self, binding: Binding, key: u8) {
+        self.0.retain(|&(existing, _)| existing != binding);
+        self.0.push((binding, key));
+    }
+
+    /// Turns the directions bound to match a screen turned by `rotation`
+    pub fn turn(&mut self, rotation: Rotation) {
+        for (_, key) in self.0.iter_mut() {
+            *key = rotation.turn_key(*key);
+        }
+    }
+
+    /// The keypad key a host key press maps to. Bindings by name win over bindings by position
+    pub fn lookup(&self, keycode: Option<Keycode>, scancode: Option<Scancode>) -> Option<u8> {
+        let find = |wanted: Binding| self.0.iter().find(|&&(binding, _)| binding == wanted).map(|&(_, key)| key);
+        keycode.and_then(|keycode| find(Binding::Key(keycode)))
+            .or_else(|| scancode.and_then(|scancode| find(Binding::Position(scancode))))
+    }
+}
+
+/// The CHIP-8X second keypad key a host key press maps to: the numeric keypad, 0-9 on its
+/// digits and A-F on the keys around them
+pub fn second_keypad(scancode: Option<Scancode>) -> Option<u8> {
+    use Scancode::*;
+    let layout = [
+        (Kp0, 0x0), (Kp1, 0x1), (Kp2, 0x2), (Kp3, 0x3), (Kp4, 0x4), (Kp5, 0x5), (Kp6, 0x6), (Kp7, 0x7),
+        (Kp8, 0x8), (Kp9, 0x9), (KpDivide, 0xA), (KpMultiply, 0xB), (KpMinus, 0xC), (KpPlus, 0xD),
+        (KpEnter, 0xE), (KpPeriod, 0xF),
+    ];
+    let scancode = scancode?;
+    layout.iter().find(|&&(bound, _)| bound == scancode).map(|&(_, key)| key)
+}
+
+/// Parses `NAME=KEY`, as in keymap files and --bind
+pub fn parse_binding(s: &str) -> Result<(Binding, u8), String> {
+    let (name, key) = s.rsplit_once('=').ok_or_else(|| format!("expected NAME=KEY, got `{s}`"))?;
+    let name = name.trim();
+    let binding = match name.strip_prefix("scancode:") {
+        Some(name) => Scancode::from_name(name).map(Binding::Position),
+        None => Keycode::from_name(name).map(Binding::Key),
+    };
+    let binding = binding.ok_or_else(|| format!("unknown key `{name}`"))?;
+    Ok((binding, parse_keypad_key(key)?))
+}
+
+/// Parses a keypad key given as one hex digit
+pub fn parse_keypad_key(s: &str) -> Result<u8, String> {
+    let s = s.trim();
+    u8::from_str_radix(s.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|&key| key < 16)
+        .ok_or_else(|| format!("`{s}` is not a keypad key (0-F)"))
+}
@@ -0,0 +1,56 @@
+//! The clickable keypad under the game: the canvas grows by a strip of logical pixels below
+//! the 64x32 display, holding the 16 keys in the VIP's 4x4 layout. Held keys are lit, and keys
+//! the ROM checked in the last frame are outlined, which shows a game's controls as it reads them
+
+use chip_8_rs::hints::KEYPAD_LAYOUT;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::text;
+
+/// Logical pixels the panel adds below the display
+pub const HEIGHT: u32 = 32;
+
+/// Each key's cell, in logical pixels
+const CELL_WIDTH: i32 = 16;
+const CELL_HEIGHT: i32 = 8;
+
+const KEY: Color = Color::RGB(48, 48, 48);
+const HELD: Color = Color::YELLOW;
+const CHECKED: Color = Color::RGB(80, 140, 255);
+const LABEL: Color = Color::RGB(200, 200, 200);
+
+/// Makes room for the panel below the display, growing the window to match
+pub fn enable(canvas: &mut Canvas<Window>, scale: u32) {
+    canvas.window_mut().set_size(64 * scale, (32 + HEIGHT) * scale).ok();
+    canvas.window_mut().set_minimum_size(64, 32 + HEIGHT).ok();
+    canvas.set_logical_size(64, 32 + HEIGHT).expect("failed to make room for the keypad");
+}
+
+/// The key under the logical point `(x, y)`, if it's on the panel
+pub fn key_at(x: i32, y: i32) -> Option<u8> {
+    let (col, row) = (x.div_euclid(CELL_WIDTH), (y - 32).div_euclid(CELL_HEIGHT));
+    let row = KEYPAD_LAYOUT.get(usize::try_from(row).ok()?)?;
+    row.get(usize::try_from(col).ok()?).copied()
+}
+
+/// Draws the panel with the keys in `held` lit and those in `checked` outlined
+pub fn draw(canvas: &mut Canvas<Window>, held: u16, checked: u16) {
+    canvas.set_draw_color(Color::BLACK);
+    canvas.fill_rect(Rect::new(0, 32, 64, HEIGHT)).expect("failed to draw keypad");
+    for (row, layout) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (col, &key) in layout.iter().enumerate() {
+            let cell = Rect::new(col as i32 * CELL_WIDTH + 1, 32 + row as i32 * CELL_HEIGHT, CELL_WIDTH as u32 - 2, CELL_HEIGHT as u32 - 1);
+            canvas.set_draw_color(if held & 1 << key != 0 { HELD } else { KEY });
+            canvas.fill_rect(cell).expect("failed to draw keypad");
+            if checked & 1 << key != 0 {
+                canvas.set_draw_color(CHECKED);
+                canvas.draw_rect(cell).expect("failed to draw keypad");
+            }
+            let label = [format!("{key:X}")];
+            text::draw(canvas, &label, (cell.x() + 5, cell.y()), 1, if held & 1 << key != 0 { Color::BLACK } else { LABEL });
+        }
+    }
+}
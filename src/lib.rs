@@ -0,0 +1,1036 @@
+//! Frontend-agnostic CHIP-8 core: CPU, memory, timers, and the display buffer
+
+use std::collections::{BTreeSet, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+
+use coverage::Coverage;
+use diagnostics::Diagnostics;
+use audio::{Pitch, AUDIO_PATTERN_LEN};
+use builder::Builder;
+use bus::Bus;
+use cpu::Cpu;
+use debugger::{WatchHit, Watchpoint};
+use devices::{Buzzer, Devices, FrameOutput, Keypad, Sound};
+pub use cpu::Effect;
+pub use error::Chip8Error;
+use digest::FrameDigest;
+use display::{Display, FrameSink, Palette, Resolution};
+use fault::Fault;
+use fonts::{FontSet, Fonts};
+use instruction::Instruction;
+use memory::Memory;
+use quirks::Quirks;
+use rom::RomInfo;
+use script::Script;
+use stats::Counters;
+use variant::Variant;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use state::{Reader, StateError, Writer};
+use timers::Timers;
+use trace::{Snapshot, Trace, TraceEntry};
+
+pub mod announce;
+pub mod asm;
+pub mod audio;
+pub mod bench;
+pub mod builder;
+mod bus;
+pub mod cheats;
+pub mod compare;
+pub mod config;
+pub mod conformance;
+pub mod coverage;
+pub mod crash;
+mod cycles;
+mod cpu;
+pub mod debugger;
+pub mod demo;
+pub mod devices;
+pub mod diagnostics;
+pub mod digest;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod export;
+pub mod fault;
+pub mod fonts;
+pub mod gdb;
+pub mod highscore;
+pub mod hints;
+pub mod inputs;
+pub mod instruction;
+pub mod journal;
+pub mod lockstep;
+pub mod logging;
+pub mod megachip;
+mod memory;
+pub mod menu;
+pub mod netplay;
+pub mod octo;
+pub mod pad;
+pub mod patch;
+pub mod practice;
+pub mod profiler;
+pub mod quirks;
+pub mod reference;
+pub mod remote;
+pub mod replay;
+pub mod report;
+pub mod rom;
+pub mod romdb;
+pub mod rewind;
+pub mod runahead;
+pub mod script;
+pub mod self_test;
+pub mod state;
+pub mod state_diff;
+pub mod stats;
+pub mod storage;
+pub mod symbols;
+mod timers;
+pub mod timing;
+pub mod trace;
+pub mod variant;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+pub mod zip;
+
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// SUPER-CHIP 8x10 font used by FX30, with the XO-CHIP A-F extension
+pub const BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Where FONT and BIG_FONT are placed in memory
+pub const FONT_ADDR: u16 = 0x50;
+pub const BIG_FONT_ADDR: u16 = FONT_ADDR + FONT.len() as u16;
+
+/// Number of SUPER-CHIP RPL user flags (XO-CHIP extends the original 8 to 16)
+pub const RPL_FLAGS: usize = 16;
+
+/// Rate at which the delay and sound timers count down
+pub const TIMER_HZ: u32 = 60;
+
+/// Instructions emulated per 60Hz frame when running frame by frame
+pub const INSTRUCTIONS_PER_FRAME: usize = 11;
+
+/// Random source for CXNN, owned by the machine so snapshots replay the same numbers
+#[derive(Debug, Clone)]
+struct MachineRng {
+    chacha: ChaCha12Rng,
+    /// Bytes handed out before any from `chacha`, see Chip8::script_random()
+    scripted: VecDeque<u8>,
+}
+
+impl MachineRng {
+    fn seeded(seed: u64) -> Self {
+        Self { chacha: ChaCha12Rng::seed_from_u64(seed), scripted: VecDeque::new() }
+    }
+
+    fn next(&mut self) -> u8 {
+        self.scripted.pop_front().unwrap_or_else(|| self.chacha.gen())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for MachineRng {
+    fn default() -> Self {
+        Self { chacha: ChaCha12Rng::from_entropy(), scripted: VecDeque::new() }
+    }
+}
+
+/// The browser has no entropy source without JavaScript glue, so the page calls seed_rng()
+#[cfg(target_arch = "wasm32")]
+impl Default for MachineRng {
+    fn default() -> Self {
+        Self::seeded(0)
+    }
+}
+
+/// The COSMAC VIP's CXNN generator: a counter bumped by the 60Hz interrupt indexes the
+/// interpreter's own code page at 0x100-0x1FF, and the byte found there is added to the previous
+/// result. The sequence is only as good as what occupies that page, which is where some ROMs
+/// got their "random" patterns from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct VipRng {
+    counter: u8,
+    last: u8,
+}
+
+impl VipRng {
+    const PAGE: usize = 0x100;
+
+    fn interrupt(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+
+    fn next(&mut self, memory: &[u8]) -> u8 {
+        self.counter = self.counter.wrapping_add(1);
+        self.last = self.last.wrapping_add(memory[Self::PAGE + self.counter as usize]);
+        self.last
+    }
+}
+
+/// Nested subroutine calls the stack holds, as on the HP48 interpreters
+pub const STACK_DEPTH: usize = 16;
+
+/// Address that, in the dev profile, prints every byte written to it on the host console
+pub const DEBUG_CONSOLE_ADDR: u16 = 0xFFF;
+
+/// How HIRES CHIP-8 programs begin: a jump to the VIP interpreter patch they carry for 64x64
+pub const HIRES_START: [u8; 2] = [0x12, 0x60];
+
+/// Where a HIRES CHIP-8 program's own code begins, once the patch has set up the display
+pub const HIRES_ENTRY: u16 = 0x2C0;
+
+/// Written in place of an optional keypad key in save states when there's none
+const NO_KEY: u8 = 0xFF;
+
+/// Host extensions the machine is built with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profile {
+    /// A plain machine, exactly as ROMs expect
+    #[default]
+    Standard,
+    /// Adds the debug console at DEBUG_CONSOLE_ADDR, for printf-style debugging of ROMs
+    Dev,
+}
+
+/// What to do when the program counter lands on an odd address
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AlignmentPolicy {
+    /// Execute from odd addresses silently, as the original interpreters did
+    Allow,
+    /// Execute, but report each odd address the first time it is reached
+    #[default]
+    Warn,
+    /// Halt the machine instead of executing
+    Error,
+}
+
+/// What to do when the program faults: runs an invalid opcode, returns or calls with the stack
+/// empty or full, or reaches past the end of memory. Faults are queued for take_faults() under
+/// every policy
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FaultPolicy {
+    /// Carry on silently, the instruction having done nothing or wrapped around
+    Ignore,
+    /// Carry on, logging each fault as a warning
+    #[default]
+    Log,
+    /// Stop in the debugger after the instruction
+    Pause,
+    /// Halt the machine, with exit_status() reporting the fault
+    Exit,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Chip8 {
+    display: Display,
+    keys: u16,
+    /// While latching, the keys the host holds, which `keys` takes at the next frame
+    pending_keys: Option<u16>,
+    /// CHIP-8X's second keypad, read by EXF2/EXF5
+    keys_2: u16,
+    rom: Vec<u8>,
+    cpu: Cpu,
+    bus: Bus,
+    timers: Timers,
+    diagnostics: Option<Diagnostics>,
+    trace: Option<Trace>,
+    /// The latest instructions for the debugger to look back through, apart from the trace
+    history: Option<Trace>,
+    script: Option<Script>,
+    alignment: AlignmentPolicy,
+    misaligned: BTreeSet<u16>,
+    fault_policy: FaultPolicy,
+    /// Nested calls the stack holds instead of STACK_DEPTH when set
+    stack_depth: Option<usize>,
+    halted: bool,
+    /// The fault that halted the machine under FaultPolicy::Exit, and where
+    fatal: Option<(u16, Fault)>,
+    counters: Counters,
+    rng: MachineRng,
+    vip_rng: VipRng,
+    pitch: Pitch,
+    /// Address, opcode and decoding of the instruction executed last, for debugging views and
+    /// VIP timing
+    last_instruction: Option<(u16, u16, Option<Instruction>)>,
+    /// The watched access made since the last take_watch_hit()
+    watch_hit: Option<WatchHit>,
+    /// One bit per key EX9E/EXA1 tested or FX0A waited on since the last take_checked_keys()
+    checked_keys: u16,
+    /// The XO-CHIP audio pattern loaded by F002, if the program loaded one
+    audio_pattern: Option<[u8; AUDIO_PATTERN_LEN]>,
+    quirks: Quirks,
+    /// Set by DXYN under the display wait quirk, cleared by the next timer tick
+    vblank_wait: bool,
+    /// Machine cycles left of this frame under the VIP timing quirk, below zero when the last
+    /// instruction ran into the next frame
+    vip_cycles: i64,
+    /// Outlives load_rom() like the HP48's RPL registers outlive the program
+    rpl: [u8; RPL_FLAGS],
+    /// Installed on every load_rom(), see set_font_set()
+    fonts: Fonts,
+    /// The flavor whose instructions alone decode, when set; otherwise all of them do
+    variant: Option<Variant>,
+}
+
+impl Chip8 {
+    /// Starts configuring a machine, see Builder
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Loads the ROM at `path`, applying the IPS or BPS patch at `patch` to it first if given.
+    /// Empty ROMs and ROMs too big for memory are refused; see RomInfo for what else it found
+    pub fn load(path: &Path, patch: Option<&Path>) -> Result<(Self, RomInfo), Chip8Error> {
+        Self::load_bytes(error::read(path)?, patch)
+    }
+
+    /// Like load(), for a ROM image read some other way
+    pub fn load_bytes(mut rom: Vec<u8>, patch: Option<&Path>) -> Result<(Self, RomInfo), Chip8Error> {
+        if let Some(patch) = patch {
+            rom = patch::apply(&rom, &error::read(patch)?)?;
+        }
+        let info = RomInfo::inspect(&rom)?;
+        Ok((Self::from_rom(&rom), info))
+    }
+
+    /// Serializes the complete machine state, see the state module for the format
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(self.bus.memory.bytes());
+        w.raw(&self.cpu.v);
+        w.u16(self.cpu.pc);
+        w.u16(self.cpu.i);
+        w.u8(self.cpu.stack.len() as u8);
+        for &addr in &self.cpu.stack {
+            w.u16(addr);
+        }
+        w.u8(self.timers.delay);
+        w.u8(self.timers.sound);
+        w.u8(self.cpu.key_wait.unwrap_or(NO_KEY));
+        self.display.save(&mut w);
+        w.raw(&self.rpl);
+        w.u8(self.pitch.register);
+        w.bool(self.halted);
+        w.bool(self.vblank_wait);
+        w.u8(self.vip_rng.counter);
+        w.u8(self.vip_rng.last);
+        w.raw(&self.rng.chacha.get_seed());
+        w.u64(self.rng.chacha.get_stream());
+        w.u128(self.rng.chacha.get_word_pos());
+        w.bool(self.audio_pattern.is_some());
+        w.raw(&self.audio_pattern.unwrap_or_default());
+        w.bytes(&self.rng.scripted.iter().copied().collect::<Vec<_>>());
+        self.display.save_colors(&mut w);
+        w.u8(self.cpu.i_high);
+        self.display.save_mega(&mut w);
+        w.u64(self.vip_cycles as u64);
+        self.display.save_planes(&mut w);
+        w.0
+    }
+
+    /// Restores a state written by save_state(), leaving the machine untouched if it's invalid
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut next = self.clone();
+        let (mut r, version) = Reader::new(data)?;
+        next.bus.memory = Memory::from_bytes(r.bytes()?.to_vec()).ok_or(StateError::Invalid("memory size"))?;
+        next.cpu.v = r.array()?;
+        next.cpu.pc = r.u16()?;
+        next.cpu.i = r.u16()?;
+        let depth = r.u8()? as usize;
+        if depth > next.stack_depth() {
+            return Err(StateError::Invalid("stack deeper than the machine allows"));
+        }
+        next.cpu.stack = (0..depth).map(|_| r.u16()).collect::<Result<_, _>>()?;
+        next.timers.delay = r.u8()?;
+        next.timers.sound = r.u8()?;
+        next.cpu.key_wait = match version {
+            1 => None,
+            _ => Some(r.u8()?).filter(|&key| key != NO_KEY),
+        };
+        next.display.load(&mut r)?;
+        next.rpl = r.array()?;
+        next.pitch.register = r.u8()?;
+        next.halted = r.bool()?;
+        next.fatal = None;
+        next.forget_history();
+        next.vblank_wait = r.bool()?;
+        next.vip_rng = VipRng { counter: r.u8()?, last: r.u8()? };
+        let mut chacha = ChaCha12Rng::from_seed(r.array()?);
+        chacha.set_stream(r.u64()?);
+        chacha.set_word_pos(r.u128()?);
+        next.audio_pattern = match version {
+            1 | 2 => None,
+            _ => {
+                let loaded = r.bool()?;
+                let pattern = r.array()?;
+                loaded.then_some(pattern)
+            }
+        };
+        let scripted = match version {
+            1..=3 => VecDeque::new(),
+            _ => r.bytes()?.iter().copied().collect(),
+        };
+        next.rng = MachineRng { chacha, scripted };
+        if version >= 5 {
+            next.display.load_colors(&mut r)?;
+        }
+        if version >= 6 {
+            next.cpu.i_high = r.u8()?;
+            next.display.load_mega(&mut r)?;
+        } else if next.display.resolution() == Resolution::Mega {
+            return Err(StateError::Invalid("display resolution"));
+        }
+        next.vip_cycles = match version {
+            1..=6 => 0,
+            _ => r.u64()? as i64,
+        };
+        if version >= 8 {
+            next.display.load_planes(&mut r)?;
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// The ROM image as loaded, including any edits made through patch_rom()
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Reads a byte of memory without side effects, wrapping at the end of the address space
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.bus.memory.get(addr as usize)
+    }
+
+    /// Writes a byte of live memory, wrapping at the end of the address space. Unlike
+    /// patch_rom(), the ROM image is left alone, so a reset undoes it
+    pub fn poke(&mut self, addr: u16, byte: u8) {
+        self.bus.memory.set(addr as usize, byte);
+    }
+
+    /// Overwrites the ROM byte at memory address `addr` in both the image and the live memory,
+    /// growing the image if needed. Returns false for addresses outside the program area
+    pub fn patch_rom(&mut self, addr: u16, byte: u8) -> bool {
+        let (addr, start) = (addr as usize, self.quirks.program_start() as usize);
+        if addr < start || !self.bus.memory.contains(addr) {
+            return false;
+        }
+        let offset = addr - start;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.bus.memory.set(addr, byte);
+        true
+    }
+
+    /// Writes the (possibly patched) ROM image to `path` as a plain .ch8 file
+    pub fn save_rom(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, &self.rom)
+    }
+
+    /// Creates a machine with the font installed and `rom` loaded at 0x200
+    pub fn from_rom(rom: &[u8]) -> Self {
+        let mut chip_8 = Self::default();
+        chip_8.load_rom(rom);
+        chip_8
+    }
+
+    /// Resets memory and registers and loads `rom` at 0x200 (0x300 for CHIP-8X, 0x600 for the
+    /// ETI-660), keeping the machine's settings. HIRES CHIP-8 programs, which start by jumping
+    /// to 0x260, get the 64x64 display and start at 0x2C0, past the interpreter patch they carry
+    /// for the VIP (see HIRES_START)
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        let (start, size) = (self.quirks.program_start(), self.quirks.memory_size(rom.len()));
+        self.bus.reset(Memory::with_program_at(rom, &self.fonts, start, size));
+        self.rom = rom.to_vec();
+        self.timers = Timers::default();
+        self.halted = false;
+        self.fatal = None;
+        self.audio_pattern = None;
+        self.last_instruction = None;
+        self.forget_history();
+        self.restart();
+    }
+
+    /// Points PC at the program's start and blanks the display in the program's resolution
+    fn restart(&mut self) {
+        self.display = Display::with_palette(self.display.palette());
+        if self.quirks.chip_8x {
+            self.display.enable_colors();
+        }
+        let pc = if self.rom.starts_with(&HIRES_START) {
+            self.display.set_resolution(Resolution::Tall);
+            HIRES_ENTRY
+        } else {
+            if self.quirks.eti_660 {
+                self.display.set_resolution(Resolution::Eti);
+            }
+            self.quirks.program_start()
+        };
+        self.cpu = Cpu { pc, ..Cpu::default() };
+        self.vip_cycles = 0;
+    }
+
+    /// Reloads the ROM image into fresh memory, as if it had just been loaded
+    pub fn reset(&mut self) {
+        let rom = std::mem::take(&mut self.rom);
+        self.load_rom(&rom);
+    }
+
+    /// Restarts the program with memory left as it is, like the VIP's reset switch
+    pub fn soft_reset(&mut self) {
+        self.timers = Timers::default();
+        self.halted = false;
+        self.fatal = None;
+        self.vblank_wait = false;
+        self.audio_pattern = None;
+        self.last_instruction = None;
+        self.forget_history();
+        self.restart();
+    }
+
+    /// Draws the display in `palette` from now on, across resets and ROM loads
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.display.set_palette(palette);
+    }
+
+    /// The display as last drawn by the program
+    pub fn framebuffer(&self) -> &Display {
+        &self.display
+    }
+
+    /// The display, if it changed since the last call; marks it as presented
+    pub fn take_frame(&mut self) -> Option<&Display> {
+        if self.display.changed() {
+            self.display.mark_presented();
+            Some(&self.display)
+        } else {
+            None
+        }
+    }
+
+    /// Sends the frame to `sink` if it changed since it was last rendered or presented
+    pub fn present(&mut self, sink: &mut dyn FrameSink) {
+        if self.display.changed() {
+            self.display.present(sink);
+        }
+    }
+
+    /// Holds the keys `keypad` reports held, releasing the rest
+    pub fn read_keypad(&mut self, keypad: &mut dyn Keypad) {
+        self.set_keys(keypad.keys());
+    }
+
+    /// Tells `buzzer` whether to sound, and what
+    pub fn sound_to(&self, buzzer: &mut dyn Buzzer) {
+        let Sound { on, pattern, rate } = self.sound();
+        buzzer.sound(on, pattern, rate);
+    }
+
+    /// Whether the buzzer should be sounding, and what
+    pub fn sound(&self) -> Sound {
+        Sound { on: self.sound_active(), pattern: self.audio_pattern, rate: self.pitch.rate() }
+    }
+
+    /// One frame driven by `devices`: the keypad read, the instructions run and the timers
+    /// ticked, then the frame presented if it changed and the buzzer told what to do
+    pub fn run_frame_on(&mut self, instructions: usize, devices: &mut Devices) {
+        self.read_keypad(devices.keypad);
+        self.run_frame(instructions);
+        self.present(devices.screen);
+        self.sound_to(devices.buzzer);
+    }
+
+    /// One frame of INSTRUCTIONS_PER_FRAME instructions with `keys` held, like run_frames,
+    /// handing back what to show and play. The display counts as presented afterwards
+    pub fn next_frame(&mut self, keys: u16) -> FrameOutput<'_> {
+        self.set_keys(keys);
+        self.run_frame(INSTRUCTIONS_PER_FRAME);
+        let display_changed = self.take_frame().is_some();
+        FrameOutput { display_changed, display: &self.display, sound: self.sound() }
+    }
+
+    /// The XO-CHIP pitch register and the playback rate in effect
+    pub fn pitch(&self) -> Pitch {
+        self.pitch
+    }
+
+    /// Plays audio at a fixed rate (samples per second) instead of deriving it from the pitch
+    /// register
+    pub fn override_playback_rate(&mut self, rate: Option<f32>) {
+        self.pitch.rate_override = rate;
+    }
+
+    /// The 128 1-bit samples XO-CHIP plays while the sound timer runs, most significant bit
+    /// first, or None until the program loads some with F002 and the plain beep should sound
+    pub fn audio_pattern(&self) -> Option<[u8; AUDIO_PATTERN_LEN]> {
+        self.audio_pattern
+    }
+
+    /// Whether the buzzer should be sounding, i.e. the sound timer is running
+    pub fn sound_active(&self) -> bool {
+        self.timers.sound > 0
+    }
+
+    /// Replaces the whole keypad state at once, bit N set meaning key N is held
+    pub fn set_keys(&mut self, keys: u16) {
+        *self.pending_keys.as_mut().unwrap_or(&mut self.keys) = keys;
+    }
+
+    /// The keypad state, bit N set meaning key N is held. While latching, the keys held now,
+    /// which the ROM sees from the next frame
+    pub fn keys(&self) -> u16 {
+        self.pending_keys.unwrap_or(self.keys)
+    }
+
+    /// With `on`, keys pressed and released only reach the ROM at the start of the next frame,
+    /// so every EX9E/EXA1 in a frame sees the keypad the same, as the interpreters that scanned
+    /// it once a frame did. For hosts that run instructions while the keys are changing
+    pub fn latch_keys(&mut self, on: bool) {
+        self.keys = self.keys();
+        self.pending_keys = on.then_some(self.keys);
+    }
+
+    /// Replaces the state of CHIP-8X's second keypad, which only EXF2/EXF5 read
+    pub fn set_second_keypad(&mut self, keys: u16) {
+        self.keys_2 = keys;
+    }
+
+    /// The state of CHIP-8X's second keypad, bit N set meaning key N is held
+    pub fn second_keypad(&self) -> u16 {
+        self.keys_2
+    }
+
+    /// Marks keypad key `key` (0x0-0xF) as held
+    pub fn press(&mut self, key: u8) {
+        self.set_keys(self.keys() | 1 << (key & 0xF));
+    }
+
+    /// Marks keypad key `key` (0x0-0xF) as released
+    pub fn release(&mut self, key: u8) {
+        self.set_keys(self.keys() & !(1 << (key & 0xF)));
+    }
+
+    /// Time left until the delay timer reaches 0
+    pub fn delay_remaining(&self) -> Duration {
+        self.timers.delay_remaining()
+    }
+
+    /// Time left until the sound timer reaches 0 and the buzzer stops
+    pub fn sound_remaining(&self) -> Duration {
+        self.timers.sound_remaining()
+    }
+
+    /// Starts recording which quirk-dependent behaviors the running ROM relies on
+    pub fn enable_diagnostics(&mut self) {
+        self.diagnostics.get_or_insert_with(Diagnostics::default);
+    }
+
+    pub fn diagnostics(&self) -> Option<&Diagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// Starts recording which addresses execute, which are read and written as data, and which
+    /// instructions run, see Coverage
+    pub fn enable_coverage(&mut self) {
+        self.bus.coverage.get_or_insert_with(Coverage::default);
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.bus.coverage.as_ref()
+    }
+
+    /// Starts tracing each instruction executed, keeping only the latest `limit` if given
+    pub fn enable_trace(&mut self, limit: Option<usize>) {
+        self.trace.get_or_insert_with(|| Trace::new(limit));
+    }
+
+    /// The instructions traced since the last call, oldest first
+    pub fn take_trace(&mut self) -> Vec<TraceEntry> {
+        self.trace.as_mut().map(Trace::take).unwrap_or_default()
+    }
+
+    /// Starts keeping the latest `len` instructions executed, with the registers each changed,
+    /// for looking back at how the machine got where it is. Loading a ROM or a state forgets them
+    pub fn enable_history(&mut self, len: usize) {
+        self.history = Some(Trace::new(Some(len)));
+    }
+
+    pub fn history(&self) -> Option<&Trace> {
+        self.history.as_ref()
+    }
+
+    fn forget_history(&mut self) {
+        if let Some(history) = self.history.as_mut() {
+            history.take();
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot { v: self.cpu.v, i: self.cpu.i }
+    }
+
+    fn is_pressed(&self, key: u8) -> bool {
+        self.keys >> (key & 0xF) & 1 == 1
+    }
+
+    /// The keys the ROM checked since the last call, so frontends can show which it reads
+    pub fn take_checked_keys(&mut self) -> u16 {
+        std::mem::take(&mut self.checked_keys)
+    }
+
+    /// The SUPER-CHIP RPL user flags, for frontends to persist between runs
+    pub fn rpl_flags(&self) -> [u8; RPL_FLAGS] {
+        self.rpl
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAGS]) {
+        self.rpl = flags;
+    }
+
+    /// Enables or disables host extensions such as the debug console
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.bus.profile = profile;
+    }
+
+    /// Installs `set`'s glyphs now, over whatever the program did to them, and on every
+    /// load_rom() from now on
+    pub fn set_font_set(&mut self, set: FontSet) {
+        self.fonts = set.fonts();
+        self.bus.memory.install_fonts(&self.fonts);
+    }
+
+    /// Also faults on writes below 0x200, which would overwrite the font or, on the original
+    /// interpreters, their own variables
+    pub fn set_strict_memory(&mut self, strict: bool) {
+        self.bus.strict = strict;
+    }
+
+    /// Watches for the program reading or writing a memory address or register from now on,
+    /// across resets and ROM loads. Debugger::run() pauses on what it catches
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.bus.watchpoints.push(watchpoint);
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.bus.watchpoints
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.bus.watchpoints.clear();
+    }
+
+    /// The first watched access since the last call, with the instruction that made it
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Runs `script`'s hooks from now on, in place of any script set before, across resets and
+    /// ROM loads
+    pub fn set_script(&mut self, script: Script) {
+        self.bus.hooked = script.writes();
+        self.script = Some(script);
+    }
+
+    /// Lines the script printed since the last call
+    pub fn take_script_output(&mut self) -> Vec<String> {
+        self.script.as_mut().map(Script::take_output).unwrap_or_default()
+    }
+
+    /// Runs the script's hooks for `event`, if there's a script
+    fn run_script(&mut self, event: script::Event) {
+        if let Some(mut script) = self.script.take() {
+            script.run(event, self);
+            self.script = Some(script);
+        }
+    }
+
+    /// Bytes the ROM wrote to the debug console since the last call
+    pub fn take_console_output(&mut self) -> Vec<u8> {
+        self.bus.take_console()
+    }
+
+    /// Selects which interpreter differences to emulate. Switching to or from CHIP-8X, the
+    /// ETI-660 or MegaChip reloads the ROM, as their programs load elsewhere or into more memory
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        let reload = quirks.chip_8x != self.quirks.chip_8x
+            || quirks.eti_660 != self.quirks.eti_660
+            || quirks.mega_chip != self.quirks.mega_chip
+            || quirks.wide_memory != self.quirks.wide_memory;
+        self.quirks = quirks;
+        if reload {
+            self.reset();
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// From now on faults on the instructions `variant` didn't have, and takes its quirks
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = Some(variant);
+        self.set_quirks(variant.quirks());
+    }
+
+    pub fn variant(&self) -> Option<Variant> {
+        self.variant
+    }
+
+    pub fn set_alignment_policy(&mut self, policy: AlignmentPolicy) {
+        self.alignment = policy;
+    }
+
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+    }
+
+    pub fn fault_policy(&self) -> FaultPolicy {
+        self.fault_policy
+    }
+
+    /// Lets subroutine calls nest `depth` deep, at most 255 as save states count them in a byte.
+    /// Calls already on the stack stay there
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack_depth = Some(depth.min(u8::MAX as usize));
+    }
+
+    /// How deep subroutine calls can nest before 2NNN faults
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth.unwrap_or(STACK_DEPTH)
+    }
+
+    /// Err with the fault that halted the machine under FaultPolicy::Exit
+    pub fn exit_status(&self) -> Result<(), Chip8Error> {
+        match self.fatal {
+            Some((pc, fault)) => Err(Chip8Error::Fault { pc, fault }),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether execution has stopped, e.g. on an odd pc under AlignmentPolicy::Error
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether step() is stalled until the next timer tick by the display wait quirk
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
+    /// Faults since the last call, with the address of the instruction that caused each
+    pub fn take_faults(&mut self) -> Vec<(u16, Fault)> {
+        self.bus.take_faults()
+    }
+
+    /// Running totals of executed instructions, draws, and invalid opcodes
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    /// The I register
+    pub fn index(&self) -> u16 {
+        self.cpu.i
+    }
+
+    /// V0 through VF
+    pub fn registers(&self) -> [u8; 16] {
+        self.cpu.v
+    }
+
+    /// Return addresses of the subroutines currently being executed, innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.cpu.stack
+    }
+
+    /// Where the instruction step() executed last was, and its opcode
+    pub fn last_instruction(&self) -> Option<(u16, u16)> {
+        self.last_instruction.map(|(addr, opcode, _)| (addr, opcode))
+    }
+
+    /// The delay and sound timers' current values
+    pub fn timers(&self) -> (u8, u8) {
+        (self.timers.delay, self.timers.sound)
+    }
+
+    /// The instruction step() executes next
+    pub fn next_instruction(&self) -> u16 {
+        u16::from_be_bytes([self.peek(self.cpu.pc), self.peek(self.cpu.pc.wrapping_add(1))])
+    }
+
+    /// Emulates one 60Hz frame: `instructions` instructions followed by one timer tick
+    pub fn run_frame(&mut self, instructions: usize) {
+        if self.quirks.vip_timing {
+            self.run_vip_frame();
+        } else {
+            for _ in 0..instructions {
+                self.step();
+            }
+        }
+        self.tick_timers();
+    }
+
+    /// A frame's worth of the VIP's machine cycles, however many instructions that is. Time
+    /// spent waiting for the display interrupt is lost rather than carried over
+    fn run_vip_frame(&mut self) {
+        self.vip_cycles += cycles::CYCLES_PER_FRAME as i64;
+        while self.vip_cycles > 0 {
+            if self.halted || self.vblank_wait {
+                self.vip_cycles = 0;
+                return;
+            }
+            let (pc, v) = (self.cpu.pc, self.cpu.v);
+            self.step();
+            let Some((addr, _, decoded)) = self.last_instruction.filter(|&(addr, ..)| addr == pc) else {
+                // nothing ran, e.g. the pc left memory
+                self.vip_cycles = 0;
+                return;
+            };
+            let skipped = self.cpu.pc == addr.wrapping_add(4);
+            let cycles = decoded.map_or(cycles::FETCH, |instruction| instruction.vip_cycles(&v, skipped));
+            self.vip_cycles -= cycles as i64;
+        }
+    }
+
+    /// Runs `n` frames, holding `keys_per_frame[i]` during frame `i` (no keys once it runs out),
+    /// and fingerprints the display after each one. Seed the RNG first for reproducible results
+    pub fn run_frames(&mut self, n: usize, keys_per_frame: &[u16]) -> FrameDigest {
+        let mut digest = FrameDigest::default();
+        for frame in 0..n {
+            self.set_keys(keys_per_frame.get(frame).copied().unwrap_or(0));
+            self.run_frame(INSTRUCTIONS_PER_FRAME);
+            digest.push(&self.display);
+        }
+        digest
+    }
+
+    /// Reseeds the random number generator behind CXNN, making runs reproducible
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = MachineRng::seeded(seed);
+    }
+
+    /// Makes the next CXNN instructions draw `bytes`, in order, before going back to the random
+    /// number generator; for tests and tool-assisted runs that need particular numbers
+    pub fn script_random(&mut self, bytes: &[u8]) {
+        self.rng.scripted.extend(bytes);
+    }
+
+    /// Counts the delay and sound timers down by one, and starts the next frame for latched keys;
+    /// call this at TIMER_HZ
+    pub fn tick_timers(&mut self) {
+        if self.halted {
+            return;
+        }
+        self.vblank_wait = false;
+        if let Some(keys) = self.pending_keys {
+            self.keys = keys;
+        }
+        self.vip_rng.interrupt();
+        self.timers.tick();
+        self.run_script(script::Event::Frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quirks::Preset;
+    use rand::Rng;
+
+    /// Flips one random bit somewhere in memory or the registers
+    fn chaos(chip_8: &mut Chip8, rng: &mut impl Rng) {
+        let bit = 1 << rng.gen_range(0..8);
+        let wide_bit = 1 << rng.gen_range(0..16);
+        match rng.gen_range(0..8) {
+            0 => {
+                let addr = rng.gen_range(0..memory::MEMORY_SIZE);
+                let byte = chip_8.bus.memory.get(addr);
+                chip_8.bus.memory.set(addr, byte ^ bit);
+            }
+            1 => chip_8.cpu.v[rng.gen_range(0..16)] ^= bit,
+            2 => chip_8.cpu.i ^= wide_bit,
+            3 => chip_8.cpu.pc ^= wide_bit,
+            4 => chip_8.timers.delay ^= bit,
+            5 => chip_8.timers.sound ^= bit,
+            // the edges of the address space, where off-by-one bugs live
+            6 => {
+                let edge = [0xFFE, 0xFFF, 0x1000, 0xFFFE, 0xFFFF][rng.gen_range(0..5)];
+                if rng.gen() { chip_8.cpu.pc = edge } else { chip_8.cpu.i = edge }
+            }
+            _ => match chip_8.cpu.stack.last_mut() {
+                Some(addr) if rng.gen() => *addr ^= wide_bit,
+                _ => { chip_8.decode(0x2000 | rng.gen_range(0..0x1000)); }
+            },
+        }
+    }
+
+    /// Chaos mode: random ROMs under every preset, with bits flipped between frames so the core
+    /// sees states no well-behaved program reaches. Nothing here may panic
+    #[test]
+    fn survives_random_corruption() {
+        for seed in 0..8 {
+            let mut rng = ChaCha12Rng::seed_from_u64(seed);
+            let rom: Vec<u8> = (0..0xE00).map(|_| rng.gen()).collect();
+            let mut chip_8 = Chip8::from_rom(&rom);
+            chip_8.seed_rng(seed);
+            chip_8.set_quirks(Quirks::preset([Preset::Chip8, Preset::Schip, Preset::Xochip][seed as usize % 3]));
+            chip_8.set_alignment_policy(AlignmentPolicy::Allow);
+            chip_8.enable_diagnostics();
+            for frame in 0..600 {
+                for _ in 0..rng.gen_range(0..4) {
+                    chaos(&mut chip_8, &mut rng);
+                }
+                chip_8.set_keys(rng.gen());
+                chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+                chip_8.take_faults();
+                if frame % 60 == 0 {
+                    let state = chip_8.save_state();
+                    chip_8.load_state(&state).expect("a machine can always load its own state");
+                }
+            }
+        }
+    }
+}
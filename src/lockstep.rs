@@ -0,0 +1,188 @@
+//! Running in lockstep with another emulator to find the first instruction where the two
+//! disagree. The other emulator runs as a child process speaking a line-based protocol on its
+//! stdin and stdout, which takes a small adapter to add to an emulator one trusts, e.g. Octo:
+//!
+//! ```text
+//! step N    execute N instructions, then reply with the state hash
+//! tick      count down the timers once, a 60Hz frame boundary; reply `ok`
+//! quit      exit; no reply
+//! ```
+//!
+//! The state hash is the CRC-32 (as in zip and PNG) of PC and I as two big-endian bytes each,
+//! V0 to VF, the delay and sound timers, all 4096 bytes of memory, then the display in its
+//! active resolution as a byte per pixel, 0 or 1, row by row from the top left. It's written as
+//! eight lowercase hex digits. Emulators tend to print as they go, so reply lines with spaces
+//! in them are taken for log output and skipped.
+//!
+//! `serve()` is this side of the protocol, so two builds of this crate can check each other.
+
+use std::io::{self, BufRead, Write};
+
+use crate::patch::crc32;
+use crate::Chip8;
+
+/// The hash both emulators compare, see the module documentation
+pub fn state_hash(chip_8: &Chip8) -> u32 {
+    let mut state = Vec::with_capacity(4 + 16 + 2 + chip_8.bus.memory.bytes().len() + 128 * 64);
+    state.extend_from_slice(&chip_8.cpu.pc.to_be_bytes());
+    state.extend_from_slice(&chip_8.cpu.i.to_be_bytes());
+    state.extend_from_slice(&chip_8.cpu.v);
+    state.extend_from_slice(&[chip_8.timers.delay, chip_8.timers.sound]);
+    state.extend_from_slice(chip_8.bus.memory.bytes());
+    state.extend(chip_8.display.pixels().map(|(_, _, lit)| lit as u8));
+    crc32(&state)
+}
+
+/// The reference emulator's end of the pipe
+pub struct Reference<R, W> {
+    replies: R,
+    commands: W,
+}
+
+impl<R: BufRead, W: Write> Reference<R, W> {
+    /// Talks to a reference emulator that reads `commands` and answers on `replies`
+    pub fn new(replies: R, commands: W) -> Self {
+        Self { replies, commands }
+    }
+
+    /// Has the reference execute `n` instructions and returns its state hash
+    pub fn step(&mut self, n: usize) -> io::Result<u32> {
+        let reply = self.command(&format!("step {n}"))?;
+        u32::from_str_radix(&reply, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("expected a state hash, got `{reply}`")))
+    }
+
+    pub fn tick(&mut self) -> io::Result<()> {
+        match self.command("tick")?.as_str() {
+            "ok" => Ok(()),
+            reply => Err(io::Error::new(io::ErrorKind::InvalidData, format!("expected `ok`, got `{reply}`"))),
+        }
+    }
+
+    pub fn quit(&mut self) -> io::Result<()> {
+        writeln!(self.commands, "quit")?;
+        self.commands.flush()
+    }
+
+    fn command(&mut self, command: &str) -> io::Result<String> {
+        writeln!(self.commands, "{command}")?;
+        self.commands.flush()?;
+        loop {
+            let mut reply = String::new();
+            if self.replies.read_line(&mut reply)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the reference emulator exited"));
+            }
+            let reply = reply.trim();
+            if !reply.contains(' ') {
+                return Ok(reply.to_string());
+            }
+        }
+    }
+}
+
+/// Where the two emulators first disagreed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions both had executed when the hashes differed
+    pub instructions: u64,
+    /// 60Hz frames that had gone by
+    pub frame: usize,
+    pub ours: u32,
+    pub theirs: u32,
+}
+
+/// Runs `frames` frames of `instructions_per_frame` on both `chip_8` and `reference`, comparing
+/// hashes after every `granularity` instructions. Stops at the first divergence, leaving
+/// `chip_8` just past the batch where it happened
+pub fn run<R: BufRead, W: Write>(
+    chip_8: &mut Chip8,
+    reference: &mut Reference<R, W>,
+    frames: usize,
+    instructions_per_frame: usize,
+    granularity: usize,
+) -> io::Result<Option<Divergence>> {
+    let granularity = granularity.clamp(1, instructions_per_frame.max(1));
+    let mut instructions = 0;
+    for frame in 0..frames {
+        let mut left = instructions_per_frame;
+        while left > 0 {
+            let batch = granularity.min(left);
+            for _ in 0..batch {
+                chip_8.step();
+            }
+            left -= batch;
+            instructions += batch as u64;
+            let (ours, theirs) = (state_hash(chip_8), reference.step(batch)?);
+            if ours != theirs {
+                return Ok(Some(Divergence { instructions, frame, ours, theirs }));
+            }
+        }
+        chip_8.tick_timers();
+        reference.tick()?;
+    }
+    Ok(None)
+}
+
+/// Answers the protocol for `chip_8` until told to quit or `commands` ends
+pub fn serve(chip_8: &mut Chip8, commands: impl BufRead, mut replies: impl Write) -> io::Result<()> {
+    for line in commands.lines() {
+        let line = line?;
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["step", n] => {
+                let n: usize = n.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad count `{n}`")))?;
+                for _ in 0..n {
+                    chip_8.step();
+                }
+                writeln!(replies, "{:08x}", state_hash(chip_8))?;
+            }
+            ["tick"] => {
+                chip_8.tick_timers();
+                writeln!(replies, "ok")?;
+            }
+            ["quit"] => break,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown command `{line}`"))),
+        }
+        replies.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+    use std::io::Cursor;
+
+    /// Replies `serve()` would give to `script`, as a reference to replay
+    fn recorded(chip_8: &mut Chip8, script: &str) -> Cursor<Vec<u8>> {
+        let mut replies = vec![];
+        serve(chip_8, script.as_bytes(), &mut replies).unwrap();
+        Cursor::new(replies)
+    }
+
+    #[test]
+    fn identical_machines_agree() {
+        // a shift, which the presets disagree on, then a loop
+        let rom = [0x61, 0x01, 0x62, 0x80, 0x81, 0x2E, 0x12, 0x06];
+        let script = "step 2\nstep 2\ntick\nstep 2\nstep 2\ntick\n";
+        let mut them = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build();
+        let mut replies = recorded(&mut them, script).into_inner();
+        // log output mixed in with the replies is skipped
+        replies.splice(0..0, b"Invalid instruction: 0xffff\n".iter().copied());
+        let mut reference = Reference::new(Cursor::new(replies), io::sink());
+        let mut us = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build();
+        assert_eq!(run(&mut us, &mut reference, 2, 4, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn stops_at_the_first_difference() {
+        let rom = [0x61, 0x01, 0x62, 0x80, 0x81, 0x2E, 0x12, 0x06];
+        let mut them = Chip8::builder().platform(Preset::Chip8).rom_bytes(&rom).build();
+        let mut reference = Reference::new(recorded(&mut them, "step 1\nstep 1\nstep 1\n"), io::sink());
+        let mut us = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build();
+        let divergence = run(&mut us, &mut reference, 1, 4, 1).unwrap().unwrap();
+        assert_eq!((divergence.instructions, divergence.frame), (3, 0));
+        assert_eq!(divergence.ours, state_hash(&us));
+        assert_eq!(divergence.theirs, state_hash(&them));
+    }
+}
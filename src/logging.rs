@@ -0,0 +1,169 @@
+//! Diagnostics from the core and the frontends, such as faults, odd jumps and what the host
+//! could set up, written to stderr or a file at the level picked with --log-level. Messages for
+//! the player, like a state being saved, are printed as they happen instead.
+//!
+//! Events go through [`event!`](crate::event), which only formats its message when the level
+//! is enabled, and [`Span`]s time a stretch of work between an event when it starts and one
+//! when it's done.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Level {
+    /// Nothing at all
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// Whether events at `level` get through when this is the level set
+    pub fn allows(self, level: Level) -> bool {
+        level != Level::Off && level <= self
+    }
+
+    fn from_u8(n: u8) -> Self {
+        [Level::Off, Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace][n as usize]
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Off => "off",
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        })
+    }
+}
+
+/// Writes events as lines stamped with the time since it was made
+pub struct Logger {
+    start: Instant,
+    output: Box<dyn Write + Send>,
+}
+
+impl Logger {
+    pub fn new(output: Box<dyn Write + Send>) -> Self {
+        Self { start: Instant::now(), output }
+    }
+
+    pub fn log(&mut self, level: Level, target: &str, message: fmt::Arguments) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        // nowhere left to report a failing log to
+        writeln!(self.output, "{elapsed:9.3}s {level:<5} {target}: {message}").ok();
+        self.output.flush().ok();
+    }
+}
+
+/// Info until set_level() says otherwise, as the frontends always printed that much; off in the
+/// browser, which has no stderr and no clock to stamp lines with
+static LEVEL: AtomicU8 = AtomicU8::new(if cfg!(target_arch = "wasm32") { Level::Off } else { Level::Info } as u8);
+/// Stderr until set_output() says otherwise
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+pub fn level() -> Level {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Sends events to `output` from now on, e.g. a log file
+pub fn set_output(output: Box<dyn Write + Send>) {
+    *LOGGER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Logger::new(output));
+}
+
+pub fn enabled(level: Level) -> bool {
+    self::level().allows(level)
+}
+
+/// Logs an event regardless of the level set; event!() checks it first
+pub fn log(level: Level, target: &str, message: fmt::Arguments) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    logger.get_or_insert_with(|| Logger::new(Box::new(std::io::stderr()))).log(level, target, message);
+}
+
+/// Logs `format!`-style arguments at a [`Level`], from the calling module
+#[macro_export]
+macro_rules! event {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::log($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+/// A stretch of work, logged when it starts and again with how long it took when dropped
+pub struct Span {
+    level: Level,
+    name: String,
+    /// None when the level wasn't enabled on entering
+    start: Option<Instant>,
+}
+
+impl Span {
+    pub fn enter(level: Level, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let start = enabled(level).then(Instant::now);
+        if start.is_some() {
+            log(level, &name, format_args!("started"));
+        }
+        Self { level, name, start }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(start) = self.start.filter(|_| enabled(self.level)) {
+            log(self.level, &self.name, format_args!("done in {:.3}s", start.elapsed().as_secs_f64()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn levels_let_through_the_more_severe_and_lines_name_where_they_came_from() {
+        assert!(Level::Warn.allows(Level::Error) && Level::Warn.allows(Level::Warn));
+        assert!(!Level::Warn.allows(Level::Info));
+        assert!(!Level::Off.allows(Level::Error) && !Level::Trace.allows(Level::Off));
+        for level in [Level::Off, Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace] {
+            assert_eq!(Level::from_u8(level as u8), level);
+        }
+
+        let output = Shared::default();
+        let mut logger = Logger::new(Box::new(output.clone()));
+        logger.log(Level::Warn, "chip_8_rs::cpu", format_args!("odd jump to {:#05x}", 0x201));
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let line = text.strip_suffix('\n').unwrap();
+        assert!(line.ends_with("s warn  chip_8_rs::cpu: odd jump to 0x201"), "{line}");
+    }
+}
@@ -1,395 +1,2293 @@
+// the messages and --frontend values only a left-out frontend uses are still there without it
+#![cfg_attr(not(all(feature = "tui", feature = "headless")), allow(dead_code))]
+
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
-use std::time::Duration;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use display::Display;
-use rand::Rng;
+use args::{Cli, Command, Frontend, RunArgs, VariantChoice};
+use crt::CrtEffects;
+use emulation::{Core, Emulation};
+use gamepad::Gamepads;
+use gdb_server::GdbServer;
+use i18n::{Locale, Msg};
+use input_log::InputLog;
+use peer::{Peer, Turn};
+use keymap::Keymap;
+use pause_menu::{Choice, PauseMenu};
+use record::Recorder;
+use rom_watch::RomWatch;
+use rotation::Rotation;
+use chip_8_rs::announce::Announcer;
+use chip_8_rs::audio::{Fader, Oscillator, PatternPlayer, Pitch, Tone, AUDIO_PATTERN_LEN};
+use chip_8_rs::cheats::Cheats;
+use chip_8_rs::compare::Comparison;
+use chip_8_rs::config::Config;
+use chip_8_rs::coverage::CoverageMap;
+use chip_8_rs::debugger::{Debugger, MemoryView, Registers, WatchExpr, MEMORY_ROW, MEMORY_ROWS};
+use chip_8_rs::devices::Buzzer;
+use chip_8_rs::display::{Afterglow, Display, FlashLimiter, FlickerFusion, Palette, PhosphorDecay};
+use chip_8_rs::highscore::{Leaderboard, PlayTimes, ScoreWatch};
+use chip_8_rs::hints::{Hints, KEYPAD_LAYOUT};
+use chip_8_rs::inputs::InputHistory;
+use chip_8_rs::journal::{read_journal, Frame, JournalWriter};
+use chip_8_rs::logging::{Level, Span};
+use chip_8_rs::crash::{self, CrashReport};
+use chip_8_rs::fault::{CrashLoopDetector, Fault};
+use chip_8_rs::quirks::{Preset, Quirks};
+use chip_8_rs::variant::Variant;
+use chip_8_rs::rewind::Rewind;
+use chip_8_rs::runahead::RunAhead;
+use chip_8_rs::script::Script;
+use chip_8_rs::export::{base64_decode, base64_encode, screenshot, WavWriter};
+use chip_8_rs::profiler::ProfileReport;
+use chip_8_rs::report::Report;
+use chip_8_rs::rom::{RomId, RomInfo, RomWarning};
+use chip_8_rs::pad::PadProfile;
+use chip_8_rs::practice::{LoopEnd, PracticeLoop};
+use chip_8_rs::romdb::RomDb;
+use chip_8_rs::stats::{Counters, RateMeter, Rates, SessionStats};
+use chip_8_rs::storage::{FileStorage, MemoryStorage, Storage};
+use chip_8_rs::symbols::Symbols;
+use chip_8_rs::timing::FixedTimestep;
+use chip_8_rs::menu::Recent;
+use chip_8_rs::{event, logging, menu, octo, zip, Chip8, FONT, RPL_FLAGS, TIMER_HZ};
+use clap::Parser;
 use sdl2::EventPump;
-use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice, AudioStatus};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::render::{Texture, Canvas};
-use sdl2::video::Window;
-use sdl2::pixels::PixelFormatEnum;
-
-pub mod args;
-pub mod display;
-
-pub const FONT: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-
-#[derive(Debug, Default)]
-pub struct Chip8 {
-    display: Display,
-    input: Option<u8>,
-    memory: Vec<u8>,
-    pc: u16,
-    i: u16,
-    stack: Vec<u16>,
-    v: [u8; 16],
-    dt: u8,
-    st: u8,
-}
-
-trait Nibbles {
-    fn x(&self) -> usize;
-    fn y(&self) -> usize;
-    fn n(&self) -> u8;
-    fn nn(&self) -> u8;
-    fn nnn(&self) -> u16;
-}
-
-impl Nibbles for u16 {
-    /// Returns the second-most significant 4 bits (0000_XXXX_0000_0000)
-    /// This is always used as a memory index 
-    fn x(&self) -> usize {
-        (self >> 8) as usize & 0xF
-    }
-    
-    /// Returns the second-least significant 4 bits (0000_0000_XXXX_0000)
-    /// This is always used as a memory index 
-    fn y(&self) -> usize {
-        (self >> 4) as usize & 0xF
-    }
-
-    /// Returns the least significant 4 bits (0000_0000_0000_XXXX)
-    fn n(&self) -> u8 {
-        *self as u8 & 0xF
-    }
-
-    /// Returns the lower byte
-    fn nn(&self) -> u8 {
-        *self as u8
-    }
-
-    /// Returns the lowest 12 bits
-    fn nnn(&self) -> u16 {
-        self & 0xFFF
-    }
-}
-
-impl Chip8 {
-    pub fn new(path: &str) -> Self {
-        let mut memory = vec![0; 512];
-        for i in 0..80 {
-            memory[0x50 + i] = FONT[i];
-        }
-        let file = File::open(Path::new(path)).expect("failed to open");
-        let mut buf = vec![];
-        let mut reader = BufReader::new(file);
-        reader.read_to_end(&mut buf).expect("failed to read file");
-        memory.append(&mut buf);
-        memory.resize(4096, 0);
-        Self { memory, pc: 0x200, ..Default::default() }
-    }
-
-    pub fn render(&mut self, texture: &mut Texture, canvas: &mut Canvas<Window>) {
-        if self.display.changed() {
-            self.display.render(texture, canvas);
-        }
-    }
-
-    pub fn beep(&mut self, audio_device: &AudioDevice<SquareWave>) {
-        match (self.st > 0, audio_device.status()) {
-            (true, AudioStatus::Paused) => audio_device.resume(),
-            (false, AudioStatus::Playing) => audio_device.pause(),
-            _ => {/*Do nothing*/}
+use sdl2::audio::{AudioCallback, AudioDevice};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Texture, TextureCreator, Canvas};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+mod args;
+mod commands;
+mod connect;
+mod crt;
+mod debug_overlay;
+mod emulation;
+mod gdb_server;
+mod gamepad;
+#[cfg(feature = "headless")]
+mod headless;
+mod i18n;
+mod input_log;
+mod keymap;
+mod keypad_panel;
+mod pause_menu;
+mod peer;
+mod record;
+mod rom_watch;
+mod rotation;
+mod serve;
+mod source;
+mod split_screen;
+mod startup;
+mod text;
+#[cfg(feature = "tui")]
+mod tui;
+
+/// The menu runs faster than games do, so a page of names draws in a frame or two
+const MENU_INSTRUCTIONS_PER_FRAME: usize = 200;
+
+/// How long audio takes to fade out when emulation pauses, and back in when it resumes
+const AUDIO_FADE: Duration = Duration::from_millis(5);
+
+/// How long a beep takes to rise to full volume and to die away again; switching the wave on
+/// and off at whatever point it's at pops
+const BEEP_RAMP: Duration = Duration::from_millis(3);
+
+/// How much faster than full speed the game runs while Tab is held
+const FAST_FORWARD: f64 = 5.0;
+
+/// How much faster than full speed --turbo-boot runs the start
+const TURBO_BOOT: f64 = 20.0;
+
+/// How far Ctrl+Minus and Ctrl+Equals can take the instruction rate from --ips, either way
+const MAX_RATE: f64 = 16.0;
+
+/// The beep, or XO-CHIP's pattern once a program loads one
+pub struct Speaker {
+    oscillator: Oscillator,
+    pattern: PatternPlayer,
+    fader: Fader,
+    /// Follows the sound timer, with the device left running so beeps ramp rather than cut
+    envelope: Fader,
+    /// Where --record-audio is writing what's played
+    capture: Option<AudioCapture>,
+}
+
+impl AudioCallback for Speaker {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [Self::Channel]) {
+        for x in out.iter_mut() {
+            let sample = self.pattern.next_sample().unwrap_or_else(|| self.oscillator.next_sample());
+            *x = sample * self.envelope.next_gain() * self.fader.next_gain();
+        }
+        if let Some(capture) = self.capture.as_mut() {
+            capture.write(out);
         }
     }
-
-    pub fn get_input(&mut self, event_pump: &mut EventPump) {
-        if let Some(event) = event_pump.poll_event() {
-            use Keycode::*;
-            match event {
-                Event::Quit { .. } |
-                Event::KeyDown { keycode: Some(Escape), .. } => std::process::exit(0),
-                Event::KeyDown { keycode: Some(Num1), .. } => self.input = Some(0x1),
-                Event::KeyDown { keycode: Some(Num2), .. } => self.input = Some(0x2),
-                Event::KeyDown { keycode: Some(Num3), .. } => self.input = Some(0x3),
-                Event::KeyDown { keycode: Some(Num4), .. } => self.input = Some(0xC),
-                Event::KeyDown { keycode: Some(Q), .. } => self.input = Some(0x4),
-                Event::KeyDown { keycode: Some(W), .. } => self.input = Some(0x5),
-                Event::KeyDown { keycode: Some(E), .. } => self.input = Some(0x6), 
-                Event::KeyDown { keycode: Some(R), .. } => self.input = Some(0xD),
-                Event::KeyDown { keycode: Some(A), .. } => self.input = Some(0x7),
-                Event::KeyDown { keycode: Some(S), .. } => self.input = Some(0x8),
-                Event::KeyDown { keycode: Some(D), .. } => self.input = Some(0x9),
-                Event::KeyDown { keycode: Some(F), .. } => self.input = Some(0xE),
-                Event::KeyDown { keycode: Some(Z), .. } => self.input = Some(0xA),
-                Event::KeyDown { keycode: Some(X), .. } => self.input = Some(0x0),
-                Event::KeyDown { keycode: Some(C), .. } => self.input = Some(0xB),
-                Event::KeyDown { keycode: Some(V), .. } => self.input = Some(0xF),
-                _ => self.input = None,
+}
+
+/// A WAV being written from the audio thread, which has nowhere to report a failed write, so
+/// the first error is kept for finish()
+struct AudioCapture {
+    wav: WavWriter<BufWriter<File>>,
+    error: Option<std::io::Error>,
+}
+
+impl AudioCapture {
+    fn start(path: &Path, sample_rate: i32) -> std::io::Result<Self> {
+        let wav = WavWriter::new(BufWriter::new(File::create(path)?), sample_rate as u32)?;
+        Ok(Self { wav, error: None })
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        if self.error.is_none() {
+            self.error = self.wav.write(samples).err();
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self.error {
+            Some(e) => Err(e),
+            None => self.wav.finish().map(drop),
+        }
+    }
+}
+
+/// Everything drawn on top of the game
+struct Overlays<'a> {
+    /// Pixels to mark, e.g. where a frame diverged from its reference
+    highlight: &'a [(usize, usize)],
+    /// Draw the keypad with these keys lit
+    keypad: Option<u16>,
+    /// The clickable keypad below the game, with the keys held and the keys the ROM checked
+    panel: Option<(u16, u16)>,
+    inputs: Option<&'a InputHistory>,
+    crt: CrtEffects,
+    /// The machine whose registers to show
+    registers: Option<&'a Chip8>,
+    /// The memory pane, and whether its cursor is shown for editing
+    memory: Option<(&'a Chip8, &'a MemoryView, bool)>,
+    /// The machine and the --show values to show from it
+    watches: Option<(&'a Chip8, &'a [WatchExpr])>,
+    /// How many instructions run for every one at full speed and --ips, shown unless it's 1
+    speed: f64,
+    /// The ROM's file name and the last second's rates
+    hud: Option<(&'a str, &'a RomId, Rates)>,
+    /// The pause menu's lines and the one selected
+    pause_menu: Option<(&'a [String], usize)>,
+}
+
+/// The texture the display is drawn into, one texel per pixel of its active resolution, which
+/// the canvas scales to fill the window
+struct Screen<'a> {
+    creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    /// Whether the texture was just made and holds no frame yet
+    blank: bool,
+}
+
+impl<'a> Screen<'a> {
+    fn new(creator: &'a TextureCreator<WindowContext>) -> Result<Self, String> {
+        let texture = creator.create_texture_target(PixelFormatEnum::RGB24, 64, 32).map_err(|e| e.to_string())?;
+        Ok(Self { creator, texture, blank: true })
+    }
+
+    /// Makes the texture `width` by `height` if it isn't already
+    fn fit(&mut self, width: usize, height: usize) -> Result<(), String> {
+        let query = self.texture.query();
+        if (query.width as usize, query.height as usize) != (width, height) {
+            self.texture = self
+                .creator
+                .create_texture_target(PixelFormatEnum::RGB24, width as u32, height as u32)
+                .map_err(|e| e.to_string())?;
+            self.blank = true;
+        }
+        Ok(())
+    }
+}
+
+/// Draws `display`, or `afterglow`'s blend of its recent frames, turned by `rotation` with
+/// `overlays` on top
+fn render(
+    display: &Display,
+    afterglow: Option<&dyn Afterglow>,
+    overlays: &Overlays,
+    rotation: Rotation,
+    screen: &mut Screen,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+    let (width, height) = display.dimensions();
+    screen.fit(width, height)?;
+    // the texture keeps the last frame, so only the rows that changed since are redrawn, except
+    // in a new texture or with an afterglow, which changes pixels the program didn't touch
+    let rows: Vec<usize> = match std::mem::take(&mut screen.blank) || afterglow.is_some() {
+        true => (0..height).collect(),
+        false => display.dirty_rows().collect(),
+    };
+    for run in rows.chunk_by(|&above, &below| above + 1 == below) {
+        let mut data = Vec::with_capacity(run.len() * width * 3);
+        for &y in run {
+            for x in 0..width {
+                let color = match afterglow {
+                    Some(afterglow) => afterglow.color(x, y),
+                    None => display.color(x, y),
+                };
+                data.extend_from_slice(&color);
+            }
+        }
+        let rect = Rect::new(0, run[0] as i32, width as u32, run.len() as u32);
+        screen.texture.update(rect, &data, width * 3).map_err(|e| e.to_string())?;
+    }
+    // the top of the canvas, which also has the keypad panel below the display when it's shown
+    let at = rotation.screen();
+    canvas.copy_ex(&screen.texture, None, at, rotation.degrees(), None, false, false)?;
+    let (columns, rows) = if rotation.portrait() { (32, 64) } else { (64, 32) };
+    crt::draw(canvas, overlays.crt, (columns, rows));
+    if let Some((held, checked)) = overlays.panel {
+        keypad_panel::draw(canvas, held, checked);
+    }
+    if !overlays.highlight.is_empty() {
+        // the canvas is scaled to 64x32 logical pixels whatever the display resolution
+        canvas.set_draw_color(Color::RED);
+        for &(x, y) in overlays.highlight {
+            canvas.draw_point(rotation.point((x * 64 / width) as i32, (y * 32 / height) as i32))?;
+        }
+    }
+    if let Some(keys) = overlays.keypad {
+        // upright, to be read, in the middle of the display whichever way it's turned
+        draw_keypad(canvas, keys, (at.center().x() - 32, at.center().y() - 16));
+    }
+    if let Some(inputs) = overlays.inputs {
+        draw_input_display(canvas, inputs);
+    }
+    if let Some(chip_8) = overlays.registers {
+        debug_overlay::draw(canvas, chip_8);
+    }
+    // under the memory pane, which goes in the same corner
+    if let Some((chip_8, exprs)) = overlays.watches {
+        debug_overlay::draw_watches(canvas, chip_8, exprs);
+    }
+    if let Some((chip_8, view, editing)) = overlays.memory {
+        debug_overlay::draw_memory(canvas, chip_8, view, editing);
+    }
+    if overlays.speed != 1.0 {
+        debug_overlay::draw_speed(canvas, overlays.speed);
+    }
+    if let Some((rom, id, rates)) = overlays.hud {
+        debug_overlay::draw_hud(canvas, rom, id, rates);
+    }
+    if let Some((lines, selected)) = overlays.pause_menu {
+        debug_overlay::draw_pause_menu(canvas, lines, selected);
+    }
+    Ok(())
+}
+
+/// Height of the input display strip in window pixels
+const INPUT_DISPLAY_HEIGHT: u32 = 36;
+
+/// Draws the held keys along the bottom of the window: the keypad as it is this frame, then one
+/// 2px column per recent frame with a row per key, newest on the right. Faint lines mark every
+/// 10th frame and brighter ones every 60th, so press frames can be counted off the strip
+fn draw_input_display(canvas: &mut Canvas<Window>, inputs: &InputHistory) {
+    // laid out in window pixels, so the strip stays readable however the game is scaled
+    let (logical_width, logical_height) = canvas.logical_size();
+    canvas.set_logical_size(0, 0).expect("failed to draw input display");
+    let (width, height) = canvas.output_size().expect("failed to draw input display");
+    let top = height.saturating_sub(INPUT_DISPLAY_HEIGHT) as i32;
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+    canvas.fill_rect(Rect::new(0, top, width, INPUT_DISPLAY_HEIGHT)).expect("failed to draw input display");
+    let lit = |pressed: bool| if pressed { Color::YELLOW } else { Color::RGB(48, 48, 48) };
+
+    let current = inputs.current();
+    for (row, layout) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (col, &key) in layout.iter().enumerate() {
+            canvas.set_draw_color(lit(current & 1 << key != 0));
+            let cell = Rect::new(2 + col as i32 * 8, top + 2 + row as i32 * 8, 7, 7);
+            canvas.fill_rect(cell).expect("failed to draw input display");
+        }
+    }
+
+    let strip_left = 40;
+    let columns = (width as usize).saturating_sub(strip_left as usize) / 2;
+    let frames: Vec<_> = inputs.frames().collect();
+    for (n, &(frame, keys)) in frames[frames.len().saturating_sub(columns)..].iter().enumerate() {
+        let x = strip_left + n as i32 * 2;
+        if frame % 10 == 0 {
+            let shade = if frame % 60 == 0 { 128 } else { 64 };
+            canvas.set_draw_color(Color::RGB(shade, shade, shade));
+            canvas.draw_line(Point::new(x, top + 1), Point::new(x, top + 34)).expect("failed to draw input display");
+        }
+        canvas.set_draw_color(Color::YELLOW);
+        for key in (0..16).filter(|key| keys & 1 << key != 0) {
+            canvas.fill_rect(Rect::new(x, top + 2 + key * 2, 2, 2)).expect("failed to draw input display");
+        }
+    }
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_logical_size(logical_width, logical_height).expect("failed to draw input display");
+}
+
+/// Draws the keypad over the middle of the 64x32 area with its top left corner at `(left, top)`,
+/// lighting up the keys in `keys`
+fn draw_keypad(canvas: &mut Canvas<Window>, keys: u16, (left, top): (i32, i32)) {
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+    canvas.fill_rect(Rect::new(left + 18, top, 28, 32)).expect("failed to draw overlay");
+    for (row, layout) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (col, &key) in layout.iter().enumerate() {
+            let lit = keys & 1 << key != 0;
+            canvas.set_draw_color(if lit { Color::YELLOW } else { Color::RGB(64, 64, 64) });
+            let glyph = &FONT[key as usize * 5..][..5];
+            for (dy, bits) in glyph.iter().enumerate() {
+                for dx in (0..4).filter(|dx| bits & 0x80 >> dx != 0) {
+                    // 6x7 cells leave a pixel between glyphs, centered on the 64x32 canvas
+                    let point = Point::new(left + (20 + col * 6 + dx) as i32, top + (2 + row * 7 + dy) as i32);
+                    canvas.draw_point(point).expect("failed to draw overlay");
+                }
+            }
+        }
+    }
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+/// The audio device as the machine's buzzer, started and stopped with the sound timer and
+/// playing what the machine plays
+struct SdlBuzzer<'a>(&'a mut AudioDevice<Speaker>);
+
+impl Buzzer for SdlBuzzer<'_> {
+    fn sound(&mut self, on: bool, pattern: Option<[u8; AUDIO_PATTERN_LEN]>, rate: f32) {
+        let mut speaker = self.0.lock();
+        speaker.pattern.set(pattern, rate);
+        speaker.envelope.set(on);
+    }
+}
+
+/// Modifiers that turn Backspace from rewind into reset (Shift) and soft reset (Ctrl), and Tab
+/// from fast forward into slow motion (Shift); Ctrl also makes C and V copy and paste, R start
+/// and stop recording, D toggle the debug overlay, M the memory pane, 1 to 3 toggle the CRT
+/// effects, and Minus and Equals halve and double the instruction rate
+const SHIFT: Mod = Mod::LSHIFTMOD.union(Mod::RSHIFTMOD);
+const CTRL: Mod = Mod::LCTRLMOD.union(Mod::RCTRLMOD);
+/// With Enter, toggles fullscreen
+const ALT: Mod = Mod::LALTMOD.union(Mod::RALTMOD);
+
+/// A plain 64x32 image is too small to make out once pasted
+const CLIPBOARD_SCREENSHOT_SCALE: usize = 4;
+
+/// Number of save state slots, selected with F6/F7
+const SAVE_SLOTS: u8 = 10;
+
+/// The --config file and what its settings go on top of
+struct ConfigFile {
+    path: PathBuf,
+    watch: RomWatch,
+    /// The keymap before the file's bindings, and unturned
+    keymap: Keymap,
+    rotation: Rotation,
+    /// --ips, which the file's rate is taken as a multiple of
+    ips: u32,
+}
+
+/// Frontend state that lives for the whole run: persistence and what the hotkeys act on
+struct Session {
+    storage: Box<dyn Storage>,
+    /// Identifies the ROM, its CRC-32 naming what's stored for it
+    rom_id: RomId,
+    /// The RPL flags as last stored, so they're only written when the ROM changes them
+    rpl_flags: [u8; RPL_FLAGS],
+    save_rom: Option<PathBuf>,
+    locale: Locale,
+    slot: u8,
+    states_saved: u64,
+    /// How long each ROM has been played for, counted up to when this one started
+    play_times: PlayTimes,
+    play_started: std::time::Instant,
+    announcer: Announcer,
+    /// Where announcements go, until writing to it fails
+    announcements: Option<Box<dyn Write>>,
+    /// Whether the rewind key is held
+    rewinding: bool,
+    /// Whether the fast forward key is held
+    fast_forward: bool,
+    /// Whether the slow motion key is held
+    slow_motion: bool,
+    /// The instruction rate as a multiple of --ips, leaving the timers at 60Hz
+    rate: f64,
+    /// What N runs while paused to advance a frame
+    instructions_per_frame: usize,
+    hints: Hints,
+    /// Whether the keypad overlay is drawn over the game
+    show_hints: bool,
+    /// Whether the input display strip is drawn
+    show_inputs: bool,
+    /// Notices the ROM file being rebuilt
+    rom_watch: RomWatch,
+    /// The --config file, if there is one
+    config: Option<ConfigFile>,
+    /// Quirks from the config file, waiting for the next reset
+    pending_quirks: Option<Quirks>,
+    /// Whether the clickable keypad is below the game
+    show_panel: bool,
+    /// The panel key held down with the mouse
+    clicked: Option<u8>,
+    /// Whether the registers are drawn over the game
+    show_registers: bool,
+    /// The memory pane, while it is open
+    memory_view: Option<MemoryView>,
+    title: String,
+    debugger: Debugger,
+    /// Names for the ROM's addresses, shown by the debugger and in traces
+    symbols: Symbols,
+    /// Hex digits typed so far while entering a breakpoint address
+    prompt: Option<String>,
+    preset: Preset,
+    keymap: Keymap,
+    gamepads: Gamepads,
+    /// What F3 switches to after a crash loop
+    suggested_preset: Option<Preset>,
+    crt: CrtEffects,
+    /// Names screenshots
+    rom_name: String,
+    screenshot_dir: PathBuf,
+    screenshot_scale: usize,
+    screenshot_text: bool,
+    /// The recording in progress and where it's going
+    recording: Option<(Recorder, PathBuf)>,
+    record_scale: usize,
+    /// Whether losing focus pauses the machine
+    pause_in_background: bool,
+    /// Whether it did, so getting it back resumes, and not a pause the player chose
+    paused_in_background: bool,
+    /// The Escape menu, while it is up
+    pause_menu: Option<PauseMenu>,
+    /// Whether a netplay peer is connected, whose machine has to stay in step with ours
+    netplay: bool,
+    /// While --turbo-boot fast forwards the start, the counters as it began, so the first draw
+    /// or keypad check ends it
+    booting: Option<Counters>,
+    /// The practice loop marked with Ctrl+A, and where --loop-end ends new ones
+    practice: Option<PracticeLoop>,
+    loop_end: Option<LoopEnd>,
+    /// Whether the loop's end is a breakpoint it added, to take away with the loop
+    loop_breakpoint: bool,
+}
+
+impl Session {
+    fn state_key(&self) -> String {
+        format!("states/{}/{}.state", self.rom_id.key(), self.slot)
+    }
+
+    fn rpl_key(&self) -> String {
+        format!("rpl/{}.bin", self.rom_id.key())
+    }
+
+    /// Gives the machine the RPL flags the ROM saved on an earlier run
+    fn load_rpl_flags(&mut self, chip_8: &mut Chip8) {
+        match self.storage.read(&self.rpl_key()) {
+            Ok(Some(stored)) => {
+                let n = stored.len().min(RPL_FLAGS);
+                self.rpl_flags[..n].copy_from_slice(&stored[..n]);
+                chip_8.set_rpl_flags(self.rpl_flags);
+            }
+            Ok(None) => {}
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::RplReadFailed, &[&e])),
+        }
+    }
+
+    /// Sends the machine's state to --announce-to or stdout, now, or at the end of the frame if
+    /// `periodic` and one is due
+    fn announce(&mut self, chip_8: &Chip8, periodic: bool) {
+        let text = match periodic {
+            true => self.announcer.frame(chip_8),
+            false => Some(self.announcer.announce(chip_8)),
+        };
+        if let (Some(text), Some(out)) = (text, self.announcements.as_mut()) {
+            if let Err(e) = out.write_all(text.as_bytes()).and_then(|()| out.flush()) {
+                event!(Level::Warn, "{}", self.locale.format(Msg::AnnounceFailed, &[&e]));
+                self.announcements = None;
+            }
+        }
+    }
+
+    /// Counts the time since the ROM started, or since this was last called, as played
+    fn count_play_time(&mut self) {
+        let now = std::time::Instant::now();
+        self.play_times.add(&self.rom_id.key(), now - self.play_started);
+        self.play_started = now;
+    }
+
+    /// Writes the RPL flags out as soon as FX75 changes them, so a crash doesn't lose them
+    fn save_rpl_flags(&mut self, chip_8: &Chip8) {
+        let flags = chip_8.rpl_flags();
+        if flags == self.rpl_flags {
+            return;
+        }
+        // remembered even if the write fails, to complain once rather than every frame
+        self.rpl_flags = flags;
+        if let Err(e) = self.storage.write(&self.rpl_key(), &flags) {
+            event!(Level::Warn, "{}", self.locale.format(Msg::RplSaveFailed, &[&e]));
+        }
+    }
+
+    fn save_state(&mut self, chip_8: &Chip8) {
+        match self.storage.write(&self.state_key(), &chip_8.save_state()) {
+            Ok(()) => {
+                self.states_saved += 1;
+                println!("{}", self.locale.format(Msg::StateSaved, &[&self.slot]));
+            }
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::StateSaveFailed, &[&self.slot, &e])),
+        }
+    }
+
+    fn autosave_key(&self) -> String {
+        format!("states/{}/autosave.state", self.rom_id.key())
+    }
+
+    /// Saves the state --autosave picks up from the next time the ROM runs
+    fn autosave(&mut self, chip_8: &Chip8) {
+        match self.storage.write(&self.autosave_key(), &chip_8.save_state()) {
+            Ok(()) => println!("{}", self.locale.text(Msg::Autosaved)),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::AutosaveFailed, &[&e])),
+        }
+    }
+
+    /// Picks up from the ROM's autosave, if it has one
+    fn restore_autosave(&mut self, chip_8: &mut Chip8) {
+        let loaded = match self.storage.read(&self.autosave_key()) {
+            Ok(Some(state)) => chip_8.load_state(&state).map_err(|e| e.to_string()),
+            Ok(None) => return,
+            Err(e) => Err(e.to_string()),
+        };
+        match loaded {
+            Ok(()) => println!("{}", self.locale.text(Msg::AutosaveLoaded)),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::AutosaveLoadFailed, &[&e])),
+        }
+    }
+
+    /// Puts the screen on the clipboard, or with `state` a save state, both as base64 text since
+    /// that's all SDL's clipboard holds. The screenshot is a data: URL that browsers can open
+    fn copy(&self, chip_8: &Chip8, window: &Window, state: bool) {
+        let (text, msg) = if state {
+            (base64_encode(&chip_8.save_state()), Msg::StateCopied)
+        } else {
+            (format!("data:image/png;base64,{}", base64_encode(&screenshot(chip_8.framebuffer(), CLIPBOARD_SCREENSHOT_SCALE))), Msg::ScreenshotCopied)
+        };
+        match window.subsystem().clipboard().set_clipboard_text(&text) {
+            Ok(()) => println!("{}", self.locale.text(msg)),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::ClipboardFailed, &[&e])),
+        }
+    }
+
+    /// Runs instructions at `rate` times --ips, within MAX_RATE of it
+    fn set_rate(&mut self, rate: f64) {
+        self.rate = rate.clamp(1.0 / MAX_RATE, MAX_RATE);
+        println!("{}", self.locale.format(Msg::InstructionRate, &[&self.rate]));
+    }
+
+    /// Saves the screen as <ROM>-<milliseconds since 1970>.png, plus a .txt dump if asked to
+    fn screenshot(&self, chip_8: &Chip8) {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = self.screenshot_dir.join(format!("{}-{millis}.png", self.rom_name));
+        let display = chip_8.framebuffer();
+        let res = std::fs::write(&path, screenshot(display, self.screenshot_scale))
+            .and_then(|()| if self.screenshot_text {
+                std::fs::write(path.with_extension("txt"), display.to_string())
+            } else {
+                Ok(())
+            });
+        match res {
+            Ok(()) => println!("{}", self.locale.format(Msg::ScreenshotSaved, &[&path.display()])),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::ScreenshotFailed, &[&path.display(), &e])),
+        }
+    }
+
+    /// Writes a crash report for `fault` at `pc` into `dir` as
+    /// <ROM>-crash-<milliseconds since 1970>.txt
+    fn write_crash_report(&self, chip_8: &Chip8, dir: &Path, pc: u16, fault: Fault) {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = dir.join(format!("{}-crash-{millis}.txt", self.rom_name));
+        let report = CrashReport { chip_8, rom_id: &self.rom_id, symbols: &self.symbols, pc, fault };
+        match std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&path, report.to_string())) {
+            Ok(()) => println!("{}", self.locale.format(Msg::CrashReportWritten, &[&path.display()])),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::CrashReportFailed, &[&path.display(), &e])),
+        }
+    }
+
+    fn start_recording(&mut self, chip_8: &Chip8, path: PathBuf) {
+        match Recorder::start(&path, chip_8.framebuffer(), self.record_scale) {
+            Ok(recorder) => {
+                println!("{}", self.locale.format(Msg::RecordingStarted, &[&path.display()]));
+                self.recording = Some((recorder, path));
             }
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::RecordingFailed, &[&path.display(), &e])),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some((recorder, path)) = self.recording.take() else {
+            return;
+        };
+        match recorder.finish() {
+            Ok(()) => println!("{}", self.locale.format(Msg::RecordingSaved, &[&path.display()])),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::RecordingFailed, &[&path.display(), &e])),
+        }
+    }
+
+    /// Stops the recording in progress, or starts a GIF named like screenshots are
+    fn toggle_recording(&mut self, chip_8: &Chip8) {
+        if self.recording.is_some() {
+            self.stop_recording();
         } else {
-            self.input = None;
+            let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            let path = self.screenshot_dir.join(format!("{}-{millis}.gif", self.rom_name));
+            self.start_recording(chip_8, path);
+        }
+    }
+
+    /// Adds the displayed frame to the recording, dropping the recording if that fails
+    fn record_frame(&mut self, chip_8: &Chip8) {
+        let Some((recorder, path)) = self.recording.as_mut() else {
+            return;
+        };
+        if let Err(e) = recorder.frame(chip_8.framebuffer()) {
+            event!(Level::Warn, "{}", self.locale.format(Msg::RecordingFailed, &[&path.display(), &e]));
+            self.recording = None;
+        }
+    }
+
+    /// Loads a save state copied with copy(), returning whether it did
+    fn paste_state(&self, chip_8: &mut Chip8, window: &Window) -> bool {
+        let text = match window.subsystem().clipboard().clipboard_text() {
+            Ok(text) => text,
+            Err(e) => {
+                event!(Level::Warn, "{}", self.locale.format(Msg::ClipboardFailed, &[&e]));
+                return false;
+            }
+        };
+        let Some(state) = base64_decode(&text) else {
+            println!("{}", self.locale.text(Msg::ClipboardNoState));
+            return false;
+        };
+        match chip_8.load_state(&state) {
+            Ok(()) => {
+                println!("{}", self.locale.text(Msg::StatePasted));
+                true
+            }
+            Err(e) => {
+                println!("{}", self.locale.format(Msg::PastedStateInvalid, &[&e]));
+                false
+            }
+        }
+    }
+
+    /// Marks a practice loop starting from here, replacing any other, or with `clear` just
+    /// takes the loop away. An address ending it is a breakpoint, and the loop goes back to its
+    /// start when the debugger stops there
+    fn mark_loop(&mut self, chip_8: &Chip8, clear: bool) {
+        self.drop_loop_breakpoint();
+        if clear {
+            self.practice = None;
+            println!("{}", self.locale.text(Msg::LoopCleared));
+            return;
+        }
+        self.practice = Some(PracticeLoop::mark(chip_8, self.loop_end));
+        match self.loop_end {
+            Some(LoopEnd::Address(addr)) => {
+                self.loop_breakpoint = self.debugger.breakpoints.insert(addr);
+                println!("{}", self.locale.format(Msg::LoopMarkedAt, &[&self.symbols.describe(addr)]));
+            }
+            Some(LoopEnd::Frames(frames)) => {
+                let secs = format_args!("{:.1}", frames / TIMER_HZ as f64);
+                println!("{}", self.locale.format(Msg::LoopMarkedAfter, &[&secs]));
+            }
+            None => println!("{}", self.locale.text(Msg::LoopMarkedOpen)),
+        }
+    }
+
+    /// Ends the practice loop here, going back to its start, if there is one
+    fn end_loop_here(&mut self, chip_8: &mut Chip8) -> Control {
+        self.drop_loop_breakpoint();
+        let Some(practice) = self.practice.as_mut() else {
+            println!("{}", self.locale.text(Msg::NoLoop));
+            return Control::Continue;
+        };
+        if let LoopEnd::Frames(frames) = practice.end_here(chip_8) {
+            let secs = format_args!("{:.1}", frames / TIMER_HZ as f64);
+            println!("{}", self.locale.format(Msg::LoopEndMarked, &[&secs]));
+        }
+        Control::Restarted
+    }
+
+    /// Takes away the breakpoint the practice loop added to end at
+    fn drop_loop_breakpoint(&mut self) {
+        if let Some(LoopEnd::Address(addr)) = self.practice.as_ref().and_then(PracticeLoop::end) {
+            if std::mem::take(&mut self.loop_breakpoint) {
+                self.debugger.breakpoints.remove(&addr);
+            }
         }
     }
 
-    fn fetch(&mut self) -> u16 {
-        let i = self.pc as usize;
-        self.pc += 2;
-        u16::from_be_bytes([self.memory[i], self.memory[i + 1]])
+    fn toggle_pause(&mut self, chip_8: &Chip8) {
+        if self.debugger.paused() {
+            self.debugger.resume();
+            println!("{}", self.locale.text(Msg::Resumed));
+        } else {
+            self.debugger.pause();
+            print!("{}\n{}", self.locale.text(Msg::Paused), Registers(chip_8));
+        }
     }
 
-    fn tick(&mut self) {
-        let instruction = self.fetch();
-        self.decode(instruction);
-        if self.dt > 0 {
-            self.dt -= 1;
+    /// Applies the --config file's settings: all but quirks at once, and quirks too when
+    /// `starting`, with nothing run yet, or else at the next reset. A bad file is only
+    /// reported, leaving the settings as they were
+    fn apply_config(&mut self, chip_8: &mut Chip8, starting: bool) {
+        let Some(file) = &self.config else {
+            return;
+        };
+        let locale = self.locale;
+        let config = std::fs::read_to_string(&file.path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Config::parse(&text))
+            .and_then(|config| {
+                let bindings = config.bind.iter().map(|s| keymap::parse_binding(s)).collect::<Result<Vec<_>, _>>()?;
+                Ok((config, bindings))
+            });
+        let (config, bindings) = match config {
+            Ok(config) => config,
+            Err(e) => {
+                println!("{}", locale.format(Msg::ConfigInvalid, &[&file.path.display(), &e]));
+                return;
+            }
+        };
+        let mut keymap = file.keymap.clone();
+        for (binding, key) in bindings {
+            keymap.bind(binding, key);
         }
-        if self.st > 0 {
-            self.st -= 1;
+        keymap.turn(file.rotation);
+        self.keymap = keymap;
+        let (path, ips) = (file.path.clone(), file.ips);
+        if let Some(palette) = config.palette {
+            chip_8.set_palette(palette);
+        }
+        if !starting {
+            println!("{}", locale.format(Msg::ConfigReloaded, &[&path.display()]));
+        }
+        if let Some(rate) = config.ips {
+            self.set_rate(rate as f64 / ips as f64);
+        }
+        match config.quirks(chip_8.quirks()) {
+            Some(quirks) if starting => chip_8.set_quirks(quirks),
+            Some(quirks) if quirks != chip_8.quirks() => {
+                self.pending_quirks = Some(quirks);
+                println!("{}", locale.text(Msg::QuirksOnReset));
+            }
+            _ => self.pending_quirks = None,
+        }
+    }
+
+    /// Restarts the ROM, under the config file's new quirks if they're waiting
+    fn reset(&mut self, chip_8: &mut Chip8) {
+        if let Some(quirks) = self.pending_quirks.take() {
+            chip_8.set_quirks(quirks);
         }
+        chip_8.reset();
+        self.debugger.resume();
+        println!("{}", self.locale.text(Msg::Reset));
     }
 
-    fn decode(&mut self, instruction: u16) {
-        match instruction >> 12 {
-            0x0 => match instruction.nnn() {
-                    0x0E0 => self.display.clear(),
-                    0x0EE => { self.pc = self.stack.pop().expect("stack is empty") },
-                    // SuperChip instructions
-                    0x0FF => { /*enable 128x64 graphics*/ }
-                    0x0FE => { /*disable 128x64 graphics*/ }
-                    _n @ 0x0C0..=0x0CF => self.display.scroll_down(instruction.n() as usize),
-                    0x0FB => self.display.scroll_right(),
-                    0x0FC => self.display.scroll_left(),
-                    _ => { /*Ignore for modern interpreters*/ }
+    /// Restarts the ROM under another quirks preset
+    fn switch_preset(&mut self, chip_8: &mut Chip8, preset: Preset) {
+        self.preset = preset;
+        self.pending_quirks = None;
+        chip_8.set_quirks(Quirks::preset(preset));
+        chip_8.reset();
+        self.debugger.resume();
+        println!("{}", self.locale.format(Msg::PresetSwitched, &[&preset]));
+    }
+
+    /// Brings up the pause menu, pausing the machine under it
+    fn open_pause_menu(&mut self) {
+        self.pause_menu = Some(PauseMenu::new(self.debugger.paused()));
+        self.debugger.pause();
+    }
+
+    /// Handles a key press while the pause menu is up
+    fn pause_menu_key(&mut self, chip_8: &mut Chip8, keycode: Keycode) -> Control {
+        let Some(menu) = self.pause_menu.as_mut() else {
+            return Control::Continue;
+        };
+        let was_paused = menu.was_paused;
+        match menu.key(keycode) {
+            None => Control::Continue,
+            Some(Choice::LoadRom | Choice::Reset | Choice::Quirks) if self.netplay_refuses() => Control::Continue,
+            Some(choice @ (Choice::Resume | Choice::LoadRom)) => {
+                self.pause_menu = None;
+                if !was_paused {
+                    self.debugger.resume();
                 }
-            0x1 => self.pc = instruction.nnn(),
-            0x2 => {
-                self.stack.push(self.pc);
-                self.pc = instruction.nnn();
+                if choice == Choice::LoadRom { Control::PickRom } else { Control::Continue }
             }
-            0x3 => if self.v[instruction.x()] == instruction.nn() {
-                    self.pc += 2;
-                },
-            0x4 => if self.v[instruction.x()] != instruction.nn() {
-                    self.pc += 2;
-                },
-            0x5 => if self.v[instruction.x()] == self.v[instruction.y()] {
-                    self.pc += 2;
-                },
-            0x6 => { self.v[instruction.x()] = instruction.nn() }
-            0x7 => { self.v[instruction.x()] = self.v[instruction.x()].wrapping_add(instruction.nn()); }
-            0x8 => match instruction.n() {
-                    0x0 => self.v[instruction.x()] = self.v[instruction.y()],
-                    0x1 => self.v[instruction.x()] |= self.v[instruction.y()],
-                    0x2 => self.v[instruction.x()] &= self.v[instruction.y()],
-                    0x3 => self.v[instruction.x()] ^= self.v[instruction.y()],
-                    0x4 => {
-                        let (res, carry) = self.v[instruction.x()].overflowing_add(self.v[instruction.y()]);
-                        self.v[instruction.x()] = res;
-                        self.v[0xF] = if carry {
-                            1
-                        } else {
-                            0
-                        };
-                    }
-                    0x5 => {
-                        let (res, carry) = self.v[instruction.x()].overflowing_sub(self.v[instruction.y()]);
-                        self.v[instruction.x()] = res;
-                        self.v[0xF] = if !carry {
-                            1
-                        } else {
-                            0
-                        };
-                    }
-                    0x6 => {
-                        // Optional self.v[instruction.x()] = self.v[instruction.y()];
-                        self.v[0xF] = self.v[instruction.x()] & 1;
-                        self.v[instruction.x()] >>= 1;
-                    }
-                    0x7 => {
-                        let (res, carry) = self.v[instruction.y()].overflowing_sub(self.v[instruction.x()]);
-                        self.v[instruction.x()] = res;
-                        self.v[0xF] = if !carry {
-                            1
-                        } else {
-                            0
-                        };
-                    }
-                    0xE => {
-                        // Optional self.v[instruction.x()] = self.v[instruction.y()];
-                        self.v[0xF] = self.v[instruction.x()] >> 7 & 1;
-                        self.v[instruction.x()] <<= 1;
-                    }
-                    _ => println!("Invalid instruction: {instruction:#06x}"),
+            Some(Choice::Reset) => {
+                self.pause_menu = None;
+                self.reset(chip_8);
+                Control::Restarted
+            }
+            // these two leave the menu up to go on to the next
+            Some(Choice::Quirks) => {
+                self.switch_preset(chip_8, self.preset.next());
+                self.debugger.pause();
+                Control::Restarted
+            }
+            Some(Choice::Palette) => {
+                let palette = chip_8.framebuffer().palette();
+                let n = Palette::NAMED.iter().position(|&(_, named)| named == palette).map_or(0, |n| n + 1);
+                chip_8.set_palette(Palette::NAMED[n % Palette::NAMED.len()].1);
+                Control::Continue
+            }
+            Some(Choice::Quit) => Control::Quit,
+        }
+    }
+
+    /// Whether an action that resets or replaces the machine's state has to be refused, as it
+    /// would put a netplay peer's machine out of step with ours; says so if it does
+    fn netplay_refuses(&self) -> bool {
+        if self.netplay {
+            println!("{}", self.locale.text(Msg::NetplayRefused));
+        }
+        self.netplay
+    }
+
+    /// Handles a key press while the breakpoint prompt is open
+    fn prompt_key(&mut self, keycode: Keycode) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        match keycode {
+            Keycode::Return | Keycode::KpEnter => {
+                let Some(addr) = u16::from_str_radix(prompt, 16).ok().filter(|&addr| addr < 0x1000) else {
+                    println!("{}", self.locale.format(Msg::BadAddress, &[prompt]));
+                    return;
+                };
+                let msg = match self.debugger.toggle_breakpoint(addr) {
+                    true => Msg::BreakpointSet,
+                    false => Msg::BreakpointCleared,
+                };
+                println!("{}", self.locale.format(msg, &[&self.symbols.describe(addr)]));
+                self.prompt = None;
+            }
+            Keycode::Escape => self.prompt = None,
+            Keycode::Backspace => {
+                prompt.pop();
+            }
+            _ => {
+                let name = keycode.name();
+                if prompt.len() < 3 && name.len() == 1 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+                    prompt.push_str(&name);
                 }
-            0x9 => if self.v[instruction.x()] != self.v[instruction.y()] {
-                    self.pc += 2;
+            }
+        }
+    }
+
+    /// Handles a key for the memory pane, returning whether it was one. The page keys scroll it
+    /// whenever it is open; moving the cursor and typing bytes in only work while paused
+    fn memory_key(&mut self, chip_8: &mut Chip8, keycode: Keycode, keymod: Mod) -> bool {
+        let Some(view) = self.memory_view.as_mut() else {
+            return false;
+        };
+        if self.prompt.is_some() || keymod.intersects(CTRL | ALT) {
+            return false;
+        }
+        let page = (MEMORY_ROW * MEMORY_ROWS) as i32;
+        match keycode {
+            Keycode::PageUp => view.move_cursor(-page),
+            Keycode::PageDown => view.move_cursor(page),
+            _ if !self.debugger.paused() => return false,
+            Keycode::Left => view.move_cursor(-1),
+            Keycode::Right => view.move_cursor(1),
+            Keycode::Up => view.move_cursor(-(MEMORY_ROW as i32)),
+            Keycode::Down => view.move_cursor(MEMORY_ROW as i32),
+            Keycode::Home => view.go_to(chip_8.index()),
+            Keycode::End => view.go_to(chip_8.pc()),
+            _ => {
+                let name = keycode.name();
+                match u8::from_str_radix(&name, 16) {
+                    Ok(digit) if name.len() == 1 => view.type_digit(chip_8, digit),
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    fn load_state(&mut self, chip_8: &mut Chip8) {
+        match self.storage.read(&self.state_key()) {
+            Ok(Some(state)) => match chip_8.load_state(&state) {
+                Ok(()) => println!("{}", self.locale.format(Msg::StateLoaded, &[&self.slot])),
+                Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::StateLoadFailed, &[&self.slot, &e])),
+            }
+            Ok(None) => println!("{}", self.locale.format(Msg::SlotEmpty, &[&self.slot])),
+            Err(e) => event!(Level::Warn, "{}", self.locale.format(Msg::SlotReadFailed, &[&self.slot, &e])),
+        }
+    }
+}
+
+/// What the main loop does after a frame's events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Continue,
+    /// A hotkey reset the machine or replaced its state, so anything run ahead of it is stale
+    Restarted,
+    /// The pause menu asked for another ROM from the ROM directory
+    PickRom,
+    /// Ctrl+PageDown or Ctrl+PageUp asked for the next or previous of the ROMs given with --also
+    SwitchRom(isize),
+    Quit,
+}
+
+/// Switches between a window and desktop fullscreen. The logical size set up at startup keeps
+/// the display centered and scaled by whole pixels either way
+fn toggle_fullscreen(window: &mut Window, locale: Locale) {
+    let state = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    if let Err(e) = window.set_fullscreen(state) {
+        event!(Level::Warn, "{}", locale.format(Msg::FullscreenFailed, &[&e]));
+    }
+}
+
+/// Whether a hotkey resets or replaces the machine's state, or switches it to another ROM or
+/// preset
+fn forks_state(keycode: Keycode, keymod: Mod) -> bool {
+    match keycode {
+        Keycode::F3 | Keycode::F9 => true,
+        Keycode::Backspace => keymod.intersects(SHIFT | CTRL),
+        Keycode::B | Keycode::V | Keycode::PageUp | Keycode::PageDown => keymod.intersects(CTRL),
+        _ => false,
+    }
+}
+
+/// Feeds pending SDL events to the machine and acts on the hotkeys
+fn handle_events(chip_8: &mut Chip8, event_pump: &mut EventPump, mut window: Option<&mut Window>, session: &mut Session) -> Control {
+    let mut control = Control::Continue;
+    for event in event_pump.poll_iter() {
+        if session.gamepads.handle(&event, chip_8) {
+            if matches!(event, Event::ControllerButtonDown { .. }) {
+                session.show_hints = false;
+            }
+            continue;
+        }
+        if let Event::KeyDown { keycode: Some(keycode), keymod, .. } = event {
+            if session.pause_menu.is_none() && session.memory_key(chip_8, keycode, keymod) {
+                continue;
+            }
+        }
+        match event {
+            Event::KeyDown { keycode: Some(keycode), .. } if session.pause_menu.is_some() => match session.pause_menu_key(chip_8, keycode) {
+                Control::Continue => {}
+                Control::Restarted => control = Control::Restarted,
+                done => return done,
+            },
+            Event::KeyDown { keycode: Some(keycode), .. } if session.prompt.is_some() => session.prompt_key(keycode),
+            Event::Quit { .. } => return Control::Quit,
+            Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => session.open_pause_menu(),
+            Event::KeyDown { keycode: Some(keycode), keymod, .. } if forks_state(keycode, keymod) && session.netplay_refuses() => {}
+            Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => session.screenshot(chip_8),
+            Event::KeyDown { keycode: Some(Keycode::F2), .. } => if let Some(path) = &session.save_rom {
+                match chip_8.save_rom(path) {
+                    Ok(()) => println!("{}", session.locale.format(Msg::RomSaved, &[&path.display()])),
+                    Err(e) => event!(Level::Warn, "{}", session.locale.format(Msg::RomSaveFailed, &[&path.display(), &e])),
                 }
-            0xA => { self.i = instruction.nnn(); }
-            0xB => { 
-                // Optional self.pc = instruction.nnn() + self.v[0] as u16;
-                self.pc = instruction.nnn() + self.v[instruction.x()] as u16;
-            }
-            0xC => self.v[instruction.x()] = rand::thread_rng().gen::<u8>() & instruction.nn(),
-            0xD => {
-                self.v[0xF] = 0;
-                let x = self.v[instruction.x()] & 63;
-                let y = self.v[instruction.y()] as usize & 31;
-                let mut sprite = vec![];
-                for row in 0..instruction.n() as usize {
-                    sprite.push(self.memory[self.i as usize + row]);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Backspace), keymod, .. } if keymod.intersects(SHIFT) => {
+                session.reset(chip_8);
+                control = Control::Restarted;
+            }
+            Event::KeyDown { keycode: Some(Keycode::T), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.announce(chip_8, false);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Backspace), keymod, .. } if keymod.intersects(CTRL) => {
+                chip_8.soft_reset();
+                session.debugger.resume();
+                println!("{}", session.locale.text(Msg::SoftReset));
+                control = Control::Restarted;
+            }
+            Event::KeyDown { keycode: Some(Keycode::C), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                if let Some(window) = window.as_deref() {
+                    session.copy(chip_8, window, keymod.intersects(SHIFT));
                 }
-                if self.display.draw(x, y, sprite) {
-                    self.v[0xF] = 1;
+            }
+            Event::KeyDown { keycode: Some(Keycode::PageDown), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                return Control::SwitchRom(1);
+            }
+            Event::KeyDown { keycode: Some(Keycode::PageUp), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                return Control::SwitchRom(-1);
+            }
+            Event::KeyDown { keycode: Some(Keycode::A), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.mark_loop(chip_8, keymod.intersects(SHIFT));
+            }
+            Event::KeyDown { keycode: Some(Keycode::B), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                if let Control::Restarted = session.end_loop_here(chip_8) {
+                    control = Control::Restarted;
                 }
             }
-            0xE => match instruction.nn() {
-                    0x9E => if self.input == Some(self.v[instruction.x()]) {
-                        self.pc += 2;
+            Event::KeyDown { keycode: Some(Keycode::V), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                if let Some(window) = window.as_deref() {
+                    if session.paste_state(chip_8, window) {
+                        control = Control::Restarted;
                     }
-                    0xA1 => if self.input != Some(self.v[instruction.x()]) {
-                        self.pc += 2;
+                }
+            }
+            Event::KeyDown { keycode: Some(Keycode::D), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.show_registers = !session.show_registers;
+            }
+            Event::KeyDown { keycode: Some(Keycode::M), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.memory_view = match session.memory_view {
+                    Some(_) => None,
+                    None => Some(MemoryView::default()),
+                };
+            }
+            Event::KeyDown { keycode: Some(Keycode::R), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.toggle_recording(chip_8);
+            }
+            Event::KeyDown { keycode: Some(Keycode::L), keymod, repeat: false, .. } if keymod.intersects(CTRL) => {
+                session.rom_watch.toggle();
+                println!("{}", session.locale.text(if session.rom_watch.enabled() { Msg::ReloadOn } else { Msg::ReloadOff }));
+            }
+            Event::KeyDown { keycode: Some(Keycode::Num1), keymod, .. } if keymod.intersects(CTRL) => {
+                session.crt.scanlines = !session.crt.scanlines;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Num2), keymod, .. } if keymod.intersects(CTRL) => {
+                session.crt.grid = !session.crt.grid;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Num3), keymod, .. } if keymod.intersects(CTRL) => {
+                session.crt.vignette = !session.crt.vignette;
+            }
+            // the keys let go of elsewhere never come up here
+            Event::Window { win_event: WindowEvent::FocusLost, .. } => {
+                chip_8.set_keys(0);
+                (session.rewinding, session.fast_forward, session.slow_motion) = (false, false, false);
+                if session.pause_in_background && !session.debugger.paused() {
+                    session.debugger.pause();
+                    session.paused_in_background = true;
+                }
+            }
+            Event::Window { win_event: WindowEvent::FocusGained, .. } if session.paused_in_background => {
+                session.debugger.resume();
+                session.paused_in_background = false;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => session.rewinding = true,
+            Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => session.rewinding = false,
+            // unless the keymap puts a keypad key there
+            Event::KeyDown { keycode: Some(Keycode::Tab), scancode, keymod, .. }
+                if session.keymap.lookup(Some(Keycode::Tab), scancode).is_none() => {
+                if keymod.intersects(SHIFT) {
+                    session.slow_motion = true;
+                } else {
+                    session.fast_forward = true;
+                }
+            }
+            Event::KeyUp { keycode: Some(Keycode::Tab), scancode, .. }
+                if session.keymap.lookup(Some(Keycode::Tab), scancode).is_none() => {
+                session.fast_forward = false;
+                session.slow_motion = false;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Minus), keymod, .. } if keymod.intersects(CTRL) => {
+                session.set_rate(session.rate / 2.0);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Equals), keymod, .. } if keymod.intersects(CTRL) => {
+                session.set_rate(session.rate * 2.0);
+            }
+            Event::KeyDown { keycode: Some(Keycode::F5), .. } => session.save_state(chip_8),
+            Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+                session.load_state(chip_8);
+                control = Control::Restarted;
+            }
+            Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                session.slot = (session.slot + SAVE_SLOTS - 1) % SAVE_SLOTS;
+                println!("{}", session.locale.format(Msg::SlotSelected, &[&session.slot]));
+            }
+            Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                session.slot = (session.slot + 1) % SAVE_SLOTS;
+                println!("{}", session.locale.format(Msg::SlotSelected, &[&session.slot]));
+            }
+            // the preset suggested after a crash loop, otherwise the next one
+            Event::KeyDown { keycode: Some(Keycode::F3), .. } => {
+                let preset = session.suggested_preset.take().unwrap_or(session.preset.next());
+                session.switch_preset(chip_8, preset);
+                control = Control::Restarted;
+            }
+            Event::KeyDown { keycode: Some(Keycode::F8), .. } => session.toggle_pause(chip_8),
+            // unless the keymap puts a keypad key there
+            Event::KeyDown { keycode: Some(Keycode::P), scancode, repeat: false, .. }
+                if session.keymap.lookup(Some(Keycode::P), scancode).is_none() => session.toggle_pause(chip_8),
+            Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
+                session.debugger.step_over(chip_8);
+                if session.debugger.paused() {
+                    print!("{}", Registers(chip_8));
+                }
+            }
+            // F11 steps while the debugger has the machine paused, and goes fullscreen otherwise
+            Event::KeyDown { keycode: Some(Keycode::F11), .. } if session.debugger.paused() => {
+                session.debugger.step(chip_8);
+                print!("{}", Registers(chip_8));
+            }
+            Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => if let Some(window) = window.as_deref_mut() {
+                toggle_fullscreen(window, session.locale);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Return), keymod, repeat: false, .. } if keymod.intersects(ALT) => {
+                if let Some(window) = window.as_deref_mut() {
+                    toggle_fullscreen(window, session.locale);
+                }
+            }
+            // unless the keymap puts a keypad key there
+            Event::KeyDown { keycode: Some(Keycode::N), scancode, .. }
+                if session.debugger.paused() && session.keymap.lookup(Some(Keycode::N), scancode).is_none() => {
+                session.debugger.advance_frame(chip_8, session.instructions_per_frame);
+                print!("{}", Registers(chip_8));
+            }
+            // , and . look back and forth through the instructions that led here
+            Event::KeyDown { keycode: Some(keycode @ (Keycode::Comma | Keycode::Period)), scancode, .. }
+                if session.debugger.paused() && session.keymap.lookup(Some(keycode), scancode).is_none() => {
+                let delta = if keycode == Keycode::Comma { 1 } else { -1 };
+                let quirks = chip_8.quirks();
+                match session.debugger.look_back(chip_8, delta) {
+                    Some((back, entry)) => {
+                        let entry = entry.describe(&session.symbols, &quirks);
+                        println!("{}", session.locale.format(Msg::HistoryEntry, &[&back, &entry]));
                     }
-                    _ => println!("Invalid instruction: {instruction:#06x}"),
+                    None => print!("{}", Registers(chip_8)),
                 }
-            0xF => match instruction.nn() {
-                    // Set Vx to the value of the delay timer
-                    0x07 => self.v[instruction.x()] = self.dt,
-                    0x0A => {
-                        match self.input {
-                            Some(n) => self.v[instruction.x()] = n,
-                            None => {
-                                // decrements the pc by 2 before incrementing it in tick(), so we end up here until input
-                                self.pc -= 2;
-                                self.tick();
-                            },
-                        }
+            }
+            // unless the keymap puts a keypad key there
+            Event::KeyDown { keycode: Some(Keycode::G), scancode, .. }
+                if session.debugger.paused() && session.keymap.lookup(Some(Keycode::G), scancode).is_none() => {
+                session.gamepads.next_layout(chip_8);
+                println!("{}", session.locale.format(Msg::PadProfile, &[session.gamepads.profile()]));
+            }
+            Event::KeyDown { keycode: Some(Keycode::B), .. } if session.debugger.paused() => {
+                session.prompt = Some(String::new());
+            }
+            Event::KeyDown { keycode: Some(Keycode::F1), .. } => session.show_hints = !session.show_hints,
+            Event::KeyDown { keycode: Some(Keycode::F4), .. } => session.show_inputs = !session.show_inputs,
+            Event::KeyDown { scancode, .. } | Event::KeyUp { scancode, .. }
+                if chip_8.quirks().chip_8x && keymap::second_keypad(scancode).is_some() =>
+            {
+                let key = keymap::second_keypad(scancode).unwrap_or_default();
+                let held = matches!(event, Event::KeyDown { .. });
+                chip_8.set_second_keypad(chip_8.second_keypad() & !(1 << key) | u16::from(held) << key);
+            }
+            Event::KeyDown { keycode, scancode, .. } => if let Some(key) = session.keymap.lookup(keycode, scancode) {
+                // the overlay only needs to stay up until the player starts playing
+                session.show_hints = false;
+                chip_8.press(key);
+            }
+            Event::KeyUp { keycode, scancode, .. } => if let Some(key) = session.keymap.lookup(keycode, scancode) {
+                chip_8.release(key);
+            }
+            // taps come as clicks too, in logical pixels like the rest
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if session.show_panel => {
+                if let Some(key) = keypad_panel::key_at(x, y) {
+                    session.show_hints = false;
+                    chip_8.press(key);
+                    session.clicked = Some(key);
+                }
+            }
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => if let Some(key) = session.clicked.take() {
+                chip_8.release(key);
+            }
+            _ => {}
+        }
+    }
+    control
+}
 
-                    }
-                    // Set the delay timer to Vx
-                    0x15 => self.dt = self.v[instruction.x()],
-                    // Set the sound timer to Vx
-                    0x18 => self.st = self.v[instruction.x()],
-                    0x1E => {
-                        let res = self.i.wrapping_add(self.v[instruction.x()] as u16);
-                        // If I + Vx overflows out of normal addressing range set VF to 1
-                        // This was not universal, but when unused it shouldn't matter
-                        if res > 0xFFF || res < self.i {
-                            self.v[0xF] = 1;
-                        }
-                        self.i = res;
-                    }
-                    0x29 => self.i = 0x50 + 5 * instruction.x() as u16,
-                    // SuperChip BigHex characters
-                    0x30 => {}
-                    0x33 => {
-                        let vx = self.v[instruction.x()];
-                        let i = self.i as usize;
-                        self.memory[i] = vx / 100;
-                        self.memory[i + 1] = (vx / 10) % 10;
-                        self.memory[i + 2] = vx % 10;
-                    }
-                    0x55 => {
-                        for n in 0..instruction.x() as usize {
-                            self.memory[self.i as usize + n] = self.v[n];
-                        }
-                    }
-                    0x65 => {
-                        for n in 0..=instruction.x() as usize {
-                            self.v[n] = self.memory[self.i as usize + n];
-                        }
-                    }
-                    0x75 => {}
-                    0x85 => {}
-                    _ => println!("Invalid instruction: {instruction:#06x}"),
+/// Runs the built-in menu over the ROMs in `dir` until one is picked, or the window is closed.
+/// ROMs with a high score in `scores` are listed with it
+#[allow(clippy::too_many_arguments)]
+fn pick_rom(
+    dir: &Path,
+    recent: &Recent,
+    db: &RomDb,
+    scores: &Leaderboard,
+    locale: Locale,
+    canvas: &mut Canvas<Window>,
+    screen: &mut Screen,
+    event_pump: &mut EventPump,
+    keymap: &Keymap,
+    gamepads: &mut Gamepads,
+    rotation: Rotation,
+) -> Option<PathBuf> {
+    let roms = menu::scan(dir).unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&dir.display(), &e])));
+    if roms.is_empty() {
+        println!("{}", locale.format(Msg::MenuEmpty, &[&dir.display()]));
+        return None;
+    }
+    if roms.len() > menu::capacity() {
+        println!("{}", locale.format(Msg::MenuFull, &[&menu::capacity()]));
+    }
+    let mut roms = roms;
+    recent.order(&mut roms);
+    // known ROMs go by their titles and come with their high scores, which takes reading them all
+    let label = |rom: &Path| {
+        let id = (!db.0.is_empty() || !scores.is_empty()).then(|| source::read(rom).ok()).flatten().map(|bytes| RomId::of(&bytes));
+        let title = id.as_ref().and_then(|id| db.lookup(id)?.title.clone());
+        let name = title.unwrap_or_else(|| rom.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+        menu::label(&name, id.and_then(|id| scores.best(&id.key())))
+    };
+    let names: Vec<String> = roms.iter().map(|rom| label(rom)).collect();
+    let mut chip_8 = menu::boot(&names);
+    let overlays = Overlays {
+        highlight: &[],
+        keypad: None,
+        panel: None,
+        inputs: None,
+        crt: CrtEffects::default(),
+        registers: None,
+        memory: None,
+        watches: None,
+        speed: 1.0,
+        hud: None,
+        pause_menu: None,
+    };
+    loop {
+        for event in event_pump.poll_iter() {
+            if gamepads.handle(&event, &mut chip_8) {
+                continue;
+            }
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return None,
+                // the arrows and Enter work as well as the keypad's 5, 8 and 6
+                Event::KeyDown { keycode, scancode, .. } => if let Some(key) = keycode.and_then(menu_key).or_else(|| keymap.lookup(keycode, scancode)) {
+                    chip_8.press(key);
                 }
-            _ => { /*categorically impossible*/ }
+                Event::KeyUp { keycode, scancode, .. } => if let Some(key) = keycode.and_then(menu_key).or_else(|| keymap.lookup(keycode, scancode)) {
+                    chip_8.release(key);
+                }
+                _ => {}
+            }
         }
+        chip_8.run_frame(MENU_INSTRUCTIONS_PER_FRAME);
+        if let Some(n) = menu::choice(&chip_8) {
+            return roms.get(n).cloned();
+        }
+        if let Err(e) = render(chip_8.framebuffer(), None, &overlays, rotation, screen, canvas) {
+            fail(locale.format(Msg::RenderFailed, &[&e]));
+        }
+        chip_8.take_frame();
+        canvas.present();
+        std::thread::sleep(Duration::from_secs(1) / TIMER_HZ);
+    }
+}
+
+/// The menu's keypad key for a navigation key
+fn menu_key(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::Up => Some(0x5),
+        Keycode::Down => Some(0x8),
+        Keycode::Return | Keycode::KpEnter => Some(0x6),
+        _ => None,
     }
 }
 
-pub struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+/// Where --announce's announcements go: the --announce-to listener, or stdout
+fn announcements(cli: &RunArgs, locale: Locale) -> Box<dyn Write> {
+    match &cli.announce_to {
+        Some(addr) => match std::net::TcpStream::connect(addr) {
+            Ok(stream) => Box::new(stream),
+            Err(e) => fail(locale.format(Msg::AnnounceConnectFailed, &[addr, &e])),
+        },
+        None => Box::new(std::io::stdout()),
+    }
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
+/// Logs the instructions traced since the last call to stderr, or with --trace-ring only once
+/// something went wrong, as the ones leading up to it
+fn write_trace(chip_8: &mut Chip8, cli: &RunArgs, went_wrong: bool, symbols: &Symbols) {
+    if cli.trace_ring.is_some() && !went_wrong {
+        return;
+    }
+    let mut stderr = std::io::stderr().lock();
+    for entry in chip_8.take_trace() {
+        writeln!(stderr, "{}", entry.describe(symbols, &chip_8.quirks())).ok();
+    }
+}
 
-    fn callback(&mut self, out: &mut [Self::Channel]) {
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+/// The --symbols file, or the .sym file next to the ROM if there is one. Octo source brings
+/// its own labels
+fn load_symbols(cli: &RunArgs, rom_path: &Path, locale: Locale) -> Symbols {
+    if cli.symbols.is_none() && source::is_source(rom_path) {
+        // it compiled once already to get this far
+        let program = std::fs::read_to_string(rom_path).ok().and_then(|text| octo::compile(&text).ok());
+        return program.map(|program| program.symbols()).unwrap_or_default();
+    }
+    let path = cli.symbols.clone().unwrap_or_else(|| rom_path.with_extension("sym"));
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Symbols::parse(&text).unwrap_or_else(|e| {
+            eprintln!("{}", locale.format(Msg::BadSymbols, &[&format_args!("{}: {e}", path.display())]));
+            Symbols::default()
+        }),
+        // only a symbol file asked for is missed
+        Err(e) if cli.symbols.is_some() => fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])),
+        Err(_) => Symbols::default(),
+    }
+}
+
+/// The --rom-db database, or romdb.toml in the data directory if there is one
+fn load_rom_db(cli: &RunArgs, locale: Locale) -> RomDb {
+    let Some(path) = cli.rom_db.clone().or_else(|| FileStorage::default_root().map(|root| root.join("romdb.toml"))) else {
+        return RomDb::default();
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => RomDb::parse(&text).unwrap_or_else(|e| {
+            eprintln!("{}", locale.format(Msg::BadRomDb, &[&format_args!("{}: {e}", path.display())]));
+            RomDb::default()
+        }),
+        Err(e) if cli.rom_db.is_some() => fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])),
+        Err(_) => RomDb::default(),
+    }
+}
+
+/// Writes the --profile-opcodes report, if asked for one
+fn write_profile(chip_8: &Chip8, cli: &RunArgs, locale: Locale) {
+    let (Some(path), Some(profile)) = (&cli.profile_opcodes, ProfileReport::new(chip_8)) else {
+        return;
+    };
+    if path.as_os_str() == "-" {
+        print!("{profile}");
+        return;
+    }
+    match std::fs::write(path, profile.to_string()) {
+        Ok(()) => eprintln!("{}", locale.format(Msg::ProfileWritten, &[&path.display()])),
+        Err(e) => event!(Level::Warn, "{}", locale.format(Msg::ProfileWriteFailed, &[&path.display(), &e])),
+    }
+}
+
+/// Writes the --coverage-map map, if asked for one
+fn write_coverage_map(chip_8: &Chip8, cli: &RunArgs, locale: Locale) {
+    let (Some(path), Some(coverage)) = (&cli.coverage_map, chip_8.coverage()) else {
+        return;
+    };
+    let map = CoverageMap(coverage);
+    if path.as_os_str() == "-" {
+        print!("{map}");
+        return;
+    }
+    match std::fs::write(path, map.to_string()) {
+        Ok(()) => eprintln!("{}", locale.format(Msg::CoverageMapWritten, &[&path.display()])),
+        Err(e) => event!(Level::Warn, "{}", locale.format(Msg::CoverageMapWriteFailed, &[&path.display(), &e])),
+    }
+}
+
+/// Plays in the terminal or headless, whichever --frontend asks for, or says this build lacks it
+#[cfg_attr(not(all(feature = "tui", feature = "headless")), allow(unused_variables))]
+fn run_without_sdl(cli: &RunArgs, chip_8: Chip8, symbols: &Symbols, locale: Locale) {
+    match cli.frontend {
+        #[cfg(feature = "headless")]
+        Frontend::Headless => headless::run(cli, chip_8, symbols, locale),
+        #[cfg(feature = "tui")]
+        Frontend::Tui => tui::run(cli, chip_8, symbols, locale),
+        frontend => fail(locale.format(Msg::FrontendMissing, &[&frontend.feature(), &frontend.feature()])),
+    }
+}
+
+/// E.g. `2h 05m`, or `4m 10s` under an hour
+fn format_play_time(played: Duration) -> String {
+    let secs = played.as_secs();
+    match secs / 3600 {
+        0 => format!("{}m {:02}s", secs / 60, secs % 60),
+        hours => format!("{hours}h {:02}m", secs / 60 % 60),
+    }
+}
+
+/// Prints `message` and exits with a failure status, for errors there's no going on after
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+/// Loads the ROM again after its file changed, keeping the machine's settings. A file that
+/// fails to load, e.g. one caught half written, leaves the old ROM running
+fn reload_rom(chip_8: &mut Chip8, cli: &RunArgs, rom_path: &Path, session: &mut Session) -> bool {
+    let locale = session.locale;
+    match source::read(rom_path).and_then(|rom| Chip8::load_bytes(rom, cli.patch.as_deref())) {
+        Ok((fresh, _)) => {
+            if let Some(quirks) = session.pending_quirks.take() {
+                chip_8.set_quirks(quirks);
+            }
+            chip_8.load_rom(fresh.rom());
+            for &(addr, byte) in &cli.poke {
+                chip_8.patch_rom(addr, byte);
+            }
+            session.debugger.resume();
+            session.symbols = load_symbols(cli, rom_path, locale);
+            println!("{}", locale.format(Msg::RomReloaded, &[&rom_path.display()]));
+            true
+        }
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::RomReloadFailed, &[&rom_path.display(), &e]));
+            false
+        }
+    }
+}
+
+/// Puts the ROM at `rom_path` in the machine in place of the one it runs, under the same
+/// settings, and makes it the session's ROM
+fn switch_rom(chip_8: &mut Chip8, cli: &RunArgs, rom_path: &Path, session: &mut Session) -> bool {
+    let locale = session.locale;
+    match source::read(rom_path).and_then(|rom| Chip8::load_bytes(rom, cli.patch.as_deref())) {
+        Ok((fresh, info)) => {
+            eprintln!("{}", locale.format(Msg::RomLoaded, &[&rom_path.display(), &info]));
+            eprintln!("{}", locale.format(Msg::RomIdentified, &[&info.id]));
+            chip_8.load_rom(fresh.rom());
+            session.debugger.resume();
+            session.count_play_time();
+            session.rom_id = info.id;
+            session.rpl_flags = [0; RPL_FLAGS];
+            chip_8.set_rpl_flags(session.rpl_flags);
+            session.load_rpl_flags(chip_8);
+            session.symbols = load_symbols(cli, rom_path, locale);
+            session.title = format!("CHIP-8 - {}", rom_path.file_name().unwrap_or_default().to_string_lossy());
+            session.rom_name = rom_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            session.rom_watch = RomWatch::new(rom_path, session.rom_watch.enabled());
+            // its start is the last ROM's
+            session.drop_loop_breakpoint();
+            session.practice = None;
+            true
+        }
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::RomLoadFailed, &[&e]));
+            false
         }
     }
 }
 
+/// Where the pause menu picks another ROM from: the directory or archive the ROM came from,
+/// the ROM directory, or failing those the one the ROM is in
+fn rom_dir(cli: &RunArgs, rom_path: &Path) -> PathBuf {
+    cli.rom
+        .clone()
+        .filter(|rom| rom.is_dir() || zip::is_zip(rom))
+        .or_else(|| cli.rom_dir.clone())
+        .or_else(|| FileStorage::default_root().map(|root| root.join("roms")).filter(|dir| dir.is_dir()))
+        .unwrap_or_else(|| match rom_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+}
+
+/// The controller profile the command line asks for, with its --pad-bind bindings on top
+fn pad_profile(cli: &RunArgs) -> PadProfile {
+    let mut profile = cli.pad.clone().unwrap_or_default();
+    for &(button, key) in &cli.pad_bind {
+        profile.bind(button, key);
+    }
+    profile
+}
+
+/// The built-in demo's path, saying that's what runs for want of a ROM
+fn demo(locale: Locale) -> PathBuf {
+    eprintln!("{}", locale.text(Msg::RunningDemo));
+    PathBuf::from(source::DEMO)
+}
+
+/// Loads the ROM at `rom_path` into a machine set up the way the command line asks, filling in
+/// the preset, rate and controller profile it leaves open from the ROM database. Returns the
+/// ROM's identity too, as it was before any --poke
+fn load_machine(cli: &mut RunArgs, rom_path: &Path, locale: Locale) -> (Chip8, RomId) {
+    let (mut chip_8, info) = source::read(rom_path)
+        .and_then(|rom| Chip8::load_bytes(rom, cli.patch.as_deref()))
+        .unwrap_or_else(|e| fail(locale.format(Msg::RomLoadFailed, &[&e])));
+    // on stderr, as headless runs can write the screen to stdout
+    eprintln!("{}", locale.format(Msg::RomLoaded, &[&rom_path.display(), &info]));
+    eprintln!("{}", locale.format(Msg::RomIdentified, &[&info.id]));
+    let quirks_given = cli.quirks.is_some();
+    let mut quirks = Quirks::preset(cli.preset());
+    if let Some(entry) = load_rom_db(cli, locale).lookup(&info.id) {
+        eprintln!("{}", locale.format(Msg::KnownRom, &[entry]));
+        // what the command line says wins
+        if cli.quirks.is_none() {
+            quirks = entry.quirks();
+            cli.quirks = entry.platform;
+        }
+        cli.ips = cli.ips.or(entry.ips);
+        cli.turbo_boot |= entry.turbo_boot;
+        if let Some(pad) = entry.pad.as_ref().filter(|_| cli.pad.is_none()) {
+            eprintln!("{}", locale.format(Msg::PadProfile, &[pad]));
+            cli.pad = Some(pad.clone());
+        }
+    }
+    for warning in &info.warnings {
+        match *warning {
+            RomWarning::NeedsPlatform { platform, .. } if !RomInfo::runs_on(platform, cli.preset()) => {
+                eprintln!("{}", locale.format(Msg::RomNeedsPreset, &[warning, &platform]));
+            }
+            RomWarning::NeedsPlatform { .. } => {}
+            RomWarning::LooksLikeSource => eprintln!("{}", locale.format(Msg::RomWarning, &[warning])),
+        }
+    }
+    let variant = cli.variant.map(|choice| match choice {
+        VariantChoice::Auto => Variant::detect(&info),
+        VariantChoice::Named(variant) => variant,
+    });
+    if let Some(variant) = variant {
+        eprintln!("{}", locale.format(Msg::RunningVariant, &[&variant]));
+        chip_8.set_variant(variant);
+        if !quirks_given {
+            quirks = variant.quirks();
+            cli.quirks = Some(variant.preset());
+        }
+    }
+    if cli.diagnose_quirks {
+        chip_8.enable_diagnostics();
+    }
+    if cli.report.is_some() || cli.profile_opcodes.is_some() || cli.coverage_map.is_some() {
+        chip_8.enable_coverage();
+    }
+    if cli.trace {
+        chip_8.enable_trace(cli.trace_ring);
+    }
+    chip_8.set_profile(cli.profile);
+    chip_8.set_alignment_policy(cli.odd_pc);
+    chip_8.set_fault_policy(cli.on_fault);
+    chip_8.set_stack_depth(cli.stack_depth.into());
+    chip_8.set_strict_memory(cli.strict_memory);
+    for &watchpoint in &cli.watch {
+        chip_8.add_watchpoint(watchpoint);
+    }
+    chip_8.set_palette(cli.palette);
+    chip_8.set_font_set(cli.font);
+    if let Some(load_store) = cli.load_store {
+        quirks.load_store = load_store;
+    }
+    quirks.vip_rng |= cli.vip_rng;
+    quirks.vip_timing |= cli.vip_timing;
+    quirks.half_scroll |= cli.half_scroll;
+    quirks.key_wait_press |= cli.key_wait_press;
+    quirks.display_wait |= cli.display_wait;
+    if cli.display_wait {
+        cli.ips = cli.ips.or(Some(args::DISPLAY_WAIT_IPS));
+    }
+    chip_8.set_quirks(quirks);
+    if let Some(seed) = cli.seed {
+        chip_8.seed_rng(seed as u64);
+    }
+    chip_8.override_playback_rate(cli.playback_rate);
+    if let Some(path) = &cli.script {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+        let script = Script::parse(&text).unwrap_or_else(|e| fail(locale.format(Msg::BadScript, &[&path.display(), &e])));
+        chip_8.set_script(script);
+    }
+    for &(addr, byte) in &cli.poke {
+        if !chip_8.patch_rom(addr, byte) {
+            println!("{}", locale.format(Msg::PokeOutsideProgram, &[&format_args!("{addr:#05x}")]));
+        }
+    }
+    (chip_8, info.id)
+}
+
 fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("CHIP-8", 64 * 8, 32 * 8)
-        .position_centered()
-        .build()
-        .unwrap();
-
-    let mut canvas = window
-        .into_canvas()
-        .present_vsync()
-        .build()
-        .unwrap();
-    
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    canvas.set_scale(8.0, 8.0).unwrap();
-
-    let creator = canvas.texture_creator();
-    let mut texture = creator.create_texture_target(PixelFormatEnum::RGB24, 64, 32).unwrap();
-
-    let audio_subsystem = sdl_context.audio().unwrap();
-    let desired_spec = AudioSpecDesired {
-        freq: Some(44100),
-        channels: Some(1),
-        samples: None,
+    let cli = Cli::parse();
+    let locale = cli.lang.unwrap_or_else(Locale::from_env);
+    logging::set_level(cli.log_level);
+    if let Some(path) = &cli.log_file {
+        match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => logging::set_output(Box::new(file)),
+            Err(e) => fail(locale.format(Msg::LogFileFailed, &[&path.display(), &e])),
+        }
+    }
+    let mut cli = match cli.command {
+        None => cli.run,
+        Some(Command::Run(run)) => *run,
+        Some(Command::Connect { addr, scale }) => return connect::run(&addr, scale, locale),
+        Some(command) => return commands::run(&command, locale),
+    };
+    if cli.list_audio_devices {
+        return startup::list_audio_devices(locale);
+    }
+    if cli.self_test {
+        return commands::self_test();
+    }
+    if cli.headless {
+        cli.frontend = Frontend::Headless;
+    }
+    // the frontends without SDL, which have no menu to pick a ROM from a directory
+    if cli.serve.is_some() || cli.frontend != Frontend::Sdl {
+        let mode = match cli.frontend {
+            _ if cli.serve.is_some() => "--serve".to_string(),
+            frontend => format!("--frontend {}", frontend.feature()),
+        };
+        let rom_path = cli.rom.clone().unwrap_or_else(|| demo(locale));
+        let rom_path = source::single_rom(&rom_path).unwrap_or(rom_path);
+        if rom_path.is_dir() || zip::is_zip(&rom_path) {
+            println!("{}", locale.format(Msg::RomFileNeeded, &[&mode]));
+            std::process::exit(2);
+        }
+        let (chip_8, _) = load_machine(&mut cli, &rom_path, locale);
+        let symbols = load_symbols(&cli, &rom_path, locale);
+        return match &cli.serve {
+            Some(addr) => serve::run(&cli, addr, chip_8, &symbols, locale),
+            None => run_without_sdl(&cli, chip_8, &symbols, locale),
+        };
+    }
+    let tone = Tone { waveform: cli.waveform, frequency: cli.tone, volume: cli.volume };
+    let output = startup::AudioOutput { device: cli.audio_device.clone(), sample_rate: cli.sample_rate, buffer: cli.audio_buffer };
+    let startup::Subsystems { mut canvas, mut event_pump, audio: mut audio_device, controllers, sdl: _sdl } = startup::init(locale, cli.scale, tone, &output);
+    if let Some(canvas) = canvas.as_mut().filter(|_| cli.keypad_panel) {
+        keypad_panel::enable(canvas, cli.scale);
+    }
+    if let Some(canvas) = canvas.as_mut() {
+        cli.rotate.enable(canvas, cli.scale);
+    }
+    let creator = canvas.as_ref().map(|canvas| canvas.texture_creator());
+    let mut screen = creator
+        .as_ref()
+        .map(|creator| Screen::new(creator).unwrap_or_else(|e| fail(locale.format(Msg::RenderFailed, &[&e]))));
+
+    let mut keymap = match &cli.keymap {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+            Keymap::parse(&text).unwrap_or_else(|e| fail(locale.format(Msg::KeymapInvalid, &[&path.display(), &e])))
+        }
+        None if cli.keycodes => Keymap::by_keycode(),
+        None if cli.variant == Some(VariantChoice::Named(Variant::Eti660)) => Keymap::eti_660(),
+        None => Keymap::default(),
+    };
+    for &(binding, key) in &cli.bind {
+        keymap.bind(binding, key);
+    }
+    let config = cli.config.as_ref().map(|path| ConfigFile {
+        path: path.clone(),
+        watch: RomWatch::new(path, true),
+        keymap: keymap.clone(),
+        rotation: cli.rotate,
+        ips: cli.ips(),
+    });
+    keymap.turn(cli.rotate);
+
+    let mut gamepads = Gamepads::new(controllers, pad_profile(&cli), cli.rotate, cli.rumble, locale);
+
+    let mut storage: Box<dyn Storage> = match FileStorage::default_root() {
+        Some(root) => Box::new(FileStorage::new(root)),
+        None => {
+            println!("{}", locale.text(Msg::NoDataDir));
+            Box::new(MemoryStorage::default())
+        }
+    };
+    let mut recent = storage.read(Recent::KEY).ok().flatten().map(|data| Recent::parse(&String::from_utf8_lossy(&data))).unwrap_or_default();
+
+    // without a ROM the ROM directory's menu comes up, and an archive holding a single ROM needs none
+    let rom = cli
+        .rom
+        .clone()
+        .or_else(|| cli.rom_dir.clone())
+        .or_else(|| FileStorage::default_root().map(|root| root.join("roms")).filter(|dir| dir.is_dir()))
+        .map(|rom| source::single_rom(&rom).unwrap_or(rom));
+    let mut rom_path = match rom {
+        Some(dir) if dir.is_dir() || zip::is_zip(&dir) => {
+            let (Some(canvas), Some(screen), Some(event_pump)) = (canvas.as_mut(), screen.as_mut(), event_pump.as_mut()) else {
+                println!("{}", locale.text(Msg::MenuNeedsWindow));
+                return;
+            };
+            let db = load_rom_db(&cli, locale);
+            let scores = Leaderboard::load(storage.as_ref());
+            match pick_rom(&dir, &recent, &db, &scores, locale, canvas, screen, event_pump, &keymap, &mut gamepads, cli.rotate) {
+                Some(rom) => rom,
+                None => return,
+            }
+        }
+        Some(rom) => rom,
+        None => demo(locale),
+    };
+    let (mut chip_8, rom_id) = load_machine(&mut cli, &rom_path, locale);
+    // the ROM database may have picked a profile for the ROM
+    gamepads.set_profile(pad_profile(&cli));
+    recent.played(&rom_path);
+    if let Err(e) = storage.write(Recent::KEY, recent.to_string().as_bytes()) {
+        event!(Level::Warn, "{}", locale.format(Msg::RecentSaveFailed, &[&e]));
+    }
+    if let Some(preset) = cli.compare {
+        let (Some(canvas), Some(event_pump)) = (canvas, event_pump) else {
+            println!("{}", locale.text(Msg::CompareNeedsWindow));
+            return;
+        };
+        let comparison = Comparison::new(&chip_8, chip_8.quirks(), Quirks::preset(preset));
+        let instructions_per_frame = (cli.ips() / TIMER_HZ).max(1) as usize;
+        let presets = (cli.preset(), preset);
+        return split_screen::run(comparison, presets, instructions_per_frame, canvas, event_pump, audio_device, &keymap, locale);
+    }
+    let mut rom_file = rom_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let mut meter = RateMeter::new(std::time::Instant::now());
+    let _span = Span::enter(Level::Debug, format!("session {rom_file}"));
+
+    let score_watch = cli.score.map(|watch| ScoreWatch { bcd: cli.score_bcd, ..watch });
+    let play_times = PlayTimes::load(storage.as_ref());
+    let mut session = Session {
+        storage,
+        rom_id,
+        rpl_flags: [0; RPL_FLAGS],
+        save_rom: cli.save_rom.clone(),
+        locale,
+        slot: 0,
+        states_saved: 0,
+        play_times,
+        play_started: std::time::Instant::now(),
+        announcer: Announcer::new(cli.announce.unwrap_or(0) * TIMER_HZ),
+        announcements: Some(announcements(&cli, locale)),
+        rewinding: false,
+        fast_forward: false,
+        slow_motion: false,
+        rate: 1.0,
+        instructions_per_frame: (cli.ips() / TIMER_HZ).max(1) as usize,
+        hints: Hints::default(),
+        show_hints: false,
+        show_inputs: cli.input_display,
+        show_panel: cli.keypad_panel && canvas.is_some(),
+        rom_watch: RomWatch::new(&rom_path, cli.reload),
+        config,
+        pending_quirks: None,
+        clicked: None,
+        show_registers: cli.debug_overlay,
+        memory_view: None,
+        title: format!("CHIP-8 - {rom_file}"),
+        debugger: Debugger::default(),
+        symbols: load_symbols(&cli, &rom_path, locale),
+        prompt: None,
+        preset: cli.preset(),
+        keymap,
+        gamepads,
+        suggested_preset: None,
+        crt: cli.crt,
+        rom_name: rom_path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        screenshot_dir: cli.screenshot_dir.clone(),
+        screenshot_scale: cli.screenshot_scale as usize,
+        screenshot_text: cli.screenshot_text,
+        recording: None,
+        record_scale: cli.record_scale as usize,
+        pause_in_background: !cli.run_in_background,
+        paused_in_background: false,
+        pause_menu: None,
+        netplay: false,
+        booting: cli.turbo_boot.then(|| chip_8.counters()),
+        practice: None,
+        loop_end: cli.loop_end,
+        loop_breakpoint: false,
+    };
+    session.apply_config(&mut chip_8, true);
+    if let Some(path) = &cli.record {
+        session.start_recording(&chip_8, path.clone());
+    }
+    session.debugger.breakpoints.extend(&cli.breakpoints);
+    session.debugger.opcode_breakpoints.extend(&cli.opcode_breakpoints);
+    if cli.history > 0 {
+        chip_8.enable_history(cli.history);
+    } else if cli.crash_dir.is_some() {
+        // enough for the report to show how the ROM got there
+        chip_8.enable_history(crash::TRACE_LINES);
+    }
+    session.load_rpl_flags(&mut chip_8);
+    if cli.autosave {
+        session.restore_autosave(&mut chip_8);
+    }
+    // a hints file next to the ROM wins over one kept in the data directory
+    let hints = std::fs::read(rom_path.with_extension("hints"))
+        .ok()
+        .or_else(|| session.storage.read(&format!("hints/{}.txt", session.rom_id.key())).ok().flatten());
+    if let Some(hints) = hints {
+        match Hints::parse(&String::from_utf8_lossy(&hints)) {
+            Ok(hints) => {
+                println!("{}", locale.format(Msg::Controls, &[&hints]));
+                session.title = format!("{} - {hints}", session.title);
+                session.show_hints = !cli.no_hints;
+                session.hints = hints;
+            }
+            Err(e) => println!("{}", locale.format(Msg::BadHints, &[&e])),
+        }
+    }
+    // likewise for cheats, with any given on the command line added to the file's
+    let cheat_file = std::fs::read(rom_path.with_extension("cht"))
+        .ok()
+        .or_else(|| session.storage.read(&format!("cheats/{}.cht", session.rom_id.key())).ok().flatten());
+    let mut cheats = match cheat_file.map(|text| Cheats::parse(&String::from_utf8_lossy(&text))) {
+        Some(Ok(cheats)) => cheats,
+        Some(Err(e)) => {
+            println!("{}", locale.format(Msg::BadCheats, &[&e]));
+            Cheats::default()
+        }
+        None => Cheats::default(),
     };
+    cheats.0.extend(&cli.cheat);
+    if !cheats.0.is_empty() {
+        println!("{}", locale.format(Msg::CheatsOn, &[&cheats]));
+    }
+    let mut leaderboard = score_watch.map(|_| Leaderboard::load(session.storage.as_ref()));
+    if let Some(best) = leaderboard.as_ref().and_then(|board| board.best(&session.rom_id.key())) {
+        println!("{}", locale.format(Msg::HighScore, &[&best]));
+    }
+    let mut session_best = 0;
+    let session_start = std::time::Instant::now();
+    if let Some(played) = session.play_times.of(&session.rom_id.key()) {
+        println!("{}", locale.format(Msg::PlayedBefore, &[&format_play_time(played)]));
+    }
 
-    let audio_device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
-        SquareWave {
-            phase_inc: 440.0 / spec.freq as f32,
-            phase: 0.0,
-            volume: 0.25,
+    let mut run_ahead = cli.run_ahead.then(RunAhead::default);
+    let mut crash_loops = CrashLoopDetector::default();
+    let mut afterglow: Option<Box<dyn Afterglow>> = match (cli.flicker_fusion, cli.phosphor) {
+        (Some(frames), _) => Some(Box::new(FlickerFusion::new(frames as usize))),
+        (_, Some(frames)) => Some(Box::new(PhosphorDecay::new(frames as usize))),
+        _ if cli.reduce_flashing => Some(Box::<FlashLimiter>::default()),
+        _ => None,
+    };
+    // enough frames to fill the strip in the window as created
+    let mut inputs = InputHistory::new(256);
+    let mut input_log = InputLog::start(&cli, &mut chip_8, locale);
+    let mut peer = Peer::start(&cli, &mut chip_8, locale);
+    session.netplay = peer.is_some();
+    let mut gdb = cli.gdb.as_deref().map(|addr| GdbServer::listen(addr, locale));
+    // rewinding would fork the recorded timeline, so there are no states to rewind to
+    let rewind_states = if input_log.active() || peer.is_some() { 0 } else { cli.rewind_seconds * TIMER_HZ / cli.rewind_interval };
+    let mut rewind = Rewind::new(rewind_states as usize, cli.rewind_interval);
+    // the ROMs Ctrl+PageUp and Ctrl+PageDown switch between, with the state each was left in
+    let mut roms: Vec<(PathBuf, Option<Vec<u8>>)> =
+        std::iter::once(rom_path.clone()).chain(cli.also.iter().cloned()).map(|path| (path, None)).collect();
+    let mut current_rom = 0;
+    let mut crash_reported = false;
+    let mut audio_paused = false;
+    if let Some(path) = &cli.record_audio {
+        match audio_device.as_mut() {
+            Some(device) => match AudioCapture::start(path, device.spec().freq) {
+                Ok(capture) => {
+                    println!("{}", locale.format(Msg::RecordingStarted, &[&path.display()]));
+                    device.lock().capture = Some(capture);
+                }
+                Err(e) => event!(Level::Warn, "{}", locale.format(Msg::RecordingFailed, &[&path.display(), &e])),
+            },
+            None => println!("{}", locale.text(Msg::AudioRecordingNeedsDevice)),
         }
-    }).unwrap();
+    }
+    let frame_time = FixedTimestep::timers().period();
+    let instructions_per_frame = session.instructions_per_frame;
 
-    let mut chip_8 = Chip8::new("chip8-test-rom-with-audio.ch8");
-    let mut start = std::time::Instant::now();
-    let mut cycles = 0;
+    let mut frame_recorder = cli.record_frames.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| fail(locale.format(Msg::FileCreateFailed, &[&path.display(), &e])));
+        JournalWriter::new(BufWriter::new(file))
+    });
+    let reference_frames = cli.compare_frames.as_ref().map(|path| {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(locale.format(Msg::FileReadFailed, &[&path.display(), &e])));
+        read_journal(&text).unwrap_or_else(|e| fail(locale.format(Msg::JournalInvalid, &[&path.display(), &e])))
+    });
+    // journals, input recordings and netplay need the same instructions in every frame, so they
+    // run frame by frame like run-ahead
+    let frame_stepped = run_ahead.is_some()
+        || frame_recorder.is_some()
+        || reference_frames.is_some()
+        || input_log.active()
+        || peer.is_some();
+    let mut frame_index = 0;
+    let mut divergence: Vec<(usize, usize)> = vec![];
+    // emulated frames owed to the frame by frame modes, which slow down by skipping some
+    let mut frame_credit = 0.0;
+    let debugger = std::mem::take(&mut session.debugger);
+    // the thread runs instructions while the keys change; the frame by frame modes set them
+    // between frames already
+    chip_8.latch_keys(!frame_stepped);
+    let emulation = Emulation::new(Core::new(chip_8, debugger), cli.ips(), !frame_stepped);
 
     loop {
-        cycles += 1;
-        chip_8.tick();
-        chip_8.render(&mut texture, &mut canvas);
-        chip_8.beep(&audio_device);
-        chip_8.get_input(&mut event_pump);
-        if start.elapsed() >= Duration::new(1, 0) {
-            start = std::time::Instant::now();
-            println!("cycles last second: {cycles}");
-            cycles = 0;
-        }
-    }    
+        // each iteration is one displayed frame, paced by vsync when it's presented at the end.
+        // The machine is ours until then, debugger included
+        let mut guard = emulation.lock();
+        let core = &mut *guard;
+        std::mem::swap(&mut session.debugger, &mut core.debugger);
+        let speed = match (session.fast_forward, session.slow_motion) {
+            (true, _) => FAST_FORWARD,
+            (_, true) => cli.slow_motion,
+            _ if session.booting.is_some() => TURBO_BOOT,
+            _ => 1.0,
+        };
+        core.speed = speed;
+        core.rate = session.rate;
+        core.running = !session.rewinding && divergence.is_empty();
+        meter.ran(std::mem::take(&mut core.executed) as u64);
+        let hit_breakpoint = std::mem::take(&mut core.hit_breakpoint);
+        let chip_8 = &mut core.chip_8;
+        // a rewound frame is shown as it was, cheats and all
+        if !session.rewinding {
+            cheats.apply(chip_8);
+        }
+        frame_credit += speed;
+        let mut frame_due = !frame_stepped || frame_credit >= 1.0;
+        if frame_stepped && frame_due {
+            // they run no more than a frame per frame shown, so fast forward only gets them to full speed
+            frame_credit = (frame_credit - 1.0).min(1.0);
+        }
+        if let Some(gdb) = gdb.as_mut() {
+            gdb.poll(chip_8, &mut session.debugger);
+        }
+        let was_paused = session.debugger.paused();
+        let mut exchanged = false;
+        if !session.rewinding && !was_paused && frame_due && divergence.is_empty() {
+            input_log.frame(chip_8);
+            match peer.as_mut().map(|peer| peer.frame(chip_8)) {
+                Some(Turn::Play) => exchanged = true,
+                // the frame waits for the other player, the window going on meanwhile
+                Some(Turn::Wait) => frame_due = false,
+                Some(Turn::Gone) => (peer, session.netplay) = (None, false),
+                None => {}
+            }
+        }
+        if !session.rewinding && !was_paused && frame_due {
+            inputs.push(chip_8.keys());
+        }
+        match run_ahead.as_mut() {
+            // stop on the first divergent frame so it stays on screen
+            _ if !divergence.is_empty() => {}
+            _ if session.rewinding => {
+                rewind.rewind(chip_8);
+                // the speculative frame and any time spent rewinding belong to the abandoned timeline
+                if let Some(run_ahead) = run_ahead.as_mut() {
+                    *run_ahead = RunAhead::default();
+                }
+                core.restart_clocks = true;
+            }
+            _ if session.debugger.paused() => {}
+            _ if !frame_due => {}
+            // breakpoints aren't checked under run-ahead, as the speculative frame could trip them
+            Some(run_ahead) => {
+                meter.ran(instructions_per_frame as u64);
+                run_ahead.run_frame(chip_8, instructions_per_frame);
+            }
+            None if frame_stepped => {
+                meter.ran(session.debugger.run(chip_8, instructions_per_frame) as u64);
+                if !session.debugger.paused() {
+                    chip_8.tick_timers();
+                }
+            }
+            // the emulation thread runs the machine
+            None => {}
+        }
+        if let Some(peer) = peer.as_ref().filter(|_| exchanged) {
+            peer.frame_done(chip_8);
+        }
+        if let Some(boot) = session.booting {
+            let counters = chip_8.counters();
+            if counters.draws != boot.draws || counters.key_checks != boot.key_checks {
+                session.booting = None;
+            }
+        }
+        let faults = chip_8.take_faults();
+        // one report a session, the first fault being the one that led to the rest
+        if let (Some(dir), Some(&(pc, fault))) = (cli.crash_dir.as_ref().filter(|_| !crash_reported), faults.first()) {
+            session.write_crash_report(chip_8, dir, pc, fault);
+            crash_reported = true;
+        }
+        let went_wrong = !faults.is_empty() || chip_8.halted();
+        write_trace(chip_8, &cli, went_wrong, &session.symbols);
+        // stop the session rather than the process, so the summary and report still get written
+        if chip_8.exit_status().is_err() {
+            break;
+        }
+        if let Some(crash_loop) = crash_loops.observe(&faults) {
+            session.debugger.pause();
+            let pc = format_args!("{:#05x}", crash_loop.pc);
+            println!("{}", locale.format(Msg::CrashLoop, &[&crash_loop.fault, &pc, &crash_loop.count]));
+            let suggestion = crash_loop.suggest(session.preset);
+            println!("{}", locale.format(Msg::SwitchPreset, &[&suggestion]));
+            session.suggested_preset = Some(suggestion);
+        }
+        if let Some(practice) = session.practice.as_mut() {
+            let stopped = session.debugger.paused() && (!was_paused || hit_breakpoint);
+            let looped = if stopped && practice.ends_at(chip_8.pc()) {
+                practice.restart(chip_8);
+                session.debugger.resume();
+                true
+            } else {
+                let running = !session.rewinding && !session.debugger.paused() && frame_due;
+                running && practice.elapse(chip_8, if frame_stepped { 1.0 } else { speed })
+            };
+            if looped {
+                println!("{}", locale.format(Msg::Looped, &[&practice.loops()]));
+                if let Some(run_ahead) = run_ahead.as_mut() {
+                    *run_ahead = RunAhead::default();
+                }
+                core.restart_clocks = true;
+            }
+        }
+        if session.debugger.paused() && (!was_paused || hit_breakpoint) {
+            let pc = session.symbols.describe(chip_8.pc());
+            if let Some(hit) = session.debugger.take_watch_hit() {
+                println!("{}", locale.format(Msg::WatchpointHit, &[&hit]));
+            }
+            print!("{}\n{}", locale.format(Msg::BreakpointHit, &[&pc]), Registers(chip_8));
+        }
+        if !session.rewinding && frame_due {
+            rewind.record(chip_8);
+        }
+        if frame_stepped && frame_due && divergence.is_empty() && !session.rewinding {
+            let frame = Frame::capture(chip_8.framebuffer());
+            if let (Some(recorder), Some(path)) = (frame_recorder.as_mut(), cli.record_frames.as_ref()) {
+                if let Err(e) = recorder.write_frame(&frame) {
+                    event!(Level::Warn, "{}", locale.format(Msg::RecordingFailed, &[&path.display(), &e]));
+                    frame_recorder = None;
+                }
+            }
+            if let Some(reference) = reference_frames.as_ref().and_then(|frames| frames.get(frame_index)) {
+                divergence = frame.diff(reference);
+                if !divergence.is_empty() {
+                    println!("{}", locale.format(Msg::FrameDiverges, &[&frame_index, &divergence.len()]));
+                }
+            }
+            frame_index += 1;
+        }
+        if let (Some(screen), Some(canvas)) = (screen.as_mut(), canvas.as_mut()) {
+            let menu_lines = session.pause_menu.map(|menu| {
+                let palette = chip_8.framebuffer().palette();
+                let named = Palette::NAMED.iter().find(|&&(_, named)| named == palette);
+                menu.lines(session.preset, named.map_or("custom", |&(name, _)| name))
+            });
+            let overlays = Overlays {
+                highlight: &divergence,
+                keypad: session.show_hints.then(|| session.hints.keys()),
+                panel: session.show_panel.then(|| (chip_8.keys(), chip_8.take_checked_keys())),
+                inputs: session.show_inputs.then_some(&inputs),
+                crt: session.crt,
+                registers: session.show_registers.then_some(&*chip_8),
+                memory: session.memory_view.as_ref().map(|view| (&*chip_8, view, session.debugger.paused())),
+                watches: (!cli.show.is_empty()).then_some((&*chip_8, cli.show.as_slice())),
+                // the frame by frame modes keep to --ips, as their recordings depend on it
+                speed: if frame_stepped { speed.min(1.0) } else { speed * session.rate },
+                hud: cli.hud.then(|| meter.rates().map(|rates| (rom_file.as_str(), &session.rom_id, rates))).flatten(),
+                pause_menu: session.pause_menu.zip(menu_lines.as_deref()).map(|(menu, lines)| (lines, menu.selected())),
+            };
+            if let Some(afterglow) = afterglow.as_mut() {
+                afterglow.push(chip_8.framebuffer());
+            }
+            // stop the session rather than the process, so the summary and report still get written
+            if let Err(e) = render(chip_8.framebuffer(), afterglow.as_deref(), &overlays, cli.rotate, screen, canvas) {
+                event!(Level::Warn, "{}", locale.format(Msg::RenderFailed, &[&e]));
+                break;
+            }
+            chip_8.take_frame();
+        }
+        session.record_frame(chip_8);
+        let console = chip_8.take_console_output();
+        if !console.is_empty() {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(&console).and_then(|()| stdout.flush()).ok();
+        }
+        for line in chip_8.take_script_output() {
+            println!("{line}");
+        }
+        if let Some(audio_device) = audio_device.as_mut() {
+            chip_8.sound_to(&mut SdlBuzzer(audio_device));
+            let stopped = chip_8.halted() || session.debugger.paused();
+            if audio_paused != stopped {
+                audio_paused = stopped;
+                // fade out rather than cutting the device off when the machine stops
+                audio_device.lock().fader.set(!audio_paused);
+            }
+        }
+        session.gamepads.rumble(chip_8.sound_active() && !chip_8.halted() && !session.debugger.paused());
+        session.announce(chip_8, true);
+        if session.config.as_mut().is_some_and(|file| file.watch.changed()) && !session.netplay_refuses() {
+            session.apply_config(chip_8, false);
+        }
+        if session.rom_watch.changed() && !session.netplay_refuses() && reload_rom(chip_8, &cli, &rom_path, &mut session) {
+            if let Some(run_ahead) = run_ahead.as_mut() {
+                *run_ahead = RunAhead::default();
+            }
+            core.restart_clocks = true;
+        }
+        if let Some(event_pump) = event_pump.as_mut() {
+            match handle_events(chip_8, event_pump, canvas.as_mut().map(Canvas::window_mut), &mut session) {
+                Control::Continue => {}
+                Control::Restarted => {
+                    if let Some(run_ahead) = run_ahead.as_mut() {
+                        *run_ahead = RunAhead::default();
+                    }
+                    core.restart_clocks = true;
+                }
+                Control::PickRom => {
+                    let picked = match (canvas.as_mut(), screen.as_mut()) {
+                        (Some(canvas), Some(screen)) => {
+                            let db = load_rom_db(&cli, locale);
+                            let dir = rom_dir(&cli, &rom_path);
+                            let scores = Leaderboard::load(session.storage.as_ref());
+                            pick_rom(&dir, &recent, &db, &scores, locale, canvas, screen, event_pump, &session.keymap, &mut session.gamepads, cli.rotate)
+                        }
+                        _ => None,
+                    };
+                    if picked.is_some() && cli.autosave {
+                        session.autosave(chip_8);
+                    }
+                    if let Some(path) = picked.filter(|path| switch_rom(chip_8, &cli, path, &mut session)) {
+                        if cli.autosave {
+                            session.restore_autosave(chip_8);
+                        }
+                        roms[current_rom] = (path.clone(), None);
+                        session.booting = cli.turbo_boot.then(|| chip_8.counters());
+                        recent.played(&path);
+                        if let Err(e) = session.storage.write(Recent::KEY, recent.to_string().as_bytes()) {
+                            event!(Level::Warn, "{}", locale.format(Msg::RecentSaveFailed, &[&e]));
+                        }
+                        rom_file = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                        rom_path = path;
+                        // the states to rewind to are the last ROM's
+                        rewind = Rewind::new(rewind_states as usize, cli.rewind_interval);
+                        if let Some(run_ahead) = run_ahead.as_mut() {
+                            *run_ahead = RunAhead::default();
+                        }
+                    }
+                    // however long the menu was up
+                    core.restart_clocks = true;
+                }
+                Control::SwitchRom(_) if roms.len() == 1 => println!("{}", locale.text(Msg::NoOtherRoms)),
+                Control::SwitchRom(step) => {
+                    let next = (current_rom as isize + step).rem_euclid(roms.len() as isize) as usize;
+                    let state = chip_8.save_state();
+                    if switch_rom(chip_8, &cli, &roms[next].0, &mut session) {
+                        roms[current_rom].1 = Some(state);
+                        current_rom = next;
+                        // a ROM switched back to carries on where it was left
+                        match roms[next].1.take() {
+                            Some(state) => {
+                                chip_8.load_state(&state).expect("switching ROMs keeps states this machine wrote");
+                            }
+                            None => session.booting = cli.turbo_boot.then(|| chip_8.counters()),
+                        }
+                        let path = roms[next].0.clone();
+                        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                        println!("{}", locale.format(Msg::RomSwitched, &[&(next + 1), &roms.len(), &name]));
+                        rom_file = name;
+                        rom_path = path;
+                        rewind = Rewind::new(rewind_states as usize, cli.rewind_interval);
+                        if let Some(run_ahead) = run_ahead.as_mut() {
+                            *run_ahead = RunAhead::default();
+                        }
+                        core.restart_clocks = true;
+                    }
+                }
+                Control::Quit => break,
+            }
+        }
+        if let Some(canvas) = canvas.as_mut() {
+            let title = match &session.prompt {
+                Some(prompt) => locale.format(Msg::BreakpointPrompt, &[prompt]),
+                None => match meter.rates() {
+                    Some(rates) if !cli.hud => format!("{} - {rates}", session.title),
+                    _ => session.title.clone(),
+                },
+            };
+            if canvas.window().title() != title {
+                canvas.window_mut().set_title(&title).ok();
+            }
+        }
+        if let Some(watch) = score_watch {
+            session_best = session_best.max(watch.read_from(chip_8));
+        }
+        session.save_rpl_flags(chip_8);
+        meter.frame();
+        if meter.update(std::time::Instant::now()).is_some() {
+            let pitch = chip_8.pitch();
+            if pitch != Pitch::default() {
+                event!(Level::Debug, "{}", locale.format(Msg::AudioPitch, &[&pitch.register, &format_args!("{:.0}", pitch.rate())]));
+            }
+        }
+        // a pause, whatever paused it, shouldn't count against the clocks once it's over
+        core.restart_clocks |= session.debugger.paused();
+        std::mem::swap(&mut session.debugger, &mut core.debugger);
+        drop(guard);
+        match canvas.as_mut() {
+            Some(canvas) => canvas.present(),
+            // nothing paces the loop without vsync
+            None => std::thread::sleep(frame_time),
+        }
+    }
+    let Core { chip_8, .. } = emulation.finish();
+
+    // however the session ended: by the window closing, Ctrl-C, which SDL turns into the same
+    // quit event, the pause menu or the ROM exiting. The sound stops first, so nothing more
+    // reaches the audio recording
+    if let Some(device) = &audio_device {
+        device.pause();
+    }
+    session.save_rpl_flags(&chip_8);
+    // a machine that crashed is no state to pick up from
+    if cli.autosave && chip_8.exit_status().is_ok() {
+        session.autosave(&chip_8);
+    }
+    session.stop_recording();
+    let capture = audio_device.as_mut().and_then(|device| device.lock().capture.take());
+    if let (Some(capture), Some(path)) = (capture, &cli.record_audio) {
+        match capture.finish() {
+            Ok(()) => println!("{}", locale.format(Msg::RecordingSaved, &[&path.display()])),
+            Err(e) => event!(Level::Warn, "{}", locale.format(Msg::RecordingFailed, &[&path.display(), &e])),
+        }
+    }
+    input_log.finish();
+    if let Some(board) = leaderboard.as_mut() {
+        if board.submit(&session.rom_id.key(), session_best) {
+            println!("{}", locale.format(Msg::NewHighScore, &[&session_best]));
+            if let Err(e) = board.save(session.storage.as_mut()) {
+                event!(Level::Warn, "{}", locale.format(Msg::ScoresSaveFailed, &[&e]));
+            }
+        }
+    }
+    session.count_play_time();
+    if let Err(e) = session.play_times.save(session.storage.as_mut()) {
+        event!(Level::Warn, "{}", locale.format(Msg::PlayTimeSaveFailed, &[&e]));
+    }
+    let stats = SessionStats {
+        play_time: session_start.elapsed(),
+        counters: chip_8.counters(),
+        states_saved: session.states_saved,
+    };
+    match &cli.stats_file {
+        Some(path) => if let Err(e) = std::fs::write(path, stats.to_string()) {
+            event!(Level::Warn, "{}", locale.format(Msg::SummaryWriteFailed, &[&path.display(), &e]));
+        }
+        None => print!("{stats}"),
+    }
+    if let Some(diagnostics) = chip_8.diagnostics() {
+        print!("{diagnostics}");
+    }
+    write_profile(&chip_8, &cli, locale);
+    write_coverage_map(&chip_8, &cli, locale);
+    if let Some(path) = &cli.report {
+        let config = [
+            ("Emulator", concat!("chip_8_rs ", env!("CARGO_PKG_VERSION")).to_string()),
+            ("ROM", rom_path.display().to_string()),
+            ("CRC32", session.rom_id.key()),
+            ("SHA-1", session.rom_id.sha1_hex()),
+            ("Preset", session.preset.to_string()),
+            ("Profile", format!("{:?}", cli.profile)),
+            ("Odd PC policy", format!("{:?}", cli.odd_pc)),
+            ("Fault policy", format!("{:?}", cli.on_fault)),
+            ("Play time", format!("{:.1}s", stats.play_time.as_secs_f64())),
+        ]
+        .map(|(name, value)| (name.to_string(), value));
+        let title = format!("CHIP-8 session: {}", rom_path.file_name().unwrap_or_default().to_string_lossy());
+        let report = Report { title: &title, chip_8: &chip_8, config: &config };
+        match std::fs::write(path, report.to_string()) {
+            Ok(()) => println!("{}", locale.format(Msg::ReportWritten, &[&path.display()])),
+            Err(e) => event!(Level::Warn, "{}", locale.format(Msg::ReportWriteFailed, &[&path.display(), &e])),
+        }
+    }
+    if let Err(e) = chip_8.exit_status() {
+        fail(locale.format(Msg::FaultExit, &[&e]));
+    }
 }
+
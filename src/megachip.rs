@@ -0,0 +1,233 @@
+//! MegaChip's screen: 256x192 pixels whose colors come from a palette of 255 the program loads
+//! itself, drawn with sprites of any size that hold one palette index per pixel. The program
+//! draws out of sight and 00E0 puts the finished picture up, starting the next one blank.
+//! Display keeps one of these while MegaChip mode is on
+
+use crate::state::{Reader, StateError, Writer};
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+/// The palette index CHIP-8 font sprites are drawn in, as they carry no colors of their own
+pub const FONT_COLOR: u8 = 0xFF;
+
+/// How a sprite's colors combine with what's already on the screen, set by 080N
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The sprite's colors replace the screen's
+    #[default]
+    Normal,
+    /// A quarter of the sprite's color over the screen's
+    Quarter,
+    /// Half and half
+    Half,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    /// The mode 080N selects, or None for an N MegaChip doesn't define
+    pub fn from_number(n: u8) -> Option<Self> {
+        Some(match n {
+            0 => BlendMode::Normal,
+            1 => BlendMode::Quarter,
+            2 => BlendMode::Half,
+            3 => BlendMode::Add,
+            4 => BlendMode::Multiply,
+            _ => return None,
+        })
+    }
+
+    fn number(self) -> u8 {
+        match self {
+            BlendMode::Normal => 0,
+            BlendMode::Quarter => 1,
+            BlendMode::Half => 2,
+            BlendMode::Add => 3,
+            BlendMode::Multiply => 4,
+        }
+    }
+
+    fn apply(self, sprite: [u8; 3], screen: [u8; 3]) -> [u8; 3] {
+        std::array::from_fn(|n| {
+            let (s, d) = (sprite[n] as u16, screen[n] as u16);
+            (match self {
+                BlendMode::Normal => s,
+                BlendMode::Quarter => (s + 3 * d) / 4,
+                BlendMode::Half => (s + d) / 2,
+                BlendMode::Add => (s + d).min(255),
+                BlendMode::Multiply => s * d / 255,
+            }) as u8
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaScreen {
+    /// The palette index last drawn to each pixel of the picture being drawn, for collisions
+    indices: Vec<u8>,
+    /// The picture being drawn, in the colors blending made of it
+    drawing: Vec<[u8; 3]>,
+    /// The picture 00E0 last put up
+    shown: Vec<[u8; 3]>,
+    /// ARGB colors loaded by 02NN; 0 is transparent and never drawn
+    pub palette: [[u8; 4]; 256],
+    /// Size of the sprites DXYN draws, from 03NN and 04NN
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    pub blend: BlendMode,
+    /// Drawing over a pixel in this palette index sets VF, from 09NN; 0, the default, never does
+    pub collision_color: u8,
+    /// The screen's alpha from 05NN, kept for the program's sake as the screen isn't composited
+    pub alpha: u8,
+}
+
+impl Default for MegaScreen {
+    /// Blank, with every palette entry white until the program loads its own
+    fn default() -> Self {
+        let mut palette = [[0xFF; 4]; 256];
+        palette[0] = [0; 4];
+        Self {
+            indices: vec![0; WIDTH * HEIGHT],
+            drawing: vec![[0; 3]; WIDTH * HEIGHT],
+            shown: vec![[0; 3]; WIDTH * HEIGHT],
+            palette,
+            sprite_width: 0,
+            sprite_height: 0,
+            blend: BlendMode::Normal,
+            collision_color: 0,
+            alpha: 0xFF,
+        }
+    }
+}
+
+impl MegaScreen {
+    /// The color shown at (`x`, `y`)
+    pub fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        self.shown[y * WIDTH + x]
+    }
+
+    /// Sets the palette entries from 1 on to the ARGB colors in `colors`, 4 bytes each
+    pub fn load_palette(&mut self, colors: &[u8]) {
+        for (entry, color) in self.palette[1..].iter_mut().zip(colors.chunks_exact(4)) {
+            entry.copy_from_slice(color);
+        }
+    }
+
+    /// Draws a sprite of sprite_width by sprite_height palette indices at (`x`, `y`), leaving
+    /// out index 0 and whatever falls off the screen. Returns whether any pixel drawn over was
+    /// in the collision color
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let width = self.sprite_width;
+        let mut collided = false;
+        for (row, indices) in sprite.chunks(width.max(1)).enumerate() {
+            for (col, &index) in indices.iter().enumerate() {
+                collided |= self.plot(x + col, y + row, index);
+            }
+        }
+        collided
+    }
+
+    /// Draws a plain CHIP-8 sprite, 8 pixels to a byte, in FONT_COLOR
+    pub fn draw_bits(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collided = false;
+        for (row, &bits) in sprite.iter().enumerate() {
+            for col in (0..8).filter(|col| bits >> (7 - col) & 1 == 1) {
+                collided |= self.plot(x + col, y + row, FONT_COLOR);
+            }
+        }
+        collided
+    }
+
+    fn plot(&mut self, x: usize, y: usize, index: u8) -> bool {
+        if index == 0 || x >= WIDTH || y >= HEIGHT {
+            return false;
+        }
+        let n = y * WIDTH + x;
+        let collided = self.collision_color != 0 && self.indices[n] == self.collision_color;
+        let [_, r, g, b] = self.palette[index as usize];
+        self.indices[n] = index;
+        self.drawing[n] = self.blend.apply([r, g, b], self.drawing[n]);
+        collided
+    }
+
+    /// 00E0: puts the picture drawn so far up and starts the next one blank
+    pub fn present(&mut self) {
+        std::mem::swap(&mut self.shown, &mut self.drawing);
+        self.drawing.fill([0; 3]);
+        self.indices.fill(0);
+    }
+
+    /// Moves the picture being drawn by `dx` and `dy` pixels, blanking what's uncovered
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let from = |x: usize, y: usize| {
+            let (x, y) = (x.checked_add_signed(-dx)?, y.checked_add_signed(-dy)?);
+            (x < WIDTH && y < HEIGHT).then_some(y * WIDTH + x)
+        };
+        let (indices, drawing) = (self.indices.clone(), self.drawing.clone());
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let n = y * WIDTH + x;
+                (self.indices[n], self.drawing[n]) = from(x, y).map_or((0, [0; 3]), |m| (indices[m], drawing[m]));
+            }
+        }
+    }
+
+    pub(crate) fn save(&self, w: &mut Writer) {
+        w.raw(&self.indices);
+        w.raw(self.drawing.as_flattened());
+        w.raw(self.shown.as_flattened());
+        w.raw(self.palette.as_flattened());
+        w.u16(self.sprite_width as u16);
+        w.u16(self.sprite_height as u16);
+        w.u8(self.blend.number());
+        w.u8(self.collision_color);
+        w.u8(self.alpha);
+    }
+
+    pub(crate) fn load(r: &mut Reader) -> Result<Self, StateError> {
+        let pixels = |r: &mut Reader| -> Result<Vec<[u8; 3]>, StateError> {
+            Ok(r.raw(WIDTH * HEIGHT * 3)?.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect())
+        };
+        let indices = r.raw(WIDTH * HEIGHT)?.to_vec();
+        let (drawing, shown) = (pixels(r)?, pixels(r)?);
+        let mut palette = [[0; 4]; 256];
+        for entry in palette.iter_mut() {
+            *entry = r.array()?;
+        }
+        let (sprite_width, sprite_height) = (r.u16()? as usize, r.u16()? as usize);
+        let blend = BlendMode::from_number(r.u8()?).ok_or(StateError::Invalid("blend mode"))?;
+        Ok(Self { indices, drawing, shown, palette, sprite_width, sprite_height, blend, collision_color: r.u8()?, alpha: r.u8()? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quirks::Preset;
+    use crate::Chip8;
+
+    #[test]
+    fn sprites_are_drawn_in_the_loaded_palette_and_shown_by_00e0() {
+        // MEGAON, I := 0x220, load one color, 2x1 sprites, I := 0x224, draw at (10, 10), show it
+        let mut rom = vec![
+            0x00, 0x11, 0x01, 0x00, 0x02, 0x20, 0x02, 0x01, 0x03, 0x02, 0x04, 0x01,
+            0x01, 0x00, 0x02, 0x24, 0x60, 0x0A, 0xD0, 0x05, 0x00, 0xE0, 0x00, 0xFD,
+        ];
+        rom.resize(0x20, 0);
+        rom.extend([0xFF, 0x12, 0x34, 0x56, 0x01, 0x00]);
+        let mut chip_8 = Chip8::builder().platform(Preset::Megachip).rom_bytes(&rom).build();
+        for _ in 0..8 {
+            chip_8.step();
+        }
+        assert_eq!(chip_8.framebuffer().dimensions(), (256, 192));
+        assert_eq!(chip_8.framebuffer().color(10, 10), [0, 0, 0]);
+        chip_8.step();
+        assert_eq!(chip_8.framebuffer().color(10, 10), [0x12, 0x34, 0x56]);
+        assert!(!chip_8.framebuffer().pixel(11, 10));
+
+        let state = chip_8.save_state();
+        let mut again = Chip8::builder().platform(Preset::Megachip).rom_bytes(&rom).build();
+        again.load_state(&state).unwrap();
+        assert_eq!(again.framebuffer().color(10, 10), [0x12, 0x34, 0x56]);
+    }
+}
@@ -0,0 +1,126 @@
+//! The 4KB address space: the interpreter area holding the fonts, then the program from 0x200.
+//! XO-CHIP programs get 64KB, all that a 16-bit I reaches, and MegaChip ones as much as their
+//! 24-bit I needs to reach all of the ROM. Addresses past the end wrap around, whatever the size
+
+use crate::fonts::Fonts;
+use crate::{BIG_FONT_ADDR, FONT_ADDR};
+
+/// Size of the address space on the interpreters before XO-CHIP
+pub const MEMORY_SIZE: usize = 4096;
+
+/// Size of XO-CHIP's address space
+pub const WIDE_MEMORY_SIZE: usize = 0x10000;
+
+/// Where programs are loaded and start executing
+pub const PROGRAM_START: u16 = 0x200;
+
+/// The most MegaChip can address
+pub const MEGA_MEMORY_SIZE: usize = 1 << 24;
+
+/// The least MegaChip programs get, leaving them room beyond a small ROM
+const MEGA_MEMORY_MIN: usize = WIDE_MEMORY_SIZE;
+
+/// Memory for a MegaChip program of `rom_len` bytes: the next power of two that holds it, so
+/// addresses wrap around past the end as they do in 4KB
+pub fn mega_memory_size(rom_len: usize) -> usize {
+    (PROGRAM_START as usize + rom_len).next_power_of_two().clamp(MEGA_MEMORY_MIN, MEGA_MEMORY_SIZE)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Memory(Vec<u8>);
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::with_program(&[], &Fonts::default())
+    }
+}
+
+impl Memory {
+    /// Fresh memory with `fonts` installed and `rom` at PROGRAM_START, cut off where memory ends
+    pub fn with_program(rom: &[u8], fonts: &Fonts) -> Self {
+        Self::with_program_at(rom, fonts, PROGRAM_START, MEMORY_SIZE)
+    }
+
+    /// Like with_program(), with `rom` at `start` in `size` bytes of memory instead
+    pub fn with_program_at(rom: &[u8], fonts: &Fonts, start: u16, size: usize) -> Self {
+        let mut memory = Self(vec![0; size]);
+        memory.install_fonts(fonts);
+        let program = &mut memory.0[start as usize..];
+        let len = rom.len().min(program.len());
+        program[..len].copy_from_slice(&rom[..len]);
+        memory
+    }
+
+    /// Writes `fonts` over the glyphs at FONT_ADDR and BIG_FONT_ADDR
+    pub fn install_fonts(&mut self, fonts: &Fonts) {
+        self.0[FONT_ADDR as usize..][..fonts.small.len()].copy_from_slice(&fonts.small);
+        self.0[BIG_FONT_ADDR as usize..][..fonts.big.len()].copy_from_slice(&fonts.big);
+    }
+
+    /// Memory restored from a snapshot, or None if it isn't the size of an address space
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        let len = bytes.len();
+        let mega = len.is_power_of_two() && (MEGA_MEMORY_MIN..=MEGA_MEMORY_SIZE).contains(&len);
+        (len == MEMORY_SIZE || mega).then_some(Self(bytes))
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether `addr` is inside the address space, as opposed to wrapping around into it
+    pub fn contains(&self, addr: usize) -> bool {
+        addr < self.0.len()
+    }
+
+    /// The byte at `addr`, wrapping around past the end
+    pub fn get(&self, addr: usize) -> u8 {
+        self.0[addr % self.0.len()]
+    }
+
+    /// Stores `byte` at `addr`, wrapping around past the end
+    pub fn set(&mut self, addr: usize, byte: u8) {
+        let len = self.0.len();
+        self.0[addr % len] = byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BIG_FONT, FONT};
+
+    #[test]
+    fn program_follows_the_fonts() {
+        let memory = Memory::with_program(&[0x12, 0x34], &Fonts::default());
+        assert_eq!(memory.bytes().len(), MEMORY_SIZE);
+        assert_eq!(&memory.bytes()[FONT_ADDR as usize..][..FONT.len()], &FONT);
+        assert_eq!(&memory.bytes()[BIG_FONT_ADDR as usize..][..BIG_FONT.len()], &BIG_FONT);
+        assert_eq!(memory.get(0x200), 0x12);
+        assert_eq!(memory.get(0x201), 0x34);
+        assert_eq!(memory.get(0x202), 0);
+    }
+
+    #[test]
+    fn oversized_programs_are_cut_off() {
+        let memory = Memory::with_program(&[0xAA; MEMORY_SIZE], &Fonts::default());
+        assert_eq!(memory.bytes().len(), MEMORY_SIZE);
+        assert_eq!(memory.get(MEMORY_SIZE - 1), 0xAA);
+    }
+
+    #[test]
+    fn addresses_wrap_past_the_end() {
+        let mut memory = Memory::default();
+        memory.set(MEMORY_SIZE + 0x300, 7);
+        assert_eq!(memory.get(0x300), 7);
+        assert_eq!(memory.get(MEMORY_SIZE + 0x300), 7);
+        assert!(memory.contains(MEMORY_SIZE - 1));
+        assert!(!memory.contains(MEMORY_SIZE));
+    }
+
+    #[test]
+    fn snapshots_must_cover_the_address_space() {
+        assert!(Memory::from_bytes(vec![0; MEMORY_SIZE]).is_some());
+        assert!(Memory::from_bytes(vec![0; 512]).is_none());
+    }
+}
@@ -0,0 +1,101 @@
+; The built-in ROM menu. The host appends the number of games after `count`, then each game's
+; name as a 56x5 strip of seven 8x5 sprites, left to right. 5 and 8 move the cursor and 6
+; starts the game under it, by exiting with its number in V0.
+;
+; V2 cursor, V3 number of games, V4 first game on the page, V5 row on the page,
+; V6/V7 where the next sprite goes, V0/V1/V8/V9 scratch
+
+        LD I, count
+        LD V0, [I]
+        LD V3, V0
+        LD V2, 0
+        SNE V3, 0
+empty:  JP empty                ; nothing to pick from
+
+page:   CLS
+        ; find the page holding the cursor, and the first name on it
+        LD I, count
+        LD V8, 1
+        ADD I, V8
+        LD V4, 0
+        LD V9, 175              ; a page: 5 names of 35 bytes
+find:   LD V8, V4
+        ADD V8, 5
+        LD V0, V2
+        SUB V0, V8              ; VF is set when the cursor is on a later page
+        SE VF, 1
+        JP rows
+        LD V4, V8
+        ADD I, V9
+        JP find
+
+rows:   LD V5, 0
+        LD V7, 1
+row:    LD V0, V4
+        ADD V0, V5
+        SNE V0, V3              ; past the last game
+        JP shown
+        LD V6, 4
+        LD V8, 5
+chunk:  DRW V6, V7, 5
+        ADD I, V8
+        ADD V6, 8
+        SE V6, 60
+        JP chunk
+        ADD V7, 6
+        ADD V5, 1
+        SE V5, 5
+        JP row
+shown:  CALL cursor
+
+input:  LD V0, K
+        SNE V0, 5
+        JP up
+        SNE V0, 8
+        JP down
+        SNE V0, 6
+        JP start
+        JP input
+
+up:     SNE V2, 0
+        JP input
+        CALL cursor             ; drawing the arrow again erases it
+        ADD V2, 255
+        JP moved
+down:   LD V8, V2
+        ADD V8, 1
+        SNE V8, V3
+        JP input
+        CALL cursor
+        LD V2, V8
+moved:  ; start over if the cursor left the page
+        LD V0, V2
+        SUB V0, V4
+        SE VF, 1
+        JP page
+        LD V1, 5
+        SUBN V1, V0             ; VF is set when the cursor is 5 or more rows down
+        SE VF, 0
+        JP page
+        CALL cursor
+        JP input
+
+start:  LD V0, V2
+        EXIT
+
+; draws the arrow next to the game under the cursor
+cursor: LD V0, V2
+        SUB V0, V4
+        LD V1, 1
+offset: SNE V0, 0
+        JP point
+        ADD V1, 6
+        ADD V0, 255
+        JP offset
+point:  LD I, arrow
+        LD V0, 0
+        DRW V0, V1, 5
+        RET
+
+arrow:  db 0b10000000, 0b11000000, 0b11100000, 0b11000000, 0b10000000
+count:
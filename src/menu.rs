@@ -0,0 +1,243 @@
+//! The built-in ROM menu: a CHIP-8 program, see menu.asm, that lists the games in a directory
+//! or a zip archive and lets the player pick one with the keypad alone. 5 and 8 move the
+//! cursor, 6 starts the game.
+//!
+//! The host renders the names into sprite strips with a 3x5 font and appends them to the
+//! program; the menu exits (00FD) with the chosen game's number in V0.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::asm::assemble;
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+use crate::quirks::Preset;
+use crate::{zip, Chip8};
+
+const SOURCE: &str = include_str!("menu.asm");
+
+/// File extensions picked up as ROMs, Octo source included as it's compiled on loading
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "8o"];
+
+/// Characters that fit on a line next to the cursor
+const NAME_LEN: usize = 14;
+
+/// Each name is stored as seven 8x5 sprites
+const STRIP_BYTES: usize = NAME_LEN / 2 * 5;
+
+/// How many recently played ROMs are remembered
+pub const RECENT_LEN: usize = 8;
+
+/// The ROMs played last, newest first, kept under `recent.txt` in storage one path a line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recent(pub Vec<PathBuf>);
+
+impl Recent {
+    pub const KEY: &'static str = "recent.txt";
+
+    pub fn parse(text: &str) -> Self {
+        Self(text.lines().filter(|line| !line.trim().is_empty()).map(PathBuf::from).take(RECENT_LEN).collect())
+    }
+
+    /// Puts `rom` at the front, dropping the oldest past RECENT_LEN
+    pub fn played(&mut self, rom: &Path) {
+        self.0.retain(|recent| recent != rom);
+        self.0.insert(0, rom.to_path_buf());
+        self.0.truncate(RECENT_LEN);
+    }
+
+    /// Moves the recently played ones among `roms` to the front, newest first
+    pub fn order(&self, roms: &mut [PathBuf]) {
+        roms.sort_by_key(|rom| self.0.iter().position(|recent| recent == rom).unwrap_or(RECENT_LEN));
+    }
+}
+
+impl std::fmt::Display for Recent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for rom in &self.0 {
+            writeln!(f, "{}", rom.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The ROMs in `dir`, sorted by file name. For a zip archive they're the ROM entries, as
+/// paths under the archive's
+pub fn scan(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let is_rom = |path: &Path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+    };
+    let mut roms = vec![];
+    if zip::is_zip(dir) {
+        let entries = zip::entries(&std::fs::read(dir)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        roms.extend(entries.into_iter().map(|entry| dir.join(entry.name)).filter(|path| is_rom(path)));
+    } else {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if is_rom(&path) && path.is_file() {
+                roms.push(path);
+            }
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+fn program() -> Vec<u8> {
+    assemble(SOURCE).unwrap_or_else(|e| panic!("the built-in menu doesn't assemble: {e}"))
+}
+
+/// How many games the menu has room for; the rest of a longer list is left out
+pub fn capacity() -> usize {
+    let free = MEMORY_SIZE - PROGRAM_START as usize - program().len() - 1;
+    (free / STRIP_BYTES).min(u8::MAX as usize)
+}
+
+/// `name` as the menu lists it, with `best`, its high score if it has one, at the end of the line
+/// and the name cut short to make room
+pub fn label(name: &str, best: Option<u64>) -> String {
+    let Some(best) = best else {
+        return name.to_string();
+    };
+    let best = best.to_string();
+    let room = NAME_LEN.saturating_sub(best.len() + 1);
+    format!("{:room$} {best}", name.chars().take(room).collect::<String>())
+}
+
+/// A machine running the menu, listing `names`
+pub fn boot(names: &[String]) -> Chip8 {
+    let names = &names[..names.len().min(capacity())];
+    let mut rom = program();
+    rom.push(names.len() as u8);
+    for name in names {
+        rom.extend_from_slice(&strip(name));
+    }
+    // no display wait, so a page draws in a single frame
+    Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).build()
+}
+
+/// The number of the game picked, once the menu has exited
+pub fn choice(chip_8: &Chip8) -> Option<usize> {
+    chip_8.halted().then(|| chip_8.registers()[0] as usize)
+}
+
+/// Renders `name` as the sprites the menu draws, two characters to each 8x5 sprite
+fn strip(name: &str) -> [u8; STRIP_BYTES] {
+    let mut glyphs = name.chars().map(glyph).chain(std::iter::repeat([0; 5])).take(NAME_LEN);
+    let mut strip = [0; STRIP_BYTES];
+    for sprite in strip.chunks_mut(5) {
+        let (left, right) = (glyphs.next().unwrap_or_default(), glyphs.next().unwrap_or_default());
+        for (row, byte) in sprite.iter_mut().enumerate() {
+            *byte = left[row] << 5 | right[row] << 1;
+        }
+    }
+    strip
+}
+
+/// 3x5 glyph rows, bit 2 leftmost, also used for frontends' on-screen text. Characters the font
+/// lacks come out blank
+pub fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 3, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 2, 2, 2],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        '-' => [0, 0, 7, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        '_' => [0, 0, 0, 0, 7],
+        ',' => [0, 0, 0, 2, 4],
+        ':' => [0, 2, 0, 2, 0],
+        '[' => [6, 4, 4, 4, 6],
+        ']' => [3, 1, 1, 1, 3],
+        '?' => [6, 1, 2, 0, 2],
+        _ => [0; 5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::INSTRUCTIONS_PER_FRAME;
+
+    /// Presses and releases `key`, then gives the menu time to redraw a page
+    fn tap(chip_8: &mut Chip8, key: u8) {
+        chip_8.press(key);
+        chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+        chip_8.release(key);
+        for _ in 0..30 {
+            chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+        }
+    }
+
+    #[test]
+    fn picks_with_the_keypad() {
+        let names: Vec<String> = (0..7).map(|n| format!("GAME {n}")).collect();
+        let mut chip_8 = boot(&names);
+        for _ in 0..60 {
+            chip_8.run_frame(INSTRUCTIONS_PER_FRAME);
+        }
+        // down onto the second page and back up one
+        for key in [0x8, 0x8, 0x8, 0x8, 0x8, 0x8, 0x5] {
+            tap(&mut chip_8, key);
+        }
+        assert_eq!(choice(&chip_8), None);
+        tap(&mut chip_8, 0x6);
+        assert_eq!(choice(&chip_8), Some(5));
+        assert!(chip_8.take_faults().is_empty());
+    }
+
+    #[test]
+    fn recently_played_come_first() {
+        let mut recent = Recent::parse("b.ch8\n");
+        recent.played(Path::new("c.ch8"));
+        recent.played(Path::new("b.ch8"));
+        assert_eq!(Recent::parse(&recent.to_string()), recent);
+        let mut roms: Vec<PathBuf> = ["a.ch8", "b.ch8", "c.ch8", "d.ch8"].map(PathBuf::from).into();
+        recent.order(&mut roms);
+        assert_eq!(roms, ["b.ch8", "c.ch8", "a.ch8", "d.ch8"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn names_are_drawn_two_characters_to_a_sprite() {
+        let strip = strip("a1");
+        assert_eq!(strip[..5], [0b0100_0100, 0b1010_1100, 0b1110_0100, 0b1010_0100, 0b1010_1110]);
+        assert!(strip[5..].iter().all(|&byte| byte == 0));
+        // high scores go at the end of the line
+        assert_eq!(label("SPACE INVADERS", Some(1200)), "SPACE INV 1200");
+        assert_eq!(label("PONG", Some(7)), "PONG         7");
+        assert_eq!(label("PONG", None), "PONG");
+    }
+}
@@ -0,0 +1,324 @@
+//! Two players on two machines, each running its own core in lockstep with the other's. Both
+//! start from the same ROM, quirks, variant, rate and CXNN seed, and every frame each sends the other the keys
+//! its player holds, so both run every frame with the same keypad: the two players' keys
+//! together. That's the two-player games' way, Pong's player one on 1/4 and player two on C/D.
+//!
+//! Keys take effect `delay` frames after they're sent, for the other side's to have arrived by
+//! the time they're needed; a peer that falls further behind holds the other up. Over a stream
+//! such as TCP, after a handshake of [`Hello`]s, each frame is six bytes: the keys, then the
+//! [`state_hash`] of the machine as the frame began, both big-endian. Comparing the hashes
+//! tells when the two machines drifted apart after all.
+//!
+//! A side that pauses stops sending, and the other side's frames wait for it. Given a stream
+//! with a read timeout, a frame that times out waiting comes back as not ready to run rather
+//! than blocking, and is finished by calling frame() again.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use crate::lockstep::state_hash;
+use crate::patch::crc32;
+use crate::Chip8;
+
+/// Frames keys are sent ahead of when they take effect, unless the host asks for other
+pub const DEFAULT_DELAY: u8 = 2;
+
+const MAGIC: &[u8; 4] = b"C8NP";
+const HELLO_SIZE: usize = 21;
+
+/// What the two sides have to agree on, as each sends it on connecting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hello {
+    /// The CRC-32 of the ROM
+    pub rom: u32,
+    /// The CRC-32 of the quirks and variant, see [`machine_digest`]
+    pub machine: u32,
+    pub ips: u32,
+    /// The CXNN seed, the host's being the one both use
+    pub seed: u32,
+    /// The host's delay, which both use
+    pub delay: u8,
+}
+
+impl Hello {
+    pub fn new(chip_8: &Chip8, ips: u32, seed: u32, delay: u8) -> Self {
+        Self { rom: crc32(chip_8.rom()), machine: machine_digest(chip_8), ips, seed, delay }
+    }
+
+    fn to_bytes(self) -> [u8; HELLO_SIZE] {
+        let mut bytes = [0; HELLO_SIZE];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4..8].copy_from_slice(&self.rom.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.machine.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.ips.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.seed.to_be_bytes());
+        bytes[20] = self.delay;
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HELLO_SIZE]) -> io::Result<Self> {
+        if &bytes[..4] != MAGIC {
+            return Err(invalid("the other side isn't a CHIP-8 netplay peer".to_string()));
+        }
+        let word = |at: usize| u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        Ok(Self { rom: word(4), machine: word(8), ips: word(12), seed: word(16), delay: bytes[20] })
+    }
+}
+
+/// The CRC-32 of `chip_8`'s quirks and variant, which two machines running the same ROM have to
+/// share to stay in step
+pub fn machine_digest(chip_8: &Chip8) -> u32 {
+    crc32(format!("{:?} {:?}", chip_8.quirks(), chip_8.variant()).as_bytes())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct Netplay<S> {
+    stream: S,
+    delay: u64,
+    /// Frames exchanged so far
+    frame: u64,
+    /// Our player's keys that haven't taken effect yet, oldest first
+    queued: VecDeque<u16>,
+    /// Our hashes the other side's haven't been compared with yet, oldest first
+    hashes: VecDeque<u32>,
+    /// Our player's keys as of the last frame, for the frontend to go on from
+    local: u16,
+    /// Whether this frame's message went out already, while we wait for the other side's
+    sent: bool,
+    /// The part of the other side's message that came before a read timed out
+    received: Vec<u8>,
+    /// Whether the machines have been found to differ, to say so once
+    desynced: bool,
+    /// The frame they were found to differ from, until taken
+    desync: Option<u64>,
+}
+
+impl<S: Read + Write> Netplay<S> {
+    /// Exchanges hellos over `stream`, `ours` being this side's, returning the hello both go by:
+    /// ours with the host's seed and delay. Fails when the two don't run the same ROM on the same
+    /// machine at the same rate
+    pub fn connect(mut stream: S, ours: Hello, host: bool) -> io::Result<(Self, Hello)> {
+        stream.write_all(&ours.to_bytes())?;
+        stream.flush()?;
+        let mut bytes = [0; HELLO_SIZE];
+        stream.read_exact(&mut bytes)?;
+        let theirs = Hello::from_bytes(&bytes)?;
+        if theirs.rom != ours.rom {
+            return Err(invalid(format!("the other side runs another ROM (CRC {:08x}, ours {:08x})", theirs.rom, ours.rom)));
+        }
+        if theirs.machine != ours.machine {
+            return Err(invalid("the other side runs the ROM with other quirks or another variant".to_string()));
+        }
+        if theirs.ips != ours.ips {
+            return Err(invalid(format!("the other side runs at {} instructions per second, we run at {}", theirs.ips, ours.ips)));
+        }
+        let agreed = if host { ours } else { Hello { seed: theirs.seed, delay: theirs.delay, ..ours } };
+        let delay = agreed.delay as u64;
+        let netplay = Self {
+            stream,
+            delay,
+            frame: 0,
+            queued: std::iter::repeat_n(0, delay as usize).collect(),
+            hashes: VecDeque::new(),
+            local: 0,
+            sent: false,
+            received: Vec::new(),
+            desynced: false,
+            desync: None,
+        };
+        Ok((netplay, agreed))
+    }
+
+    /// Call before emulating each frame, with our player's keys held in `chip_8`: sends them and
+    /// holds both players' keys due this frame instead. Returns false, leaving `chip_8` alone,
+    /// when the other side's keys for the frame haven't come before the stream's read timed out;
+    /// the frame is then still to run, and calling this again goes on waiting for them
+    pub fn frame(&mut self, chip_8: &mut Chip8) -> io::Result<bool> {
+        if !self.sent {
+            self.local = chip_8.keys();
+            let hash = state_hash(chip_8);
+            let mut message = [0; 6];
+            message[..2].copy_from_slice(&self.local.to_be_bytes());
+            message[2..].copy_from_slice(&hash.to_be_bytes());
+            self.stream.write_all(&message)?;
+            self.stream.flush()?;
+            self.queued.push_back(self.local);
+            self.hashes.push_back(hash);
+            self.sent = true;
+        }
+
+        let theirs = if self.frame >= self.delay {
+            let Some(message) = self.receive()? else {
+                return Ok(false);
+            };
+            let theirs = u32::from_be_bytes([message[2], message[3], message[4], message[5]]);
+            if self.hashes.pop_front() != Some(theirs) && !self.desynced {
+                self.desynced = true;
+                self.desync = Some(self.frame - self.delay);
+            }
+            u16::from_be_bytes([message[0], message[1]])
+        } else {
+            0
+        };
+        let ours = self.queued.pop_front().unwrap_or_default();
+        chip_8.set_keys(ours | theirs);
+        self.frame += 1;
+        self.sent = false;
+        Ok(true)
+    }
+
+    /// The other side's message for this frame, or None if the read timed out first
+    fn receive(&mut self) -> io::Result<Option<[u8; 6]>> {
+        let mut message = [0; 6];
+        while self.received.len() < message.len() {
+            let want = message.len() - self.received.len();
+            match self.stream.read(&mut message[..want]) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.received.extend_from_slice(&message[..n]),
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        message.copy_from_slice(&self.received);
+        self.received.clear();
+        Ok(Some(message))
+    }
+
+    /// The stream, e.g. for setting its read timeout once connected
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// The frame the hashes first showed the machines differ from, once
+    pub fn take_desync(&mut self) -> Option<u64> {
+        self.desync.take()
+    }
+
+    /// Call after emulating each frame frame() let run: puts our player's keys back in `chip_8`,
+    /// for key presses and releases to go on from
+    pub fn frame_done(&self, chip_8: &mut Chip8) {
+        chip_8.set_keys(self.local);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+    use std::cell::RefCell;
+    use std::net::{TcpListener, TcpStream};
+    use std::rc::Rc;
+
+    /// Runs `frames` frames on both sides, player one holding 1 from frame 1 on and player two C
+    /// from frame 2 on, returning what each machine's keypad was each frame
+    fn play(frames: u64, rom_b: &[u8]) -> io::Result<(Vec<u16>, Vec<u16>, Option<u64>)> {
+        let rom = [0x12, 0x00];
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        std::thread::scope(|scope| {
+            let guest = scope.spawn(move || -> io::Result<(Vec<u16>, Option<u64>, Netplay<TcpStream>)> {
+                let mut chip_8 = Chip8::from_rom(rom_b);
+                let hello = Hello::new(&chip_8, 700, 0, 0);
+                let (mut netplay, agreed) = Netplay::connect(TcpStream::connect(addr)?, hello, false)?;
+                assert_eq!((agreed.seed, agreed.delay), (42, DEFAULT_DELAY));
+                let (mut keys, mut desync) = (vec![], None);
+                for frame in 0..frames {
+                    if frame == 2 {
+                        chip_8.press(0xC);
+                    }
+                    assert!(netplay.frame(&mut chip_8)?);
+                    desync = desync.or(netplay.take_desync());
+                    keys.push(chip_8.keys());
+                    chip_8.step();
+                    netplay.frame_done(&mut chip_8);
+                }
+                // closing with the last frames' messages unread resets the connection, so the
+                // host closes the guest's end once both are done
+                Ok((keys, desync, netplay))
+            });
+            let mut chip_8 = Chip8::from_rom(&rom);
+            let hello = Hello::new(&chip_8, 700, 42, DEFAULT_DELAY);
+            let (mut netplay, _) = Netplay::connect(listener.accept()?.0, hello, true)?;
+            let mut keys = vec![];
+            for frame in 0..frames {
+                if frame == 1 {
+                    chip_8.press(0x1);
+                }
+                assert!(netplay.frame(&mut chip_8)?);
+                keys.push(chip_8.keys());
+                chip_8.step();
+                netplay.frame_done(&mut chip_8);
+            }
+            let (guest_keys, desync, _guest) = guest.join().unwrap()?;
+            Ok((keys, guest_keys, desync))
+        })
+    }
+
+    #[test]
+    fn both_sides_run_with_both_players_keys() {
+        let (host, guest, desync) = play(6, &[0x12, 0x00]).unwrap();
+        // each player's keys take effect two frames on, on both sides alike
+        assert_eq!(host, [0, 0, 0, 0x0002, 0x1002, 0x1002]);
+        assert_eq!(guest, host);
+        assert_eq!(desync, None);
+        let e = play(1, &[0x12, 0x02]).unwrap_err();
+        assert!(e.to_string().starts_with("the other side runs another ROM"), "{e}");
+    }
+
+    /// The other side's end of a stream, whose reads time out once it runs dry
+    #[derive(Clone, Default)]
+    struct Pipe(Rc<RefCell<VecDeque<u8>>>);
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.borrow_mut().read(buf)? {
+                0 => Err(io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_wait_for_a_paused_side() {
+        let mut chip_8 = Chip8::from_rom(&[0x12, 0x00]);
+        let hello = Hello::new(&chip_8, 700, 0, 0);
+        let pipe = Pipe::default();
+        pipe.0.borrow_mut().extend(hello.to_bytes());
+        let (mut netplay, _) = Netplay::connect(pipe.clone(), hello, true).unwrap();
+        chip_8.press(0x1);
+        assert!(!netplay.frame(&mut chip_8).unwrap());
+        assert_eq!(chip_8.keys(), 0x0002);
+        // half a message, then the rest
+        let hash = state_hash(&chip_8).to_be_bytes();
+        pipe.0.borrow_mut().extend([0x10, 0x00, hash[0]]);
+        assert!(!netplay.frame(&mut chip_8).unwrap());
+        pipe.0.borrow_mut().extend(&hash[1..]);
+        assert!(netplay.frame(&mut chip_8).unwrap());
+        assert_eq!((chip_8.keys(), netplay.take_desync()), (0x1002, None));
+    }
+
+    #[test]
+    fn peers_on_other_machines_are_turned_away() {
+        let mut chip_8 = Chip8::from_rom(&[0x12, 0x00]);
+        let theirs = Hello::new(&chip_8, 700, 0, 0);
+        chip_8.set_quirks(Quirks { clip: !chip_8.quirks().clip, ..chip_8.quirks() });
+        let pipe = Pipe::default();
+        pipe.0.borrow_mut().extend(theirs.to_bytes());
+        let e = Netplay::connect(pipe, Hello::new(&chip_8, 700, 0, 0), true).err().unwrap();
+        assert!(e.to_string().contains("other quirks"), "{e}");
+    }
+}
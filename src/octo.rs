@@ -0,0 +1,496 @@
+//! Compiler for Octo, the high-level assembly most CHIP-8 programs are written in nowadays, so
+//! `.8o` source can be run directly: labels, `:const` and `:alias`, the register operators,
+//! `if ... then` and `if ... begin ... else ... end`, `loop ... while ... again`, subroutine
+//! calls by naming a label, bare numbers as data, and the SUPER-CHIP and XO-CHIP statements
+//! short of `save vx - vy` and friends. Macros and `:calc` aren't supported.
+//!
+//! ```text
+//! : main
+//!   i := ball
+//!   loop
+//!     sprite v0 v1 2
+//!     v0 += 1
+//!     if v0 == 60 then v0 := 0
+//!     sprite v0 v1 2
+//!   again
+//! : ball 0b11000000 0b11000000
+//! ```
+//!
+//! When `main` isn't at the top, the program starts with a jump to it, as Octo's does
+
+use std::collections::BTreeMap;
+
+use crate::asm::AsmError;
+use crate::disasm::PROGRAM_START;
+use crate::symbols::Symbols;
+
+/// A compiled program: the ROM image, to be loaded at 0x200, and where its labels ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Program {
+    /// The labels, for the debugger and disassembler to name addresses by
+    pub fn symbols(&self) -> Symbols {
+        Symbols(self.labels.iter().map(|(name, &addr)| (addr, name.clone())).collect())
+    }
+}
+
+pub fn compile(source: &str) -> Result<Program, AsmError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(n, line)| line.split('#').next().unwrap_or_default().split_whitespace().map(move |token| (n + 1, token)))
+        .collect();
+    let mut compiler = Compiler { tokens, ..Compiler::default() };
+    let jumps_to_main = compiler.tokens.iter().any(|&(_, token)| token == "main")
+        && compiler.tokens.get(1).is_none_or(|&(_, token)| token != "main");
+    if jumps_to_main {
+        compiler.emit_with_target(0x1000, "main", 0);
+    }
+    while compiler.pos < compiler.tokens.len() {
+        compiler.statement()?;
+    }
+    if let Some(block) = compiler.blocks.last() {
+        let what = match block {
+            Block::If(..) | Block::Else(..) => "`begin` without an `end`",
+            Block::Loop { .. } => "`loop` without an `again`",
+        };
+        return Err(AsmError { line: compiler.tokens.last().map_or(0, |&(line, _)| line), message: what.to_string() });
+    }
+    for (offset, label, line) in std::mem::take(&mut compiler.fixups) {
+        let addr = *compiler.labels.get(label).ok_or_else(|| AsmError { line, message: format!("unknown label `{label}`") })?;
+        compiler.patch(offset, addr, line)?;
+    }
+    let labels = compiler.labels.into_iter().map(|(name, addr)| (name.to_string(), addr)).collect();
+    Ok(Program { rom: compiler.rom, labels })
+}
+
+/// An `if ... begin`, `else` or `loop` waiting for its end
+enum Block {
+    /// The jump past the `begin` body, to patch at `else` or `end`
+    If(usize),
+    /// The jump past the `else` body
+    Else(usize),
+    /// Where `again` jumps back to, and the jumps out `while` left to patch
+    Loop { start: u16, exits: Vec<usize> },
+}
+
+/// The test before an `if` body or `while`, as the instruction that skips the next one when
+/// the condition doesn't hold
+#[derive(Debug, Clone, Copy)]
+struct Condition(u16);
+
+impl Condition {
+    /// The instruction that skips when the condition does hold
+    fn negate(self) -> Self {
+        Condition(match self.0 >> 12 {
+            0x3 | 0x4 => self.0 ^ 0x7000,
+            0x5 | 0x9 => self.0 ^ 0xC000,
+            // EX9E and EXA1
+            _ => self.0 ^ (0x9E ^ 0xA1),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Compiler<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: BTreeMap<&'a str, u16>,
+    consts: BTreeMap<&'a str, u16>,
+    aliases: BTreeMap<&'a str, u16>,
+    /// 12-bit operands to fill in once every label is known: offset in the ROM, label, line
+    fixups: Vec<(usize, &'a str, usize)>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Compiler<'a> {
+    fn here(&self) -> u16 {
+        PROGRAM_START + self.rom.len() as u16
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos.saturating_sub(1)).map_or(0, |&(line, _)| line)
+    }
+
+    fn err(&self, message: impl Into<String>) -> AsmError {
+        AsmError { line: self.line(), message: message.into() }
+    }
+
+    fn next(&mut self) -> Result<&'a str, AsmError> {
+        let token = self.tokens.get(self.pos).map(|&(_, token)| token);
+        self.pos += 1;
+        token.ok_or_else(|| self.err("unexpected end of the program"))
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|&(_, token)| token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AsmError> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(self.err(format!("expected `{expected}`, got `{token}`"))),
+        }
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend(opcode.to_be_bytes());
+    }
+
+    /// Emits `opcode` with its low 12 bits going to `label`'s address once that's known
+    fn emit_with_target(&mut self, opcode: u16, label: &'a str, line: usize) {
+        self.fixups.push((self.rom.len(), label, line));
+        self.emit(opcode);
+    }
+
+    fn patch(&mut self, offset: usize, addr: u16, line: usize) -> Result<(), AsmError> {
+        if addr > 0xFFF {
+            return Err(AsmError { line, message: format!("{addr:#x} doesn't fit in a 12-bit address") });
+        }
+        self.rom[offset] = self.rom[offset] & 0xF0 | (addr >> 8) as u8;
+        self.rom[offset + 1] = addr as u8;
+        Ok(())
+    }
+
+    fn number(&self, token: &str) -> Option<i32> {
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i32::from_str_radix(hex, 16).ok()?
+        } else if let Some(bin) = digits.strip_prefix("0b") {
+            i32::from_str_radix(bin, 2).ok()?
+        } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            digits.parse().ok()?
+        } else {
+            return self.consts.get(token).map(|&value| value as i32);
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    fn register(&self, token: &str) -> Option<u16> {
+        if let Some(&x) = self.aliases.get(token) {
+            return Some(x);
+        }
+        let x = token.strip_prefix(['v', 'V']).filter(|x| x.len() == 1)?;
+        u16::from_str_radix(x, 16).ok()
+    }
+
+    fn next_register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.register(token).ok_or_else(|| self.err(format!("expected a register, got `{token}`")))
+    }
+
+    fn next_value(&mut self, max: i32) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.number(token) {
+            Some(value) if (-128..=max).contains(&value) => Ok(value as u16 & max as u16),
+            Some(value) => Err(self.err(format!("{value} is out of range"))),
+            None => Err(self.err(format!("expected a number, got `{token}`"))),
+        }
+    }
+
+    /// Emits `opcode` with the address the next token names, a label or a number
+    fn emit_address(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match self.number(token) {
+            Some(addr @ 0..=0xFFF) => self.emit(opcode | addr as u16),
+            Some(addr) => return Err(self.err(format!("{addr:#x} doesn't fit in a 12-bit address"))),
+            None => self.emit_with_target(opcode, token, self.line()),
+        }
+        Ok(())
+    }
+
+    /// `vx == n`, `vx != vy`, `vx key` or `vx -key`
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.next_register()? << 8;
+        let opcode = match self.next()? {
+            "key" => 0xE0A1 | x,
+            "-key" => 0xE09E | x,
+            op @ ("==" | "!=") => {
+                let rhs = self.peek().and_then(|token| self.register(token));
+                let opcode = match (op, rhs) {
+                    ("==", Some(y)) => 0x9000 | x | y << 4,
+                    (_, Some(y)) => 0x5000 | x | y << 4,
+                    ("==", None) => 0x4000 | x | self.next_value(0xFF)?,
+                    _ => 0x3000 | x | self.next_value(0xFF)?,
+                };
+                if rhs.is_some() {
+                    self.pos += 1;
+                }
+                opcode
+            }
+            op => return Err(self.err(format!("`{op}` comparisons aren't supported"))),
+        };
+        Ok(Condition(opcode))
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name, self.here()).is_some() {
+                    return Err(self.err(format!("label `{name}` is defined twice")));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next_value(0xFFFF)?;
+                self.consts.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.next_register()?;
+                self.aliases.insert(name, x);
+            }
+            ":org" => {
+                let addr = self.next_value(0xFFFF)?;
+                if addr < self.here() {
+                    return Err(self.err(format!(":org {addr:#x} is behind the code already there")));
+                }
+                self.rom.resize((addr - PROGRAM_START) as usize, 0);
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ";" | "return" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "hires" => self.emit(0x00FF),
+            "lores" => self.emit(0x00FE),
+            "exit" => self.emit(0x00FD),
+            "scroll-left" => self.emit(0x00FC),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-down" => {
+                let n = self.next_value(0xF)?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.next_value(0xF)?;
+                self.emit(0x00D0 | n);
+            }
+            "audio" => self.emit(0xF002),
+            "plane" => {
+                let n = self.next_value(0xF)?;
+                self.emit(0xF001 | n << 8);
+            }
+            "jump" => self.emit_address(0x1000)?,
+            "jump0" => self.emit_address(0xB000)?,
+            "sprite" => {
+                let (x, y) = (self.next_register()?, self.next_register()?);
+                let n = self.next_value(0xF)?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "save" | "load" | "saveflags" | "loadflags" | "bcd" => {
+                let x = self.next_register()?;
+                if self.peek() == Some("-") {
+                    return Err(self.err(format!("`{token} vx - vy` isn't supported")));
+                }
+                let low = match token {
+                    "save" => 0x55,
+                    "load" => 0x65,
+                    "saveflags" => 0x75,
+                    "loadflags" => 0x85,
+                    _ => 0x33,
+                };
+                self.emit(0xF000 | x << 8 | low);
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.next_register()?;
+                let low = match token {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(0xF000 | x << 8 | low);
+            }
+            "i" => self.index()?,
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()? {
+                    "then" => self.emit(condition.0),
+                    "begin" => {
+                        self.emit(condition.negate().0);
+                        self.blocks.push(Block::If(self.rom.len()));
+                        self.emit(0x1000);
+                    }
+                    other => return Err(self.err(format!("expected `then` or `begin`, got `{other}`"))),
+                }
+            }
+            "else" => {
+                let Some(Block::If(jump)) = self.blocks.pop() else {
+                    return Err(self.err("`else` without an `if ... begin`"));
+                };
+                self.blocks.push(Block::Else(self.rom.len()));
+                self.emit(0x1000);
+                self.patch(jump, self.here(), self.line())?;
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If(jump) | Block::Else(jump)) => self.patch(jump, self.here(), self.line())?,
+                _ => return Err(self.err("`end` without an `if ... begin`")),
+            },
+            "loop" => self.blocks.push(Block::Loop { start: self.here(), exits: vec![] }),
+            "while" => {
+                let condition = self.condition()?;
+                self.emit(condition.negate().0);
+                let exit = self.rom.len();
+                self.emit(0x1000);
+                match self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. })) {
+                    Some(Block::Loop { exits, .. }) => exits.push(exit),
+                    _ => return Err(self.err("`while` outside a `loop`")),
+                }
+            }
+            "again" => {
+                let Some(Block::Loop { start, exits }) = self.blocks.pop() else {
+                    return Err(self.err("`again` without a `loop`"));
+                };
+                self.emit(0x1000 | start);
+                for exit in exits {
+                    self.patch(exit, self.here(), self.line())?;
+                }
+            }
+            _ if self.register(token).is_some() => self.assignment(token)?,
+            _ if token.starts_with(':') => return Err(self.err(format!("`{token}` isn't supported"))),
+            _ => match self.number(token) {
+                Some(byte @ -128..=255) => self.rom.push(byte as u8),
+                Some(value) => return Err(self.err(format!("{value} doesn't fit in a byte"))),
+                // anything else names a subroutine to call
+                None => self.emit_with_target(0x2000, token, self.line()),
+            },
+        }
+        Ok(())
+    }
+
+    fn index(&mut self) -> Result<(), AsmError> {
+        match self.next()? {
+            "+=" => {
+                let x = self.next_register()?;
+                self.emit(0xF01E | x << 8);
+            }
+            ":=" => match self.peek() {
+                Some("hex" | "bighex") => {
+                    let low = if self.next()? == "hex" { 0x29 } else { 0x30 };
+                    let x = self.next_register()?;
+                    self.emit(0xF000 | x << 8 | low);
+                }
+                Some("long") => {
+                    self.pos += 1;
+                    self.emit(0xF000);
+                    let token = self.next()?;
+                    match self.number(token) {
+                        Some(addr @ 0..=0xFFFF) => self.emit(addr as u16),
+                        Some(addr) => return Err(self.err(format!("{addr:#x} doesn't fit in 16 bits"))),
+                        // labels all sit below 0x1000, so the 12-bit fixup does
+                        None => self.emit_with_target(0x0000, token, self.line()),
+                    }
+                }
+                _ => self.emit_address(0xA000)?,
+            },
+            op => return Err(self.err(format!("`i {op}` isn't an i operation"))),
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, register: &'a str) -> Result<(), AsmError> {
+        let x = self.register(register).unwrap_or_default() << 8;
+        let op = self.next()?;
+        let y = self.peek().and_then(|token| self.register(token));
+        if let Some(y) = y {
+            self.pos += 1;
+            let low = match op {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(self.err(format!("`{op}` doesn't take a register"))),
+            };
+            self.emit(0x8000 | x | y << 4 | low);
+            return Ok(());
+        }
+        match (op, self.peek()) {
+            (":=", Some("random")) => {
+                self.pos += 1;
+                let mask = self.next_value(0xFF)?;
+                self.emit(0xC000 | x | mask);
+            }
+            (":=", Some("delay")) => {
+                self.pos += 1;
+                self.emit(0xF007 | x);
+            }
+            (":=", Some("key")) => {
+                self.pos += 1;
+                self.emit(0xF00A | x);
+            }
+            (":=", _) => {
+                let n = self.next_value(0xFF)?;
+                self.emit(0x6000 | x | n);
+            }
+            ("+=", _) => {
+                let n = self.next_value(0xFF)?;
+                self.emit(0x7000 | x | n);
+            }
+            ("-=", _) => {
+                let n = self.next_value(0xFF)?;
+                self.emit(0x7000 | x | (n as u8).wrapping_neg() as u16);
+            }
+            _ => return Err(self.err(format!("`{op}` needs a register on the right"))),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn compiles_control_flow_and_runs() {
+        let source = "
+            :const limit 3
+            :alias counter v2
+            : count  # ends with v1 = 3
+              counter += 1
+              v1 := counter
+            ;
+            : main
+              loop
+                count
+                while counter != limit
+              again
+              if v1 == 3 begin
+                v3 := 0xAA
+              else
+                v3 := 0x55
+              end
+              i := ball
+              save v3
+              loop again
+            : ball 0 0 0 0
+        ";
+        let program = compile(source).unwrap();
+        // jump main, then count at 0x202
+        assert_eq!(&program.rom[..4], &[0x12, 0x08, 0x72, 0x01]);
+        assert_eq!(program.symbols().name(0x202), Some("count"));
+        let mut chip_8 = Chip8::from_rom(&program.rom);
+        chip_8.run_frame(100);
+        assert_eq!(chip_8.registers()[1..4], [3, 3, 0xAA]);
+        assert_eq!(chip_8.peek(program.labels["ball"] + 3), 0xAA);
+
+        let err = compile(": main\n  loop\n    v0 += 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: `loop` without an `again`");
+        assert_eq!(compile("jump nowhere").unwrap_err().to_string(), "line 1: unknown label `nowhere`");
+    }
+}
@@ -0,0 +1,194 @@
+//! Controller profiles: which keypad key each gamepad button presses. A profile is a built-in
+//! layout with any buttons bound otherwise on top, e.g. `numpad, b=4`, as the ROM database's
+//! `pad` key and --pad take it. Games disagree on which keys move and which fire, so a ROM can
+//! come with the profile it wants, and the player can switch layouts while paused
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The buttons a profile binds, named as SDL names them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadButton {
+    DPadUp,
+    DPadLeft,
+    DPadDown,
+    DPadRight,
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Start,
+    LeftShoulder,
+    RightShoulder,
+}
+
+impl PadButton {
+    pub const ALL: [Self; 12] = [
+        Self::DPadUp,
+        Self::DPadLeft,
+        Self::DPadDown,
+        Self::DPadRight,
+        Self::A,
+        Self::B,
+        Self::X,
+        Self::Y,
+        Self::Back,
+        Self::Start,
+        Self::LeftShoulder,
+        Self::RightShoulder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DPadUp => "dpup",
+            Self::DPadLeft => "dpleft",
+            Self::DPadDown => "dpdown",
+            Self::DPadRight => "dpright",
+            Self::A => "a",
+            Self::B => "b",
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Back => "back",
+            Self::Start => "start",
+            Self::LeftShoulder => "leftshoulder",
+            Self::RightShoulder => "rightshoulder",
+        }
+    }
+}
+
+impl FromStr for PadButton {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::ALL.into_iter().find(|button| button.name().eq_ignore_ascii_case(s)).ok_or_else(|| format!("unknown button `{s}`"))
+    }
+}
+
+/// The built-in layouts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PadLayout {
+    /// The d-pad presses 5/7/8/9, A 6 and B 4, the WASD-style keys most directional games use
+    #[default]
+    Wasd,
+    /// The d-pad presses 2/4/8/6 and A 5 in the middle of them, as mazes and shooters use
+    Numpad,
+}
+
+impl PadLayout {
+    pub fn bindings(self) -> [(PadButton, u8); 10] {
+        let (directions, a, b) = match self {
+            PadLayout::Wasd => ([0x5, 0x7, 0x8, 0x9], 0x6, 0x4),
+            PadLayout::Numpad => ([0x2, 0x4, 0x8, 0x6], 0x5, 0x0),
+        };
+        [
+            (PadButton::DPadUp, directions[0]),
+            (PadButton::DPadLeft, directions[1]),
+            (PadButton::DPadDown, directions[2]),
+            (PadButton::DPadRight, directions[3]),
+            (PadButton::A, a),
+            (PadButton::B, b),
+            (PadButton::X, 0x1),
+            (PadButton::Y, 0xC),
+            (PadButton::Back, 0x0),
+            (PadButton::Start, 0xF),
+        ]
+    }
+
+    /// The one after it, for switching while paused
+    pub fn next(self) -> Self {
+        match self {
+            PadLayout::Wasd => PadLayout::Numpad,
+            PadLayout::Numpad => PadLayout::Wasd,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PadLayout::Wasd => "wasd",
+            PadLayout::Numpad => "numpad",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PadProfile {
+    pub layout: PadLayout,
+    /// Buttons bound otherwise than the layout has them, latest last
+    overrides: Vec<(PadButton, u8)>,
+}
+
+impl PadProfile {
+    /// Binds `button` to keypad `key`, whatever the layout
+    pub fn bind(&mut self, button: PadButton, key: u8) {
+        self.overrides.retain(|&(bound, _)| bound != button);
+        self.overrides.push((button, key));
+    }
+
+    /// The keypad key `button` presses, if any
+    pub fn key(&self, button: PadButton) -> Option<u8> {
+        let bound = |&(bound, _): &(PadButton, u8)| bound == button;
+        let overridden = self.overrides.iter().copied().find(bound);
+        overridden.or_else(|| self.layout.bindings().into_iter().find(bound)).map(|(_, key)| key)
+    }
+}
+
+/// A layout's name and the overrides, then or alone the overrides on the default layout
+impl FromStr for PadProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profile = Self::default();
+        for (n, item) in s.split(',').map(str::trim).enumerate() {
+            match item.split_once('=') {
+                Some((button, key)) => {
+                    let key = u8::from_str_radix(key.trim().trim_start_matches("0x"), 16)
+                        .ok()
+                        .filter(|&key| key < 16)
+                        .ok_or_else(|| format!("`{}` is not a keypad key (0-F)", key.trim()))?;
+                    profile.bind(button.parse()?, key);
+                }
+                None if n == 0 => {
+                    profile.layout = <PadLayout as clap::ValueEnum>::from_str(item, true).map_err(|_| format!("unknown layout `{item}`"))?;
+                }
+                None => return Err(format!("expected BUTTON=KEY, got `{item}`")),
+            }
+        }
+        Ok(profile)
+    }
+}
+
+/// E.g. `numpad, b=4`
+impl fmt::Display for PadProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.layout.name())?;
+        for (button, key) in &self.overrides {
+            write!(f, ", {}={key:X}", button.name())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_a_layout_with_buttons_rebound() {
+        let profile: PadProfile = "numpad, a=6, DPUP=0x3".parse().unwrap();
+        assert_eq!(profile.layout, PadLayout::Numpad);
+        assert_eq!(profile.key(PadButton::A), Some(0x6));
+        assert_eq!(profile.key(PadButton::DPadUp), Some(0x3));
+        assert_eq!(profile.key(PadButton::DPadRight), Some(0x6));
+        assert_eq!(profile.key(PadButton::LeftShoulder), None);
+        assert_eq!(profile.to_string(), "numpad, a=6, dpup=3");
+        assert_eq!(profile.to_string().parse(), Ok(profile));
+        // overrides alone go on the default
+        let profile: PadProfile = "leftshoulder=e".parse().unwrap();
+        assert_eq!((profile.layout, profile.key(PadButton::LeftShoulder)), (PadLayout::Wasd, Some(0xE)));
+        assert!("numpad, qwerty".parse::<PadProfile>().is_err());
+        assert!("a=g".parse::<PadProfile>().is_err() && "trigger=1".parse::<PadProfile>().is_err());
+        assert_eq!(PadLayout::Numpad.next(), PadLayout::Wasd);
+    }
+}
@@ -0,0 +1,282 @@
+//! Applies IPS and BPS patch files to ROM images
+
+use crate::memory::{MEGA_MEMORY_SIZE, MEMORY_SIZE};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The file starts with neither the IPS nor the BPS magic
+    UnknownFormat,
+    /// The patch ended in the middle of a record
+    Truncated,
+    /// A BPS copy or read referenced bytes outside the source or target, or a size or offset
+    /// too large for any ROM
+    OutOfBounds,
+    /// The ROM being patched is not the one the BPS patch was made for
+    SourceMismatch,
+    /// A BPS checksum did not match after patching
+    ChecksumMismatch,
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            PatchError::UnknownFormat => "not an IPS or BPS patch",
+            PatchError::Truncated => "patch file is truncated",
+            PatchError::OutOfBounds => "patch references data outside the ROM",
+            PatchError::SourceMismatch => "patch was made for a different ROM",
+            PatchError::ChecksumMismatch => "patched ROM failed its checksum",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Applies `patch` to `rom`, detecting the format from its header
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+/// Applies an International Patching System patch
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut out = rom.to_vec();
+    let mut pos = 5;
+    let mut take = |n: usize| -> Result<&[u8], PatchError> {
+        let bytes = patch.get(pos..pos + n).ok_or(PatchError::Truncated)?;
+        pos += n;
+        Ok(bytes)
+    };
+    loop {
+        let offset = take(3)?;
+        if offset == b"EOF" {
+            // some patchers append a 3-byte size to truncate the output to
+            if let Ok(size) = take(3) {
+                out.truncate(be(size));
+            }
+            return Ok(out);
+        }
+        let offset = be(offset);
+        let size = be(take(2)?);
+        let (size, data) = if size == 0 {
+            // RLE record: 2-byte run length followed by the byte to repeat
+            let run = be(take(2)?);
+            (run, None)
+        } else {
+            (size, Some(take(size)?))
+        };
+        if out.len() < offset + size {
+            out.resize(offset + size, 0);
+        }
+        match data {
+            Some(data) => out[offset..offset + size].copy_from_slice(data),
+            None => {
+                let byte = take(1)?[0];
+                out[offset..offset + size].fill(byte);
+            }
+        }
+    }
+}
+
+/// Applies a beat (BPS) patch, verifying the source, target, and patch checksums
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < 4 + 12 {
+        return Err(PatchError::Truncated);
+    }
+    let footer = &patch[patch.len() - 12..];
+    let checksum = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != checksum(8) {
+        return Err(PatchError::ChecksumMismatch);
+    }
+    if crc32(rom) != checksum(0) {
+        return Err(PatchError::SourceMismatch);
+    }
+
+    let actions = &patch[..patch.len() - 12];
+    let mut pos = 4;
+    let source_size = varint(actions, &mut pos)?;
+    let target_size = varint(actions, &mut pos)?;
+    let metadata_size = varint(actions, &mut pos)?;
+    if source_size != rom.len() {
+        return Err(PatchError::SourceMismatch);
+    }
+    // no ROM outgrows the largest memory
+    if target_size > MEGA_MEMORY_SIZE {
+        return Err(PatchError::OutOfBounds);
+    }
+    pos = pos.checked_add(metadata_size).ok_or(PatchError::Truncated)?;
+
+    let mut out = Vec::with_capacity(target_size.min(MEMORY_SIZE));
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+    let relative = |offset: &mut usize, data: usize| -> Result<(), PatchError> {
+        let delta = data >> 1;
+        let moved = if data & 1 == 1 { offset.checked_sub(delta) } else { offset.checked_add(delta) };
+        *offset = moved.ok_or(PatchError::OutOfBounds)?;
+        Ok(())
+    };
+    let range = |start: usize, length: usize| start.checked_add(length).map(|end| start..end).ok_or(PatchError::OutOfBounds);
+    while pos < actions.len() {
+        let data = varint(actions, &mut pos)?;
+        let length = (data >> 2) + 1;
+        // every action writes `length` bytes, which have to fit the target
+        if out.len() + length > target_size {
+            return Err(PatchError::OutOfBounds);
+        }
+        match data & 3 {
+            // SourceRead: copy from the same position in the source
+            0 => out.extend_from_slice(rom.get(range(out.len(), length)?).ok_or(PatchError::OutOfBounds)?),
+            // TargetRead: literal bytes from the patch
+            1 => {
+                let literal = range(pos, length).map_err(|_| PatchError::Truncated)?;
+                out.extend_from_slice(actions.get(literal).ok_or(PatchError::Truncated)?);
+                pos += length;
+            }
+            // SourceCopy: copy from a relative position in the source
+            2 => {
+                relative(&mut source_offset, varint(actions, &mut pos)?)?;
+                out.extend_from_slice(rom.get(range(source_offset, length)?).ok_or(PatchError::OutOfBounds)?);
+                source_offset += length;
+            }
+            // TargetCopy: copy already written output, byte by byte since the ranges may overlap
+            _ => {
+                relative(&mut target_offset, varint(actions, &mut pos)?)?;
+                for _ in 0..length {
+                    let byte = *out.get(target_offset).ok_or(PatchError::OutOfBounds)?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size || crc32(&out) != checksum(4) {
+        return Err(PatchError::ChecksumMismatch);
+    }
+    Ok(out)
+}
+
+/// Reads one of BPS's variable-length numbers, advancing `pos` past it. Numbers too large for
+/// a usize are out of bounds of anything
+fn varint(data: &[u8], pos: &mut usize) -> Result<usize, PatchError> {
+    let mut value = 0usize;
+    let mut shift = 1usize;
+    loop {
+        let byte = *data.get(*pos).ok_or(PatchError::Truncated)?;
+        *pos += 1;
+        let digit = ((byte & 0x7F) as usize).checked_mul(shift).ok_or(PatchError::OutOfBounds)?;
+        value = value.checked_add(digit).ok_or(PatchError::OutOfBounds)?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_mul(0x80).ok_or(PatchError::OutOfBounds)?;
+        value = value.checked_add(shift).ok_or(PatchError::OutOfBounds)?;
+    }
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize)
+}
+
+/// Standard CRC-32 (IEEE 802.3), as used by BPS
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(mut value: usize, out: &mut Vec<u8>) {
+        loop {
+            let digit = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(digit | 0x80);
+                return;
+            }
+            out.push(digit);
+            value -= 1;
+        }
+    }
+
+    /// A BPS patch from `rom` to `target` with `body` as its header and actions
+    fn bps(rom: &[u8], target: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.extend_from_slice(body);
+        patch.extend_from_slice(&crc32(rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn ips_records_runs_and_truncation_apply() {
+        // 2 bytes at 1, a run of 3 7s at 4 growing the ROM, then truncating it to 6
+        let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBB\x00\x00\x04\x00\x00\x00\x03\x07EOF\x00\x00\x06";
+        assert_eq!(apply(&[1, 2, 3, 4], patch), Ok(vec![1, 0xAA, 0xBB, 4, 7, 7]));
+        assert_eq!(apply(&[1, 2, 3, 4], &patch[..12]), Err(PatchError::Truncated));
+        assert_eq!(apply(&[1, 2], b"NOPE"), Err(PatchError::UnknownFormat));
+    }
+
+    #[test]
+    fn bps_actions_apply() {
+        let rom = [1, 2, 3, 4];
+        let target = [1, 2, 9, 3, 4, 9, 3];
+        let mut body = vec![];
+        for size in [rom.len(), target.len(), 0] {
+            encode(size, &mut body);
+        }
+        // SourceRead 2, TargetRead [9], SourceCopy 2 from 2, TargetCopy 2 from 2
+        encode(1 << 2, &mut body);
+        encode(1, &mut body);
+        body.push(9);
+        encode((1 << 2) | 2, &mut body);
+        encode(2 << 1, &mut body);
+        encode((1 << 2) | 3, &mut body);
+        encode(2 << 1, &mut body);
+        let patch = bps(&rom, &target, &body);
+        assert_eq!(apply(&rom, &patch), Ok(target.to_vec()));
+        assert_eq!(apply(&[1, 2, 3, 5], &patch), Err(PatchError::SourceMismatch));
+        assert_eq!(apply(&rom, &patch[..10]), Err(PatchError::Truncated));
+        let mut corrupt = patch.clone();
+        corrupt[5] ^= 1;
+        assert_eq!(apply(&rom, &corrupt), Err(PatchError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn oversized_bps_numbers_fail_instead_of_crashing() {
+        let rom = [1, 2, 3, 4];
+        let sized = |target: usize, metadata: usize, actions: &[u8]| {
+            let mut body = vec![];
+            for size in [rom.len(), target, metadata] {
+                encode(size, &mut body);
+            }
+            body.extend_from_slice(actions);
+            bps(&rom, &[], &body)
+        };
+        assert_eq!(apply(&rom, &sized(1 << 50, 0, &[])), Err(PatchError::OutOfBounds));
+        assert_eq!(apply(&rom, &sized(4, usize::MAX - 2, &[])), Err(PatchError::Truncated));
+        // a SourceCopy back past the start, and a TargetRead longer than the target
+        let mut actions = vec![];
+        encode((1 << 2) | 2, &mut actions);
+        encode((5 << 1) | 1, &mut actions);
+        assert_eq!(apply(&rom, &sized(4, 0, &actions)), Err(PatchError::OutOfBounds));
+        let mut actions = vec![];
+        encode((usize::MAX >> 2 << 2) | 1, &mut actions);
+        assert_eq!(apply(&rom, &sized(4, 0, &actions)), Err(PatchError::OutOfBounds));
+        // a number that never ends before overflowing
+        assert_eq!(apply(&rom, &sized(4, 0, &[0; 12])), Err(PatchError::OutOfBounds));
+    }
+}
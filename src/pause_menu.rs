@@ -0,0 +1,62 @@
+//! The menu Escape brings up over the game, paused underneath it: resume, reset, another ROM
+//! from the ROM directory, the next quirks preset or palette, or quit. Up and Down pick, Enter
+//! chooses and Escape goes back to the game
+
+use sdl2::keyboard::Keycode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Resume,
+    Reset,
+    LoadRom,
+    Quirks,
+    Palette,
+    Quit,
+}
+
+const CHOICES: [Choice; 6] = [Choice::Resume, Choice::Reset, Choice::LoadRom, Choice::Quirks, Choice::Palette, Choice::Quit];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseMenu {
+    selected: usize,
+    /// Whether the machine was paused before the menu came up, so closing it leaves it that way
+    pub was_paused: bool,
+}
+
+impl PauseMenu {
+    pub fn new(was_paused: bool) -> Self {
+        Self { selected: 0, was_paused }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// The menu's lines in the overlay font, the quirks and palette ones naming what's in use
+    pub fn lines(&self, preset: impl std::fmt::Display, palette: &str) -> Vec<String> {
+        CHOICES
+            .iter()
+            .map(|choice| match choice {
+                Choice::Resume => "RESUME".to_string(),
+                Choice::Reset => "RESET".to_string(),
+                Choice::LoadRom => "LOAD ROM".to_string(),
+                Choice::Quirks => format!("QUIRKS: {preset}").to_uppercase(),
+                Choice::Palette => format!("PALETTE: {palette}").to_uppercase(),
+                Choice::Quit => "QUIT".to_string(),
+            })
+            .collect()
+    }
+
+    /// Moves the selection for the arrows, wrapping around, and returns what Enter chooses.
+    /// Escape chooses to resume
+    pub fn key(&mut self, keycode: Keycode) -> Option<Choice> {
+        match keycode {
+            Keycode::Up => self.selected = (self.selected + CHOICES.len() - 1) % CHOICES.len(),
+            Keycode::Down => self.selected = (self.selected + 1) % CHOICES.len(),
+            Keycode::Return | Keycode::KpEnter => return Some(CHOICES[self.selected]),
+            Keycode::Escape => return Some(Choice::Resume),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,93 @@
+//! --netplay-host and --netplay-join: the connection to the other player's emulator. The host
+//! waits for the other player to connect, then both seed CXNN with the host's seed, so from the
+//! first frame on the two machines run alike. Reads time out after a frame, so a window whose
+//! other player paused goes on handling its events while it waits
+
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use chip_8_rs::logging::Level;
+use chip_8_rs::netplay::{Hello, Netplay};
+use chip_8_rs::{event, Chip8, TIMER_HZ};
+
+use crate::args::RunArgs;
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+
+/// What to do with the frame due, as Peer::frame() says
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    /// Run it with both players' keys
+    Play,
+    /// Leave it for later, the other player's keys not being here yet
+    Wait,
+    /// Run it alone, the other player being gone
+    Gone,
+}
+
+pub struct Peer {
+    netplay: Netplay<TcpStream>,
+    locale: Locale,
+    /// Frames in a row spent waiting for the other player
+    waited: u32,
+}
+
+impl Peer {
+    /// Connects to the other player if asked to, seeding `chip_8` as agreed
+    pub fn start(cli: &RunArgs, chip_8: &mut Chip8, locale: Locale) -> Option<Self> {
+        let (stream, addr, host) = match (&cli.netplay_host, &cli.netplay_join) {
+            (Some(addr), _) => {
+                let listener = TcpListener::bind(addr).unwrap_or_else(|e| fail(locale.format(Msg::ServeFailed, &[addr, &e])));
+                let local = listener.local_addr().map_or_else(|_| addr.to_string(), |local| local.to_string());
+                println!("{}", locale.format(Msg::NetplayWaiting, &[&local]));
+                let (stream, peer) = listener.accept().unwrap_or_else(|e| fail(locale.format(Msg::ServeFailed, &[addr, &e])));
+                (stream, peer.to_string(), true)
+            }
+            (None, Some(addr)) => {
+                let stream = TcpStream::connect(addr).unwrap_or_else(|e| fail(locale.format(Msg::ConnectFailed, &[addr, &e])));
+                (stream, addr.clone(), false)
+            }
+            (None, None) => return None,
+        };
+        let seed = cli.seed.unwrap_or_else(rand::random);
+        let hello = Hello::new(chip_8, cli.ips(), seed, cli.netplay_delay);
+        let (netplay, agreed) = stream
+            .set_nodelay(true)
+            .and_then(|()| Netplay::connect(stream, hello, host))
+            .and_then(|(netplay, agreed)| netplay.stream().set_read_timeout(Some(Duration::from_secs(1) / TIMER_HZ)).map(|()| (netplay, agreed)))
+            .unwrap_or_else(|e| fail(locale.format(Msg::NetplayFailed, &[&addr, &e])));
+        chip_8.seed_rng(agreed.seed as u64);
+        println!("{}", locale.format(Msg::NetplayConnected, &[&addr]));
+        Some(Self { netplay, locale, waited: 0 })
+    }
+
+    /// Call before emulating each frame, see Netplay::frame
+    pub fn frame(&mut self, chip_8: &mut Chip8) -> Turn {
+        match self.netplay.frame(chip_8) {
+            Ok(true) => {
+                if let Some(frame) = self.netplay.take_desync() {
+                    println!("{}", self.locale.format(Msg::NetplayDesync, &[&frame]));
+                }
+                self.waited = 0;
+                Turn::Play
+            }
+            Ok(false) => {
+                // a second without a word is the other player pausing rather than the network
+                self.waited += 1;
+                if self.waited == TIMER_HZ {
+                    println!("{}", self.locale.text(Msg::NetplayPaused));
+                }
+                Turn::Wait
+            }
+            Err(e) => {
+                event!(Level::Error, "{}", self.locale.format(Msg::NetplayLost, &[&e]));
+                Turn::Gone
+            }
+        }
+    }
+
+    /// Call after emulating a frame begun with frame()
+    pub fn frame_done(&self, chip_8: &mut Chip8) {
+        self.netplay.frame_done(chip_8);
+    }
+}
@@ -0,0 +1,128 @@
+//! Practice loops: a save state marked at the start of a hard part of a game, and an end it's
+//! restored from every time the game gets there, so the part can be played over and over. The
+//! end is an address the PC reaches, or a time after the start.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Chip8, TIMER_HZ};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopEnd {
+    /// The PC reaching this address
+    Address(u16),
+    /// This many 60Hz frames after the start
+    Frames(f64),
+}
+
+/// `0x2a4` for an address, or `10s` for a time
+impl FromStr for LoopEnd {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            return u16::from_str_radix(hex, 16).map(LoopEnd::Address).map_err(|_| format!("`{s}` is not an address"));
+        }
+        match s.strip_suffix('s').map(|secs| secs.trim().parse::<f64>()) {
+            Some(Ok(secs)) if secs > 0.0 => Ok(LoopEnd::Frames(secs * TIMER_HZ as f64)),
+            _ => Err(format!("expected an address like 0x2a4 or a time like 10s, got `{s}`")),
+        }
+    }
+}
+
+/// `at 0x2a4` or `after 10.0s`
+impl fmt::Display for LoopEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoopEnd::Address(addr) => write!(f, "at {addr:#05x}"),
+            LoopEnd::Frames(frames) => write!(f, "after {:.1}s", frames / TIMER_HZ as f64),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PracticeLoop {
+    start: Vec<u8>,
+    end: Option<LoopEnd>,
+    /// Frames run since the start
+    elapsed: f64,
+    /// Times it went back to the start
+    loops: u32,
+}
+
+impl PracticeLoop {
+    /// Starts a loop from `chip_8` as it is now, ending at `end`, or with none yet wherever
+    /// end_here() is called
+    pub fn mark(chip_8: &Chip8, end: Option<LoopEnd>) -> Self {
+        Self { start: chip_8.save_state(), end, elapsed: 0.0, loops: 0 }
+    }
+
+    pub fn end(&self) -> Option<LoopEnd> {
+        self.end
+    }
+
+    pub fn loops(&self) -> u32 {
+        self.loops
+    }
+
+    /// Ends the loop as long after the start as it's been running, and goes back to the start
+    pub fn end_here(&mut self, chip_8: &mut Chip8) -> LoopEnd {
+        let end = LoopEnd::Frames(self.elapsed.max(1.0));
+        self.end = Some(end);
+        self.restart(chip_8);
+        end
+    }
+
+    /// Whether the PC being at `pc` ends the loop
+    pub fn ends_at(&self, pc: u16) -> bool {
+        self.end == Some(LoopEnd::Address(pc))
+    }
+
+    /// Counts `frames` more of running, going back to the start once a time ending the loop
+    /// has passed. Returns whether it went back
+    pub fn elapse(&mut self, chip_8: &mut Chip8, frames: f64) -> bool {
+        self.elapsed += frames;
+        match self.end {
+            Some(LoopEnd::Frames(end)) if self.elapsed >= end => {
+                self.restart(chip_8);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Goes back to the start
+    pub fn restart(&mut self, chip_8: &mut Chip8) {
+        // states don't include the keypad, so the keys held right now stay in effect
+        chip_8.load_state(&self.start).expect("practice loops hold states this machine wrote");
+        self.elapsed = 0.0;
+        self.loops += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loops_go_back_to_the_start_at_their_end() {
+        // ADD V0 1 forever
+        let mut chip_8 = Chip8::from_rom(&[0x70, 0x01, 0x12, 0x00]);
+        chip_8.run_frame(10);
+        let mut practice = PracticeLoop::mark(&chip_8, None);
+        chip_8.run_frame(10);
+        assert!(!practice.elapse(&mut chip_8, 1.0));
+        chip_8.run_frame(10);
+        practice.elapse(&mut chip_8, 1.0);
+        assert_eq!(practice.end_here(&mut chip_8), LoopEnd::Frames(2.0));
+        assert_eq!((chip_8.registers()[0], practice.loops()), (5, 1));
+        chip_8.run_frame(10);
+        assert!(!practice.elapse(&mut chip_8, 1.0) && practice.elapse(&mut chip_8, 1.0));
+        assert_eq!((chip_8.registers()[0], practice.loops()), (5, 2));
+        assert_eq!("0x2A4".parse(), Ok(LoopEnd::Address(0x2A4)));
+        assert!("0x2a4".parse::<LoopEnd>().is_ok_and(|end| PracticeLoop { end: Some(end), ..practice }.ends_at(0x2A4)));
+        assert_eq!("1.5s".parse::<LoopEnd>().map(|end| end.to_string()), Ok("after 1.5s".to_string()));
+        assert!("10".parse::<LoopEnd>().is_err() && "0x10000".parse::<LoopEnd>().is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Where a run spent its instructions, for ROM authors looking for what to speed up and for
+//! picking an instruction rate: the most executed instructions, the hot loops, and the share
+//! that went to loops polling the delay timer, which a faster machine only spends polling
+//! longer. Built from the coverage counts, so the machine needs enable_coverage() for the run
+
+use std::fmt;
+
+use crate::coverage::Coverage;
+use crate::disasm::mnemonic;
+use crate::instruction::Instruction;
+use crate::memory::MEMORY_SIZE;
+use crate::Chip8;
+
+/// How many rows each table shows
+const TOP: usize = 10;
+
+/// A loop closed by a backward jump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotLoop {
+    pub start: u16,
+    /// Address of the jump back to `start`
+    pub end: u16,
+    /// Times the jump back was taken or fallen out of
+    pub iterations: u64,
+    /// Instructions executed from inside the loop, inner loops included
+    pub instructions: u64,
+    /// Whether the loop reads the delay timer, i.e. is likely waiting on it
+    pub polls_delay: bool,
+}
+
+pub struct ProfileReport<'a> {
+    chip_8: &'a Chip8,
+    coverage: &'a Coverage,
+}
+
+impl<'a> ProfileReport<'a> {
+    /// None unless coverage was enabled
+    pub fn new(chip_8: &'a Chip8) -> Option<Self> {
+        Some(Self { chip_8, coverage: chip_8.coverage()? })
+    }
+
+    fn opcode(&self, addr: u16) -> u16 {
+        u16::from_be_bytes([self.chip_8.peek(addr), self.chip_8.peek(addr.wrapping_add(1))])
+    }
+
+    fn instruction(&self, addr: u16) -> Option<Instruction> {
+        Instruction::decode_with(self.opcode(addr), &self.chip_8.quirks())
+    }
+
+    fn executed(&self) -> impl Iterator<Item = (u16, u64)> + '_ {
+        (0..MEMORY_SIZE as u16).map(|addr| (addr, self.coverage.hits(addr))).filter(|&(_, hits)| hits > 0)
+    }
+
+    /// Executed addresses with their hit counts, most executed first
+    pub fn hottest(&self) -> Vec<(u16, u64)> {
+        let mut hottest: Vec<_> = self.executed().collect();
+        hottest.sort_by_key(|&(addr, hits)| (std::cmp::Reverse(hits), addr));
+        hottest
+    }
+
+    /// Loops found from the backward jumps that were executed, most instructions first
+    pub fn loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<_> = self
+            .executed()
+            .filter_map(|(end, iterations)| match self.instruction(end) {
+                Some(Instruction::Jump(start)) if start <= end => Some((start, end, iterations)),
+                _ => None,
+            })
+            .map(|(start, end, iterations)| {
+                let body: Vec<_> = (start..=end).map(|addr| (addr, self.coverage.hits(addr))).filter(|&(_, hits)| hits > 0).collect();
+                HotLoop {
+                    start,
+                    end,
+                    iterations,
+                    instructions: body.iter().map(|&(_, hits)| hits).sum(),
+                    polls_delay: body.iter().any(|&(addr, _)| matches!(self.instruction(addr), Some(Instruction::LoadDelay { .. }))),
+                }
+            })
+            .collect();
+        loops.sort_by_key(|hot| (std::cmp::Reverse(hot.instructions), hot.start));
+        loops
+    }
+
+    /// Instructions executed in total while coverage was on
+    pub fn total(&self) -> u64 {
+        self.executed().map(|(_, hits)| hits).sum()
+    }
+
+    /// Instructions spent in loops that poll the delay timer, counting nested loops once
+    pub fn polling(&self) -> u64 {
+        let loops: Vec<_> = self.loops().into_iter().filter(|hot| hot.polls_delay).collect();
+        self.executed()
+            .filter(|&(addr, _)| loops.iter().any(|hot| (hot.start..=hot.end).contains(&addr)))
+            .map(|(_, hits)| hits)
+            .sum()
+    }
+}
+
+impl fmt::Display for ProfileReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        let share = |count: u64| count as f64 * 100.0 / total.max(1) as f64;
+        writeln!(f, "profile: {total} instructions from {} addresses", self.coverage.executed())?;
+        writeln!(f, "most executed instructions:")?;
+        for (addr, hits) in self.hottest().into_iter().take(TOP) {
+            let opcode = self.opcode(addr);
+            let text = mnemonic(opcode, &self.chip_8.quirks()).unwrap_or_else(|| format!("{opcode:04x}"));
+            writeln!(f, "  {addr:#05x}  {text:<20} {hits:>10} {:>5.1}%", share(hits))?;
+        }
+        let loops = self.loops();
+        if !loops.is_empty() {
+            writeln!(f, "hot loops:")?;
+        }
+        for hot in loops.iter().take(TOP) {
+            let range = format!("{:#05x}-{:#05x}", hot.start, hot.end);
+            let polls = if hot.polls_delay { "  polls the delay timer" } else { "" };
+            writeln!(
+                f,
+                "  {range}  {:>10} times {:>10} instructions {:>5.1}%{polls}",
+                hot.iterations,
+                hot.instructions,
+                share(hot.instructions),
+            )?;
+        }
+        writeln!(f, "instruction forms:")?;
+        for (form, count) in self.coverage.histogram(&self.chip_8.quirks()).into_iter().take(TOP) {
+            writeln!(f, "  {form}  {count:>10} {:>5.1}%", share(count))?;
+        }
+        let polling = self.polling();
+        if polling > 0 {
+            writeln!(
+                f,
+                "{:.1}% of instructions went to polling the delay timer, which a lower --ips would mostly cut",
+                share(polling)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_delay_wait_loop() {
+        // LD V1 3, LD DT V1, then LD V0 DT, SE V0 0, JP back until the timer runs out, which it
+        // never does here as the timers don't tick
+        let mut chip_8 = Chip8::from_rom(&[0x61, 0x03, 0xF1, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x12, 0x0A]);
+        chip_8.enable_coverage();
+        for _ in 0..20 {
+            chip_8.step();
+        }
+        let report = ProfileReport::new(&chip_8).unwrap();
+        assert_eq!(report.total(), 20);
+        let loops = report.loops();
+        assert_eq!(loops, [HotLoop { start: 0x204, end: 0x208, iterations: 6, instructions: 18, polls_delay: true }]);
+        assert_eq!(report.polling(), 18);
+        assert_eq!(report.hottest()[0], (0x204, 6));
+    }
+}
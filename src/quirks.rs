@@ -0,0 +1,254 @@
+//! Behaviors that differ between historical CHIP-8 interpreters
+
+use crate::memory::{self, MEMORY_SIZE, PROGRAM_START};
+
+/// Where CHIP-8X programs load, after its larger interpreter
+pub const CHIP_8X_START: u16 = 0x300;
+
+/// Where ETI-660 programs load
+pub const ETI_660_START: u16 = 0x600;
+
+/// How FX55/FX65 walk the registers and what they leave in I
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LoadStore {
+    /// COSMAC VIP: V0..=Vx, I is left pointing past the last byte (I += x + 1)
+    Vip,
+    /// CHIP-48 and SCHIP 1.0: V0..=Vx, I is off by one (I += x)
+    Chip48,
+    /// SCHIP 1.1 and most modern interpreters: V0..=Vx, I is unchanged
+    #[default]
+    Modern,
+    /// Earlier versions of this emulator: FX55 stores only V0..Vx-1, I is unchanged
+    Legacy,
+}
+
+impl LoadStore {
+    /// Registers FX55 with register `x` stores
+    pub fn store_count(&self, x: usize) -> usize {
+        match self {
+            LoadStore::Legacy => x,
+            _ => x + 1,
+        }
+    }
+
+    /// How far FX55/FX65 with register `x` advance I
+    pub fn index_increment(&self, x: usize) -> u16 {
+        match self {
+            LoadStore::Vip => x as u16 + 1,
+            LoadStore::Chip48 => x as u16,
+            LoadStore::Modern | LoadStore::Legacy => 0,
+        }
+    }
+
+    /// Short description of the choice, for diagnostics
+    pub fn describe(&self) -> &'static str {
+        match self {
+            LoadStore::Vip => "increment i by x + 1",
+            LoadStore::Chip48 => "increment i by x",
+            LoadStore::Modern => "leave i unchanged",
+            LoadStore::Legacy => "store v0..vx-1, leave i unchanged",
+        }
+    }
+}
+
+/// Named sets of quirks matching well-known interpreters
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// The original COSMAC VIP interpreter
+    Chip8,
+    /// SUPER-CHIP 1.1 on the HP48
+    #[default]
+    Schip,
+    /// Octo's XO-CHIP
+    Xochip,
+    /// The COSMAC VIP's CHIP-8X, for its color board and second keypad; programs load at 0x300
+    Chip8x,
+    /// MegaChip, SUPER-CHIP with a 256x192 color screen and ROMs of up to 16MB
+    Megachip,
+}
+
+impl Preset {
+    /// The following preset, wrapping around after the last
+    pub fn next(self) -> Self {
+        match self {
+            Preset::Chip8 => Preset::Schip,
+            Preset::Schip => Preset::Xochip,
+            Preset::Xochip => Preset::Chip8x,
+            Preset::Chip8x => Preset::Megachip,
+            Preset::Megachip => Preset::Chip8,
+        }
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Preset::Chip8 => "chip8",
+            Preset::Schip => "schip",
+            Preset::Xochip => "xochip",
+            Preset::Chip8x => "chip8x",
+            Preset::Megachip => "megachip",
+        })
+    }
+}
+
+/// Every behavior that differs between interpreters, consulted by decode()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE copy Vy into Vx before shifting, instead of shifting Vx in place
+    pub shift_vy: bool,
+    /// FX55/FX65 register range and I behavior
+    pub load_store: LoadStore,
+    /// BNNN is BXNN: jump to XNN + Vx instead of NNN + V0
+    pub jump_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// DXYN clips sprites at the screen edges instead of wrapping them around
+    pub clip: bool,
+    /// DXYN waits for the next 60Hz frame before the program continues
+    pub display_wait: bool,
+    /// FX1E sets VF when I leaves the 12-bit address range
+    pub index_overflow: bool,
+    /// CXNN follows the COSMAC VIP's deterministic sequence instead of a seeded PRNG
+    pub vip_rng: bool,
+    /// Frames run the COSMAC VIP's machine cycles, each instruction taking as many as it did
+    /// there, instead of a fixed number of instructions; DXYN then waits for the display
+    pub vip_timing: bool,
+    /// 00CN/00DN/00FB/00FC scroll the lo-res screen half as far, counting in hi-res pixels as
+    /// SUPER-CHIP 1.0 and 1.1 did on the HP48; the half pixel an odd distance leaves is dropped
+    pub half_scroll: bool,
+    /// DXY0 draws an 8x16 sprite on the lo-res screen, as SUPER-CHIP did, instead of 16x16
+    pub lores_tall_sprites: bool,
+    /// DXYN on the hi-res screen sets VF to how many sprite rows collided or were clipped off
+    /// the bottom, as SUPER-CHIP 1.1 did, instead of 1
+    pub collision_rows: bool,
+    /// FX0A finishes as soon as a key goes down, as most later interpreters did, instead of
+    /// waiting for it to be released again as the COSMAC VIP did
+    pub key_wait_press: bool,
+    /// CHIP-8X: 02A0, 5XY1, BXYN and EXF2/EXF5 are its color and second keypad instructions,
+    /// and programs load at 0x300
+    pub chip_8x: bool,
+    /// ETI-660: programs load at 0x600, and the screen is 64x48
+    pub eti_660: bool,
+    /// MegaChip: 0010/0011 and 01NN-09NN switch its screen on and off and drive it, and ROMs
+    /// get as much memory as they need
+    pub mega_chip: bool,
+    /// XO-CHIP's 64KB of memory, with F000 NNNN loading I with all 16 bits of the word after it
+    /// and skips stepping over both words of it, instead of 4KB that addresses wrap around in
+    pub wide_memory: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::preset(Preset::default())
+    }
+}
+
+impl Quirks {
+    /// Where programs are loaded and start
+    pub fn program_start(&self) -> u16 {
+        match () {
+            _ if self.chip_8x => CHIP_8X_START,
+            _ if self.eti_660 => ETI_660_START,
+            _ => PROGRAM_START,
+        }
+    }
+
+    /// How much memory a ROM of `rom_len` bytes runs in
+    pub fn memory_size(&self, rom_len: usize) -> usize {
+        match () {
+            _ if self.mega_chip => memory::mega_memory_size(rom_len),
+            _ if self.wide_memory => memory::WIDE_MEMORY_SIZE,
+            _ => MEMORY_SIZE,
+        }
+    }
+
+    /// Sets the boolean quirk named like its field, returning false for names that aren't one
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let quirk = match name {
+            "shift_vy" => &mut self.shift_vy,
+            "jump_vx" => &mut self.jump_vx,
+            "vf_reset" => &mut self.vf_reset,
+            "clip" => &mut self.clip,
+            "display_wait" => &mut self.display_wait,
+            "index_overflow" => &mut self.index_overflow,
+            "vip_rng" => &mut self.vip_rng,
+            "vip_timing" => &mut self.vip_timing,
+            "half_scroll" => &mut self.half_scroll,
+            "lores_tall_sprites" => &mut self.lores_tall_sprites,
+            "collision_rows" => &mut self.collision_rows,
+            "key_wait_press" => &mut self.key_wait_press,
+            "chip_8x" => &mut self.chip_8x,
+            "eti_660" => &mut self.eti_660,
+            "mega_chip" => &mut self.mega_chip,
+            "wide_memory" => &mut self.wide_memory,
+            _ => return false,
+        };
+        *quirk = on;
+        true
+    }
+
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Chip8 => Self {
+                shift_vy: true,
+                load_store: LoadStore::Vip,
+                jump_vx: false,
+                vf_reset: true,
+                clip: true,
+                display_wait: true,
+                index_overflow: false,
+                vip_rng: false,
+                vip_timing: false,
+                half_scroll: false,
+                lores_tall_sprites: false,
+                collision_rows: false,
+                key_wait_press: false,
+                chip_8x: false,
+                eti_660: false,
+                mega_chip: false,
+                wide_memory: false,
+            },
+            Preset::Schip => Self {
+                shift_vy: false,
+                load_store: LoadStore::Modern,
+                jump_vx: true,
+                vf_reset: false,
+                clip: true,
+                display_wait: false,
+                index_overflow: false,
+                vip_rng: false,
+                vip_timing: false,
+                half_scroll: false,
+                lores_tall_sprites: true,
+                collision_rows: true,
+                key_wait_press: false,
+                chip_8x: false,
+                eti_660: false,
+                mega_chip: false,
+                wide_memory: false,
+            },
+            Preset::Xochip => Self {
+                shift_vy: true,
+                load_store: LoadStore::Vip,
+                jump_vx: false,
+                vf_reset: false,
+                clip: false,
+                display_wait: false,
+                index_overflow: false,
+                vip_rng: false,
+                vip_timing: false,
+                half_scroll: false,
+                lores_tall_sprites: false,
+                collision_rows: false,
+                key_wait_press: false,
+                chip_8x: false,
+                eti_660: false,
+                mega_chip: false,
+                wide_memory: true,
+            },
+            Preset::Chip8x => Self { chip_8x: true, ..Self::preset(Preset::Chip8) },
+            Preset::Megachip => Self { mega_chip: true, ..Self::preset(Preset::Schip) },
+        }
+    }
+}
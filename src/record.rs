@@ -0,0 +1,61 @@
+//! Recording gameplay: to an animated GIF, or to any video ffmpeg can make by piping it the raw
+//! frames
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use chip_8_rs::display::Display;
+use chip_8_rs::export::{recording_frame, GifWriter, RECORDING_SIZE};
+
+pub enum Recorder {
+    Gif(GifWriter<BufWriter<File>>),
+    Ffmpeg { ffmpeg: Child, scale: usize },
+}
+
+impl Recorder {
+    /// Starts recording to `path` at `scale` times the recording size: a GIF if it ends in .gif,
+    /// otherwise whatever ffmpeg makes of the extension
+    pub fn start(path: &Path, display: &Display, scale: usize) -> io::Result<Self> {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif")) {
+            let file = BufWriter::new(File::create(path)?);
+            return Ok(Self::Gif(GifWriter::new(file, &display.palette(), scale)?));
+        }
+        let size = format!("{}x{}", RECORDING_SIZE.0 * scale, RECORDING_SIZE.1 * scale);
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(["-video_size", &size, "-framerate", "60", "-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        Ok(Self::Ffmpeg { ffmpeg, scale })
+    }
+
+    /// Adds the frame on screen; called once per displayed frame
+    pub fn frame(&mut self, display: &Display) -> io::Result<()> {
+        match self {
+            Self::Gif(gif) => gif.frame(display),
+            Self::Ffmpeg { ffmpeg, scale } => {
+                let stdin = ffmpeg.stdin.as_mut().expect("ffmpeg's stdin is piped");
+                stdin.write_all(&recording_frame(display, *scale))
+            }
+        }
+    }
+
+    /// Ends the recording, waiting for ffmpeg to finish encoding
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Gif(gif) => gif.finish().map(drop),
+            Self::Ffmpeg { mut ffmpeg, .. } => {
+                drop(ffmpeg.stdin.take());
+                let status = ffmpeg.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("ffmpeg exited with {status}")))
+                }
+            }
+        }
+    }
+}
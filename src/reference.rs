@@ -0,0 +1,459 @@
+//! Checking the core against a second opinion, one instruction at a time, to stop at the first
+//! one after which they disagree. The second opinion is a reference interpreter written here as
+//! plainly as the instruction set allows, sharing no code with the core, or a trace another
+//! emulator recorded of its registers.
+//!
+//! The reference knows CHIP-8's instructions and the quirks that change what they do, not the
+//! extensions; running into one of those ends the check as unsupported rather than diverged.
+//! CXNN's random byte comes from the core, as there's no agreeing on a sequence, though its AND
+//! with NN is still the reference's own. No keys are ever held.
+//!
+//! Traces have a line per instruction with the registers before it executes, PC, I and V0 to
+//! VF in hex, separated by spaces, commas or both. Anything up to a `:` or `=` in a field is a
+//! label and skipped, so `PC:0200 I:0000 V0:00 ...` reads as well as `0200 0000 00 ...`, and
+//! lines starting with `#` are comments.
+
+use std::fmt;
+
+use crate::display::Resolution;
+use crate::quirks::Quirks;
+use crate::{rom, Chip8};
+
+/// The registers, timers and stack of one of the two
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub delay: u8,
+    pub sound: u8,
+    pub stack: Vec<u16>,
+}
+
+impl State {
+    fn of(chip_8: &Chip8) -> Self {
+        let (delay, sound) = chip_8.timers();
+        Self { pc: chip_8.pc(), i: chip_8.index(), v: chip_8.registers(), delay, sound, stack: chip_8.stack().to_vec() }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "PC {:#05x}  I {:#05x}  DT {}  ST {}", self.pc, self.i, self.delay, self.sound)?;
+        for (n, v) in self.v.iter().enumerate() {
+            write!(f, "{}V{n:X} {v:02X}", if n > 0 { " " } else { "" })?;
+        }
+        writeln!(f)?;
+        write!(f, "stack:")?;
+        for addr in &self.stack {
+            write!(f, " {addr:#05x}")?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The first instruction the two disagreed after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Instructions executed, the one they disagreed after included
+    pub instructions: u64,
+    /// Counting from 0
+    pub frame: u64,
+    /// The instruction, where the core executed it
+    pub addr: u16,
+    pub opcode: u16,
+    /// What differs, e.g. `V3`, `[0x300]` or `pixel (4, 10)`
+    pub differences: Vec<String>,
+    pub core: State,
+    pub reference: State,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "diverged after instruction {} ({:04X} at {:#05x}), in frame {}: {} differ",
+            self.instructions,
+            self.opcode,
+            self.addr,
+            self.frame,
+            self.differences.join(", "),
+        )?;
+        write!(f, "core:\n{}reference:\n{}", self.core, self.reference)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Agreed on every instruction run, this many of them
+    Agreed(u64),
+    Diverged(Box<Mismatch>),
+    /// The reference doesn't know the instruction at `addr`
+    Unsupported { addr: u16, opcode: u16 },
+}
+
+/// The plain interpreter
+#[derive(Debug, Clone)]
+pub struct Reference {
+    state: State,
+    memory: Vec<u8>,
+    screen: [[bool; 64]; 32],
+    quirks: Quirks,
+    stack_depth: usize,
+}
+
+impl Reference {
+    /// A reference in the state `chip_8` is in, memory included, which needs it in low
+    /// resolution and without the CHIP-8X or MegaChip quirks
+    pub fn new(chip_8: &Chip8) -> Option<Self> {
+        let quirks = chip_8.quirks();
+        if quirks.chip_8x || quirks.mega_chip || chip_8.display.resolution() != Resolution::Low {
+            return None;
+        }
+        let mut screen = [[false; 64]; 32];
+        for (x, y, lit) in chip_8.display.pixels() {
+            screen[y][x] = lit;
+        }
+        let memory = chip_8.bus.memory.bytes().to_vec();
+        Some(Self { state: State::of(chip_8), memory, screen, quirks, stack_depth: chip_8.stack_depth() })
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.memory[addr as usize % self.memory.len()]
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) {
+        let len = self.memory.len();
+        self.memory[addr as usize % len] = byte;
+    }
+
+    /// Executes the instruction at PC, `random` being what CXNN drew. Err with the opcode if
+    /// it's an extension's
+    pub fn step(&mut self, random: u8) -> Result<(), u16> {
+        let opcode = u16::from_be_bytes([self.read(self.state.pc), self.read(self.state.pc.wrapping_add(1))]);
+        if rom::platform(opcode).is_some() {
+            return Err(opcode);
+        }
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = opcode & 0xF;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let s = &mut self.state;
+        s.pc = s.pc.wrapping_add(2);
+        let (vx, vy) = (s.v[x], s.v[y]);
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.screen = [[false; 64]; 32],
+            0x0 if opcode == 0x00EE => {
+                if let Some(addr) = s.stack.pop() {
+                    s.pc = addr;
+                }
+            }
+            // machine code routines, which there's no running
+            0x0 => {}
+            0x1 => s.pc = nnn,
+            // a call with the stack full does nothing, as in the core
+            0x2 if s.stack.len() < self.stack_depth => {
+                s.stack.push(s.pc);
+                s.pc = nnn;
+            }
+            0x3 if vx == nn => s.pc = s.pc.wrapping_add(2),
+            0x4 if vx != nn => s.pc = s.pc.wrapping_add(2),
+            0x5 if n == 0 && vx == vy => s.pc = s.pc.wrapping_add(2),
+            0x6 => s.v[x] = nn,
+            0x7 => s.v[x] = vx.wrapping_add(nn),
+            0x8 => {
+                let flag = match n {
+                    0x0 => {
+                        s.v[x] = vy;
+                        None
+                    }
+                    0x1..=0x3 => {
+                        s.v[x] = match n {
+                            0x1 => vx | vy,
+                            0x2 => vx & vy,
+                            _ => vx ^ vy,
+                        };
+                        self.quirks.vf_reset.then_some(0)
+                    }
+                    0x4 => {
+                        s.v[x] = vx.wrapping_add(vy);
+                        Some(u8::from(vx as u16 + vy as u16 > 0xFF))
+                    }
+                    0x5 => {
+                        s.v[x] = vx.wrapping_sub(vy);
+                        Some(u8::from(vx >= vy))
+                    }
+                    0x7 => {
+                        s.v[x] = vy.wrapping_sub(vx);
+                        Some(u8::from(vy >= vx))
+                    }
+                    0x6 | 0xE => {
+                        let source = if self.quirks.shift_vy { vy } else { vx };
+                        if n == 0x6 {
+                            s.v[x] = source >> 1;
+                            Some(source & 1)
+                        } else {
+                            s.v[x] = source << 1;
+                            Some(source >> 7)
+                        }
+                    }
+                    _ => None,
+                };
+                // written last, so it's the flag that VF ends up holding
+                if let Some(flag) = flag {
+                    s.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 && vx != vy => s.pc = s.pc.wrapping_add(2),
+            0xA => s.i = nnn,
+            0xB if self.quirks.jump_vx => s.pc = nnn + vx as u16,
+            0xB => s.pc = nnn + s.v[0] as u16,
+            0xC => s.v[x] = random & nn,
+            0xD => {
+                let (left, top) = (vx as usize % 64, vy as usize % 32);
+                s.v[0xF] = 0;
+                let i = s.i;
+                for row in 0..n as usize {
+                    let bits = self.read(i.wrapping_add(row as u16));
+                    if self.quirks.clip && top + row >= 32 {
+                        break;
+                    }
+                    for col in 0..8 {
+                        if bits & 0x80 >> col == 0 || (self.quirks.clip && left + col >= 64) {
+                            continue;
+                        }
+                        let pixel = &mut self.screen[(top + row) % 32][(left + col) % 64];
+                        if *pixel {
+                            self.state.v[0xF] = 1;
+                        }
+                        *pixel = !*pixel;
+                    }
+                }
+            }
+            // with no keys held, EX9E never skips and EXA1 always does
+            0xE if nn == 0xA1 => s.pc = s.pc.wrapping_add(2),
+            0xF => match nn {
+                0x07 => s.v[x] = s.delay,
+                // waits for a key forever
+                0x0A => s.pc = s.pc.wrapping_sub(2),
+                0x15 => s.delay = vx,
+                0x18 => s.sound = vx,
+                0x1E => {
+                    let sum = s.i.wrapping_add(vx as u16);
+                    if self.quirks.index_overflow && (sum > 0xFFF || sum < s.i) {
+                        s.v[0xF] = 1;
+                    }
+                    s.i = sum;
+                }
+                0x29 => s.i = crate::FONT_ADDR + 5 * (vx & 0xF) as u16,
+                0x33 => {
+                    let i = s.i;
+                    for (n, digit) in [vx / 100, vx / 10 % 10, vx % 10].into_iter().enumerate() {
+                        self.write(i.wrapping_add(n as u16), digit);
+                    }
+                }
+                0x55 | 0x65 => {
+                    let load_store = self.quirks.load_store;
+                    let count = if nn == 0x55 { load_store.store_count(x) } else { x + 1 };
+                    let i = s.i;
+                    for n in 0..count {
+                        let addr = i.wrapping_add(n as u16);
+                        match nn {
+                            0x55 => self.write(addr, self.state.v[n]),
+                            _ => self.state.v[n] = self.read(addr),
+                        }
+                    }
+                    self.state.i = i.wrapping_add(load_store.index_increment(x));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A 60Hz frame boundary
+    pub fn tick(&mut self) {
+        self.state.delay = self.state.delay.saturating_sub(1);
+        self.state.sound = self.state.sound.saturating_sub(1);
+    }
+
+    /// What differs between the reference and `chip_8`
+    fn differences(&self, chip_8: &Chip8) -> Vec<String> {
+        let core = State::of(chip_8);
+        let mut differences = registers(&core, &self.state);
+        for (field, differs) in [("DT", core.delay != self.state.delay), ("ST", core.sound != self.state.sound), ("stack", core.stack != self.state.stack)] {
+            if differs {
+                differences.push(field.to_string());
+            }
+        }
+        let memory = chip_8.bus.memory.bytes();
+        differences.extend((0..memory.len()).filter(|&addr| memory[addr] != self.memory[addr]).map(|addr| format!("[{addr:#05x}]")));
+        for (y, row) in self.screen.iter().enumerate() {
+            let differing = (0..64).filter(|&x| chip_8.display.pixel(x, y) != row[x]);
+            differences.extend(differing.map(|x| format!("pixel ({x}, {y})")));
+        }
+        differences
+    }
+}
+
+/// Which of PC, I and V0-VF differ
+fn registers(core: &State, other: &State) -> Vec<String> {
+    let mut differences = vec![];
+    if core.pc != other.pc {
+        differences.push(String::from("PC"));
+    }
+    if core.i != other.i {
+        differences.push(String::from("I"));
+    }
+    differences.extend((0..16).filter(|&n| core.v[n] != other.v[n]).map(|n| format!("V{n:X}")));
+    differences
+}
+
+/// Runs `frames` frames of `instructions` instructions on `chip_8` and the reference side by
+/// side, or None if the machine is in a state the reference can't start from. The display wait
+/// and VIP timing quirks are turned off, as they decide when instructions run rather than what
+/// they do
+pub fn verify(chip_8: &mut Chip8, frames: u64, instructions: usize) -> Option<Outcome> {
+    let mut quirks = chip_8.quirks();
+    (quirks.display_wait, quirks.vip_timing) = (false, false);
+    chip_8.set_quirks(quirks);
+    let mut reference = Reference::new(chip_8)?;
+    let mut executed = 0;
+    for frame in 0..frames {
+        for _ in 0..instructions {
+            let (addr, opcode) = (chip_8.pc(), chip_8.next_instruction());
+            chip_8.step();
+            executed += 1;
+            let random = chip_8.registers()[(opcode >> 8 & 0xF) as usize];
+            if reference.step(random).is_err() {
+                return Some(Outcome::Unsupported { addr, opcode });
+            }
+            let differences = reference.differences(chip_8);
+            if !differences.is_empty() {
+                let (core, reference) = (State::of(chip_8), reference.state);
+                let mismatch = Mismatch { instructions: executed, frame, addr, opcode, differences, core, reference };
+                return Some(Outcome::Diverged(Box::new(mismatch)));
+            }
+        }
+        chip_8.tick_timers();
+        reference.tick();
+    }
+    Some(Outcome::Agreed(executed))
+}
+
+/// The registers a trace recorded before an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recorded {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+}
+
+/// Parses a trace, see the module docs for the format
+pub fn parse_trace(text: &str) -> Result<Vec<Recorded>, String> {
+    let mut trace = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split([' ', ',', '\t']).filter(|field| !field.is_empty()).collect();
+        let values: Result<Vec<u16>, _> = fields
+            .iter()
+            .map(|field| field.rsplit([':', '=']).next().unwrap_or(field))
+            .map(|value| u16::from_str_radix(value.trim_start_matches("0x"), 16))
+            .collect();
+        let values = values.map_err(|_| format!("line {}: expected hex numbers, got `{line}`", n + 1))?;
+        if values.len() != 18 || values[2..].iter().any(|&v| v > 0xFF) {
+            return Err(format!("line {}: expected PC, I and V0 to VF, got `{line}`", n + 1));
+        }
+        let mut v = [0; 16];
+        for (register, &value) in v.iter_mut().zip(&values[2..]) {
+            *register = value as u8;
+        }
+        trace.push(Recorded { pc: values[0], i: values[1], v });
+    }
+    Ok(trace)
+}
+
+/// Runs `chip_8` through `trace`, an instruction per line and a frame every `instructions`,
+/// comparing its registers with each line before executing
+pub fn verify_trace(chip_8: &mut Chip8, trace: &[Recorded], instructions: usize) -> Outcome {
+    let mut last = None;
+    for (n, recorded) in trace.iter().enumerate() {
+        let core = State::of(chip_8);
+        let theirs = State { pc: recorded.pc, i: recorded.i, v: recorded.v, ..core.clone() };
+        let differences = registers(&core, &theirs);
+        if !differences.is_empty() {
+            // what's off is the work of the instruction before, or the starting state
+            let (addr, opcode) = last.unwrap_or((core.pc, chip_8.next_instruction()));
+            let mismatch = Mismatch {
+                instructions: n as u64,
+                frame: (n.saturating_sub(1) / instructions.max(1)) as u64,
+                addr,
+                opcode,
+                differences,
+                core,
+                reference: theirs,
+            };
+            return Outcome::Diverged(Box::new(mismatch));
+        }
+        last = Some((chip_8.pc(), chip_8.next_instruction()));
+        chip_8.step();
+        if (n + 1) % instructions.max(1) == 0 {
+            chip_8.tick_timers();
+        }
+    }
+    Outcome::Agreed(trace.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    #[test]
+    fn the_reference_agrees_until_the_core_goes_wrong() {
+        // draws a digit, counts with carries and BCD, stores and calls in a loop
+        let rom = [
+            0x60, 0x07, 0xF0, 0x29, 0xD1, 0x25, 0x71, 0x0D, 0x81, 0x04, 0xA3, 0x00, 0xF1, 0x33, 0xF2, 0x55,
+            0x22, 0x16, 0x12, 0x00, 0x00, 0x00, 0x83, 0x16, 0x8F, 0x3E, 0xC4, 0x0F, 0xF3, 0x1E, 0x00, 0xEE,
+        ];
+        for preset in [Preset::Chip8, Preset::Schip, Preset::Xochip] {
+            let mut chip_8 = Chip8::builder().platform(preset).rom_bytes(&rom).seed(1).build();
+            assert_eq!(verify(&mut chip_8, 30, 10), Some(Outcome::Agreed(300)), "{preset}");
+        }
+        // a core that forgets the carry
+        let mut chip_8 = Chip8::builder().platform(Preset::Schip).rom_bytes(&rom).seed(1).build();
+        let mut reference = Reference::new(&chip_8).unwrap();
+        for _ in 0..5 {
+            chip_8.step();
+            reference.step(0).unwrap();
+        }
+        chip_8.cpu.v[0xF] ^= 1;
+        assert_eq!(reference.differences(&chip_8), ["VF"]);
+
+        // SUPER-CHIP's HIGH is beyond it
+        let mut chip_8 = Chip8::from_rom(&[0x00, 0xFF]);
+        assert_eq!(verify(&mut chip_8, 1, 10), Some(Outcome::Unsupported { addr: 0x200, opcode: 0x00FF }));
+    }
+
+    #[test]
+    fn traces_stop_at_the_first_line_the_core_disagrees_with() {
+        // LD V0 5; ADD V0 3; LD I 0x300
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x05, 0x70, 0x03, 0xA3, 0x00]);
+        let zeros = " 00".repeat(15);
+        let text = format!("# from elsewhere\nPC:0200 I:0000 V0:00{zeros}\n202 0 05{zeros}\n0x204,0x000,09{zeros}\n");
+        let trace = parse_trace(&text).unwrap();
+        assert_eq!(trace.len(), 3);
+        let Outcome::Diverged(mismatch) = verify_trace(&mut chip_8, &trace, 10) else {
+            panic!("the third line has V0 wrong");
+        };
+        assert_eq!((mismatch.addr, mismatch.opcode, mismatch.instructions), (0x202, 0x7003, 2));
+        assert_eq!(mismatch.differences, ["V0"]);
+        assert!(mismatch.to_string().contains("core:\nPC 0x204  I 0x000  DT 0  ST 0\nV0 08"), "{mismatch}");
+        assert!(parse_trace("0200 0000").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! The remote display protocol, for running the machine on one box and playing it on another.
+//! The server runs the ROM and streams its screen and buzzer to every client connected over
+//! TCP, and each client sends back the keys held on its end, all of them counting as held.
+//!
+//! The server opens each connection with `MAGIC`, after which both sides send messages of a
+//! tag byte and a payload:
+//!
+//! ```text
+//! F w h rows   to the client: the screen, w by h, each row w/8 bytes with the leftmost pixel in the top bit
+//! S on         to the client: 1 when the buzzer starts, 0 when it stops
+//! K keys       to the server: the keys held, two bytes big-endian with bit N for key N
+//! ```
+
+use std::io::{self, Read, Write};
+
+use crate::journal::Frame;
+
+/// Sent by the server first: the protocol's name and version
+pub const MAGIC: [u8; 5] = *b"CH8R\x01";
+
+/// Messages from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    Frame(Frame),
+    Sound(bool),
+}
+
+/// Messages from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientMessage {
+    Keys(u16),
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+impl ServerMessage {
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Frame(frame) => {
+                let mut message = vec![b'F', frame.width as u8, frame.height as u8];
+                for row in &frame.rows {
+                    message.extend_from_slice(&row.to_be_bytes()[16 - frame.width / 8..]);
+                }
+                w.write_all(&message)
+            }
+            Self::Sound(on) => w.write_all(&[b'S', *on as u8]),
+        }
+    }
+
+    pub fn read(r: &mut impl Read) -> io::Result<Self> {
+        match read_u8(r)? {
+            b'F' => {
+                let (width, height) = (read_u8(r)? as usize, read_u8(r)? as usize);
+                if width == 0 || width % 8 != 0 || width > 128 || height == 0 {
+                    return Err(invalid(format!("unsupported screen size {width}x{height}")));
+                }
+                let mut rows = Vec::with_capacity(height);
+                let mut row = vec![0; width / 8];
+                for _ in 0..height {
+                    r.read_exact(&mut row)?;
+                    rows.push(row.iter().fold(0, |row, &byte| row << 8 | byte as u128));
+                }
+                Ok(Self::Frame(Frame { width, height, rows }))
+            }
+            b'S' => Ok(Self::Sound(read_u8(r)? != 0)),
+            tag => Err(invalid(format!("unknown message {tag:#04x}"))),
+        }
+    }
+}
+
+impl ClientMessage {
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let Self::Keys(keys) = self;
+        let [high, low] = keys.to_be_bytes();
+        w.write_all(&[b'K', high, low])
+    }
+
+    pub fn read(r: &mut impl Read) -> io::Result<Self> {
+        match read_u8(r)? {
+            b'K' => Ok(Self::Keys(u16::from_be_bytes([read_u8(r)?, read_u8(r)?]))),
+            tag => Err(invalid(format!("unknown message {tag:#04x}"))),
+        }
+    }
+}
+
+/// Reads the server's greeting, failing if it doesn't speak this version of the protocol
+pub fn expect_magic(r: &mut impl Read) -> io::Result<()> {
+    let mut magic = [0; MAGIC.len()];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a CHIP-8 remote display server, or a different version".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_survive_the_round_trip() {
+        let frame = Frame { width: 64, height: 2, rows: vec![0x8000_0000_0000_0001, 0xff] };
+        let mut bytes = vec![];
+        ServerMessage::Frame(frame.clone()).write(&mut bytes).unwrap();
+        ServerMessage::Sound(true).write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 3 + 2 * 8 + 2);
+        let mut r = bytes.as_slice();
+        assert_eq!(ServerMessage::read(&mut r).unwrap(), ServerMessage::Frame(frame));
+        assert_eq!(ServerMessage::read(&mut r).unwrap(), ServerMessage::Sound(true));
+
+        let mut bytes = vec![];
+        ClientMessage::Keys(0x8421).write(&mut bytes).unwrap();
+        assert_eq!(bytes, b"K\x84\x21");
+        assert_eq!(ClientMessage::read(&mut bytes.as_slice()).unwrap(), ClientMessage::Keys(0x8421));
+        assert!(ClientMessage::read(&mut &b"X"[..]).is_err());
+    }
+}
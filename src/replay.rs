@@ -0,0 +1,123 @@
+//! Input recordings: the CXNN seed and the keypad state at every frame it changed, so a run
+//! played back frame by frame on the same quirks repeats exactly.
+//!
+//! ```text
+//! seed 1234
+//! 0 0000
+//! 93 0020
+//! 97 0000
+//! end 600
+//! ```
+//!
+//! Each change line is a frame number and the keys held from then on as a hex keypad mask,
+//! bit N for key N. `end` is how many frames the recording ran for
+
+use std::io::{self, Write};
+
+/// Appends keypad changes to a recording as frames run
+pub struct InputRecorder<W: Write> {
+    out: W,
+    frames: u64,
+    last: Option<u16>,
+}
+
+impl<W: Write> InputRecorder<W> {
+    pub fn new(mut out: W, seed: u32) -> io::Result<Self> {
+        writeln!(out, "seed {seed}")?;
+        Ok(Self { out, frames: 0, last: None })
+    }
+
+    /// Notes the keys held during the next frame
+    pub fn frame(&mut self, keys: u16) -> io::Result<()> {
+        if self.last != Some(keys) {
+            writeln!(self.out, "{} {keys:04x}", self.frames)?;
+            self.last = Some(keys);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        writeln!(self.out, "end {}", self.frames)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// A recording being played back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u32,
+    /// Frames recorded; None if the recording was cut off before it finished
+    pub frames: Option<u64>,
+    changes: Vec<(u64, u16)>,
+    next: usize,
+    keys: u16,
+}
+
+impl Replay {
+    /// Parses a recording written by InputRecorder
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let seed = match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(words) if words.len() == 2 && words[0] == "seed" => {
+                words[1].parse().map_err(|e| format!("line 1: bad seed: {e}"))?
+            }
+            _ => return Err("line 1: expected `seed <number>`".to_string()),
+        };
+        let mut replay = Self { seed, frames: None, changes: vec![], next: 0, keys: 0 };
+        for (n, line) in lines {
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            if replay.frames.is_some() {
+                return Err(err("nothing can follow `end`"));
+            }
+            let (first, second) = line.trim().split_once(' ').ok_or_else(|| err("expected `<frame> <keys>`"))?;
+            if first == "end" {
+                replay.frames = Some(second.parse().map_err(|_| err("bad frame count"))?);
+                continue;
+            }
+            let frame: u64 = first.parse().map_err(|_| err("bad frame number"))?;
+            let keys = u16::from_str_radix(second, 16).map_err(|_| err("bad keypad mask"))?;
+            if replay.changes.last().is_some_and(|&(last, _)| frame <= last) {
+                return Err(err("frames must increase"));
+            }
+            replay.changes.push((frame, keys));
+        }
+        Ok(replay)
+    }
+
+    /// The keys to hold during the next frame, or None once the recording has run out
+    pub fn frame(&mut self, frame: u64) -> Option<u16> {
+        if self.frames.is_some_and(|frames| frame >= frames) {
+            return None;
+        }
+        while let Some(&(_, keys)) = self.changes.get(self.next).filter(|&&(at, _)| at <= frame) {
+            self.keys = keys;
+            self.next += 1;
+        }
+        Some(self.keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_play_back_the_keys_they_recorded() {
+        let mut recorder = InputRecorder::new(vec![], 42).unwrap();
+        let keys = [0, 0, 0x20, 0x20, 0x21, 0];
+        for &held in &keys {
+            recorder.frame(held).unwrap();
+        }
+        let text = String::from_utf8(recorder.finish().unwrap()).unwrap();
+        assert_eq!(text, "seed 42\n0 0000\n2 0020\n4 0021\n5 0000\nend 6\n");
+        let mut replay = Replay::parse(&text).unwrap();
+        assert_eq!(replay.seed, 42);
+        let played: Vec<_> = (0..7).map(|frame| replay.frame(frame)).collect();
+        let mut expected: Vec<_> = keys.iter().map(|&held| Some(held)).collect();
+        expected.push(None);
+        assert_eq!(played, expected);
+        assert!(Replay::parse("seed 1\n5 0001\n3 0000\n").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! A session written up as one self-contained HTML page, for bug reports and compatibility
+//! submissions: the final screen, the configuration, what the ROM executed and what went wrong.
+//! Everything is inline, the screen and coverage map as SVG, so the file can be shared as is
+
+use std::fmt;
+
+use crate::coverage::Use;
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+use crate::Chip8;
+
+/// Pixels per CHIP-8 pixel in the lo-res screenshot; hi-res uses half
+const SCREEN_SCALE: usize = 8;
+
+/// Addresses per row of the coverage map
+const MAP_WIDTH: usize = 64;
+
+/// Pixels per address in the coverage map
+const MAP_CELL: usize = 8;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;max-width:60em}\
+    table{border-collapse:collapse}td,th{padding:2px 8px;text-align:left}\
+    tr:nth-child(even){background:#eee}.bar{background:#4a7;height:1em}\
+    code{font-family:monospace}";
+
+pub struct Report<'a> {
+    pub title: &'a str,
+    pub chip_8: &'a Chip8,
+    /// How the session was run, e.g. the ROM path and options, as (name, value) rows
+    pub config: &'a [(String, String)],
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = escape(self.title);
+        writeln!(f, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>")?;
+        writeln!(f, "<style>{STYLE}</style></head><body>\n<h1>{title}</h1>")?;
+        writeln!(f, "<h2>Final screen</h2>")?;
+        self.screen(f)?;
+        writeln!(f, "<h2>Configuration</h2>")?;
+        self.config(f)?;
+        writeln!(f, "<h2>Instructions</h2>")?;
+        self.histogram(f)?;
+        writeln!(f, "<h2>Coverage</h2>")?;
+        self.coverage(f)?;
+        writeln!(f, "<h2>Invalid opcodes</h2>")?;
+        self.invalid(f)?;
+        if let Some(diagnostics) = self.chip_8.diagnostics() {
+            writeln!(f, "<h2>Quirks relied on</h2>\n<pre>{}</pre>", escape(&diagnostics.to_string()))?;
+        }
+        writeln!(f, "</body></html>")
+    }
+}
+
+impl Report<'_> {
+    fn screen(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = self.chip_8.framebuffer();
+        let palette = display.palette();
+        let (width, height) = display.dimensions();
+        let scale = SCREEN_SCALE * 64 / width;
+        writeln!(
+            f,
+            "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {width} {height}\" shape-rendering=\"crispEdges\">",
+            width * scale,
+            height * scale,
+        )?;
+        writeln!(f, "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>", hex(palette.off()))?;
+        writeln!(f, "<g fill=\"{}\">", hex(palette.on()))?;
+        // one rect per run of lit pixels keeps the file small
+        for y in 0..height {
+            let mut x = 0;
+            while x < width {
+                let start = x;
+                while x < width && display.pixel(x, y) {
+                    x += 1;
+                }
+                if x > start {
+                    writeln!(f, "<rect x=\"{start}\" y=\"{y}\" width=\"{}\" height=\"1\"/>", x - start)?;
+                }
+                x += 1;
+            }
+        }
+        writeln!(f, "</g></svg>")
+    }
+
+    fn config(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quirks = self.chip_8.quirks();
+        let counters = self.chip_8.counters();
+        let rows = self.config.iter().cloned().chain([
+            ("ROM size".to_string(), format!("{} bytes", self.chip_8.rom().len())),
+            ("shift_vy".to_string(), quirks.shift_vy.to_string()),
+            ("load_store".to_string(), quirks.load_store.describe().to_string()),
+            ("jump_vx".to_string(), quirks.jump_vx.to_string()),
+            ("vf_reset".to_string(), quirks.vf_reset.to_string()),
+            ("clip".to_string(), quirks.clip.to_string()),
+            ("display_wait".to_string(), quirks.display_wait.to_string()),
+            ("index_overflow".to_string(), quirks.index_overflow.to_string()),
+            ("vip_rng".to_string(), quirks.vip_rng.to_string()),
+            ("vip_timing".to_string(), quirks.vip_timing.to_string()),
+            ("half_scroll".to_string(), quirks.half_scroll.to_string()),
+            ("lores_tall_sprites".to_string(), quirks.lores_tall_sprites.to_string()),
+            ("collision_rows".to_string(), quirks.collision_rows.to_string()),
+            ("key_wait_press".to_string(), quirks.key_wait_press.to_string()),
+            ("wide_memory".to_string(), quirks.wide_memory.to_string()),
+            ("Instructions executed".to_string(), counters.instructions.to_string()),
+            ("Draws".to_string(), counters.draws.to_string()),
+        ]);
+        writeln!(f, "<table>")?;
+        for (name, value) in rows {
+            writeln!(f, "<tr><th>{}</th><td>{}</td></tr>", escape(&name), escape(&value))?;
+        }
+        writeln!(f, "</table>")
+    }
+
+    fn histogram(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(coverage) = self.chip_8.coverage() else {
+            return writeln!(f, "<p>Not recorded.</p>");
+        };
+        let histogram = coverage.histogram(&self.chip_8.quirks());
+        let most = histogram.first().map_or(1, |(_, count)| *count).max(1);
+        writeln!(f, "<table>")?;
+        for (form, count) in &histogram {
+            let width = count * 100 / most;
+            writeln!(
+                f,
+                "<tr><td><code>{form}</code></td><td>{count}</td><td style=\"width:20em\">\
+                 <div class=\"bar\" style=\"width:{width}%\"></div></td></tr>",
+            )?;
+        }
+        writeln!(f, "</table>")
+    }
+
+    fn coverage(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(coverage) = self.chip_8.coverage() else {
+            return writeln!(f, "<p>Not recorded.</p>");
+        };
+        let rom = PROGRAM_START as usize..PROGRAM_START as usize + self.chip_8.rom().len();
+        writeln!(
+            f,
+            "<p>{} addresses executed. Each cell is a byte of memory, {MAP_WIDTH} to a row from 0x000: \
+             grey for the ROM, green for where instructions were executed, brighter the more often, \
+             orange for code that was also written, blue for data written and teal for data only read.</p>",
+            coverage.executed(),
+        )?;
+        let rows = MEMORY_SIZE / MAP_WIDTH;
+        writeln!(
+            f,
+            "<svg width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">",
+            MAP_WIDTH * MAP_CELL,
+            rows * MAP_CELL,
+        )?;
+        writeln!(f, "<rect width=\"100%\" height=\"100%\" fill=\"#222\"/>")?;
+        let most = (0..MEMORY_SIZE as u16).map(|addr| coverage.hits(addr)).max().unwrap_or(0).max(1) as f64;
+        for addr in 0..MEMORY_SIZE {
+            let hits = coverage.hits(addr as u16);
+            let fill = match coverage.usage(addr as u16) {
+                Use::SelfModified => "#e80".to_string(),
+                _ if hits > 0 => {
+                    // log scale, so a hot loop doesn't wash out code that ran a handful of times
+                    let heat = (hits as f64).ln_1p() / most.ln_1p();
+                    let green = 96 + (heat * 159.0) as u8;
+                    format!("#20{green:02x}40")
+                }
+                Use::Written => "#47d".to_string(),
+                Use::Read => "#399".to_string(),
+                _ if rom.contains(&addr) => "#666".to_string(),
+                _ => continue,
+            };
+            let (x, y) = (addr % MAP_WIDTH * MAP_CELL, addr / MAP_WIDTH * MAP_CELL);
+            writeln!(
+                f,
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{MAP_CELL}\" height=\"{MAP_CELL}\" fill=\"{fill}\">\
+                 <title>{addr:#05x}: {hits}</title></rect>",
+            )?;
+        }
+        writeln!(f, "</svg>")
+    }
+
+    fn invalid(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(coverage) = self.chip_8.coverage() else {
+            return writeln!(f, "<p>Not recorded.</p>");
+        };
+        let mut invalid = coverage.invalid().peekable();
+        if invalid.peek().is_none() {
+            return writeln!(f, "<p>None.</p>");
+        }
+        writeln!(f, "<table>\n<tr><th>Address</th><th>Opcode</th><th>Times</th></tr>")?;
+        for (addr, opcode, count) in invalid {
+            writeln!(f, "<tr><td><code>{addr:#05x}</code></td><td><code>{opcode:04X}</code></td><td>{count}</td></tr>")?;
+        }
+        writeln!(f, "</table>")
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_session() {
+        // draw the 0 glyph, then an invalid opcode, then spin
+        let rom = [0xA0, 0x50, 0xD0, 0x05, 0x8F, 0xF8, 0x12, 0x06];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        chip_8.enable_coverage();
+        for _ in 0..10 {
+            chip_8.step();
+        }
+        let config = [("ROM".to_string(), "<zero>.ch8".to_string())];
+        let html = Report { title: "test & such", chip_8: &chip_8, config: &config }.to_string();
+        assert!(html.contains("<h1>test &amp; such</h1>"));
+        assert!(html.contains("<td>&lt;zero&gt;.ch8</td>"));
+        // the 0 glyph's top row, 4 pixels wide
+        assert!(html.contains("<rect x=\"0\" y=\"0\" width=\"4\" height=\"1\"/>"));
+        assert!(html.contains("<code>DXYN</code></td><td>1</td>"));
+        assert!(html.contains("<td><code>0x204</code></td><td><code>8FF8</code></td><td>1</td>"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Rewind: a ring buffer of recent save states that can be played back in reverse.
+//!
+//! A state is recorded every `interval` frames, and rewinding restores one state every
+//! `interval` frames, so holding rewind runs the game backwards at the speed it was played.
+
+use std::collections::VecDeque;
+
+use crate::Chip8;
+
+#[derive(Debug)]
+pub struct Rewind {
+    states: VecDeque<Vec<u8>>,
+    capacity: usize,
+    interval: u32,
+    /// Frames until the next state is recorded, or restored while rewinding
+    countdown: u32,
+}
+
+impl Rewind {
+    /// Keeps up to `capacity` states, one every `interval` frames
+    pub fn new(capacity: usize, interval: u32) -> Self {
+        let interval = interval.max(1);
+        Self { states: VecDeque::with_capacity(capacity), capacity, interval, countdown: 0 }
+    }
+
+    /// Call once per emulated frame; records a state every `interval` frames
+    pub fn record(&mut self, chip_8: &Chip8) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.countdown == 0 {
+            if self.states.len() == self.capacity {
+                self.states.pop_front();
+            }
+            self.states.push_back(chip_8.save_state());
+            self.countdown = self.interval;
+        }
+        self.countdown -= 1;
+    }
+
+    /// Call once per frame instead of emulating while rewind is held; steps `chip_8` back one
+    /// recorded state every `interval` frames. Returns false once the buffer is exhausted
+    pub fn rewind(&mut self, chip_8: &mut Chip8) -> bool {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return !self.states.is_empty();
+        }
+        let Some(state) = self.states.pop_back() else {
+            return false;
+        };
+        // states don't include the keypad, so the keys held right now stay in effect
+        chip_8.load_state(&state).expect("rewind buffer holds states this machine wrote");
+        self.countdown = self.interval - 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewinding_restores_states_newest_first_down_to_the_oldest() {
+        // ADD V0 1 forever, so V0 counts the instructions run
+        let mut chip_8 = Chip8::from_rom(&[0x70, 0x01, 0x12, 0x00]);
+        let mut rewind = Rewind::new(3, 2);
+        for _ in 0..8 {
+            rewind.record(&chip_8);
+            chip_8.run_frame(2);
+        }
+        // states from frames 0, 2, 4 and 6, the first pushed out; each lasts two frames
+        let mut counts = vec![];
+        let mut going = vec![];
+        for _ in 0..8 {
+            going.push(rewind.rewind(&mut chip_8));
+            counts.push(chip_8.registers()[0]);
+        }
+        assert_eq!(counts, [6, 6, 4, 4, 2, 2, 2, 2]);
+        assert_eq!(going, [true, true, true, true, true, false, false, false]);
+    }
+}
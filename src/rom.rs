@@ -0,0 +1,227 @@
+//! Checks run on a ROM image before it's loaded: whether it fits, and what it looks like, so a
+//! wrong file or a ROM for another platform is pointed out instead of just misbehaving. Its
+//! checksums identify it too, to everything kept per game
+
+use std::fmt;
+
+use crate::disasm::{Disassembly, Line};
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+use crate::memory::{MEGA_MEMORY_SIZE, MEMORY_SIZE, PROGRAM_START};
+use crate::patch::crc32;
+use crate::quirks::{Preset, Quirks};
+
+/// The most a ROM can hold: everything from 0x200 to the end of memory
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START as usize;
+
+/// The most a MegaChip ROM can hold, and so any ROM that turns MegaChip mode on first thing
+pub const MAX_MEGA_ROM_SIZE: usize = MEGA_MEMORY_SIZE - PROGRAM_START as usize;
+
+/// 0011, which MegaChip programs start with
+const MEGA_START: [u8; 2] = [0x00, 0x11];
+
+/// Leading bytes shown when describing a ROM
+const HEAD_LEN: usize = 8;
+
+/// Something about a ROM that suggests it won't run as it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    /// The file is all printable text, most likely Octo source rather than an assembled ROM
+    LooksLikeSource,
+    /// Reachable code uses an instruction only `platform` has, the first being `opcode` at `addr`
+    NeedsPlatform { platform: Preset, addr: u16, opcode: u16 },
+}
+
+impl fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RomWarning::LooksLikeSource => write!(f, "this looks like Octo source code, not an assembled ROM; name it .8o to have it compiled"),
+            RomWarning::NeedsPlatform { platform, addr, opcode } => {
+                write!(f, "uses {platform} instructions, starting with {opcode:04X} at {addr:#05x}")
+            }
+        }
+    }
+}
+
+/// Which ROM an image is, by its checksums. The CRC-32 is what the ROM database, save states,
+/// RPL flags, hints, cheats and high scores are kept under; the SHA-1 is for looking the ROM
+/// up elsewhere, as ROM archives list it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RomId {
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+}
+
+impl RomId {
+    pub fn of(rom: &[u8]) -> Self {
+        Self { crc32: crc32(rom), sha1: sha1(rom) }
+    }
+
+    /// The CRC-32 as 8 hex digits, the name per-game files go by
+    pub fn key(&self) -> String {
+        format!("{:08x}", self.crc32)
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        self.sha1.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+/// E.g. `CRC32 1a2b3c4d, SHA-1 da39a3ee5e6b4b0d3255bfef95601890afd80709`
+impl fmt::Display for RomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CRC32 {}, SHA-1 {}", self.key(), self.sha1_hex())
+    }
+}
+
+/// SHA-1, as ROM archives identify their ROMs by
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (n, word) in block.chunks_exact(4).enumerate() {
+            w[n] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for n in 16..80 {
+            w[n] = (w[n - 3] ^ w[n - 8] ^ w[n - 14] ^ w[n - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (n, &word) in w.iter().enumerate() {
+            let (f, k) = match n {
+                0..=19 => (b & c | !b & d, 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => (b & c | b & d | c & d, 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// What a ROM is, as far as can be told without running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub len: usize,
+    pub id: RomId,
+    pub warnings: Vec<RomWarning>,
+    head: Vec<u8>,
+}
+
+impl RomInfo {
+    /// Checks that `rom` fits in memory and looks for signs it's something else
+    pub fn inspect(rom: &[u8]) -> Result<Self, Chip8Error> {
+        if rom.is_empty() {
+            return Err(Chip8Error::EmptyRom);
+        }
+        let limit = if rom.starts_with(&MEGA_START) { MAX_MEGA_ROM_SIZE } else { MAX_ROM_SIZE };
+        if rom.len() > limit {
+            return Err(Chip8Error::RomTooLarge(rom.len()));
+        }
+        let mut warnings = vec![];
+        if rom.iter().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()) {
+            warnings.push(RomWarning::LooksLikeSource);
+        }
+        // only the most demanding platform, as XO-CHIP includes SUPER-CHIP
+        // a MegaChip ROM's code is in its first 4KB, where CHIP-8 jumps can reach
+        let needs = Disassembly::new(&rom[..rom.len().min(MAX_ROM_SIZE)], &Quirks::default()).lines.into_iter().filter_map(|line| match line {
+            Line::Code { addr, opcode, .. } => platform(opcode).map(|platform| (platform, addr, opcode)),
+            Line::Data { .. } => None,
+        });
+        let mut needed: Option<(Preset, u16, u16)> = None;
+        for (platform, addr, opcode) in needs {
+            if needed.is_none_or(|(other, ..)| rank(platform) > rank(other)) {
+                needed = Some((platform, addr, opcode));
+            }
+        }
+        if let Some((platform, addr, opcode)) = needed {
+            warnings.push(RomWarning::NeedsPlatform { platform, addr, opcode });
+        }
+        Ok(Self { len: rom.len(), id: RomId::of(rom), warnings, head: rom.iter().take(HEAD_LEN).copied().collect() })
+    }
+
+    /// The platform the ROM's code needs beyond plain CHIP-8, if any
+    pub fn platform(&self) -> Option<Preset> {
+        self.warnings.iter().find_map(|warning| match warning {
+            RomWarning::NeedsPlatform { platform, .. } => Some(*platform),
+            RomWarning::LooksLikeSource => None,
+        })
+    }
+
+    /// Whether code written for `platform` runs on `preset`
+    pub fn runs_on(platform: Preset, preset: Preset) -> bool {
+        // MegaChip builds on SUPER-CHIP, not XO-CHIP
+        rank(platform) <= rank(preset) && !(platform == Preset::Xochip && preset == Preset::Megachip)
+    }
+}
+
+/// Size and leading bytes, e.g. `246 bytes, starting 00 E0 A2 2A`
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes, starting", self.len)?;
+        for byte in &self.head {
+            write!(f, " {byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The platform that introduced `opcode`, or None for plain CHIP-8 and unknown opcodes
+pub(crate) fn platform(opcode: u16) -> Option<Preset> {
+    Instruction::decode(opcode).and_then(Instruction::platform)
+}
+
+fn rank(preset: Preset) -> u8 {
+    match preset {
+        // CHIP-8X extends plain CHIP-8 in a direction of its own
+        Preset::Chip8 | Preset::Chip8x => 0,
+        Preset::Schip => 1,
+        Preset::Xochip => 2,
+        Preset::Megachip => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roms_are_sized_and_checked_for_extensions() {
+        assert!(matches!(RomInfo::inspect(&[]), Err(Chip8Error::EmptyRom)));
+        assert!(matches!(RomInfo::inspect(&[0; MAX_ROM_SIZE + 1]), Err(Chip8Error::RomTooLarge(3585))));
+        // CLS; HIGH; JP 0x204
+        let info = RomInfo::inspect(&[0x00, 0xE0, 0x00, 0xFF, 0x12, 0x04]).unwrap();
+        assert_eq!(info.to_string(), "6 bytes, starting 00 E0 00 FF 12 04");
+        assert_eq!(info.warnings, [RomWarning::NeedsPlatform { platform: Preset::Schip, addr: 0x202, opcode: 0x00FF }]);
+        let info = RomInfo::inspect(b": main\n  v0 := 1\n  loop again\n").unwrap();
+        assert_eq!(info.warnings, [RomWarning::LooksLikeSource]);
+        assert!(RomInfo::runs_on(Preset::Schip, Preset::Xochip));
+        assert!(!RomInfo::runs_on(Preset::Xochip, Preset::Schip));
+    }
+
+    #[test]
+    fn roms_are_identified_by_crc32_and_sha1() {
+        assert_eq!(RomId::of(b"").sha1_hex(), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(RomId::of(b"abc").sha1_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // past a block, and with the length needing a block of its own
+        let long = [b'a'; 119];
+        assert_eq!(RomId::of(&long).sha1_hex(), "ee971065aaa017e0632a8ca6c77bb3bf8b1dfc56");
+        let id = RomInfo::inspect(&[0x12, 0x00]).unwrap().id;
+        assert_eq!(id, RomId::of(&[0x12, 0x00]));
+        assert_eq!(id.to_string(), format!("CRC32 {:08x}, SHA-1 {}", crc32(&[0x12, 0x00]), id.sha1_hex()));
+    }
+}
@@ -0,0 +1,52 @@
+//! Noticing the ROM file change on disk, so the running game can be reloaded whenever an
+//! outside assembler rebuilds it. The file's modification time is polled, a few times a second
+//! at most, as there's nothing to watch for ROMs from archives, stdin or URLs and they simply
+//! never change
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the file is looked at
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct RomWatch {
+    path: PathBuf,
+    /// When the file was last modified, as of the last look
+    modified: Option<SystemTime>,
+    polled: Instant,
+    enabled: bool,
+}
+
+impl RomWatch {
+    pub fn new(path: &Path, enabled: bool) -> Self {
+        Self { path: path.to_path_buf(), modified: modified(path), polled: Instant::now(), enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Switches watching on or off, taking the file as it is now as unchanged
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.modified = modified(&self.path);
+    }
+
+    /// Whether the file changed since the last time this said so
+    pub fn changed(&mut self) -> bool {
+        if !self.enabled || self.polled.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.polled = Instant::now();
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,138 @@
+//! A database of known ROMs, keyed by the CRC32 of the image as in its RomId, with the
+//! platform, instruction rate and quirks each wants, so they run right without flags. The file
+//! is a small subset of TOML: a `[crc32]` table per ROM holding any of `title`, `author`,
+//! `platform` (a preset name), `ips`, `load_store`, `pad` (a controller profile, see the pad
+//! module), `turbo_boot` (fast forward until it first draws, as for --turbo-boot) and the
+//! boolean quirks by their Quirks field names:
+//!
+//! ```text
+//! # Octo jam games
+//! [8c1f3e4a]
+//! title = "Outlaw"
+//! author = "John Earnest"
+//! platform = "xochip"
+//! ips = 1000
+//! pad = "numpad, b=4"
+//! clip = true
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::pad::PadProfile;
+use crate::quirks::{LoadStore, Preset, Quirks};
+use crate::rom::RomId;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomEntry {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub platform: Option<Preset>,
+    /// Instructions per second
+    pub ips: Option<u32>,
+    pub load_store: Option<LoadStore>,
+    /// The controller profile the game plays best with
+    pub pad: Option<PadProfile>,
+    /// Whether to fast forward through its start, which is slow to draw anything
+    pub turbo_boot: bool,
+    /// Boolean quirks to set on top of the platform's, by name
+    pub quirks: Vec<(String, bool)>,
+}
+
+impl RomEntry {
+    /// The platform's quirks with the entry's own settings applied
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::preset(self.platform.unwrap_or_default());
+        if let Some(load_store) = self.load_store {
+            quirks.load_store = load_store;
+        }
+        for (name, on) in &self.quirks {
+            quirks.set(name, *on);
+        }
+        quirks
+    }
+}
+
+/// E.g. `Outlaw by John Earnest (xochip, 1000 ips)`
+impl fmt::Display for RomEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.title.as_deref().unwrap_or("untitled"))?;
+        if let Some(author) = &self.author {
+            write!(f, " by {author}")?;
+        }
+        let settings: Vec<String> = self.platform.map(|platform| platform.to_string()).into_iter().chain(self.ips.map(|ips| format!("{ips} ips"))).collect();
+        if !settings.is_empty() {
+            write!(f, " ({})", settings.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomDb(pub BTreeMap<u32, RomEntry>);
+
+impl RomDb {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        let mut current = None;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            if let Some(key) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let crc = u32::from_str_radix(key.trim().trim_matches('"'), 16).map_err(|_| err(&format!("`{key}` is not a CRC32")))?;
+                entries.insert(crc, RomEntry::default());
+                current = Some(crc);
+                continue;
+            }
+            let entry = current.and_then(|crc| entries.get_mut(&crc)).ok_or_else(|| err("expected a `[crc32]` table first"))?;
+            let (key, value) = line.split_once('=').ok_or_else(|| err("expected `<key> = <value>`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let string = || value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).ok_or_else(|| err("expected a quoted string"));
+            match key {
+                "title" => entry.title = Some(string()?.to_string()),
+                "author" => entry.author = Some(string()?.to_string()),
+                "platform" => entry.platform = Some(Preset::from_str(string()?, true).map_err(|_| err(&format!("unknown platform {value}")))?),
+                "load_store" => entry.load_store = Some(LoadStore::from_str(string()?, true).map_err(|_| err(&format!("unknown load_store {value}")))?),
+                "pad" => entry.pad = Some(string()?.parse().map_err(|e: String| err(&e))?),
+                "turbo_boot" => entry.turbo_boot = value.parse().map_err(|_| err(&format!("`{value}` is not true or false")))?,
+                "ips" => entry.ips = Some(value.parse().ok().filter(|&ips| ips > 0).ok_or_else(|| err(&format!("`{value}` is not an instruction rate")))?),
+                _ if Quirks::default().set(key, false) => {
+                    let on = value.parse().map_err(|_| err(&format!("`{value}` is not true or false")))?;
+                    entry.quirks.push((key.to_string(), on));
+                }
+                _ => return Err(err(&format!("unknown key `{key}`"))),
+            }
+        }
+        Ok(Self(entries))
+    }
+
+    /// The entry for the ROM `id` identifies, if it's a known ROM
+    pub fn lookup(&self, id: &RomId) -> Option<&RomEntry> {
+        self.0.get(&id.crc32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pad::PadLayout;
+
+    #[test]
+    fn entries_are_found_by_crc_and_set_their_quirks() {
+        let rom = [0x12, 0x00];
+        let text = format!("# test\n[{}]\ntitle = \"Loop\"\nplatform = \"chip8\"\nips = 1000\npad = \"numpad\"\nturbo_boot = true\nclip = false\n", RomId::of(&rom).key());
+        let db = RomDb::parse(&text).unwrap();
+        let entry = db.lookup(&RomId::of(&rom)).unwrap();
+        assert_eq!(entry.to_string(), "Loop (chip8, 1000 ips)");
+        assert_eq!(entry.quirks(), Quirks { clip: false, ..Quirks::preset(Preset::Chip8) });
+        assert_eq!(entry.pad.as_ref().map(|pad| pad.layout), Some(PadLayout::Numpad));
+        assert!(entry.turbo_boot);
+        assert!(db.lookup(&RomId::of(&[0x00, 0xE0])).is_none());
+        assert_eq!(RomDb::parse("[0]\nwrap = true").unwrap_err(), "line 2: unknown key `wrap`");
+    }
+}
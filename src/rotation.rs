@@ -0,0 +1,76 @@
+//! --rotate: the picture turned clockwise for ROMs drawn for a screen on its side, with the
+//! canvas turned portrait for a quarter turn. The directions the player presses are turned
+//! with it, so the key that moves right on screen is the one on the right of the cluster: the
+//! 5/7/8/9 keys the gamepad's d-pad presses, as most directional games use them
+
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Rotation {
+    #[default]
+    #[value(name = "0")]
+    Upright,
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+/// Up, right, down and left on the keypad, clockwise
+const DIRECTIONS: [u8; 4] = [0x5, 0x9, 0x8, 0x7];
+
+impl Rotation {
+    fn quarter_turns(self) -> usize {
+        self as usize
+    }
+
+    pub fn degrees(self) -> f64 {
+        90.0 * self.quarter_turns() as f64
+    }
+
+    /// Whether the 64x32 picture stands 32 wide and 64 tall
+    pub fn portrait(self) -> bool {
+        self.quarter_turns() % 2 == 1
+    }
+
+    /// Where on the canvas the display goes before it's turned about its center, which puts a
+    /// quarter-turned one in the 32x64 portrait canvas
+    pub fn screen(self) -> Rect {
+        match self.portrait() {
+            true => Rect::new(-16, 16, 64, 32),
+            false => Rect::new(0, 0, 64, 32),
+        }
+    }
+
+    /// Where the point `(x, y)` of the 64x32 picture ends up on the canvas
+    pub fn point(self, x: i32, y: i32) -> Point {
+        match self {
+            Rotation::Upright => Point::new(x, y),
+            Rotation::Quarter => Point::new(31 - y, x),
+            Rotation::Half => Point::new(63 - x, 31 - y),
+            Rotation::ThreeQuarters => Point::new(y, 63 - x),
+        }
+    }
+
+    /// The key the game gets for `key`: pressing the direction that points one way on the
+    /// turned screen presses the one that points that way in the game
+    pub fn turn_key(self, key: u8) -> u8 {
+        match DIRECTIONS.iter().position(|&direction| direction == key) {
+            Some(n) => DIRECTIONS[(n + 4 - self.quarter_turns()) % 4],
+            None => key,
+        }
+    }
+
+    /// Turns the window and the canvas portrait for a quarter turn
+    pub fn enable(self, canvas: &mut Canvas<Window>, scale: u32) {
+        if self.portrait() {
+            canvas.window_mut().set_size(32 * scale, 64 * scale).ok();
+            canvas.window_mut().set_minimum_size(32, 64).ok();
+            canvas.set_logical_size(32, 64).expect("failed to turn the screen");
+        }
+    }
+}
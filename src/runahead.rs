@@ -0,0 +1,59 @@
+//! Run-ahead: show the machine one frame into the future, so input takes effect a frame earlier.
+//!
+//! Every frame the real state is snapshotted and a speculative frame is emulated with the
+//! current keys. If the keys are the same next frame the speculative frame becomes the real
+//! one, otherwise the machine rolls back to the snapshot and redoes the frame with the new keys.
+
+use crate::Chip8;
+
+#[derive(Debug, Default)]
+pub struct RunAhead {
+    /// The real machine before the last speculative frame, and the keys that frame assumed
+    snapshot: Option<(Chip8, u16)>,
+}
+
+impl RunAhead {
+    /// Advances `chip_8` by one real frame of `instructions` and leaves it one speculative frame ahead
+    pub fn run_frame(&mut self, chip_8: &mut Chip8, instructions: usize) {
+        let keys = chip_8.keys();
+        match self.snapshot.take() {
+            // the speculative frame already ran with these keys, so it stands as the real one
+            Some((_, assumed)) if assumed == keys => {}
+            Some((mut real, _)) => {
+                real.set_keys(keys);
+                real.run_frame(instructions);
+                *chip_8 = real;
+            }
+            None => chip_8.run_frame(instructions),
+        }
+        self.snapshot = Some((chip_8.clone(), keys));
+        chip_8.run_frame(instructions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changing_keys_play_out_as_without_run_ahead() {
+        // counts frames' instructions in V3 and the ones run with key 0 held in V2
+        let rom = [0xE1, 0xA1, 0x72, 0x01, 0x73, 0x01, 0x12, 0x00];
+        let (mut ahead, mut plain) = (Chip8::from_rom(&rom), Chip8::from_rom(&rom));
+        // the RNG is part of the state
+        ahead.seed_rng(0);
+        plain.seed_rng(0);
+        let mut run_ahead = RunAhead::default();
+        for keys in [0, 1, 1, 0, 1, 0, 0, 1] {
+            ahead.set_keys(keys);
+            run_ahead.run_frame(&mut ahead, 7);
+            plain.set_keys(keys);
+            plain.run_frame(7);
+            // run-ahead shows the frame after, as the keys held now would have it
+            let mut next = plain.clone();
+            next.run_frame(7);
+            assert_eq!(ahead.save_state(), next.save_state());
+        }
+        assert!(plain.registers()[2] > 0 && plain.registers()[2] < plain.registers()[3]);
+    }
+}
@@ -0,0 +1,368 @@
+//! Hook scripts: small programs run by the machine as it goes, for bots, automated tests and
+//! training aids that would otherwise need a recompile. A script holds one hook per line, each
+//! naming the event it runs on, an optional condition, and actions separated by `;`:
+//!
+//! ```text
+//! # press 5 whenever the game writes the score, and tell us what it was
+//! on write 0x3f0: press 5; print score {[0x3f0]}
+//! on frame if DT == 0 and V3 > 2: release 5
+//! # count the calls to the subroutine at 0x2b0
+//! on exec 0x2b0: set [0x3f1] = [0x3f1] + 1
+//! ```
+//!
+//! Hooks run on `frame`, after each timer tick, on `exec ADDR`, after the instruction at ADDR
+//! runs, and on `write ADDR`, after an instruction writes ADDR. Values are numbers, `V0`-`VF`,
+//! `I`, `PC`, `DT`, `ST` or a byte of memory as `[ADDR]`, added and subtracted with `+` and `-`
+//! and compared with `==`, `!=`, `<`, `<=`, `>` and `>=`. The actions are `press KEY`,
+//! `release KEY`, `set VX = VALUE`, `set [ADDR] = VALUE` and `print TEXT`, with values in
+//! braces in the text filled in. What gets printed is kept for the frontend to show, see
+//! Chip8::take_script_output()
+
+use std::str::FromStr;
+
+use crate::cheats::{number, Target};
+use crate::Chip8;
+
+/// What a hook runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Frame,
+    /// The instruction at this address
+    Exec(u16),
+    /// The program writing this address
+    Write(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Number(u16),
+    Register(u8),
+    Index,
+    Pc,
+    Delay,
+    Sound,
+    Memory(u16),
+}
+
+impl Value {
+    fn get(self, chip_8: &Chip8) -> u16 {
+        match self {
+            Value::Number(n) => n,
+            Value::Register(x) => chip_8.registers()[x as usize].into(),
+            Value::Index => chip_8.index(),
+            Value::Pc => chip_8.pc(),
+            Value::Delay => chip_8.timers().0.into(),
+            Value::Sound => chip_8.timers().1.into(),
+            Value::Memory(addr) => chip_8.peek(addr).into(),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            return Ok(Value::Memory(address(addr)?));
+        }
+        Ok(match s.to_ascii_uppercase().as_str() {
+            "I" => Value::Index,
+            "PC" => Value::Pc,
+            "DT" => Value::Delay,
+            "ST" => Value::Sound,
+            _ if s.starts_with(['V', 'v']) => match s.parse()? {
+                Target::Register(x) => Value::Register(x),
+                Target::Memory(_) => unreachable!("V is no number"),
+            },
+            _ => Value::Number(address(s)?),
+        })
+    }
+}
+
+/// Values added and subtracted, wrapping at 16 bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr {
+    first: Value,
+    /// Each with whether it's subtracted
+    rest: Vec<(bool, Value)>,
+}
+
+impl Expr {
+    fn parse(tokens: &[&str]) -> Result<Self, String> {
+        let (first, mut tokens) = tokens.split_first().ok_or("expected a value")?;
+        let mut expr = Self { first: first.parse()?, rest: vec![] };
+        while let [op, value, more @ ..] = tokens {
+            let minus = match *op {
+                "+" => false,
+                "-" => true,
+                _ => return Err(format!("expected `+` or `-`, got `{op}`")),
+            };
+            expr.rest.push((minus, value.parse()?));
+            tokens = more;
+        }
+        match tokens {
+            [] => Ok(expr),
+            [op] => Err(format!("expected a value after `{op}`")),
+            _ => unreachable!("taken two at a time"),
+        }
+    }
+
+    fn get(&self, chip_8: &Chip8) -> u16 {
+        self.rest.iter().fold(self.first.get(chip_8), |total, &(minus, value)| match minus {
+            false => total.wrapping_add(value.get(chip_8)),
+            true => total.wrapping_sub(value.get(chip_8)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+const COMPARISONS: [(&str, Compare); 6] = [
+    ("==", Compare::Equal),
+    ("!=", Compare::NotEqual),
+    ("<=", Compare::LessOrEqual),
+    (">=", Compare::GreaterOrEqual),
+    ("<", Compare::Less),
+    (">", Compare::Greater),
+];
+
+/// Comparisons that all have to hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition(Vec<(Expr, Compare, Expr)>);
+
+impl Condition {
+    fn parse(tokens: &[&str]) -> Result<Self, String> {
+        let mut comparisons = vec![];
+        for comparison in tokens.split(|token| token.eq_ignore_ascii_case("and")) {
+            let found = comparison.iter().enumerate().find_map(|(at, token)| {
+                COMPARISONS.iter().find(|(op, _)| token == op).map(|&(_, compare)| (at, compare))
+            });
+            let Some((at, compare)) = found else {
+                return Err(format!("expected a comparison, got `{}`", comparison.join(" ")));
+            };
+            comparisons.push((Expr::parse(&comparison[..at])?, compare, Expr::parse(&comparison[at + 1..])?));
+        }
+        Ok(Self(comparisons))
+    }
+
+    fn holds(&self, chip_8: &Chip8) -> bool {
+        self.0.iter().all(|(left, compare, right)| {
+            let (left, right) = (left.get(chip_8), right.get(chip_8));
+            match compare {
+                Compare::Equal => left == right,
+                Compare::NotEqual => left != right,
+                Compare::Less => left < right,
+                Compare::LessOrEqual => left <= right,
+                Compare::Greater => left > right,
+                Compare::GreaterOrEqual => left >= right,
+            }
+        })
+    }
+}
+
+/// Part of a print action's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Value(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Press(u8),
+    Release(u8),
+    Set(Target, Expr),
+    Print(Vec<Piece>),
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (verb, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let key = || match u8::from_str_radix(rest.trim(), 16) {
+            Ok(key) if key < 16 => Ok(key),
+            _ => Err(format!("`{}` is not a key, expected 0-F", rest.trim())),
+        };
+        Ok(match verb.to_ascii_lowercase().as_str() {
+            "press" => Action::Press(key()?),
+            "release" => Action::Release(key()?),
+            "set" => {
+                let tokens = tokens(rest)?;
+                let [place, "=", value @ ..] = tokens.as_slice() else {
+                    return Err(format!("expected `set <VX or [ADDR]> = <value>`, got `{s}`"));
+                };
+                let target = match place.parse()? {
+                    Value::Register(x) => Target::Register(x),
+                    Value::Memory(addr) => Target::Memory(addr),
+                    _ => return Err(format!("can only set a V register or a byte of memory, not `{place}`")),
+                };
+                Action::Set(target, Expr::parse(value)?)
+            }
+            "print" => {
+                let mut pieces = vec![];
+                let mut text = rest;
+                while let Some((before, after)) = text.split_once('{') {
+                    let (value, after) = after.split_once('}').ok_or("`{` without a `}`")?;
+                    pieces.push(Piece::Text(before.to_string()));
+                    pieces.push(Piece::Value(Expr::parse(&tokens(value)?)?));
+                    text = after;
+                }
+                pieces.push(Piece::Text(text.to_string()));
+                Action::Print(pieces)
+            }
+            _ => return Err(format!("unknown action `{verb}`, expected press, release, set or print")),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hook {
+    event: Event,
+    condition: Option<Condition>,
+    actions: Vec<Action>,
+}
+
+/// `on <event> [if <condition>]: <action>; <action>...`
+impl FromStr for Hook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (head, actions) = s.split_once(':').ok_or("expected a `:` before the actions")?;
+        let head = tokens(head)?;
+        let (event, condition) = match head.as_slice() {
+            [on, event, rest @ ..] if on.eq_ignore_ascii_case("on") && event.eq_ignore_ascii_case("frame") => (Event::Frame, rest),
+            [on, event, addr, rest @ ..] if on.eq_ignore_ascii_case("on") && event.eq_ignore_ascii_case("exec") => (Event::Exec(address(addr)?), rest),
+            [on, event, addr, rest @ ..] if on.eq_ignore_ascii_case("on") && event.eq_ignore_ascii_case("write") => (Event::Write(address(addr)?), rest),
+            _ => return Err(String::from("expected `on frame`, `on exec <ADDR>` or `on write <ADDR>`")),
+        };
+        let condition = match condition {
+            [] => None,
+            [word, condition @ ..] if word.eq_ignore_ascii_case("if") => Some(Condition::parse(condition)?),
+            _ => return Err(format!("expected `if` or `:`, got `{}`", condition.join(" "))),
+        };
+        let actions = actions.split(';').filter(|action| !action.trim().is_empty()).map(str::parse).collect::<Result<_, _>>()?;
+        Ok(Self { event, condition, actions })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    hooks: Vec<Hook>,
+    /// Printed since the last take_output()
+    output: Vec<String>,
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut hooks = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            hooks.push(line.parse().map_err(|e| format!("line {}: {e}", n + 1))?);
+        }
+        Ok(Self { hooks, output: vec![] })
+    }
+
+    /// The addresses write hooks are waiting on, for the bus to look out for
+    pub(crate) fn writes(&self) -> Vec<u16> {
+        self.hooks.iter().filter_map(|hook| match hook.event {
+            Event::Write(addr) => Some(addr),
+            _ => None,
+        }).collect()
+    }
+
+    /// Runs the hooks waiting on `event` whose conditions hold
+    pub(crate) fn run(&mut self, event: Event, chip_8: &mut Chip8) {
+        for hook in self.hooks.iter().filter(|hook| hook.event == event) {
+            if hook.condition.as_ref().is_some_and(|condition| !condition.holds(chip_8)) {
+                continue;
+            }
+            for action in &hook.actions {
+                match action {
+                    Action::Press(key) => chip_8.press(*key),
+                    Action::Release(key) => chip_8.release(*key),
+                    Action::Set(Target::Register(x), value) => chip_8.cpu.v[*x as usize] = value.get(chip_8) as u8,
+                    Action::Set(Target::Memory(addr), value) => chip_8.poke(*addr, value.get(chip_8) as u8),
+                    Action::Print(pieces) => {
+                        let line = pieces.iter().map(|piece| match piece {
+                            Piece::Text(text) => text.clone(),
+                            Piece::Value(value) => value.get(chip_8).to_string(),
+                        });
+                        self.output.push(line.collect::<String>().trim().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+fn address(s: &str) -> Result<u16, String> {
+    u16::try_from(number(s)?).map_err(|_| format!("out of range: {}", s.trim()))
+}
+
+/// Splits `s` into words, `[ADDR]`s and operators, spaces between them or not
+fn tokens(s: &str) -> Result<Vec<&str>, String> {
+    let mut tokens = vec![];
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '[' => rest.find(']').ok_or("`[` without a `]`")? + 1,
+            '=' | '!' | '<' | '>' if rest[1..].starts_with('=') => 2,
+            '=' | '<' | '>' | '+' | '-' => 1,
+            _ if c.is_ascii_alphanumeric() => rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len()),
+            _ => return Err(format!("unexpected `{c}`")),
+        };
+        let (token, after) = rest.split_at(len);
+        tokens.push(token);
+        rest = after.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_run_on_frames_instructions_and_writes() {
+        // LD V0 1, then forever: ADD V0 1, LD I 0x300, LD [I] V0, which writes V0 to 0x300
+        let rom = [0x60, 0x01, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02];
+        let mut chip_8 = Chip8::from_rom(&rom);
+        let script = Script::parse(
+            "# frame count in 0x301\n\
+             on frame: set [0x301] = [0x301]+1\n\
+             on exec 0x202 if V0 >= 3: press a\n\
+             on write 0x300 if [0x300] == 4 and [0x301] < 5: print wrote {[0x300]} in frame {[0x301] + 1}; release A\n",
+        )
+        .unwrap();
+        chip_8.set_script(script);
+        chip_8.run_frame(12);
+        // three times round: the key goes down once V0 reaches 3, and up again when 4 is written
+        assert_eq!(chip_8.peek(0x301), 1);
+        assert_eq!(chip_8.keys(), 0);
+        assert_eq!(chip_8.take_script_output(), ["wrote 4 in frame 1"]);
+        chip_8.run_frame(4);
+        assert_eq!((chip_8.peek(0x301), chip_8.keys()), (2, 1 << 0xA));
+        assert!(chip_8.take_script_output().is_empty());
+
+        assert_eq!(Script::parse("\non exec 0x200: jump").unwrap_err(), "line 2: unknown action `jump`, expected press, release, set or print");
+        assert_eq!(Script::parse("on frame if V0: press 1").unwrap_err(), "line 1: expected a comparison, got `V0`");
+        assert_eq!(Script::parse("on frame: set PC = 0").unwrap_err(), "line 1: can only set a V register or a byte of memory, not `PC`");
+    }
+}
@@ -0,0 +1,189 @@
+//! The power-on self test: opcode-level assertions about the core, each executing single
+//! instructions on a machine set up by hand and checking the registers and memory they leave.
+//! Where the conformance checks run whole programs on every preset, these pin down the details
+//! programs trip over, such as which way VF goes on a borrow or how many registers FX55 stores.
+//! The test suite runs them too, and --self-test lets anyone run them on their build
+
+use crate::quirks::{Preset, Quirks};
+use crate::Chip8;
+
+pub struct SelfTest {
+    pub name: &'static str,
+    run: fn(&mut Probe),
+}
+
+impl SelfTest {
+    /// Runs the test, Err with every assertion that failed
+    pub fn run(&self) -> Result<(), Vec<String>> {
+        let mut probe = Probe { chip_8: machine(Preset::Chip8), preset: Preset::Chip8, failures: vec![] };
+        (self.run)(&mut probe);
+        match probe.failures.is_empty() {
+            true => Ok(()),
+            false => Err(probe.failures),
+        }
+    }
+}
+
+/// A machine to execute instructions on, noting the expectations they don't meet
+struct Probe {
+    chip_8: Chip8,
+    /// Whose quirks the machine has, CHIP-8's until on() says otherwise
+    preset: Preset,
+    failures: Vec<String>,
+}
+
+impl Probe {
+    /// Starts over on a machine with `preset`'s quirks
+    fn on(&mut self, preset: Preset) -> &mut Self {
+        (self.chip_8, self.preset) = (machine(preset), preset);
+        self
+    }
+
+    fn set(&mut self, x: usize, byte: u8) -> &mut Self {
+        self.chip_8.cpu.v[x] = byte;
+        self
+    }
+
+    fn set_index(&mut self, addr: u16) -> &mut Self {
+        self.chip_8.cpu.i = addr;
+        self
+    }
+
+    fn exec(&mut self, opcode: u16) -> &mut Self {
+        self.chip_8.execute_raw(opcode);
+        self
+    }
+
+    fn expect(&mut self, what: String, got: u16, expected: u16) -> &mut Self {
+        if got != expected {
+            let opcode = self.chip_8.last_instruction().map_or(0, |(_, opcode)| opcode);
+            self.failures.push(format!("after {opcode:04X} on {}: {what} is {got:#04x}, expected {expected:#04x}", self.preset));
+        }
+        self
+    }
+
+    fn expect_v(&mut self, x: usize, expected: u8) -> &mut Self {
+        let got = self.chip_8.registers()[x];
+        self.expect(format!("V{x:X}"), got.into(), expected.into())
+    }
+
+    fn expect_index(&mut self, expected: u16) -> &mut Self {
+        let got = self.chip_8.index();
+        self.expect(String::from("I"), got, expected)
+    }
+
+    fn expect_memory(&mut self, addr: u16, expected: &[u8]) -> &mut Self {
+        for (addr, &byte) in (addr..).zip(expected) {
+            let got = self.chip_8.peek(addr);
+            self.expect(format!("[{addr:#05x}]"), got.into(), byte.into());
+        }
+        self
+    }
+}
+
+fn machine(preset: Preset) -> Chip8 {
+    Chip8::builder().quirks(Quirks::preset(preset)).rom_bytes(&[0x12, 0x00]).seed(0).build()
+}
+
+/// Where tests that touch memory point I, clear of the program
+const SCRATCH: u16 = 0x300;
+
+pub const SELF_TESTS: [SelfTest; 8] = [
+    SelfTest {
+        name: "add carry",
+        run: |probe| {
+            probe.set(1, 200).set(2, 100).exec(0x8124).expect_v(1, 44).expect_v(0xF, 1);
+            probe.set(1, 1).set(2, 2).exec(0x8124).expect_v(1, 3).expect_v(0xF, 0);
+            // the flag wins over the sum when VF is the destination
+            probe.set(0xF, 0xFF).set(1, 1).exec(0x8F14).expect_v(0xF, 1);
+            // 7XNN never touches VF
+            probe.set(0xF, 7).set(1, 0xFF).exec(0x7101).expect_v(1, 0).expect_v(0xF, 7);
+        },
+    },
+    SelfTest {
+        name: "sub borrow",
+        run: |probe| {
+            // VF is 1 when there's no borrow, equal values included
+            probe.set(1, 5).set(2, 10).exec(0x8125).expect_v(1, 251).expect_v(0xF, 0);
+            probe.set(1, 10).set(2, 5).exec(0x8125).expect_v(1, 5).expect_v(0xF, 1);
+            probe.set(1, 5).set(2, 5).exec(0x8125).expect_v(1, 0).expect_v(0xF, 1);
+            probe.set(1, 5).set(2, 10).exec(0x8127).expect_v(1, 5).expect_v(0xF, 1);
+            probe.set(1, 10).set(2, 5).exec(0x8127).expect_v(1, 251).expect_v(0xF, 0);
+        },
+    },
+    SelfTest {
+        name: "bcd",
+        run: |probe| {
+            for (value, digits) in [(195, [1, 9, 5]), (0, [0, 0, 0]), (255, [2, 5, 5]), (7, [0, 0, 7])] {
+                probe.set_index(SCRATCH).set(3, value).exec(0xF333).expect_memory(SCRATCH, &digits).expect_index(SCRATCH);
+            }
+        },
+    },
+    SelfTest {
+        name: "store/load",
+        run: |probe| {
+            // FX55 stores V0 through VX, VX included and no further
+            probe.on(Preset::Schip).set_index(SCRATCH);
+            for x in 0..4 {
+                probe.set(x, 0x10 + x as u8);
+            }
+            probe.exec(0xF255).expect_memory(SCRATCH, &[0x10, 0x11, 0x12, 0x00]).expect_index(SCRATCH);
+            probe.set(0, 0).set(1, 0).set(2, 0).set(3, 0x99).exec(0xF265);
+            probe.expect_v(0, 0x10).expect_v(1, 0x11).expect_v(2, 0x12).expect_v(3, 0x99);
+            // the COSMAC VIP leaves I past the last register, X + 1 on
+            probe.on(Preset::Chip8).set_index(SCRATCH).exec(0xF255).expect_index(SCRATCH + 3);
+            probe.set_index(SCRATCH).exec(0xF065).expect_index(SCRATCH + 1);
+        },
+    },
+    SelfTest {
+        name: "shift right",
+        run: |probe| {
+            // the COSMAC VIP shifts VY into VX, SUPER-CHIP VX in place; VF is the bit shifted out
+            probe.on(Preset::Chip8).set(1, 0x10).set(2, 0x03).exec(0x8126).expect_v(1, 0x01).expect_v(2, 0x03).expect_v(0xF, 1);
+            probe.on(Preset::Schip).set(1, 0x10).set(2, 0x03).exec(0x8126).expect_v(1, 0x08).expect_v(0xF, 0);
+            probe.on(Preset::Schip).set(1, 0x11).exec(0x8116).expect_v(1, 0x08).expect_v(0xF, 1);
+            // the flag wins over the shifted value when VF is VX
+            probe.on(Preset::Schip).set(0xF, 0x02).exec(0x8FF6).expect_v(0xF, 0);
+        },
+    },
+    SelfTest {
+        name: "shift left",
+        run: |probe| {
+            probe.on(Preset::Chip8).set(1, 0x01).set(2, 0x81).exec(0x812E).expect_v(1, 0x02).expect_v(2, 0x81).expect_v(0xF, 1);
+            probe.on(Preset::Schip).set(1, 0x01).set(2, 0x81).exec(0x812E).expect_v(1, 0x02).expect_v(0xF, 0);
+            probe.on(Preset::Schip).set(1, 0xC0).exec(0x811E).expect_v(1, 0x80).expect_v(0xF, 1);
+        },
+    },
+    SelfTest {
+        name: "logic",
+        run: |probe| {
+            // the COSMAC VIP clears VF after OR, AND and XOR, later interpreters leave it be
+            probe.on(Preset::Chip8).set(1, 0x0C).set(2, 0x0A).set(0xF, 5).exec(0x8121).expect_v(1, 0x0E).expect_v(0xF, 0);
+            probe.on(Preset::Schip).set(1, 0x0C).set(2, 0x0A).set(0xF, 5).exec(0x8122).expect_v(1, 0x08).expect_v(0xF, 5);
+            probe.set(1, 0x0C).exec(0x8123).expect_v(1, 0x06);
+        },
+    },
+    SelfTest {
+        name: "add index",
+        run: |probe| {
+            probe.set_index(0x0FFF).set(1, 2).exec(0xF11E).expect_index(0x1001);
+            probe.set_index(0x0300).set(1, 0xFF).exec(0xF11E).expect_index(0x03FF);
+        },
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_self_test_passes_and_catches_wrong_answers() {
+        for test in &SELF_TESTS {
+            assert_eq!(test.run(), Ok(()), "{}", test.name);
+        }
+        let wrong = SelfTest { name: "wrong", run: |probe| {
+            probe.set(1, 1).set(2, 1).exec(0x8124).expect_v(1, 3);
+        } };
+        assert_eq!(wrong.run(), Err(vec![String::from("after 8124 on chip8: V1 is 0x02, expected 0x03")]));
+    }
+}
@@ -0,0 +1,126 @@
+//! --serve: the ROM runs without a window while clients connected over TCP see the screen and
+//! play it, speaking the protocol in the library's remote module. Each client is read on a
+//! thread of its own; writes happen on the main loop, and a client that stops taking them is
+//! dropped rather than holding the game up
+
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+
+use chip_8_rs::journal::Frame;
+use chip_8_rs::remote::{ClientMessage, ServerMessage, MAGIC};
+use chip_8_rs::symbols::Symbols;
+use chip_8_rs::{Chip8, TIMER_HZ};
+
+use crate::args::RunArgs;
+use crate::fail;
+use crate::i18n::{Locale, Msg};
+use crate::input_log::InputLog;
+
+/// How long a write to a client can block before the client is taken for gone
+const WRITE_TIMEOUT: Duration = Duration::from_millis(250);
+
+struct Client {
+    id: usize,
+    peer: SocketAddr,
+    stream: TcpStream,
+    keys: u16,
+}
+
+pub fn run(cli: &RunArgs, addr: &str, mut chip_8: Chip8, symbols: &Symbols, locale: Locale) {
+    let listener = TcpListener::bind(addr)
+        .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+        .unwrap_or_else(|e| fail(locale.format(Msg::ServeFailed, &[&addr, &e])));
+    let local = listener.local_addr().map_or_else(|_| addr.to_string(), |local| local.to_string());
+    eprintln!("{}", locale.format(Msg::Serving, &[&local, &local]));
+    let (keys_sender, key_changes) = mpsc::channel();
+    let mut clients: Vec<Client> = vec![];
+    let mut next_id = 0;
+    let mut input_log = InputLog::start(cli, &mut chip_8, locale);
+    let instructions_per_frame = (cli.ips() / TIMER_HZ).max(1) as usize;
+    let frame_time = Duration::from_secs(1) / TIMER_HZ;
+    let mut frame = Frame::capture(chip_8.framebuffer());
+    let mut beeping = false;
+    let mut next_frame = Instant::now();
+    loop {
+        while let Ok((stream, peer)) = listener.accept() {
+            match greet(&stream, &frame, beeping, next_id, keys_sender.clone()) {
+                Ok(()) => {
+                    eprintln!("{}", locale.format(Msg::ClientConnected, &[&peer]));
+                    clients.push(Client { id: next_id, peer, stream, keys: 0 });
+                    next_id += 1;
+                }
+                Err(e) => eprintln!("{}", locale.format(Msg::ClientDisconnected, &[&format_args!("{peer} ({e})")])),
+            }
+        }
+        for (id, keys) in key_changes.try_iter() {
+            match keys {
+                Some(keys) => clients.iter_mut().filter(|client| client.id == id).for_each(|client| client.keys = keys),
+                None => drop_client(&mut clients, id, locale),
+            }
+        }
+        chip_8.set_keys(clients.iter().fold(0, |keys, client| keys | client.keys));
+        input_log.frame(&mut chip_8);
+        chip_8.run_frame(instructions_per_frame);
+        let went_wrong = !chip_8.take_faults().is_empty() || chip_8.halted();
+        crate::write_trace(&mut chip_8, cli, went_wrong, symbols);
+
+        let mut update = vec![];
+        let next = Frame::capture(chip_8.framebuffer());
+        if next != frame {
+            frame = next;
+            ServerMessage::Frame(frame.clone()).write(&mut update).expect("writing to a Vec");
+        }
+        if chip_8.sound_active() != beeping {
+            beeping = chip_8.sound_active();
+            ServerMessage::Sound(beeping).write(&mut update).expect("writing to a Vec");
+        }
+        if !update.is_empty() {
+            let gone: Vec<_> = clients
+                .iter_mut()
+                .filter_map(|client| client.stream.write_all(&update).is_err().then_some(client.id))
+                .collect();
+            for id in gone {
+                drop_client(&mut clients, id, locale);
+            }
+        }
+
+        next_frame += frame_time;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    }
+}
+
+/// Sends a new client the greeting and the current screen, and starts reading its keys
+fn greet(stream: &TcpStream, frame: &Frame, beeping: bool, id: usize, keys: Sender<(usize, Option<u16>)>) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut hello = MAGIC.to_vec();
+    ServerMessage::Frame(frame.clone()).write(&mut hello)?;
+    ServerMessage::Sound(beeping).write(&mut hello)?;
+    (&*stream).write_all(&hello)?;
+    let mut reader = stream.try_clone()?;
+    std::thread::spawn(move || {
+        while let Ok(ClientMessage::Keys(held)) = ClientMessage::read(&mut reader) {
+            if keys.send((id, Some(held))).is_err() {
+                return;
+            }
+        }
+        keys.send((id, None)).ok();
+    });
+    Ok(())
+}
+
+/// Forgets a client whose connection closed, releasing the keys it held
+fn drop_client(clients: &mut Vec<Client>, id: usize, locale: Locale) {
+    if let Some(n) = clients.iter().position(|client| client.id == id) {
+        let client = clients.remove(n);
+        // ends the client's reader thread too
+        client.stream.shutdown(std::net::Shutdown::Both).ok();
+        eprintln!("{}", locale.format(Msg::ClientDisconnected, &[&client.peer]));
+    }
+}
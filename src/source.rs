@@ -0,0 +1,82 @@
+//! Where a ROM is read from: a file, an entry in a zip archive named like a file in a directory
+//! (`pack.zip/games/pong.ch8`), standard input when the path is `-`, the built-in demo when it's
+//! `builtin:demo`, or with the net feature an http or https URL, fetched through the curl
+//! command line tool. Wherever it's from, a `.8o` file is Octo source and is compiled
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use chip_8_rs::error::{self, Chip8Error};
+use chip_8_rs::{demo, menu, octo, zip};
+
+/// What names the built-in demo, run when no ROM is given
+pub const DEMO: &str = "builtin:demo";
+
+/// Whether `rom` names a URL rather than a file
+pub fn is_url(rom: &str) -> bool {
+    rom.starts_with("http://") || rom.starts_with("https://")
+}
+
+/// The archive `rom` is an entry of and the entry's name, if it names one
+pub fn zip_entry(rom: &Path) -> Option<(&Path, String)> {
+    let archive = rom.ancestors().skip(1).find(|path| zip::is_zip(path))?;
+    let parts: Vec<_> = rom.strip_prefix(archive).ok()?.iter().map(|part| part.to_string_lossy()).collect();
+    Some((archive, parts.join("/")))
+}
+
+/// The ROM in `archive` when it's a zip holding exactly one, which needs no menu to pick it
+pub fn single_rom(archive: &Path) -> Option<PathBuf> {
+    if !zip::is_zip(archive) {
+        return None;
+    }
+    let mut roms = menu::scan(archive).ok()?;
+    (roms.len() == 1).then(|| roms.remove(0))
+}
+
+/// Whether `rom` names Octo source rather than a ROM image
+pub fn is_source(rom: &Path) -> bool {
+    rom.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("8o"))
+}
+
+/// The ROM image `rom` names
+pub fn read(rom: &Path) -> Result<Vec<u8>, Chip8Error> {
+    let data = read_bytes(rom)?;
+    if !is_source(rom) {
+        return Ok(data);
+    }
+    let program = octo::compile(&String::from_utf8_lossy(&data)).map_err(|error| Chip8Error::Compile { path: rom.to_path_buf(), error })?;
+    Ok(program.rom)
+}
+
+fn read_bytes(rom: &Path) -> Result<Vec<u8>, Chip8Error> {
+    if let Some((archive, name)) = zip_entry(rom) {
+        return Ok(zip::extract(&error::read(archive)?, &name)?);
+    }
+    let name = rom.to_string_lossy();
+    let res = match &*name {
+        "-" => {
+            let mut buf = vec![];
+            io::stdin().lock().read_to_end(&mut buf).map(|_| buf)
+        }
+        DEMO => return Ok(demo::ROM.to_vec()),
+        url if is_url(url) => fetch(url),
+        _ => return error::read(rom),
+    };
+    res.map_err(|source| Chip8Error::Io { path: rom.to_path_buf(), source })
+}
+
+#[cfg(feature = "net")]
+fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    let output = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch(_url: &str) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "this build can't fetch URLs, rebuild it with --features net"))
+}
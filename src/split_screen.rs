@@ -0,0 +1,98 @@
+//! --compare: the ROM under its own quirks on the left and another preset's on the right, both
+//! fed the same keys, with the pixels they disagree on marked in red. The first frame they
+//! part ways pauses both and says which registers differ and which quirk decisions were taken,
+//! and Enter runs on from there. Only the left one is heard
+
+use chip_8_rs::compare::Comparison;
+use chip_8_rs::quirks::Preset;
+use sdl2::audio::AudioDevice;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+
+use crate::i18n::{Locale, Msg};
+use crate::keymap::Keymap;
+use crate::{fail, Screen, SdlBuzzer, Speaker};
+
+/// Logical pixels between the two screens
+const GAP: i32 = 1;
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    mut comparison: Comparison,
+    presets: (Preset, Preset),
+    instructions_per_frame: usize,
+    mut canvas: Canvas<Window>,
+    mut event_pump: EventPump,
+    mut audio: Option<AudioDevice<Speaker>>,
+    keymap: &Keymap,
+    locale: Locale,
+) {
+    let (width, height) = canvas.window().size();
+    canvas.window_mut().set_size(width * 2, height).ok();
+    canvas.set_logical_size(64 * 2 + GAP as u32, 32).unwrap_or_else(|e| fail(locale.format(Msg::RenderFailed, &[&e])));
+    canvas.window_mut().set_title(&format!("CHIP-8 - {} | {}", presets.0, presets.1)).ok();
+    let creator = canvas.texture_creator();
+    let mut screens = [(); 2].map(|()| Screen::new(&creator).unwrap_or_else(|e| fail(locale.format(Msg::RenderFailed, &[&e]))));
+    let mut keys = 0u16;
+    let mut paused = false;
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                Event::KeyDown { keycode: Some(Keycode::Return | Keycode::KpEnter), .. } => paused = false,
+                Event::KeyDown { keycode, scancode, .. } => if let Some(key) = keymap.lookup(keycode, scancode) {
+                    keys |= 1 << key;
+                }
+                Event::KeyUp { keycode, scancode, .. } => if let Some(key) = keymap.lookup(keycode, scancode) {
+                    keys &= !(1 << key);
+                }
+                _ => {}
+            }
+        }
+        if !paused {
+            if let Some(divergence) = comparison.run_frame(keys, instructions_per_frame) {
+                println!("{}", locale.format(Msg::MachinesDiverged, &[&presets.0, &presets.1, divergence]));
+                paused = true;
+            }
+        }
+        if let Some(audio) = audio.as_mut() {
+            comparison.machines[0].sound_to(&mut SdlBuzzer(audio));
+        }
+        if let Err(e) = draw(&comparison, &mut screens, &mut canvas) {
+            fail(locale.format(Msg::RenderFailed, &[&e]));
+        }
+        // paced by vsync, as in the main loop
+        canvas.present();
+    }
+}
+
+fn draw(comparison: &Comparison, screens: &mut [Screen; 2], canvas: &mut Canvas<Window>) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(60, 60, 60));
+    canvas.clear();
+    for (side, (chip_8, screen)) in comparison.machines.iter().zip(screens.iter_mut()).enumerate() {
+        let display = chip_8.framebuffer();
+        let (width, height) = display.dimensions();
+        screen.fit(width, height)?;
+        let mut data = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&display.color(x, y));
+            }
+        }
+        screen.texture.update(None, &data, width * 3).map_err(|e| e.to_string())?;
+        canvas.copy(&screen.texture, None, Rect::new(side as i32 * (64 + GAP), 0, 64, 32))?;
+    }
+    // marked on both, in the left one's coordinates scaled to 64x32
+    let (width, height) = comparison.machines[0].framebuffer().dimensions();
+    canvas.set_draw_color(Color::RED);
+    for (x, y) in comparison.differing_pixels() {
+        let (x, y) = ((x * 64 / width) as i32, (y * 32 / height) as i32);
+        canvas.draw_points([Point::new(x, y), Point::new(x + 64 + GAP, y)].as_slice())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Brings up whichever SDL subsystems this machine supports, falling back step by step
+//! (accelerated video, software video, no window; audio, no audio) instead of giving up
+
+use chip_8_rs::audio::{Fader, Oscillator, PatternPlayer, Tone};
+use chip_8_rs::event;
+use chip_8_rs::logging::Level;
+use sdl2::audio::{AudioDevice, AudioSpecDesired};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::{AudioSubsystem, EventPump, GameControllerSubsystem, Sdl};
+
+use crate::i18n::{Locale, Msg};
+use crate::{Speaker, AUDIO_FADE, BEEP_RAMP};
+
+/// How long the device's buffer is when nothing says otherwise. SDL's own choice can run to
+/// a tenth of a second, which a beep from FX18 then waits behind
+const BUFFER_TIME: f64 = 0.005;
+
+/// Where the beep goes and how it's buffered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioOutput {
+    /// An output device's name as SDL lists it, or None for the system default
+    pub device: Option<String>,
+    pub sample_rate: i32,
+    /// Samples SDL asks for at a time, or None for about BUFFER_TIME's worth; fewer means less
+    /// latency but more risk of crackling
+    pub buffer: Option<u16>,
+}
+
+impl Default for AudioOutput {
+    fn default() -> Self {
+        Self { device: None, sample_rate: 44100, buffer: None }
+    }
+}
+
+/// The subsystems that came up; anything missing is simply skipped by the main loop
+pub struct Subsystems {
+    pub canvas: Option<Canvas<Window>>,
+    pub event_pump: Option<EventPump>,
+    pub audio: Option<AudioDevice<Speaker>>,
+    pub controllers: Option<GameControllerSubsystem>,
+    /// Keeps SDL initialized for as long as the rest is in use
+    pub sdl: Option<Sdl>,
+}
+
+/// `scale` is the window's size in pixels per CHIP-8 pixel, `tone` what the beep sounds like and
+/// `output` where it's played
+pub fn init(locale: Locale, scale: u32, tone: Tone, output: &AudioOutput) -> Subsystems {
+    let sdl = match sdl2::init() {
+        Ok(sdl) => sdl,
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::SdlFailed, &[&e]));
+            return Subsystems { canvas: None, event_pump: None, audio: None, controllers: None, sdl: None };
+        }
+    };
+    let canvas = init_video(&sdl, locale, scale);
+    let audio = init_audio(&sdl, locale, tone, output);
+    let controllers = sdl.game_controller()
+        .map_err(|e| event!(Level::Warn, "{}", locale.format(Msg::ControllersNone, &[&e])))
+        .ok();
+    let event_pump = sdl.event_pump().map_err(|e| event!(Level::Warn, "{}", locale.format(Msg::InputNone, &[&e]))).ok();
+    Subsystems { canvas, event_pump, audio, controllers, sdl: Some(sdl) }
+}
+
+fn init_video(sdl: &Sdl, locale: Locale, scale: u32) -> Option<Canvas<Window>> {
+    let video = match sdl.video() {
+        Ok(video) => video,
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::VideoHeadless, &[&e]));
+            return None;
+        }
+    };
+    let window = |video: &sdl2::VideoSubsystem| video
+        .window("CHIP-8", 64 * scale, 32 * scale)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string());
+    let accelerated = window(&video)
+        .and_then(|window| window.into_canvas().accelerated().present_vsync().build().map_err(|e| e.to_string()));
+    let canvas = match accelerated {
+        Ok(canvas) => {
+            event!(Level::Info, "{}", locale.text(Msg::VideoAccelerated));
+            canvas
+        }
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::AcceleratedUnavailable, &[&e]));
+            let software = window(&video)
+                .and_then(|window| window.into_canvas().software().build().map_err(|e| e.to_string()));
+            match software {
+                Ok(canvas) => {
+                    event!(Level::Info, "{}", locale.text(Msg::VideoSoftware));
+                    canvas
+                }
+                Err(e) => {
+                    event!(Level::Warn, "{}", locale.format(Msg::VideoHeadless, &[&e]));
+                    return None;
+                }
+            }
+        }
+    };
+    let mut canvas = canvas;
+    canvas.window_mut().set_minimum_size(64, 32).ok()?;
+    // drawing happens on a 64x32 grid that SDL scales by whole pixels to fit the window,
+    // letterboxing whatever is left over, so resizing never stretches or blurs the display
+    canvas.set_logical_size(64, 32).ok()?;
+    canvas.set_integer_scale(true).ok()?;
+    Some(canvas)
+}
+
+fn init_audio(sdl: &Sdl, locale: Locale, tone: Tone, output: &AudioOutput) -> Option<AudioDevice<Speaker>> {
+    let desired_spec = AudioSpecDesired {
+        freq: Some(output.sample_rate),
+        channels: Some(1),
+        samples: Some(output.buffer.unwrap_or_else(|| default_buffer(output.sample_rate))),
+    };
+    let open = |audio: &AudioSubsystem, device: Option<&str>| audio.open_playback(device, &desired_spec, |spec| {
+        Speaker {
+            oscillator: Oscillator::new(tone, spec.freq),
+            pattern: PatternPlayer::new(spec.freq, tone.volume),
+            fader: Fader::new(spec.freq, AUDIO_FADE),
+            envelope: Fader::silent(spec.freq, BEEP_RAMP),
+            capture: None,
+        }
+    });
+    let device = sdl.audio().and_then(|audio| match output.device.as_deref() {
+        Some(name) => open(&audio, Some(name)).or_else(|e| {
+            event!(Level::Warn, "{}", locale.format(Msg::AudioDeviceFailed, &[&name, &e]));
+            open(&audio, None)
+        }),
+        None => open(&audio, None),
+    });
+    match device {
+        Ok(device) => {
+            event!(Level::Info, "{}", locale.format(Msg::AudioRate, &[&device.spec().freq, &device.spec().samples]));
+            // silent until the envelope opens, and never paused after this
+            device.resume();
+            Some(device)
+        }
+        Err(e) => {
+            event!(Level::Warn, "{}", locale.format(Msg::AudioNone, &[&e]));
+            None
+        }
+    }
+}
+
+/// The power of two samples closest above BUFFER_TIME at `sample_rate`, as SDL wants them
+fn default_buffer(sample_rate: i32) -> u16 {
+    ((sample_rate as f64 * BUFFER_TIME) as u16).next_power_of_two().max(64)
+}
+
+/// Prints the names --audio-device takes, one a line
+pub fn list_audio_devices(locale: Locale) {
+    let devices = sdl2::init().and_then(|sdl| sdl.audio()).and_then(|audio| {
+        let count = audio.num_audio_playback_devices().ok_or_else(sdl2::get_error)?;
+        (0..count).map(|n| audio.audio_playback_device_name(n)).collect::<Result<Vec<_>, _>>()
+    });
+    match devices {
+        Ok(devices) if !devices.is_empty() => {
+            println!("{}", locale.text(Msg::AudioDevices));
+            for device in devices {
+                println!("  {device}");
+            }
+        }
+        Ok(_) => println!("{}", locale.text(Msg::AudioDevicesNone)),
+        Err(e) => println!("{}", locale.format(Msg::AudioNone, &[&e])),
+    }
+}
@@ -0,0 +1,164 @@
+//! Save states: the complete machine state in a small versioned binary format, the one snapshot
+//! format for save state slots, the rewind buffer and the golden states the test ROMs end in.
+//!
+//! Layout: the magic `C8ST`, a version byte, then each field in a fixed order, little-endian,
+//! with variable-length fields (memory, stack) prefixed by their u32 length. Settings such as
+//! quirks are not part of the state; they belong to whoever loads it. The fields, in order:
+//!
+//! | field | encoding |
+//! |---|---|
+//! | memory | u32 length, then the bytes |
+//! | V0-VF | 16 bytes |
+//! | PC, I | u16 each |
+//! | stack | u8 depth, then a u16 return address per level, outermost first |
+//! | delay, sound timers | u8 each |
+//! | key FX0A waits on to be released | u8, 0xFF for none (version 2) |
+//! | display | u8 resolution (0 lo-res, 1 hi-res, 2 64x64, 3 MegaChip), then 32 u64 lo-res rows and 64 u128 hi-res rows |
+//! | RPL flags | 16 bytes |
+//! | XO-CHIP pitch | u8 |
+//! | halted, waiting for vblank | bool each, a byte 0 or 1 |
+//! | VIP random number generator | u8 counter, u8 last byte |
+//! | ChaCha12 generator | 32 byte seed, u64 stream, u128 word position |
+//! | audio pattern | bool loaded, then 16 bytes (version 3) |
+//! | scripted CXNN bytes | u32 length, then the bytes (version 4) |
+//! | CHIP-8X colors | bool on, u8 background, 32 rows of 8 zone colors (version 5) |
+//! | high byte of I | u8 (version 6) |
+//! | MegaChip screen | bool present, then its indices, pixels, palette and sprite settings (version 6) |
+//! | VIP machine cycles left | u64, two's complement (version 7) |
+//! | XO-CHIP planes | u8 planes selected, then the second plane's 32 u64 lo-res rows and 64 u128 hi-res rows (version 8) |
+//!
+//! Fields only ever get added at the end, with a version bump, so a build reads every state an
+//! older one wrote, filling in what's missing; one newer than the build is refused as
+//! [`StateError::UnsupportedVersion`] instead of being misread
+
+pub const MAGIC: &[u8; 4] = b"C8ST";
+/// 2 added the key FX0A is waiting on to be released, after the timers; 3 the XO-CHIP audio
+/// pattern, at the end; 4 the scripted CXNN bytes not yet drawn, after that; 5 the CHIP-8X
+/// color attributes, after that; 6 MegaChip's high byte of I and its screen, after that; 7
+/// the machine cycles left of the frame under VIP timing, after that; 8 XO-CHIP's plane
+/// selection and second plane, last
+pub const VERSION: u8 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data doesn't start with the save state magic
+    NotAState,
+    /// Written by a newer version of the format than this build understands
+    UnsupportedVersion(u8),
+    /// The data ends before the state does
+    Truncated,
+    /// A field holds a value the machine can't be in
+    Invalid(&'static str),
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => write!(f, "unsupported save state version {v} (this build reads up to {VERSION})"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(field) => write!(f, "save state has an invalid {field}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Appends fields to a state buffer
+#[derive(Debug, Default)]
+pub(crate) struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn new() -> Self {
+        let mut w = Self::default();
+        w.raw(MAGIC);
+        w.u8(VERSION);
+        w
+    }
+
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, n: u8) {
+        self.0.push(n);
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+
+    pub fn u16(&mut self, n: u16) {
+        self.raw(&n.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, n: u64) {
+        self.raw(&n.to_le_bytes());
+    }
+
+    pub fn u128(&mut self, n: u128) {
+        self.raw(&n.to_le_bytes());
+    }
+
+    /// Length-prefixed bytes
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.raw(&(bytes.len() as u32).to_le_bytes());
+        self.raw(bytes);
+    }
+}
+
+/// Reads fields back in the order Writer wrote them
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the header, returning a reader positioned at the first field and the format version
+    pub fn new(data: &'a [u8]) -> Result<(Self, u8), StateError> {
+        if !data.starts_with(MAGIC) {
+            return Err(StateError::NotAState);
+        }
+        let mut r = Self { data, pos: MAGIC.len() };
+        match r.u8()? {
+            version @ 1..=VERSION => Ok((r, version)),
+            version => Err(StateError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn raw(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self.data.get(self.pos..self.pos + n).ok_or(StateError::Truncated)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.raw(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.raw(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, StateError> {
+        Ok(u128::from_le_bytes(self.array()?))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], StateError> {
+        let len = u32::from_le_bytes(self.array()?) as usize;
+        self.raw(len)
+    }
+}
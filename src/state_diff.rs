@@ -0,0 +1,129 @@
+//! What's different between two machines, as between two save states: the registers, timers
+//! and stack that changed, memory as runs of changed bytes, and how many pixels differ. For
+//! telling what a frame or a keypress did, saving a state before and after it
+
+use std::fmt;
+
+use crate::Chip8;
+
+/// Changed bytes closer than this to each other are listed as one run
+const RUN_GAP: usize = 4;
+
+/// The most bytes of a run listed on one line
+const RUN_LINE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Each register or timer that differs, by name, with its values in the first and second
+    pub registers: Vec<(String, u16, u16)>,
+    /// Both stacks if they differ, innermost last
+    pub stack: Option<(Vec<u16>, Vec<u16>)>,
+    /// Runs of differing memory, by where they start, with the bytes in the first and second
+    pub memory: Vec<(usize, Vec<u8>, Vec<u8>)>,
+    /// The memory sizes, if they differ; runs only cover what both have
+    pub memory_size: Option<(usize, usize)>,
+    /// How many pixels differ, 0 if the screens aren't the same size
+    pub pixels: usize,
+    /// The screen sizes, if they differ
+    pub dimensions: Option<((usize, usize), (usize, usize))>,
+}
+
+impl StateDiff {
+    pub fn between(a: &Chip8, b: &Chip8) -> Self {
+        let mut registers = vec![];
+        let mut differs = |name: String, left: u16, right: u16| {
+            if left != right {
+                registers.push((name, left, right));
+            }
+        };
+        differs("PC".to_string(), a.pc(), b.pc());
+        differs("I".to_string(), a.index(), b.index());
+        for (n, (left, right)) in a.registers().into_iter().zip(b.registers()).enumerate() {
+            differs(format!("V{n:X}"), left.into(), right.into());
+        }
+        let ((delay_a, sound_a), (delay_b, sound_b)) = (a.timers(), b.timers());
+        differs("DT".to_string(), delay_a.into(), delay_b.into());
+        differs("ST".to_string(), sound_a.into(), sound_b.into());
+        let stack = (a.stack() != b.stack()).then(|| (a.stack().to_vec(), b.stack().to_vec()));
+
+        let (left, right) = (a.bus.memory.bytes(), b.bus.memory.bytes());
+        let memory_size = (left.len() != right.len()).then_some((left.len(), right.len()));
+        let mut memory: Vec<(usize, Vec<u8>, Vec<u8>)> = vec![];
+        for (addr, (&x, &y)) in left.iter().zip(right).enumerate().filter(|(_, (x, y))| x != y) {
+            match memory.last_mut() {
+                Some((start, run_a, run_b)) if addr - (*start + run_a.len()) < RUN_GAP && run_a.len() < RUN_LINE => {
+                    let from = *start + run_a.len();
+                    run_a.extend_from_slice(&left[from..=addr]);
+                    run_b.extend_from_slice(&right[from..=addr]);
+                }
+                _ => memory.push((addr, vec![x], vec![y])),
+            }
+        }
+
+        let (screen_a, screen_b) = (a.framebuffer(), b.framebuffer());
+        let dimensions = (screen_a.dimensions() != screen_b.dimensions()).then_some((screen_a.dimensions(), screen_b.dimensions()));
+        let pixels = match dimensions {
+            Some(_) => 0,
+            None => screen_a.pixels().zip(screen_b.pixels()).filter(|((.., x), (.., y))| x != y).count(),
+        };
+        Self { registers, stack, memory, memory_size, pixels, dimensions }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.stack.is_none() && self.memory.is_empty() && self.memory_size.is_none() && self.pixels == 0 && self.dimensions.is_none()
+    }
+}
+
+/// One line per register, then the stack, memory and screen, as `first -> second`
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, left, right) in &self.registers {
+            let width = if matches!(name.as_str(), "PC" | "I") { 3 } else { 2 };
+            writeln!(f, "{name:>2}: {left:0width$X} -> {right:0width$X}")?;
+        }
+        let addrs = |stack: &[u16]| match stack {
+            [] => "empty".to_string(),
+            _ => stack.iter().map(|addr| format!("{addr:03X}")).collect::<Vec<_>>().join(" "),
+        };
+        if let Some((left, right)) = &self.stack {
+            writeln!(f, "stack: {} -> {}", addrs(left), addrs(right))?;
+        }
+        if let Some((left, right)) = self.memory_size {
+            writeln!(f, "memory: {left} bytes -> {right} bytes")?;
+        }
+        let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" ");
+        for (addr, left, right) in &self.memory {
+            writeln!(f, "{addr:#06x}: {} -> {}", hex(left), hex(right))?;
+        }
+        match (self.dimensions, self.pixels) {
+            (Some(((wa, ha), (wb, hb))), _) => writeln!(f, "screen: {wa}x{ha} -> {wb}x{hb}"),
+            (None, 0) => Ok(()),
+            (None, 1) => writeln!(f, "screen: 1 pixel differs"),
+            (None, pixels) => writeln!(f, "screen: {pixels} pixels differ"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_list_what_changed() {
+        // LD V0 5, LD I 0x300, LD [I] V0, CALL 0x208, RET
+        let mut chip_8 = Chip8::from_rom(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x22, 0x08, 0x00, 0xEE]);
+        let before = chip_8.clone();
+        assert!(StateDiff::between(&before, &chip_8).is_empty());
+        for _ in 0..4 {
+            chip_8.step();
+        }
+        let diff = StateDiff::between(&before, &chip_8);
+        assert_eq!(diff.registers[0], ("PC".to_string(), 0x200, 0x208));
+        assert_eq!(diff.stack, Some((vec![], vec![0x208])));
+        assert_eq!(diff.memory, vec![(0x300, vec![0x00], vec![0x05])]);
+        assert_eq!(
+            diff.to_string(),
+            "PC: 200 -> 208\n I: 000 -> 300\nV0: 00 -> 05\nstack: empty -> 208\n0x0300: 00 -> 05\n"
+        );
+    }
+}
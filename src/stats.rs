@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+/// Counters the core keeps while running
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    pub instructions: u64,
+    pub draws: u64,
+    pub invalid_opcodes: u64,
+    /// EX9E, EXA1 and FX0A executed
+    pub key_checks: u64,
+}
+
+/// Summary of a whole session, printed on exit
+#[derive(Debug, Clone, Copy)]
+pub struct SessionStats {
+    pub play_time: Duration,
+    pub counters: Counters,
+    pub states_saved: u64,
+}
+
+impl SessionStats {
+    /// Average instructions executed per second of play time
+    pub fn average_ips(&self) -> f64 {
+        match self.play_time.as_secs_f64() {
+            secs if secs > 0.0 => self.counters.instructions as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "session summary:")?;
+        writeln!(f, "  play time:             {:.1}s", self.play_time.as_secs_f64())?;
+        writeln!(f, "  instructions executed: {}", self.counters.instructions)?;
+        writeln!(f, "  average IPS:           {:.0}", self.average_ips())?;
+        writeln!(f, "  draws:                 {}", self.counters.draws)?;
+        writeln!(f, "  invalid opcodes:       {}", self.counters.invalid_opcodes)?;
+        writeln!(f, "  states saved:          {}", self.states_saved)
+    }
+}
+
+/// Frames and instructions per second, over the last second a frontend ran
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rates {
+    pub frames_per_second: f64,
+    pub instructions_per_second: f64,
+}
+
+impl std::fmt::Display for Rates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} fps, {:.0} ips", self.frames_per_second, self.instructions_per_second)
+    }
+}
+
+/// Counts frames shown and instructions run a second at a time, for showing how fast the game
+/// is going while it runs
+#[derive(Debug, Clone)]
+pub struct RateMeter {
+    since: Instant,
+    frames: u32,
+    instructions: u64,
+    rates: Option<Rates>,
+}
+
+impl RateMeter {
+    /// How long each count covers
+    pub const INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(now: Instant) -> Self {
+        Self { since: now, frames: 0, instructions: 0, rates: None }
+    }
+
+    pub fn frame(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn ran(&mut self, instructions: u64) {
+        self.instructions += instructions;
+    }
+
+    /// Starts the next count once INTERVAL has gone by since this one started, returning the
+    /// rates it came to
+    pub fn update(&mut self, now: Instant) -> Option<Rates> {
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed < Self::INTERVAL {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let rates = Rates {
+            frames_per_second: self.frames as f64 / seconds,
+            instructions_per_second: self.instructions as f64 / seconds,
+        };
+        *self = Self { rates: Some(rates), ..Self::new(now) };
+        Some(rates)
+    }
+
+    /// The rates the last whole count came to, None during the first
+    pub fn rates(&self) -> Option<Rates> {
+        self.rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_come_once_a_second() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(start);
+        for _ in 0..60 {
+            meter.frame();
+            meter.ran(11);
+        }
+        assert_eq!(meter.update(start + Duration::from_millis(500)), None);
+        assert_eq!(meter.rates(), None);
+        let rates = meter.update(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(rates, Rates { frames_per_second: 30.0, instructions_per_second: 330.0 });
+        assert_eq!(rates.to_string(), "30 fps, 330 ips");
+        // the next count starts from nothing
+        assert_eq!(meter.update(start + Duration::from_secs(3)), Some(Rates::default()));
+        assert_eq!(meter.rates(), Some(Rates::default()));
+    }
+}
@@ -0,0 +1,72 @@
+//! Where persistent data (high scores, RPL flags, save states, config) lives, behind a trait so
+//! hosts without a filesystem can swap in their own backend
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A flat key-value store. Keys are `/`-separated relative names such as `scores.json`
+pub trait Storage {
+    /// The data stored under `key`, or None if there is none
+    fn read(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    fn write(&mut self, key: &str, data: &[u8]) -> io::Result<()>;
+}
+
+/// Stores each key as a file under a root directory
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// `$XDG_DATA_HOME/chip_8_rs` or `~/.local/share/chip_8_rs`
+    pub fn default_root() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+        Some(base.join("chip_8_rs"))
+    }
+
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, data)
+    }
+}
+
+/// Keeps everything in memory, for tests and hosts that persist some other way
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.entries.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Names for addresses, from a symbol file next to the ROM, for the disassembler, traces and
+//! the debugger to show in place of bare numbers. Each line is `addr=name`, `name=addr` or
+//! Octo's `:const name addr`, with numbers in decimal or 0x-prefixed hex:
+//!
+//! ```text
+//! # Outlaw
+//! 0x200=main
+//! :const draw-player 0x2a4
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::cheats::number;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols(pub BTreeMap<u16, String>);
+
+impl Symbols {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = BTreeMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            let (left, right) = if let Some(rest) = line.strip_prefix(":const ") {
+                let mut words = rest.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some(name), Some(addr), None) => (name, addr),
+                    _ => return Err(err("expected `:const <name> <addr>`")),
+                }
+            } else {
+                line.split_once('=').ok_or_else(|| err("expected `<addr>=<name>` or `:const <name> <addr>`"))?
+            };
+            let (left, right) = (left.trim(), right.trim());
+            let (addr, name) = match number(left) {
+                Ok(addr) => (addr, right),
+                Err(_) => (number(right).map_err(|e| err(&e))?, left),
+            };
+            let addr = u16::try_from(addr).map_err(|_| err(&format!("address out of range: {addr:#x}")))?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(err(&format!("`{name}` is not a name")));
+            }
+            symbols.insert(addr, name.to_string());
+        }
+        Ok(Self(symbols))
+    }
+
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        self.0.get(&addr).map(String::as_str)
+    }
+
+    /// The address named `name`
+    pub fn addr(&self, name: &str) -> Option<u16> {
+        self.0.iter().find(|(_, known)| *known == name).map(|(&addr, _)| addr)
+    }
+
+    /// `addr` for messages, e.g. `0x2a4 (draw-player)`, or just `0x2a4` without a name
+    pub fn describe(&self, addr: u16) -> String {
+        match self.name(addr) {
+            Some(name) => format!("{addr:#05x} ({name})"),
+            None => format!("{addr:#05x}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::Disassembly;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn symbols_name_addresses_either_way_round() {
+        let symbols = Symbols::parse("# test\n0x200=main\nloop = 0x204\n:const sprite 0x206\n").unwrap();
+        assert_eq!((symbols.name(0x204), symbols.addr("sprite")), (Some("loop"), Some(0x206)));
+        assert_eq!(symbols.describe(0x200), "0x200 (main)");
+        assert_eq!(Symbols::parse("main").unwrap_err(), "line 1: expected `<addr>=<name>` or `:const <name> <addr>`");
+
+        // LD I sprite, JP loop at loop
+        let listing = Disassembly::new(&[0xA2, 0x06, 0x00, 0xE0, 0x12, 0x04, 0xFF], &Quirks::default()).with_symbols(symbols).to_string();
+        assert!(listing.contains("main:\n    0x200  A206  LD I, sprite\n"), "{listing}");
+        assert!(listing.contains("loop:\n    0x204  1204  JP loop\n"), "{listing}");
+        assert!(listing.contains("sprite:\n    0x206  FF"), "{listing}");
+    }
+}
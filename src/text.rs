@@ -0,0 +1,48 @@
+//! On-canvas text in the menu's 3x5 font, for everything the frontend writes over the game: the
+//! debugger's panes, the HUD, the pause menu and the keypad's labels. Characters sit in 4x6 cells
+//! of font pixels, which leave a pixel between them, and a font pixel is `size` pixels square
+
+use chip_8_rs::menu::glyph;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+/// Font pixels from one character to the next, and from one line to the next
+pub const ADVANCE: i32 = 4;
+pub const LINE_HEIGHT: i32 = 6;
+
+/// The width and height of `lines` in font pixels, not counting the pixel before the first
+/// character and above the first line
+pub fn measure(lines: &[String]) -> (i32, i32) {
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+    (columns * ADVANCE, lines.len() as i32 * LINE_HEIGHT)
+}
+
+/// Where the character at `column` of `line` starts, for lines starting at `(left, top)`
+pub fn cell((left, top): (i32, i32), line: usize, column: usize, size: i32) -> (i32, i32) {
+    (left + column as i32 * ADVANCE * size, top + line as i32 * LINE_HEIGHT * size)
+}
+
+/// The lit font pixels of `lines` starting at `(left, top)`, each inset by a font pixel in
+/// its cell
+pub fn rects(lines: &[String], origin: (i32, i32), size: i32) -> Vec<Rect> {
+    let mut rects = vec![];
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let (x, y) = cell(origin, row, column, size);
+            for (dy, bits) in glyph(c).into_iter().enumerate() {
+                for dx in (0..3).filter(|dx| bits & 4 >> dx != 0) {
+                    rects.push(Rect::new(x + (1 + dx) * size, y + (1 + dy as i32) * size, size as u32, size as u32));
+                }
+            }
+        }
+    }
+    rects
+}
+
+/// Draws `lines` in `color` starting at `(left, top)`, in whatever blend mode the canvas is in
+pub fn draw(canvas: &mut Canvas<Window>, lines: &[String], origin: (i32, i32), size: i32, color: Color) {
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&rects(lines, origin, size)).expect("failed to draw text");
+}
@@ -0,0 +1,54 @@
+//! The delay and sound timers, which count down at TIMER_HZ until they reach 0
+
+use std::time::Duration;
+
+use crate::TIMER_HZ;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timers {
+    pub delay: u8,
+    /// The buzzer sounds while this is above 0
+    pub sound: u8,
+}
+
+impl Timers {
+    /// Counts both timers down by one, stopping at 0
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    pub fn delay_remaining(&self) -> Duration {
+        ticks(self.delay)
+    }
+
+    pub fn sound_remaining(&self) -> Duration {
+        ticks(self.sound)
+    }
+}
+
+fn ticks(n: u8) -> Duration {
+    Duration::from_secs(n as u64) / TIMER_HZ
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_stop_at_zero() {
+        let mut timers = Timers { delay: 2, sound: 1 };
+        timers.tick();
+        assert_eq!(timers, Timers { delay: 1, sound: 0 });
+        timers.tick();
+        timers.tick();
+        assert_eq!(timers, Timers::default());
+    }
+
+    #[test]
+    fn remaining_time_is_in_timer_ticks() {
+        let timers = Timers { delay: TIMER_HZ as u8, sound: TIMER_HZ as u8 / 2 };
+        assert_eq!(timers.delay_remaining(), Duration::from_secs(1));
+        assert_eq!(timers.sound_remaining(), Duration::from_millis(500));
+    }
+}
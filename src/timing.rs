@@ -0,0 +1,55 @@
+//! Wall-clock scheduling for the 60Hz timers, independent of how fast instructions run
+
+use std::time::{Duration, Instant};
+
+use crate::TIMER_HZ;
+
+/// Fixed-timestep clock: reports how many whole periods have passed since it was last polled,
+/// carrying the remainder over so no time is lost between polls
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    period: Duration,
+    last: Instant,
+    accumulator: Duration,
+    /// How fast the clock runs relative to the wall clock
+    speed: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(hz: u32) -> Self {
+        Self { period: Duration::from_secs(1) / hz, last: Instant::now(), accumulator: Duration::ZERO, speed: 1.0 }
+    }
+
+    /// A clock running at the delay/sound timer rate
+    pub fn timers() -> Self {
+        Self::new(TIMER_HZ)
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Number of periods elapsed since the last call
+    pub fn ticks(&mut self) -> u32 {
+        let now = Instant::now();
+        self.accumulator += (now - self.last).mul_f64(self.speed);
+        self.last = now;
+        let mut ticks = 0;
+        while self.accumulator >= self.period {
+            self.accumulator -= self.period;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Runs the clock at `speed` times real time from now on, e.g. 0.25 for slow motion
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Forgets time accumulated so far, e.g. after a pause
+    pub fn reset(&mut self) {
+        self.last = Instant::now();
+        self.accumulator = Duration::ZERO;
+    }
+}
@@ -0,0 +1,135 @@
+//! Execution traces: every instruction executed with the registers it touched, before and after,
+//! for working out where a ROM and an interpreter start to disagree
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::disasm::{labelled, pattern};
+use crate::quirks::Quirks;
+use crate::symbols::Symbols;
+
+/// The registers an instruction can change
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    pub v: [u8; 16],
+    pub i: u16,
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub addr: u16,
+    pub opcode: u16,
+    pub before: Snapshot,
+    pub after: Snapshot,
+}
+
+impl TraceEntry {
+    /// Shown as decoded under `quirks`, with the addresses `symbols` names given by name
+    pub fn describe<'a>(&'a self, symbols: &'a Symbols, quirks: &'a Quirks) -> impl fmt::Display + 'a {
+        Labelled(self, symbols, quirks)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, symbols: &Symbols, quirks: &Quirks) -> fmt::Result {
+        let form = pattern(self.opcode, quirks);
+        let x = (self.opcode >> 8) as usize & 0xF;
+        let y = (self.opcode >> 4) as usize & 0xF;
+        let operand = |n: usize| (form.contains('X') && n == x) || (form.contains('Y') && n == y);
+        let mut registers = vec![];
+        for n in 0..16 {
+            let (before, after) = (self.before.v[n], self.after.v[n]);
+            if before != after {
+                registers.push(format!("V{n:X} {before:02X}->{after:02X}"));
+            } else if operand(n) {
+                registers.push(format!("V{n:X} {before:02X}"));
+            }
+        }
+        if self.before.i != self.after.i {
+            registers.push(format!("I {:03X}->{:03X}", self.before.i, self.after.i));
+        }
+        write!(f, "{:03X}: {:04X} ", self.addr, self.opcode)?;
+        let text = labelled(self.opcode, symbols, quirks).unwrap_or_else(|| "???".to_string());
+        match registers.is_empty() {
+            true => write!(f, "{text}"),
+            false => write!(f, "{text:<18} {}", registers.join(" ")),
+        }
+    }
+}
+
+/// Shows the address, opcode and mnemonic, then the instruction's Vx and Vy and any other
+/// register it changed as `before->after`, as decoded under the default quirks
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &Symbols::default(), &Quirks::default())
+    }
+}
+
+struct Labelled<'a>(&'a TraceEntry, &'a Symbols, &'a Quirks);
+
+impl fmt::Display for Labelled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write(f, self.1, self.2)
+    }
+}
+
+/// The instructions executed since the trace was last taken, or only the latest `limit` of them
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    entries: VecDeque<TraceEntry>,
+    limit: Option<usize>,
+}
+
+impl Trace {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self { entries: VecDeque::new(), limit }
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if self.limit.is_some_and(|limit| self.entries.len() >= limit) {
+            self.entries.pop_front();
+        }
+        if self.limit != Some(0) {
+            self.entries.push_back(entry);
+        }
+    }
+
+    /// Removes and returns everything recorded, oldest first
+    pub fn take(&mut self) -> Vec<TraceEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The instruction executed `n` before the latest, which is 0
+    pub fn back(&self, n: usize) -> Option<&TraceEntry> {
+        self.entries.len().checked_sub(n + 1).and_then(|index| self.entries.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn traces_show_registers_changed_and_keep_the_latest() {
+        // LD V1, 05; ADD V1, 03; LD I, 300; JP 200
+        let mut chip_8 = Chip8::from_rom(&[0x61, 0x05, 0x71, 0x03, 0xA3, 0x00, 0x12, 0x00]);
+        chip_8.enable_trace(Some(3));
+        for _ in 0..5 {
+            chip_8.step();
+        }
+        let lines: Vec<_> = chip_8.take_trace().iter().map(ToString::to_string).collect();
+        assert_eq!(lines, [
+            "204: A300 LD I, 0x300        I 000->300",
+            "206: 1200 JP 0x200",
+            "200: 6105 LD V1, 0x05        V1 08->05",
+        ]);
+        assert!(chip_8.take_trace().is_empty());
+    }
+}
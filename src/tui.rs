@@ -0,0 +1,199 @@
+//! The terminal frontend: the display drawn with half-block characters, two pixels to a
+//! character cell, and the keypad read from stdin. Terminals report key presses but not
+//! releases, so a key counts as held for a few frames after each byte it sends, which the
+//! terminal's key repeat keeps topping up. `stty` puts the terminal in raw mode, so this needs a
+//! Unix-like system but no extra dependencies
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Stdout, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use chip_8_rs::audio::AUDIO_PATTERN_LEN;
+use chip_8_rs::devices::{Buzzer, Keypad};
+use chip_8_rs::display::FrameSink;
+use chip_8_rs::hints::KEYPAD_LAYOUT;
+use chip_8_rs::symbols::Symbols;
+use chip_8_rs::{Chip8, TIMER_HZ};
+
+use crate::args::RunArgs;
+use crate::i18n::{Locale, Msg};
+use crate::input_log::InputLog;
+
+/// The same positions as the default keymap: 1234, QWER, ASDF and ZXCV
+const KEY_ROWS: [&[u8; 4]; 4] = [b"1234", b"qwer", b"asdf", b"zxcv"];
+
+/// Frames a key stays held after it was last seen, longer than most terminals' key repeat delay
+const HOLD_FRAMES: u8 = 30;
+
+const CTRL_C: u8 = 0x03;
+const ESC: u8 = 0x1B;
+
+pub fn run(cli: &RunArgs, mut chip_8: Chip8, symbols: &Symbols, locale: Locale) {
+    let terminal = match RawTerminal::enter() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            println!("{}", locale.format(Msg::TuiNeedsTerminal, &[&e]));
+            std::process::exit(1);
+        }
+    };
+    let input = spawn_reader();
+    let mut screen = HalfBlocks { out: io::stdout(), width: 0, text: String::new() };
+    let instructions_per_frame = (cli.ips() / TIMER_HZ).max(1) as usize;
+    let frame = Duration::from_secs(1) / TIMER_HZ;
+    let mut keypad = HeldKeys([0; 16]);
+    let mut bell = Bell { beeping: false };
+    let mut input_log = InputLog::start(cli, &mut chip_8, locale);
+    let mut next_frame = Instant::now();
+    loop {
+        let bytes: Vec<u8> = input.try_iter().collect();
+        // a lone escape, as opposed to the start of an arrow key's escape sequence
+        let escape = bytes.iter().enumerate().any(|(n, &byte)| byte == ESC && !matches!(bytes.get(n + 1), Some(b'[' | b'O')));
+        if escape || bytes.contains(&CTRL_C) {
+            break;
+        }
+        for byte in bytes.iter().map(u8::to_ascii_lowercase) {
+            if let Some(key) = keypad_key(byte) {
+                keypad.0[key as usize] = HOLD_FRAMES;
+            }
+        }
+        // not run_frame_on(), as a replay's keys go in between
+        chip_8.read_keypad(&mut keypad);
+        input_log.frame(&mut chip_8);
+        chip_8.run_frame(instructions_per_frame);
+        let went_wrong = !chip_8.take_faults().is_empty() || chip_8.halted();
+        crate::write_trace(&mut chip_8, cli, went_wrong, symbols);
+        chip_8.present(&mut screen);
+        chip_8.sound_to(&mut bell);
+        next_frame += frame;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            // running behind, so drop the backlog rather than racing through it
+            None => next_frame = Instant::now(),
+        }
+    }
+    drop(terminal);
+    input_log.finish();
+    crate::write_profile(&chip_8, cli, locale);
+}
+
+/// Frames each key stays held for, counting down once a frame as the keypad is read
+struct HeldKeys([u8; 16]);
+
+impl Keypad for HeldKeys {
+    fn keys(&mut self) -> u16 {
+        let keys = self.0.iter().enumerate().filter(|(_, &frames)| frames > 0).fold(0u16, |keys, (key, _)| keys | 1 << key);
+        for frames in self.0.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        keys
+    }
+}
+
+/// The closest a terminal has to a buzzer: the bell, rung as a beep starts
+struct Bell {
+    beeping: bool,
+}
+
+impl Buzzer for Bell {
+    fn sound(&mut self, on: bool, _pattern: Option<[u8; AUDIO_PATTERN_LEN]>, _rate: f32) {
+        if on && !self.beeping {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07").and_then(|()| stdout.flush()).ok();
+        }
+        self.beeping = on;
+    }
+}
+
+fn keypad_key(byte: u8) -> Option<u8> {
+    KEY_ROWS.iter().zip(KEYPAD_LAYOUT).find_map(|(row, keys)| row.iter().position(|&c| c == byte).map(|n| keys[n]))
+}
+
+/// Reads stdin on a thread of its own, as there's no waiting on it without blocking
+fn spawn_reader() -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            let Ok(byte) = byte else { break };
+            if sender.send(byte).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// The terminal in raw mode on the alternate screen, put back the way it was when dropped
+struct RawTerminal {
+    /// `stty -g` output, which stty takes back to restore the settings
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        let mut stdout = io::stdout();
+        // alternate screen, cursor hidden, cleared
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+        Ok(Self { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l").and_then(|()| stdout.flush()).ok();
+        stty(&[&self.saved]).ok();
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Draws frames as rows of `▀`, the top pixel of each cell in the foreground color and the
+/// bottom one in the background color
+struct HalfBlocks {
+    out: Stdout,
+    /// Width of the last frame, to clear what a wider one left behind
+    width: usize,
+    text: String,
+}
+
+impl FrameSink for HalfBlocks {
+    fn frame(&mut self, rgba: &[u8], width: usize, height: usize) {
+        self.text.clear();
+        if width != self.width {
+            self.width = width;
+            self.text.push_str("\x1b[2J");
+        }
+        self.text.push_str("\x1b[H");
+        let color = |x: usize, y: usize| {
+            let pixel = &rgba[(y * width + x) * 4..][..3];
+            (pixel[0], pixel[1], pixel[2])
+        };
+        for y in (0..height).step_by(2) {
+            let mut last = None;
+            for x in 0..width {
+                let cell = (color(x, y), color(x, y + 1));
+                // most neighbours share colors, and skipping the escape codes for them keeps
+                // frames small enough for slow terminals
+                if last != Some(cell) {
+                    let ((r, g, b), (br, bg, bb)) = cell;
+                    write!(self.text, "\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m").ok();
+                    last = Some(cell);
+                }
+                self.text.push('▀');
+            }
+            self.text.push_str("\x1b[0m\r\n");
+        }
+        self.out.write_all(self.text.as_bytes()).and_then(|()| self.out.flush()).ok();
+    }
+}
@@ -0,0 +1,141 @@
+//! The CHIP-8 flavors as whole machines: the instructions each decodes, the quirks it starts
+//! with, its largest screen and its memory. Quirks alone let a machine mix behaviors no
+//! interpreter had, and leave every extension's opcodes decoding everywhere; a machine given a
+//! variant faults on the instructions it didn't have, as its interpreter would have choked on
+//! them. The variant a ROM needs can be told from its code, see detect().
+
+use std::fmt;
+
+use crate::display::Resolution;
+use crate::quirks::{LoadStore, Preset, Quirks};
+use crate::rom::{self, RomInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Variant {
+    /// The COSMAC VIP's interpreter
+    Chip8,
+    /// The VIP's CHIP-8X, with its color board and second keypad
+    Chip8x,
+    /// The ETI-660's CHIP-8, loading programs at 0x600 and drawing on a 64x48 screen
+    #[value(name = "eti-660")]
+    Eti660,
+    /// SUPER-CHIP 1.0, which scrolled down with 00CN only from 1.1 on
+    #[value(name = "schip-1.0")]
+    Schip10,
+    /// SUPER-CHIP 1.1
+    #[value(name = "schip-1.1")]
+    Schip11,
+    Xochip,
+    Megachip,
+}
+
+impl Variant {
+    /// The quirks preset it's closest to
+    pub fn preset(self) -> Preset {
+        match self {
+            Variant::Chip8 | Variant::Eti660 => Preset::Chip8,
+            Variant::Chip8x => Preset::Chip8x,
+            Variant::Schip10 | Variant::Schip11 => Preset::Schip,
+            Variant::Xochip => Preset::Xochip,
+            Variant::Megachip => Preset::Megachip,
+        }
+    }
+
+    /// The quirks it starts with
+    pub fn quirks(self) -> Quirks {
+        let quirks = Quirks::preset(self.preset());
+        match self {
+            // 1.0 still left I off by one after FX55/FX65, like CHIP-48
+            Variant::Schip10 => Quirks { load_store: LoadStore::Chip48, ..quirks },
+            Variant::Eti660 => Quirks { eti_660: true, ..quirks },
+            _ => quirks,
+        }
+    }
+
+    /// Its largest screen
+    pub fn resolution(self) -> Resolution {
+        match self {
+            Variant::Chip8 | Variant::Chip8x => Resolution::Low,
+            Variant::Eti660 => Resolution::Eti,
+            Variant::Schip10 | Variant::Schip11 | Variant::Xochip => Resolution::High,
+            Variant::Megachip => Resolution::Mega,
+        }
+    }
+
+    /// How much memory a ROM of `rom_len` bytes runs in
+    pub fn memory_size(self, rom_len: usize) -> usize {
+        self.quirks().memory_size(rom_len)
+    }
+
+    /// Whether it has `opcode`, where opcodes that are no instruction anywhere count as had,
+    /// to fault as invalid the way they always do
+    pub fn decodes(self, opcode: u16) -> bool {
+        match rom::platform(opcode) {
+            // 00CN came with SUPER-CHIP 1.1
+            Some(Preset::Schip) if self == Variant::Schip10 => opcode & 0xFFF0 != 0x00C0,
+            Some(platform) => RomInfo::runs_on(platform, self.preset()),
+            None => true,
+        }
+    }
+
+    /// The least variant that has all the instructions in a ROM's code: CHIP-8 unless it uses
+    /// an extension's. CHIP-8X and the ETI-660 have to be asked for, as their instructions were
+    /// CHIP-8's
+    pub fn detect(info: &RomInfo) -> Self {
+        match info.platform() {
+            None | Some(Preset::Chip8 | Preset::Chip8x) => Variant::Chip8,
+            Some(Preset::Schip) => Variant::Schip11,
+            Some(Preset::Xochip) => Variant::Xochip,
+            Some(Preset::Megachip) => Variant::Megachip,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Variant::Chip8 => "chip8",
+            Variant::Chip8x => "chip8x",
+            Variant::Eti660 => "eti-660",
+            Variant::Schip10 => "schip-1.0",
+            Variant::Schip11 => "schip-1.1",
+            Variant::Xochip => "xochip",
+            Variant::Megachip => "megachip",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::Fault;
+    use crate::{Chip8, Effect};
+
+    #[test]
+    fn variants_decode_only_their_own_instructions() {
+        // HIGH, SCD 1, then spin
+        let rom = [0x00, 0xFF, 0x00, 0xC1, 0x12, 0x04];
+        let (_, info) = Chip8::load_bytes(rom.to_vec(), None).unwrap();
+        assert_eq!(Variant::detect(&info), Variant::Schip11);
+        let mut chip_8 = Chip8::builder().variant(Variant::Schip11).rom_bytes(&rom).build();
+        assert_eq!(chip_8.quirks(), Quirks::preset(Preset::Schip));
+        assert!(!matches!(chip_8.step(), Effect::Error(_)) && !matches!(chip_8.step(), Effect::Error(_)));
+        let mut chip_8 = Chip8::builder().variant(Variant::Schip10).rom_bytes(&rom).build();
+        assert_eq!(chip_8.quirks().load_store, LoadStore::Chip48);
+        chip_8.step();
+        assert_eq!(chip_8.step(), Effect::Error(Fault::InvalidOpcode(0x00C1)));
+        let mut chip_8 = Chip8::builder().variant(Variant::Chip8).rom_bytes(&rom).build();
+        assert_eq!(chip_8.step(), Effect::Error(Fault::InvalidOpcode(0x00FF)));
+        assert_eq!(chip_8.framebuffer().dimensions(), (64, 32));
+        // without a variant everything decodes, as ever
+        let mut chip_8 = Chip8::builder().platform(Preset::Chip8).rom_bytes(&rom).build();
+        assert_eq!(chip_8.step(), Effect::HiResToggle(true));
+        assert!(Variant::Megachip.decodes(0x0010) && !Variant::Xochip.decodes(0x0010));
+        assert!(Variant::Chip8.decodes(0x00E0) && Variant::Chip8.decodes(0xFFFF));
+        assert_eq!(Variant::Xochip.memory_size(100), 0x10000);
+        // the ETI-660 starts at 0x600 on its 64x48 screen
+        let chip_8 = Chip8::builder().variant(Variant::Eti660).rom_bytes(&rom).build();
+        assert_eq!((chip_8.pc(), chip_8.peek(0x600)), (0x600, 0x00));
+        assert_eq!((chip_8.peek(0x601), chip_8.framebuffer().dimensions()), (0xFF, (64, 48)));
+    }
+}
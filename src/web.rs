@@ -0,0 +1,95 @@
+//! The WebAssembly side of the browser frontend in web/index.html. There's no wasm-bindgen, so
+//! the page drives the machine through a few exported functions and reads frames straight out
+//! of the module's memory:
+//!
+//! ```text
+//! ptr = chip8_rom_buffer(len)     room for a ROM of len bytes; the page copies it to ptr
+//! chip8_load(preset, seed)        starts the ROM, preset numbered as in Preset
+//! chip8_key(key, down)            presses or releases a keypad key
+//! chip8_run_frame(instructions)   one 60Hz frame; returns 1 while the buzzer should sound
+//! ptr = chip8_frame()             the display as RGBA, chip8_width() by chip8_height() pixels
+//! ```
+
+use std::sync::{Mutex, PoisonError};
+
+use clap::ValueEnum;
+
+use crate::quirks::Preset;
+use crate::Chip8;
+
+struct Web {
+    rom: Vec<u8>,
+    chip_8: Option<Chip8>,
+    /// The last frame handed out, kept alive for the page to read
+    rgba: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+static WEB: Mutex<Web> = Mutex::new(Web { rom: Vec::new(), chip_8: None, rgba: Vec::new(), width: 0, height: 0 });
+
+fn with<T>(f: impl FnOnce(&mut Web) -> T) -> T {
+    f(&mut WEB.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_rom_buffer(len: usize) -> *mut u8 {
+    with(|web| {
+        web.rom = vec![0; len];
+        web.rom.as_mut_ptr()
+    })
+}
+
+/// Returns false for a preset number that doesn't exist
+#[no_mangle]
+pub extern "C" fn chip8_load(preset: u32, seed: u32) -> bool {
+    with(|web| {
+        let Some(&preset) = Preset::value_variants().get(preset as usize) else {
+            return false;
+        };
+        web.chip_8 = Some(Chip8::builder().platform(preset).rom_bytes(&web.rom).seed(seed as u64).build());
+        true
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_key(key: u8, down: bool) {
+    with(|web| match web.chip_8.as_mut() {
+        Some(chip_8) if down => chip_8.press(key & 0xF),
+        Some(chip_8) => chip_8.release(key & 0xF),
+        None => {}
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_run_frame(instructions: u32) -> bool {
+    with(|web| match web.chip_8.as_mut() {
+        Some(chip_8) => {
+            chip_8.run_frame(instructions as usize);
+            chip_8.sound_active()
+        }
+        None => false,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_frame() -> *const u8 {
+    with(|web| {
+        if let Some(chip_8) = web.chip_8.as_ref() {
+            let display = chip_8.framebuffer();
+            (web.width, web.height) = display.dimensions();
+            web.rgba = display.to_rgba();
+        }
+        web.rgba.as_ptr()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_width() -> u32 {
+    with(|web| web.width as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_height() -> u32 {
+    with(|web| web.height as u32)
+}
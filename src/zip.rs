@@ -0,0 +1,373 @@
+//! Reading ROMs out of zip archives, which is how many collections are shipped: the central
+//! directory for the entry names, and stored or deflated entries for their contents
+
+use std::fmt;
+use std::path::Path;
+
+use crate::patch::crc32;
+
+const END_OF_DIRECTORY: u32 = 0x0605_4B50;
+const DIRECTORY_ENTRY: u32 = 0x0201_4B50;
+const LOCAL_HEADER: u32 = 0x0403_4B50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZipError {
+    /// There's no end of central directory record
+    NotAZip,
+    /// A record runs past the end of the file
+    Truncated,
+    /// The entry is compressed with a method other than store and deflate, or encrypted
+    Unsupported(u16),
+    /// The deflate stream doesn't decode
+    Corrupt,
+    /// The entry's contents don't match the CRC the archive records
+    ChecksumMismatch,
+    NoSuchEntry(String),
+}
+
+impl fmt::Display for ZipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZipError::NotAZip => write!(f, "not a zip archive"),
+            ZipError::Truncated => write!(f, "zip archive is truncated"),
+            ZipError::Unsupported(method) => write!(f, "zip entry uses unsupported compression method {method}"),
+            ZipError::Corrupt => write!(f, "zip entry's compressed data is corrupt"),
+            ZipError::ChecksumMismatch => write!(f, "zip entry failed its checksum"),
+            ZipError::NoSuchEntry(name) => write!(f, "no entry named {name} in the zip archive"),
+        }
+    }
+}
+
+impl std::error::Error for ZipError {}
+
+/// Whether `path` is a file with a .zip extension
+pub fn is_zip(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// A file in an archive, as the central directory describes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    method: u16,
+    encrypted: bool,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    header_offset: usize,
+}
+
+/// The files in the archive, in directory order, leaving out directories
+pub fn entries(archive: &[u8]) -> Result<Vec<Entry>, ZipError> {
+    // the record is 22 bytes plus a comment of up to 64KB, so search backwards for it
+    let search_from = archive.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (search_from..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(archive, at) == Some(END_OF_DIRECTORY))
+        .ok_or(ZipError::NotAZip)?;
+    let count = u16_at(archive, end + 10).ok_or(ZipError::Truncated)?;
+    let mut at = u32_at(archive, end + 16).ok_or(ZipError::Truncated)? as usize;
+    let mut entries = vec![];
+    for _ in 0..count {
+        if u32_at(archive, at) != Some(DIRECTORY_ENTRY) {
+            return Err(ZipError::Truncated);
+        }
+        let field = |offset: usize| u16_at(archive, at + offset).ok_or(ZipError::Truncated);
+        let long = |offset: usize| u32_at(archive, at + offset).ok_or(ZipError::Truncated);
+        let (name_len, extra_len, comment_len) = (field(28)? as usize, field(30)? as usize, field(32)? as usize);
+        let name = archive.get(at + 46..at + 46 + name_len).ok_or(ZipError::Truncated)?;
+        let entry = Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: field(10)?,
+            encrypted: field(8)? & 1 != 0,
+            crc: long(16)?,
+            compressed_size: long(20)? as usize,
+            size: long(24)? as usize,
+            header_offset: long(42)? as usize,
+        };
+        if !entry.name.ends_with('/') {
+            entries.push(entry);
+        }
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// The contents of the entry called `name`
+pub fn extract(archive: &[u8], name: &str) -> Result<Vec<u8>, ZipError> {
+    let entry = entries(archive)?
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| ZipError::NoSuchEntry(name.to_string()))?;
+    let at = entry.header_offset;
+    if u32_at(archive, at) != Some(LOCAL_HEADER) {
+        return Err(ZipError::Truncated);
+    }
+    // the local header repeats the name, and may have different extra data than the directory
+    let name_len = u16_at(archive, at + 26).ok_or(ZipError::Truncated)? as usize;
+    let extra_len = u16_at(archive, at + 28).ok_or(ZipError::Truncated)? as usize;
+    let start = at + 30 + name_len + extra_len;
+    let data = archive.get(start..start + entry.compressed_size).ok_or(ZipError::Truncated)?;
+    let contents = match entry.method {
+        _ if entry.encrypted => return Err(ZipError::Unsupported(entry.method)),
+        0 => data.to_vec(),
+        8 => inflate(data, entry.size)?,
+        method => return Err(ZipError::Unsupported(method)),
+    };
+    if crc32(&contents) != entry.crc {
+        return Err(ZipError::ChecksumMismatch);
+    }
+    Ok(contents)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Reads a deflate stream a bit at a time, least significant bit first
+struct Bits<'a> {
+    data: &'a [u8],
+    at: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, ZipError> {
+        let byte = *self.data.get(self.at).ok_or(ZipError::Corrupt)?;
+        let bit = (byte >> self.bit) as u32 & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.at += 1;
+        }
+        Ok(bit)
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, ZipError> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary, as stored blocks start on one
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.at += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code: how many codes there are of each length, and the symbols in code
+/// order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate().filter(|(_, &len)| len > 0) {
+            symbols[offsets[len as usize] as usize] = symbol as u16;
+            offsets[len as usize] += 1;
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, ZipError> {
+        // codes of each length follow on from the last code of the length before
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..16 {
+            code |= bits.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied().ok_or(ZipError::Corrupt);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ZipError::Corrupt)
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// The order code length code lengths are sent in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a raw deflate stream, as zip stores them, expecting `size` bytes out
+fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, ZipError> {
+    let mut bits = Bits { data, at: 0, bit: 0 };
+    let mut out = Vec::with_capacity(size);
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let at = bits.at;
+                let len = u16_at(data, at).ok_or(ZipError::Corrupt)? as usize;
+                out.extend_from_slice(data.get(at + 4..at + 4 + len).ok_or(ZipError::Corrupt)?);
+                bits.at += 4 + len;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                inflate_block(&mut bits, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(ZipError::Corrupt),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Reads the code tables at the start of a dynamic Huffman block
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), ZipError> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(ZipError::Corrupt)?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(ZipError::Corrupt);
+    }
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(bits: &mut Bits, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), ZipError> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                let len = *LENGTH_BASE.get(code).ok_or(ZipError::Corrupt)? as usize + bits.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let code = distances.decode(bits)? as usize;
+                let distance = *DISTANCE_BASE.get(code).ok_or(ZipError::Corrupt)? as usize
+                    + bits.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+                let start = out.len().checked_sub(distance).ok_or(ZipError::Corrupt)?;
+                // the copy can overlap what it's producing, so it goes a byte at a time
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An archive of `files` as (name, method, stored bytes, uncompressed contents)
+    fn archive(files: &[(&str, u16, &[u8], &[u8])]) -> Vec<u8> {
+        let (mut zip, mut directory) = (vec![], vec![]);
+        for &(name, method, data, contents) in files {
+            let sizes = [crc32(contents), data.len() as u32, contents.len() as u32];
+            let fields: Vec<u8> = sizes.iter().flat_map(|n| n.to_le_bytes()).collect();
+            directory.extend(DIRECTORY_ENTRY.to_le_bytes());
+            directory.extend([20, 0, 20, 0, 0, 0]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0; 4]);
+            directory.extend(&fields);
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0; 12]);
+            directory.extend((zip.len() as u32).to_le_bytes());
+            directory.extend(name.as_bytes());
+            zip.extend(LOCAL_HEADER.to_le_bytes());
+            zip.extend([20, 0, 0, 0]);
+            zip.extend(method.to_le_bytes());
+            zip.extend([0; 4]);
+            zip.extend(&fields);
+            zip.extend((name.len() as u16).to_le_bytes());
+            zip.extend([0, 0]);
+            zip.extend(name.as_bytes());
+            zip.extend(data);
+        }
+        let offset = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(END_OF_DIRECTORY.to_le_bytes());
+        zip.extend([0; 4]);
+        zip.extend([files.len() as u8, 0, files.len() as u8, 0]);
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(offset.to_le_bytes());
+        zip.extend([0, 0]);
+        zip
+    }
+
+    #[test]
+    fn entries_are_listed_and_inflated() {
+        // zlib's output for 64 bytes drawn from a few opcodes' bytes, which it codes dynamically
+        let options = [0x00, 0x00, 0x00, 0x00, 0x12, 0x60, 0x80, 0xFF, 0xE0];
+        let mut x = 1u32;
+        let rom: Vec<u8> = (0..64)
+            .map(|_| {
+                x = (x * 75 + 74) % 65537;
+                options[x as usize % 9]
+            })
+            .collect();
+        let deflated = [
+            0x2D, 0x89, 0x41, 0x11, 0x00, 0x30, 0x0C, 0x83, 0xD0, 0x10, 0x85, 0x91, 0x52, 0xA9, 0x71, 0xD2, 0xB5, 0xDB,
+            0x3E, 0xDC, 0x01, 0x0E, 0x50, 0x18, 0x1A, 0x24, 0xC4, 0x7A, 0xDD, 0x88, 0x1A, 0x27, 0xF5, 0xA3, 0x97, 0x49,
+            0xE6, 0xF4, 0x13, 0xF0, 0x01,
+        ];
+        // and for a string of repeated runs, which it codes with the fixed code
+        let text = b"aaaaaaaaaaaaaaaabbbbbbbbccccdd".repeat(3);
+        let fixed = [0x4B, 0x4C, 0x44, 0x05, 0x49, 0x50, 0x90, 0x0C, 0x04, 0x29, 0x29, 0x89, 0x14, 0xC8, 0x02, 0x00];
+        let zip = archive(&[
+            ("games/", 0, b"", b""),
+            ("games/maze.ch8", 8, &deflated, &rom),
+            ("readme.txt", 8, &fixed, &text),
+            ("games/pong.ch8", 0, &[0x12, 0x00], &[0x12, 0x00]),
+        ]);
+        let names: Vec<_> = entries(&zip).unwrap().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["games/maze.ch8", "readme.txt", "games/pong.ch8"]);
+        assert_eq!(extract(&zip, "games/maze.ch8").unwrap(), rom);
+        assert_eq!(extract(&zip, "readme.txt").unwrap(), text);
+        assert_eq!(extract(&zip, "games/pong.ch8").unwrap(), [0x12, 0x00]);
+        assert_eq!(extract(&zip, "tetris.ch8"), Err(ZipError::NoSuchEntry("tetris.ch8".to_string())));
+        assert_eq!(entries(b"PK not really"), Err(ZipError::NotAZip));
+    }
+}
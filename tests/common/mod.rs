@@ -0,0 +1,53 @@
+//! Golden screens for the integration tests: a ROM is run for a number of frames and the screen
+//! it ends on compared with the text dump checked in for it, with `CHIP8_BLESS=1` writing the
+//! dumps that are missing
+
+use std::path::Path;
+
+use chip_8_rs::display::Display;
+use chip_8_rs::Chip8;
+
+/// Runs `chip_8` for `frames` frames and checks its screen against the golden text at
+/// `golden`, Err saying how many pixels differ and where the first is
+pub fn check_golden_screen(chip_8: &mut Chip8, frames: usize, golden: &Path) -> Result<(), String> {
+    chip_8.run_frames(frames, &[]);
+    let screen = chip_8.framebuffer();
+    let name = golden.file_name().unwrap_or_default().to_string_lossy();
+    let text = match std::fs::read_to_string(golden) {
+        Ok(text) => text,
+        Err(_) if std::env::var_os("CHIP8_BLESS").is_some() => {
+            std::fs::write(golden, screen.to_text()).expect("failed to write golden screen");
+            return Ok(());
+        }
+        Err(_) => return Err(format!("{name}: no golden screen, run with CHIP8_BLESS=1 to write it")),
+    };
+    let expected = Display::from_text(&text).map_err(|e| format!("{name}: {e}"))?;
+    if expected.dimensions() != screen.dimensions() {
+        let ((width, height), (expected_width, expected_height)) = (screen.dimensions(), expected.dimensions());
+        return Err(format!("{name}: the screen is {width}x{height}, the golden one {expected_width}x{expected_height}"));
+    }
+    let (width, height) = screen.dimensions();
+    let differing: Vec<_> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| screen.planes_lit(x, y) != expected.planes_lit(x, y))
+        .collect();
+    match differing.first() {
+        None => Ok(()),
+        Some(&(x, y)) => {
+            let row = |display: &Display| display.to_text().lines().nth(y).unwrap_or_default().to_owned();
+            Err(format!(
+                "{name}: {} pixels differ from the golden screen, the first at ({x}, {y})\n  got      {}\n  expected {}",
+                differing.len(),
+                row(screen),
+                row(&expected),
+            ))
+        }
+    }
+}
+
+/// check_golden_screen(), panicking on a difference
+pub fn assert_golden_screen(chip_8: &mut Chip8, frames: usize, golden: &Path) {
+    if let Err(e) = check_golden_screen(chip_8, frames, golden) {
+        panic!("{e}");
+    }
+}
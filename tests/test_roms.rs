@@ -0,0 +1,90 @@
+//! Runs the community test ROMs and compares the screen each one ends on with a golden copy,
+//! catching regressions in decoding and drawing that the built-in checks are too small to see.
+//!
+//! The ROMs aren't checked in. Copy Timendus' chip8-test-suite (`1-chip8-logo.ch8` through
+//! `8-scrolling.ch8`) and `BC_test.ch8` into tests/roms; any that are missing are skipped. The
+//! golden screens are the headless text dumps, one per ROM and platform in tests/golden, with
+//! the save state each ends in next to them: a golden state written by an older build has to
+//! load and come out the same, which keeps the save state format readable as it grows. Run
+//! with `CHIP8_BLESS=1` to write the ones that are missing, then check the screens by eye.
+
+mod common;
+
+use std::path::Path;
+
+use chip_8_rs::quirks::Preset;
+use chip_8_rs::{demo, Chip8};
+
+struct TestRom {
+    file: &'static str,
+    preset: Preset,
+    frames: usize,
+    /// Written to 0x1FF, where the suite looks for a platform to run on instead of asking
+    platform: Option<u8>,
+}
+
+const fn rom(file: &'static str, preset: Preset, frames: usize, platform: Option<u8>) -> TestRom {
+    TestRom { file, preset, frames, platform }
+}
+
+const TEST_ROMS: [TestRom; 11] = [
+    rom("1-chip8-logo.ch8", Preset::Chip8, 60, None),
+    rom("2-ibm-logo.ch8", Preset::Chip8, 60, None),
+    rom("3-corax+.ch8", Preset::Chip8, 120, None),
+    rom("4-flags.ch8", Preset::Chip8, 120, None),
+    rom("5-quirks.ch8", Preset::Chip8, 600, Some(1)),
+    rom("5-quirks.ch8", Preset::Schip, 600, Some(2)),
+    rom("5-quirks.ch8", Preset::Xochip, 600, Some(3)),
+    rom("8-scrolling.ch8", Preset::Schip, 240, Some(1)),
+    rom("8-scrolling.ch8", Preset::Schip, 240, Some(2)),
+    rom("8-scrolling.ch8", Preset::Xochip, 240, Some(4)),
+    rom("BC_test.ch8", Preset::Chip8, 120, None),
+];
+
+#[test]
+fn test_roms_match_their_golden_screens() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let bless = std::env::var_os("CHIP8_BLESS").is_some();
+    let mut failures = vec![];
+    for test in &TEST_ROMS {
+        let Ok(data) = std::fs::read(dir.join("roms").join(test.file)) else {
+            eprintln!("skipping {}: not in tests/roms", test.file);
+            continue;
+        };
+        let mut chip_8 = Chip8::builder().platform(test.preset).rom_bytes(&data).seed(0).build();
+        if let Some(platform) = test.platform {
+            chip_8.poke(0x1FF, platform);
+        }
+        let stem = test.file.trim_end_matches(".ch8");
+        let name = match test.platform {
+            Some(platform) => format!("{stem}-{}-{platform}.txt", test.preset),
+            None => format!("{stem}-{}.txt", test.preset),
+        };
+        let golden = dir.join("golden").join(&name);
+        if let Err(e) = common::check_golden_screen(&mut chip_8, test.frames, &golden) {
+            failures.push(e);
+        }
+        let state = chip_8.save_state();
+        let golden = golden.with_extension("state");
+        match std::fs::read(&golden) {
+            Ok(expected) => {
+                let mut loaded = Chip8::builder().platform(test.preset).rom_bytes(&data).build();
+                match loaded.load_state(&expected) {
+                    Ok(()) if loaded.save_state() == state => {}
+                    Ok(()) => failures.push(format!("{name}: the state differs from the golden copy")),
+                    Err(e) => failures.push(format!("{name}: the golden state doesn't load: {e}")),
+                }
+            }
+            Err(_) if bless => std::fs::write(&golden, &state).expect("failed to write golden state"),
+            Err(_) => failures.push(format!("{name}: no golden state, run with CHIP8_BLESS=1 to write it")),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn the_demo_matches_its_golden_screen() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/demo-chip8.txt");
+    let mut chip_8 = Chip8::builder().platform(Preset::Chip8).rom_bytes(demo::ROM).seed(0).build();
+    common::assert_golden_screen(&mut chip_8, 90, &golden);
+}